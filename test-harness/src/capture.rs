@@ -0,0 +1,76 @@
+//! Capturing the frames of a connection and replaying them.
+//!
+//! A [`Capture`] is a [`FrameRecorder`] (see `Config::set_frame_recorder`)
+//! which keeps all frames, including their bodies. [`replay`] feeds the
+//! frames a connection received back into another connection, e.g. to
+//! reproduce an interoperability issue.
+
+use crate::raw::{RawFrame, RawFrameClient};
+use futures::{AsyncRead, AsyncWrite};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use yamux::{FrameDirection, FrameRecorder, RecordedFrame};
+
+/// A frame recorded by a [`Capture`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedFrame {
+    pub direction: FrameDirection,
+    pub frame: RawFrame,
+}
+
+/// Collects all frames of the connections it is the recorder of.
+#[derive(Clone, Debug, Default)]
+pub struct Capture(Arc<Mutex<Vec<CapturedFrame>>>);
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    /// The frames recorded so far.
+    pub fn frames(&self) -> Vec<CapturedFrame> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The frames with the given direction recorded so far.
+    pub fn frames_in(&self, direction: FrameDirection) -> Vec<RawFrame> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|f| f.direction == direction)
+            .map(|f| f.frame.clone())
+            .collect()
+    }
+}
+
+impl FrameRecorder for Capture {
+    fn record(&self, recorded: RecordedFrame<'_>) {
+        let mut frame = RawFrame::decode_header(&recorded.header);
+        frame.body = recorded.body.to_vec();
+        let direction = recorded.direction;
+        self.0
+            .lock()
+            .unwrap()
+            .push(CapturedFrame { direction, frame })
+    }
+
+    fn max_body_len(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// Send the inbound frames of a capture to the connection under test.
+pub async fn replay<T>(frames: &[CapturedFrame], peer: &mut RawFrameClient<T>) -> io::Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    for f in frames {
+        if f.direction == FrameDirection::Inbound {
+            peer.send(f.frame.clone()).await?
+        }
+    }
+    Ok(())
+}
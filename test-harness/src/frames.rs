@@ -0,0 +1,59 @@
+//! An in-memory, bidirectional [`FrameTransport`].
+//!
+//! Frames sent on one [`FrameEndpoint`] are received by the other, without
+//! ever being serialised into a byte stream.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+use std::io;
+use std::task::{Context, Poll};
+use yamux::{EncodedFrame, FrameTransport};
+
+/// Create a pair of connected [`FrameEndpoint`]s.
+pub fn frame_pipe() -> (FrameEndpoint, FrameEndpoint) {
+    let (a_tx, a_rx) = unbounded();
+    let (b_tx, b_rx) = unbounded();
+
+    (
+        FrameEndpoint {
+            sender: a_tx,
+            receiver: b_rx,
+        },
+        FrameEndpoint {
+            sender: b_tx,
+            receiver: a_rx,
+        },
+    )
+}
+
+/// One end of a [`frame_pipe`].
+#[derive(Debug)]
+pub struct FrameEndpoint {
+    sender: UnboundedSender<EncodedFrame>,
+    receiver: UnboundedReceiver<EncodedFrame>,
+}
+
+impl FrameTransport for FrameEndpoint {
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(&mut self, frame: EncodedFrame) -> io::Result<()> {
+        self.sender
+            .unbounded_send(frame)
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn poll_flush(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<EncodedFrame>>> {
+        self.receiver.poll_next_unpin(cx).map(|f| f.map(Ok))
+    }
+}
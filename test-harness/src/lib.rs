@@ -1,6 +1,4 @@
-use futures::{
-    future, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt, TryStreamExt,
-};
+use futures::{future, AsyncReadExt, AsyncWriteExt, StreamExt, TryStreamExt};
 use futures::{stream, Stream};
 use quickcheck::{Arbitrary, Gen};
 use std::io;
@@ -11,6 +9,20 @@ use yamux::ConnectionError;
 use yamux::{Config, WindowUpdateMode};
 use yamux::{Connection, Mode};
 
+pub mod capture;
+mod frames;
+mod memory;
+mod messages;
+pub mod raw;
+mod ws;
+
+pub use capture::{Capture, CapturedFrame};
+pub use frames::{frame_pipe, FrameEndpoint};
+pub use memory::{memory_pipe, MemoryEndpoint, PipeConfig};
+pub use messages::{messages_connected_peers, MessagesConnection};
+pub use raw::{RawFrame, RawFrameClient};
+pub use ws::{ws_client, ws_connected_peers, ws_server, WsConnection};
+
 pub async fn connected_peers(
     server_config: Config,
     client_config: Config,
@@ -37,6 +49,33 @@ pub async fn connected_peers(
     futures::future::try_join(server, client).await
 }
 
+/// Like [`connected_peers`] but over a [`memory_pipe`] instead of TCP.
+pub fn memory_connected_peers(
+    server_config: Config,
+    client_config: Config,
+    pipe_config: PipeConfig,
+) -> (Connection<MemoryEndpoint>, Connection<MemoryEndpoint>) {
+    let (server, client) = memory_pipe(pipe_config);
+
+    (
+        Connection::new(server, server_config, Mode::Server),
+        Connection::new(client, client_config, Mode::Client),
+    )
+}
+
+/// Create a [`Connection`] under test and a [`RawFrameClient`] acting as its remote.
+pub fn raw_peer(
+    config: Config,
+    mode: Mode,
+) -> (Connection<MemoryEndpoint>, RawFrameClient<MemoryEndpoint>) {
+    let (local, remote) = memory_pipe(PipeConfig::default());
+
+    (
+        Connection::new(local, config, mode),
+        RawFrameClient::new(remote),
+    )
+}
+
 pub async fn bind() -> io::Result<(TcpListener, SocketAddr)> {
     let i = Ipv4Addr::new(127, 0, 0, 1);
     let s = SocketAddr::V4(SocketAddrV4::new(i, 0));
@@ -48,7 +87,7 @@ pub async fn bind() -> io::Result<(TcpListener, SocketAddr)> {
 /// For each incoming stream of `c` echo back to the sender.
 pub async fn echo_server<T>(mut c: Connection<T>) -> Result<(), ConnectionError>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: yamux::Transport,
 {
     stream::poll_fn(|cx| c.poll_next_inbound(cx))
         .try_for_each_concurrent(None, |mut stream| async move {
@@ -115,13 +154,19 @@ pub struct TestConfig(pub Config);
 impl Arbitrary for TestConfig {
     fn arbitrary(g: &mut Gen) -> Self {
         let mut c = Config::default();
-        c.set_window_update_mode(if bool::arbitrary(g) {
-            WindowUpdateMode::OnRead
-        } else {
-            WindowUpdateMode::OnReceive
-        });
+        c.set_window_update_mode(
+            *g.choose(&[
+                WindowUpdateMode::OnRead,
+                WindowUpdateMode::OnReceive,
+                WindowUpdateMode::Hybrid,
+            ])
+            .unwrap(),
+        );
         c.set_read_after_close(Arbitrary::arbitrary(g));
         c.set_receive_window(256 * 1024 + u32::arbitrary(g) % (768 * 1024));
+        c.set_buffer_pool(*g.choose(&[0, 16]).unwrap());
+        c.set_frame_budget(*g.choose(&[1, 16, 64]).unwrap());
+        c.set_write_batch_size(*g.choose(&[1, 16]).unwrap());
         TestConfig(c)
     }
 }
@@ -0,0 +1,240 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! An in-memory, bidirectional connection.
+//!
+//! Bytes written to one [`MemoryEndpoint`] can be read from the other. Each
+//! direction holds at most a configurable number of bytes in transit and may
+//! optionally simulate a max. packet size and a transmission latency.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::task::AtomicWaker;
+use futures::{ready, AsyncRead, AsyncWrite, FutureExt, StreamExt};
+use std::io::{ErrorKind, Result};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+/// Configuration of a [`memory_pipe`].
+///
+/// The default configuration values are as follows:
+///
+/// - capacity (per direction) = 64 KiB
+/// - max. packet size = unlimited
+/// - latency = none
+#[derive(Clone, Debug)]
+pub struct PipeConfig {
+    capacity: usize,
+    max_packet_size: usize,
+    latency: Option<Duration>,
+}
+
+impl Default for PipeConfig {
+    fn default() -> Self {
+        PipeConfig {
+            capacity: 64 * 1024,
+            max_packet_size: usize::MAX,
+            latency: None,
+        }
+    }
+}
+
+impl PipeConfig {
+    /// Set the max. number of bytes in transit per direction (must be > 0).
+    ///
+    /// # Panics
+    ///
+    /// If the given capacity is 0.
+    pub fn set_capacity(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.capacity = n;
+        self
+    }
+
+    /// Set the max. number of bytes transmitted as one packet (must be > 0).
+    ///
+    /// Larger writes are only partially accepted and every packet is delivered
+    /// separately to the reader, simulating a small MTU.
+    ///
+    /// # Panics
+    ///
+    /// If the given packet size is 0.
+    pub fn set_max_packet_size(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.max_packet_size = n;
+        self
+    }
+
+    /// Set the time it takes for a packet to be delivered to the reader.
+    ///
+    /// Simulating latency requires a tokio runtime.
+    pub fn set_latency(&mut self, d: Duration) -> &mut Self {
+        self.latency = Some(d);
+        self
+    }
+}
+
+/// Create a pair of connected [`MemoryEndpoint`]s.
+pub fn memory_pipe(config: PipeConfig) -> (MemoryEndpoint, MemoryEndpoint) {
+    let (a_to_b_sender, a_to_b_receiver) = unbounded();
+    let (b_to_a_sender, b_to_a_receiver) = unbounded();
+
+    let a_to_b_budget = Arc::new(Budget::default());
+    let b_to_a_budget = Arc::new(Budget::default());
+
+    let a = MemoryEndpoint {
+        config: config.clone(),
+        sender: a_to_b_sender,
+        send_budget: a_to_b_budget.clone(),
+        receiver: b_to_a_receiver,
+        recv_budget: b_to_a_budget.clone(),
+        recv_packet: None,
+        delay: None,
+        closed: false,
+    };
+
+    let b = MemoryEndpoint {
+        config,
+        sender: b_to_a_sender,
+        send_budget: b_to_a_budget,
+        receiver: a_to_b_receiver,
+        recv_budget: a_to_b_budget,
+        recv_packet: None,
+        delay: None,
+        closed: false,
+    };
+
+    (a, b)
+}
+
+/// One end of a [`memory_pipe`].
+pub struct MemoryEndpoint {
+    config: PipeConfig,
+    sender: UnboundedSender<Packet>,
+    send_budget: Arc<Budget>,
+    receiver: UnboundedReceiver<Packet>,
+    recv_budget: Arc<Budget>,
+    recv_packet: Option<Packet>,
+    delay: Option<Pin<Box<Sleep>>>,
+    closed: bool,
+}
+
+/// The bytes in transit in one direction.
+#[derive(Default)]
+struct Budget {
+    in_flight: AtomicUsize,
+    /// The writer waiting for bytes to be read.
+    waker: AtomicWaker,
+}
+
+struct Packet {
+    bytes: Vec<u8>,
+    offset: usize,
+    deliver_at: Option<Instant>,
+}
+
+impl AsyncRead for MemoryEndpoint {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let this = &mut *self;
+
+        if this.recv_packet.is_none() {
+            match ready!(this.receiver.poll_next_unpin(cx)) {
+                Some(packet) => this.recv_packet = Some(packet),
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+
+        let packet = this.recv_packet.as_mut().expect("packet is set above");
+
+        if let Some(deliver_at) = packet.deliver_at {
+            let delay = this
+                .delay
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deliver_at)));
+            ready!(delay.poll_unpin(cx));
+            this.delay = None;
+            packet.deliver_at = None;
+        }
+
+        let n = std::cmp::min(buf.len(), packet.bytes.len() - packet.offset);
+        buf[..n].copy_from_slice(&packet.bytes[packet.offset..packet.offset + n]);
+        packet.offset += n;
+        if packet.offset == packet.bytes.len() {
+            this.recv_packet = None;
+        }
+
+        this.recv_budget.in_flight.fetch_sub(n, Ordering::AcqRel);
+        this.recv_budget.waker.wake();
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for MemoryEndpoint {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let available = {
+            let available =
+                self.config.capacity - self.send_budget.in_flight.load(Ordering::Acquire);
+            if available == 0 {
+                self.send_budget.waker.register(cx.waker());
+                // Check again, in case the reader made room in the meantime.
+                let available =
+                    self.config.capacity - self.send_budget.in_flight.load(Ordering::Acquire);
+                if available == 0 {
+                    return Poll::Pending;
+                }
+                available
+            } else {
+                available
+            }
+        };
+
+        let n = std::cmp::min(
+            std::cmp::min(buf.len(), available),
+            self.config.max_packet_size,
+        );
+        let packet = Packet {
+            bytes: buf[..n].to_vec(),
+            offset: 0,
+            deliver_at: self.config.latency.map(|d| Instant::now() + d),
+        };
+
+        if self.closed {
+            return Poll::Ready(Err(ErrorKind::BrokenPipe.into()));
+        }
+
+        self.send_budget.in_flight.fetch_add(n, Ordering::AcqRel);
+        // Like with a TCP socket, bytes sent just before the remote endpoint
+        // went away are silently lost.
+        let _ = self.sender.unbounded_send(packet);
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<()>> {
+        self.closed = true;
+        self.sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
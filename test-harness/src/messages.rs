@@ -0,0 +1,24 @@
+//! Yamux connections over a message transport, see [`yamux::transport::messages`].
+
+use tokio::io::DuplexStream;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use yamux::transport::messages::Messages;
+use yamux::{Config, Connection, Frames, Mode};
+
+/// A [`Connection`] over length-delimited messages over an in-memory byte
+/// stream, like a single QUIC stream.
+pub type MessagesConnection =
+    Connection<Frames<Messages<Framed<DuplexStream, LengthDelimitedCodec>>>>;
+
+/// Like [`connected_peers`](crate::connected_peers) but over a message transport.
+pub fn messages_connected_peers(
+    server_config: Config,
+    client_config: Config,
+) -> (MessagesConnection, MessagesConnection) {
+    let (server, client) = tokio::io::duplex(64 * 1024);
+    let transport = |io| Messages::new(Framed::new(io, LengthDelimitedCodec::new()));
+    (
+        Connection::with_transport(transport(server), server_config, Mode::Server),
+        Connection::with_transport(transport(client), client_config, Mode::Client),
+    )
+}
@@ -0,0 +1,211 @@
+//! A peer writing and reading raw Yamux frames.
+//!
+//! In contrast to a [`yamux::Connection`] a [`RawFrameClient`] does not
+//! validate anything, which allows injecting malformed or unexpected frames
+//! (wrong flags, bogus stream IDs, oversized lengths, data after FIN, ...)
+//! into a connection under test and asserting on its reaction.
+
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::io;
+
+/// The serialised header size in bytes.
+pub const HEADER_SIZE: usize = 12;
+
+/// Data frame type.
+pub const TAG_DATA: u8 = 0;
+/// Window update frame type.
+pub const TAG_WINDOW_UPDATE: u8 = 1;
+/// Ping frame type.
+pub const TAG_PING: u8 = 2;
+/// Go away frame type.
+pub const TAG_GO_AWAY: u8 = 3;
+
+/// Indicates the start of a new stream.
+pub const SYN: u16 = 1;
+/// Acknowledges the start of a new stream.
+pub const ACK: u16 = 2;
+/// Indicates the half-closing of a stream.
+pub const FIN: u16 = 4;
+/// Indicates an immediate stream reset.
+pub const RST: u16 = 8;
+/// Releases a stream ID for reuse, if stream ID recycling is enabled.
+pub const RELEASE: u16 = 0x10;
+/// Asks the remote to stop sending data on a stream.
+pub const STOP_SENDING: u16 = 0x20;
+/// Offers, respectively indicates, LZ4 compression.
+pub const COMPRESS_LZ4: u16 = 0x40;
+/// Offers, respectively indicates, Zstandard compression.
+pub const COMPRESS_ZSTD: u16 = 0x80;
+/// Advertises stream ID recycling, on the first frame opening or
+/// acknowledging a stream.
+pub const EXT_STREAM_ID_RECYCLING: u16 = 0x01 << 8;
+/// Advertises support for `STOP_SENDING`, on the first frame opening or
+/// acknowledging a stream.
+pub const EXT_STOP_SENDING: u16 = 0x02 << 8;
+/// Advertises compression, on the first frame opening or acknowledging a
+/// stream.
+pub const EXT_COMPRESSION: u16 = 0x04 << 8;
+
+/// Go away code for a normal termination.
+pub const GO_AWAY_NORMAL: u32 = 0;
+/// Go away code for a protocol error.
+pub const GO_AWAY_PROTOCOL_ERROR: u32 = 1;
+/// Go away code for an internal error.
+pub const GO_AWAY_INTERNAL_ERROR: u32 = 2;
+
+/// A Yamux frame without any validation of its fields.
+///
+/// The `length` field is sent as is and not derived from `body`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawFrame {
+    pub version: u8,
+    pub tag: u8,
+    pub flags: u16,
+    pub stream_id: u32,
+    pub length: u32,
+    pub body: Vec<u8>,
+}
+
+impl RawFrame {
+    /// A data frame with the given body.
+    pub fn data(stream_id: u32, flags: u16, body: Vec<u8>) -> Self {
+        RawFrame {
+            version: 0,
+            tag: TAG_DATA,
+            flags,
+            stream_id,
+            length: body.len() as u32,
+            body,
+        }
+    }
+
+    /// A window update frame granting the given credit.
+    pub fn window_update(stream_id: u32, flags: u16, credit: u32) -> Self {
+        RawFrame {
+            version: 0,
+            tag: TAG_WINDOW_UPDATE,
+            flags,
+            stream_id,
+            length: credit,
+            body: Vec::new(),
+        }
+    }
+
+    /// A ping frame with the given nonce.
+    pub fn ping(flags: u16, nonce: u32) -> Self {
+        RawFrame {
+            version: 0,
+            tag: TAG_PING,
+            flags,
+            stream_id: 0,
+            length: nonce,
+            body: Vec::new(),
+        }
+    }
+
+    /// A go away frame with the given code.
+    pub fn go_away(code: u32) -> Self {
+        RawFrame {
+            version: 0,
+            tag: TAG_GO_AWAY,
+            flags: 0,
+            stream_id: 0,
+            length: code,
+            body: Vec::new(),
+        }
+    }
+
+    /// Is this a go away frame with the given code?
+    pub fn is_go_away(&self, code: u32) -> bool {
+        self.tag == TAG_GO_AWAY && self.length == code
+    }
+
+    /// Is this a frame resetting the given stream?
+    pub fn is_rst(&self, stream_id: u32) -> bool {
+        self.stream_id == stream_id && self.flags & RST == RST
+    }
+
+    /// Decode a frame header, leaving the body empty.
+    pub fn decode_header(header: &[u8; HEADER_SIZE]) -> Self {
+        RawFrame {
+            version: header[0],
+            tag: header[1],
+            flags: u16::from_be_bytes([header[2], header[3]]),
+            stream_id: u32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+            length: u32::from_be_bytes([header[8], header[9], header[10], header[11]]),
+            body: Vec::new(),
+        }
+    }
+
+    /// Encode the frame header.
+    pub fn encode_header(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0; HEADER_SIZE];
+        buf[0] = self.version;
+        buf[1] = self.tag;
+        buf[2..4].copy_from_slice(&self.flags.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.stream_id.to_be_bytes());
+        buf[8..HEADER_SIZE].copy_from_slice(&self.length.to_be_bytes());
+        buf
+    }
+}
+
+/// Writes and reads [`RawFrame`]s to and from the connection under test.
+#[derive(Debug)]
+pub struct RawFrameClient<T> {
+    io: T,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> RawFrameClient<T> {
+    pub fn new(io: T) -> Self {
+        RawFrameClient { io }
+    }
+
+    /// Send a frame as is.
+    pub async fn send(&mut self, frame: RawFrame) -> io::Result<()> {
+        self.io.write_all(&frame.encode_header()).await?;
+        self.io.write_all(&frame.body).await?;
+        self.io.flush().await
+    }
+
+    /// Send arbitrary bytes, e.g. a truncated frame.
+    pub async fn send_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.io.write_all(bytes).await?;
+        self.io.flush().await
+    }
+
+    /// Receive the next frame or `None` if the connection under test closed
+    /// the underlying I/O resource.
+    pub async fn recv(&mut self) -> io::Result<Option<RawFrame>> {
+        let mut header = [0; HEADER_SIZE];
+        match self.io.read_exact(&mut header).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let mut frame = RawFrame::decode_header(&header);
+
+        if frame.tag == TAG_DATA {
+            frame.body = vec![0; frame.length as usize];
+            self.io.read_exact(&mut frame.body).await?;
+        }
+
+        Ok(Some(frame))
+    }
+
+    /// Receive frames until one matches the given predicate.
+    ///
+    /// Returns `None` if the connection under test closed the underlying
+    /// I/O resource before.
+    pub async fn recv_until(
+        &mut self,
+        mut predicate: impl FnMut(&RawFrame) -> bool,
+    ) -> io::Result<Option<RawFrame>> {
+        while let Some(frame) = self.recv().await? {
+            if predicate(&frame) {
+                return Ok(Some(frame));
+            }
+        }
+        Ok(None)
+    }
+}
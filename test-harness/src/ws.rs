@@ -0,0 +1,53 @@
+//! Yamux connections over WebSocket, see [`yamux::transport::ws`].
+
+use async_tungstenite::{accept_async, client_async, WebSocketStream};
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+use yamux::transport::ws::WebSocket;
+use yamux::{Config, Connection, Frames, Mode};
+
+/// A [`Connection`] over a WebSocket over TCP.
+pub type WsConnection = Connection<Frames<WebSocket<WebSocketStream<Compat<TcpStream>>>>>;
+
+/// Like [`connected_peers`](crate::connected_peers) but over WebSocket.
+pub async fn ws_connected_peers(
+    server_config: Config,
+    client_config: Config,
+) -> io::Result<(WsConnection, WsConnection)> {
+    let (listener, addr) = crate::bind().await?;
+    futures::future::try_join(
+        ws_server(&listener, server_config),
+        ws_client(addr, client_config),
+    )
+    .await
+}
+
+/// Accept a WebSocket connection on the given listener and create a
+/// server [`Connection`] over it.
+pub async fn ws_server(listener: &TcpListener, config: Config) -> io::Result<WsConnection> {
+    let (stream, _) = listener.accept().await?;
+    let socket = accept_async(stream.compat())
+        .await
+        .map_err(io::Error::other)?;
+    Ok(Connection::with_transport(
+        WebSocket::new(socket),
+        config,
+        Mode::Server,
+    ))
+}
+
+/// Connect to the WebSocket server at the given address and create a
+/// client [`Connection`] over it.
+pub async fn ws_client(addr: SocketAddr, config: Config) -> io::Result<WsConnection> {
+    let stream = TcpStream::connect(addr).await?;
+    let (socket, _) = client_async(format!("ws://{}/", addr), stream.compat())
+        .await
+        .map_err(io::Error::other)?;
+    Ok(Connection::with_transport(
+        WebSocket::new(socket),
+        config,
+        Mode::Client,
+    ))
+}
@@ -0,0 +1,152 @@
+use futures::{future::poll_fn, AsyncRead, AsyncWrite};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use yamux::{Config, Connection, Mode, Stream};
+use yamux_core::header::{self, Header, StreamId};
+
+/// Counts the allocations of threads which are counting, i.e. not those of
+/// the test runner, see [`count_allocations`].
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn count() {
+    if COUNTING.try_with(Cell::get).unwrap_or(false) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The number of allocations of the current thread while running `f`.
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    COUNTING.with(|c| c.set(true));
+    f();
+    COUNTING.with(|c| c.set(false));
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const BODY_LEN: usize = 64;
+
+/// A stream reading from shared, pre-allocated input and discarding writes.
+struct Socket(Arc<Mutex<VecDeque<u8>>>);
+
+impl AsyncRead for Socket {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut input = self.0.lock().unwrap();
+        if input.is_empty() {
+            return Poll::Pending;
+        }
+        let n = std::cmp::min(buf.len(), input.len());
+        for (b, x) in buf.iter_mut().zip(input.drain(..n)) {
+            *b = x
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for Socket {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Receive one small data frame and read it from the stream.
+fn receive(
+    connection: &mut Connection<Socket>,
+    stream: &mut Stream,
+    input: &Mutex<VecDeque<u8>>,
+    cx: &mut Context<'_>,
+) {
+    {
+        let mut input = input.lock().unwrap();
+        input.extend(header::encode(&Header::data(
+            StreamId::new(1),
+            BODY_LEN as u32,
+        )));
+        input.extend([1; BODY_LEN]);
+    }
+    assert!(connection.poll_next_inbound(cx).is_pending());
+    let mut buf = [0; BODY_LEN];
+    match Pin::new(stream).poll_read(cx, &mut buf) {
+        Poll::Ready(Ok(n)) => assert_eq!(n, BODY_LEN),
+        other => panic!("unexpected read: {:?}", other),
+    }
+}
+
+#[test]
+fn small_frames_are_received_without_allocating() {
+    let input = Arc::new(Mutex::new(VecDeque::with_capacity(1024)));
+    let socket = Socket(input.clone());
+    let mut connection = Connection::new(socket, Config::default(), Mode::Server);
+
+    futures::executor::block_on(poll_fn(|cx| {
+        let mut syn = Header::data(StreamId::new(1), 0);
+        syn.syn();
+        input.lock().unwrap().extend(header::encode(&syn));
+        let mut stream = match connection.poll_next_inbound(cx) {
+            Poll::Ready(Some(Ok(stream))) => stream,
+            other => panic!(
+                "unexpected inbound stream: {:?}",
+                other.map(|s| s.is_some())
+            ),
+        };
+
+        // Let buffers reach their steady state.
+        for _ in 0..100 {
+            receive(&mut connection, &mut stream, &input, cx)
+        }
+
+        let n = count_allocations(|| {
+            for _ in 0..1000 {
+                receive(&mut connection, &mut stream, &input, cx)
+            }
+        });
+        assert_eq!(n, 0);
+        Poll::Ready(())
+    }))
+}
@@ -0,0 +1,173 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+use test_harness::*;
+use tokio::runtime::Runtime;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use yamux::{blocking, Config, Connection, Mode};
+use yamux_core::{
+    header::{self, Header, StreamId, HEADER_SIZE},
+    session::SessionError,
+};
+
+/// A blocking client echoes data over an async server.
+#[test]
+fn blocking_client_async_server() {
+    let rt = Runtime::new().unwrap();
+    let listener = rt
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    rt.spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        echo_server(Connection::new(
+            socket.compat(),
+            Config::default(),
+            Mode::Server,
+        ))
+        .await
+        .unwrap()
+    });
+
+    let connection =
+        blocking::Connection::new(TcpStream::connect(addr).unwrap(), Mode::Client).unwrap();
+    let streams = (0..4)
+        .map(|i| {
+            let stream = connection.open_stream().unwrap();
+            let data = vec![i as u8; 1024 * 1024];
+            thread::spawn(move || {
+                let mut echoed = Vec::new();
+                thread::scope(|s| {
+                    s.spawn(|| {
+                        (&stream).write_all(&data).unwrap();
+                        stream.close().unwrap();
+                    });
+                    (&stream).read_to_end(&mut echoed).unwrap();
+                });
+                assert_eq!(echoed, data)
+            })
+        })
+        .collect::<Vec<_>>();
+    for stream in streams {
+        stream.join().unwrap()
+    }
+    connection.close().unwrap()
+}
+
+/// A blocking server accepts a stream of another blocking connection.
+#[test]
+fn blocking_server_accepts_streams() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        let connection = blocking::Connection::new(socket, Mode::Server).unwrap();
+        let mut stream = connection.accept().unwrap();
+        let mut msg = Vec::new();
+        stream.read_to_end(&mut msg).unwrap();
+        stream.write_all(&msg).unwrap();
+        stream.close().unwrap();
+        assert!(connection.accept().is_none())
+    });
+
+    let connection =
+        blocking::Connection::new(TcpStream::connect(addr).unwrap(), Mode::Client).unwrap();
+    let mut stream = connection.open_stream().unwrap();
+    stream.write_all(b"hello").unwrap();
+    stream.close().unwrap();
+    let mut echoed = Vec::new();
+    stream.read_to_end(&mut echoed).unwrap();
+    assert_eq!(echoed, b"hello");
+    connection.close().unwrap();
+    server.join().unwrap()
+}
+
+/// Both sides write in bulk on many streams at once, which must not
+/// deadlock with the service threads of either side waiting for the other
+/// to read.
+#[test]
+fn bulk_writes_in_both_directions() {
+    const STREAMS: usize = 64;
+    const LEN: usize = 1024 * 1024;
+
+    /// Write to and read from the stream at once, checking what was read.
+    fn exchange(stream: blocking::Stream, send: u8, recv: u8) {
+        thread::scope(|s| {
+            s.spawn(|| {
+                (&stream).write_all(&vec![send; LEN]).unwrap();
+                stream.close().unwrap();
+            });
+            let mut received = Vec::new();
+            (&stream).read_to_end(&mut received).unwrap();
+            assert_eq!(received.len(), LEN);
+            assert!(received.iter().all(|b| *b == recv));
+        })
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        let connection = blocking::Connection::new(socket, Mode::Server).unwrap();
+        thread::scope(|s| {
+            for _ in 0..STREAMS {
+                let stream = connection.accept().unwrap();
+                s.spawn(move || exchange(stream, 2, 1));
+            }
+        });
+        connection.close().unwrap()
+    });
+
+    let connection =
+        blocking::Connection::new(TcpStream::connect(addr).unwrap(), Mode::Client).unwrap();
+    thread::scope(|s| {
+        for _ in 0..STREAMS {
+            let stream = connection.open_stream().unwrap();
+            s.spawn(move || exchange(stream, 1, 2));
+        }
+    });
+    server.join().unwrap();
+    connection.close().unwrap()
+}
+
+/// A remote violating the protocol fails blocked reads with the error of
+/// the session.
+#[test]
+fn session_error_is_reported_to_readers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        let connection = blocking::Connection::new(socket, Mode::Server).unwrap();
+        let mut stream = connection.accept().unwrap();
+        stream.read(&mut [0; 8]).unwrap_err()
+    });
+
+    let mut socket = TcpStream::connect(addr).unwrap();
+    let mut syn = Header::window_update(StreamId::new(1), 0);
+    syn.syn();
+    socket.write_all(&header::encode(&syn)).unwrap();
+    // Wait for the stream to be acknowledged, i.e. accepted.
+    socket.read_exact(&mut [0; HEADER_SIZE]).unwrap();
+    socket.write_all(&[0xff; HEADER_SIZE]).unwrap();
+    // Read the `GoAway` until the remote closes the socket.
+    socket.read_to_end(&mut Vec::new()).unwrap();
+    drop(socket);
+
+    let e = server.join().unwrap();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    let e = e.into_inner().unwrap().downcast::<SessionError>().unwrap();
+    assert!(matches!(*e, SessionError::Decode(_)), "{:?}", e)
+}
@@ -0,0 +1,110 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::{future, prelude::*, select_biased, AsyncReadExt};
+use std::time::Duration;
+use test_harness::raw::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Connection, Control, Mode};
+
+/// Poll the given future once and drop it if it is not ready.
+async fn poll_once<F: Future + Unpin>(f: F) -> Option<F::Output> {
+    let mut f = f.fuse();
+    select_biased! {
+        x = f => Some(x),
+        _ = future::ready(()).fuse() => None,
+    }
+}
+
+#[test]
+fn reads_cancelled_by_select_lose_no_data() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let data = (0..512 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let stream = control.open_stream().await.unwrap();
+        let (mut reader, mut writer) = AsyncReadExt::split(stream);
+        let write = async {
+            writer.write_all(&data).await.unwrap();
+            writer.close().await.unwrap();
+        };
+        let read = async {
+            let mut echoed = Vec::new();
+            let mut buf = [0; 1000];
+            loop {
+                match poll_once(reader.read(&mut buf)).await {
+                    Some(Ok(0)) => return echoed,
+                    Some(Ok(n)) => echoed.extend_from_slice(&buf[..n]),
+                    Some(Err(e)) => panic!("{}", e),
+                    None => task::yield_now().await,
+                }
+            }
+        };
+        let ((), echoed) = futures::join!(write, read);
+        assert!(echoed == data);
+    })
+}
+
+#[test]
+fn cancelled_protocol_tag_read_resumes() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+
+        remote
+            .send(RawFrame::data(1, SYN, vec![0x12]))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        // Only the first byte of the tag has been received.
+        assert!(poll_once(stream.protocol_tag().boxed()).await.is_none());
+        let (_control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        remote
+            .send(RawFrame::data(1, 0, vec![0x34, 7]))
+            .await
+            .unwrap();
+        assert_eq!(stream.protocol_tag().await.unwrap(), 0x1234);
+        let mut byte = [0];
+        stream.read_exact(&mut byte).await.unwrap();
+        assert_eq!(byte, [7]);
+    })
+}
+
+#[test]
+fn cancelled_open_stream_is_abandoned() {
+    Runtime::new().unwrap().block_on(async {
+        let (local, _remote) = memory_pipe(PipeConfig::default());
+        let mut config = Config::default();
+        config.set_max_ack_backlog(1);
+        let connection = Connection::new(local, config, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // The remote never acknowledges the first stream.
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let open = tokio::time::timeout(Duration::from_millis(100), control.open_stream());
+        assert!(open.await.is_err());
+
+        // The connection no longer waits to open the second stream and
+        // serves other commands.
+        let stats = tokio::time::timeout(Duration::from_secs(5), control.stats());
+        assert_eq!(stats.await.unwrap().unwrap().num_streams, 1);
+    })
+}
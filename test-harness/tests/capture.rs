@@ -0,0 +1,51 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use std::sync::Arc;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Control, FrameDirection, Mode};
+
+#[test]
+fn captured_frames_replay_identically() {
+    Runtime::new().unwrap().block_on(async {
+        let capture = Capture::new();
+        let mut server_cfg = Config::default();
+        server_cfg.set_frame_recorder(Arc::new(capture.clone()));
+        let (server, client) =
+            memory_connected_peers(server_cfg, Config::default(), PipeConfig::default());
+        task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        for msg in [&b"hello"[..], &[7; 1000][..]] {
+            let mut stream = control.open_stream().await.unwrap();
+            stream.write_all(msg).await.unwrap();
+            stream.close().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, msg);
+        }
+
+        let inbound = capture.frames_in(FrameDirection::Inbound);
+        let outbound = capture.frames_in(FrameDirection::Outbound);
+        assert_eq!(inbound[0], RawFrame::data(1, raw::SYN, b"hello".to_vec()));
+        assert_eq!(outbound[0], RawFrame::data(1, raw::ACK, b"hello".to_vec()));
+
+        // A new server reacts to the captured frames exactly like the first.
+        let (server, mut peer) = raw_peer(Config::default(), Mode::Server);
+        task::spawn(echo_server(server));
+        capture::replay(&capture.frames(), &mut peer).await.unwrap();
+        for expected in outbound {
+            assert_eq!(peer.recv().await.unwrap(), Some(expected));
+        }
+    })
+}
@@ -0,0 +1,202 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::{AsyncReadExt, AsyncWriteExt, TryStreamExt};
+use test_harness::raw::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Compression, Config, Control, Mode};
+
+/// Data which compresses well.
+fn compressible(len: usize) -> Vec<u8> {
+    b"hello yamux ".iter().copied().cycle().take(len).collect()
+}
+
+fn config(compression: Compression) -> Config {
+    let mut cfg = Config::default();
+    cfg.set_compression(compression);
+    cfg
+}
+
+#[test]
+fn compressed_streams_round_trip() {
+    let cases = [
+        (Compression::Lz4, Compression::Lz4),
+        (Compression::Zstd(3), Compression::Zstd(3)),
+        // Peers with different algorithms do not compress.
+        (Compression::Lz4, Compression::Zstd(3)),
+        (Compression::None, Compression::Lz4),
+    ];
+    for (server_compression, client_compression) in cases {
+        Runtime::new().unwrap().block_on(async {
+            let (server, client) = memory_connected_peers(
+                config(server_compression),
+                config(client_compression),
+                PipeConfig::default(),
+            );
+            task::spawn(echo_server(server));
+            let (mut control, client) = Control::new(client);
+            task::spawn(noop_server(client));
+
+            // The first stream negotiates the extensions and is never compressed.
+            for _ in 0..2 {
+                let data = compressible(1024 * 1024);
+                let stream = control.open_stream().await.unwrap();
+                let (mut reader, mut writer) = AsyncReadExt::split(stream);
+                let write = async {
+                    writer.write_all(&data).await.unwrap();
+                    writer.close().await.unwrap();
+                };
+                let mut echoed = Vec::new();
+                let read = reader.read_to_end(&mut echoed);
+                let ((), n) = futures::join!(write, read);
+                assert_eq!(n.unwrap(), data.len());
+                assert!(echoed == data);
+            }
+        })
+    }
+}
+
+#[test]
+fn compression_is_negotiated_per_stream() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config(Compression::Lz4), Mode::Server);
+        let (_control, connection) = Control::new(connection);
+        task::spawn(
+            connection.try_for_each_concurrent(None, |mut stream| async move {
+                let data = compressible(16 * 1024);
+                stream.write_all(&data).await?;
+                stream.write_all(&data).await?;
+                stream.close().await?;
+                Ok(())
+            }),
+        );
+
+        // The first frame acknowledges the stream and is never compressed.
+        remote
+            .send(RawFrame::data(
+                1,
+                SYN | COMPRESS_LZ4 | EXT_COMPRESSION,
+                Vec::new(),
+            ))
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame.flags, ACK | COMPRESS_LZ4 | EXT_COMPRESSION);
+        assert_eq!(frame.body, compressible(16 * 1024));
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame.flags, COMPRESS_LZ4);
+        assert!(frame.body.len() < 1024, "{:?}", frame);
+
+        // A remote which does not offer compression receives plain data.
+        remote
+            .send(RawFrame::data(3, SYN, Vec::new()))
+            .await
+            .unwrap();
+        let frame = remote
+            .recv_until(|f| f.stream_id == 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.flags, ACK);
+        let frame = remote
+            .recv_until(|f| f.stream_id == 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.flags, 0);
+        assert_eq!(frame.body, compressible(16 * 1024));
+    })
+}
+
+#[test]
+fn compression_is_offered_once_negotiated() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config(Compression::Lz4), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // The first stream advertises compression, but does not offer it.
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"1").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame.flags, SYN | EXT_COMPRESSION);
+        remote
+            .send(RawFrame::window_update(1, ACK | EXT_COMPRESSION, 0))
+            .await
+            .unwrap();
+        remote.send(RawFrame::ping(SYN, 1)).await.unwrap();
+        remote.recv_until(|f| f.tag == TAG_PING).await.unwrap();
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"3").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame.stream_id, 3);
+        assert_eq!(frame.flags, SYN | COMPRESS_LZ4);
+    })
+}
+
+#[test]
+fn compression_is_not_offered_unless_negotiated() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config(Compression::Lz4), Mode::Server);
+        let (_control, connection) = Control::new(connection);
+        task::spawn(
+            connection.try_for_each_concurrent(None, |mut stream| async move {
+                let data = compressible(16 * 1024);
+                stream.write_all(&data).await?;
+                stream.write_all(&data).await?;
+                stream.close().await?;
+                Ok(())
+            }),
+        );
+
+        // A remote which does not advertise compression is neither offered
+        // compression nor sent compressed data, even if it asks for it.
+        remote
+            .send(RawFrame::data(1, SYN | COMPRESS_LZ4, Vec::new()))
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame.flags, ACK | EXT_COMPRESSION);
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame.flags, 0);
+        assert_eq!(frame.body, compressible(16 * 1024));
+    })
+}
+
+#[test]
+fn undecompressable_frame_is_protocol_error() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config(Compression::Lz4), Mode::Server);
+        let (_control, connection) = Control::new(connection);
+        task::spawn(connection.try_collect::<Vec<_>>());
+
+        remote
+            .send(RawFrame::data(
+                1,
+                SYN | COMPRESS_LZ4 | EXT_COMPRESSION,
+                Vec::new(),
+            ))
+            .await
+            .unwrap();
+        remote
+            .send(RawFrame::data(1, COMPRESS_LZ4, vec![0xff; 8]))
+            .await
+            .unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+    })
+}
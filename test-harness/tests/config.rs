@@ -0,0 +1,251 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use test_harness::{raw, *};
+use tokio::{runtime::Runtime, task};
+use yamux::{
+    consts, Compression, Config, ConfigError, Control, Extensions, Preset, ProbeMode,
+    WindowUpdateMode,
+};
+
+#[test]
+fn config_round_trips_through_serde() {
+    let mut cfg = Config::default();
+    cfg.set_receive_window(1024 * 1024)
+        .set_window_update_mode(WindowUpdateMode::OnReceive)
+        .set_idle_timeout(Duration::from_secs(5))
+        .set_coalesce_writes(Duration::from_millis(1), 1024)
+        .set_liveness_probe(ProbeMode::Both {
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+        })
+        .set_compression(Compression::Zstd(3))
+        .set_extensions(Extensions::from_bits(5))
+        .set_rng_seed(7);
+    let value = serde_json::to_value(&cfg).unwrap();
+    assert_eq!(value["receive_window"], 1024 * 1024);
+    assert_eq!(value["rng_seed"], 7);
+    assert_eq!(value["window_update_mode"], "on_receive");
+    assert_eq!(value["idle_timeout_ms"], 5000);
+    assert_eq!(
+        value["coalesce_writes"],
+        json!({ "max_delay_ms": 1, "max_bytes": 1024 })
+    );
+    assert_eq!(
+        value["liveness_probe"],
+        json!({ "mode": "both", "interval_ms": 10000, "timeout_ms": 30000 })
+    );
+    assert_eq!(value["compression"], json!({ "zstd": 3 }));
+    assert_eq!(value["extensions"], 5);
+
+    let parsed: Config = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), value);
+}
+
+#[test]
+fn config_deserialization_validates() {
+    // Missing settings keep their defaults.
+    let cfg: Config = serde_json::from_value(json!({ "max_inbound_streams": 16 })).unwrap();
+    let value = serde_json::to_value(&cfg).unwrap();
+    assert_eq!(value["max_inbound_streams"], 16);
+    assert_eq!(value["max_outbound_streams"], serde_json::Value::Null);
+    assert_eq!(value["max_num_streams"], 8192);
+    assert_eq!(value["receive_window"], yamux::DEFAULT_CREDIT);
+
+    let e = serde_json::from_value::<Config>(json!({ "receive_window": 1 })).unwrap_err();
+    assert!(e.to_string().contains("receive_window"), "{}", e);
+
+    let e = serde_json::from_value::<Config>(json!({ "receive_windw": 1 })).unwrap_err();
+    assert!(e.to_string().contains("unknown field"), "{}", e);
+}
+
+#[test]
+fn config_validate_rejects_inconsistent_settings() {
+    let mut cfg = Config::default();
+    assert!(cfg.validate().is_ok());
+    cfg.set_max_frame_body_len(1024).set_split_send_size(2048);
+    assert_eq!(cfg.validate().unwrap_err().setting(), "split_send_size");
+
+    if let Some(n) = consts::MAX_FRAME_BODY_LEN.checked_add(1) {
+        let mut cfg = Config::default();
+        cfg.set_max_frame_body_len(n);
+        assert_eq!(cfg.validate().unwrap_err().setting(), "max_frame_body_len");
+    }
+}
+
+#[test]
+fn consts_match_the_protocol() {
+    assert_eq!(consts::PROTOCOL_VERSION, 0);
+    assert_eq!(consts::HEADER_SIZE, raw::HEADER_SIZE);
+    assert_eq!(consts::DEFAULT_RECEIVE_WINDOW, 256 * 1024);
+    assert_eq!(consts::MAX_FRAME_BODY_LEN, u32::MAX as usize);
+    for (flag, bits) in [
+        (consts::FLAG_SYN, raw::SYN),
+        (consts::FLAG_ACK, raw::ACK),
+        (consts::FLAG_FIN, raw::FIN),
+        (consts::FLAG_RST, raw::RST),
+        (consts::FLAG_RELEASE, raw::RELEASE),
+        (consts::FLAG_STOP_SENDING, raw::STOP_SENDING),
+        (consts::FLAG_COMPRESS_LZ4, raw::COMPRESS_LZ4),
+        (consts::FLAG_COMPRESS_ZSTD, raw::COMPRESS_ZSTD),
+    ] {
+        assert_eq!(flag.val(), bits);
+    }
+    assert_eq!(consts::GO_AWAY_NORMAL, raw::GO_AWAY_NORMAL);
+    assert_eq!(consts::GO_AWAY_PROTOCOL_ERROR, raw::GO_AWAY_PROTOCOL_ERROR);
+    assert_eq!(consts::GO_AWAY_INTERNAL_ERROR, raw::GO_AWAY_INTERNAL_ERROR);
+
+    // The defaults pass validation.
+    let mut cfg = Config::default();
+    cfg.set_split_send_size(consts::DEFAULT_SPLIT_SEND_SIZE)
+        .set_max_frame_body_len(consts::DEFAULT_MAX_FRAME_BODY_LEN);
+    assert!(cfg.validate().is_ok());
+}
+
+#[test]
+fn config_from_vars() {
+    let mut env = HashMap::new();
+    env.insert("YAMUX_MAX_NUM_STREAMS", "32");
+    env.insert("YAMUX_WINDOW_UPDATE_MODE", "hybrid");
+    env.insert("YAMUX_STREAM_OPEN_TIMEOUT_MS", "1500");
+    let cfg = from_vars(&env).unwrap();
+    let value = serde_json::to_value(&cfg).unwrap();
+    assert_eq!(value["max_num_streams"], 32);
+    assert_eq!(value["window_update_mode"], "hybrid");
+    assert_eq!(value["stream_open_timeout_ms"], 1500);
+
+    // Inbound streams are limited further.
+    env.insert("YAMUX_MAX_INBOUND_STREAMS", "8");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["max_inbound_streams"], 8);
+    assert_eq!(value["max_num_streams"], 32);
+    env.remove("YAMUX_MAX_INBOUND_STREAMS");
+
+    env.insert("YAMUX_LIVENESS_PROBE", "keep_alive");
+    env.insert("YAMUX_LIVENESS_PROBE_TIMEOUT_MS", "2000");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "liveness_probe");
+    env.insert("YAMUX_LIVENESS_PROBE_INTERVAL_MS", "500");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(
+        value["liveness_probe"],
+        json!({ "mode": "keep_alive", "interval_ms": 500, "timeout_ms": 2000 })
+    );
+
+    env.insert("YAMUX_WINDOW_UPDATE_THRESHOLD", "0.25");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(
+        value["window_update_threshold"],
+        json!({ "fraction": 0.25 })
+    );
+    env.insert("YAMUX_WINDOW_UPDATE_THRESHOLD", "1.5");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "window_update_threshold");
+    env.insert("YAMUX_WINDOW_UPDATE_THRESHOLD", "4096");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["window_update_threshold"], json!({ "bytes": 4096 }));
+
+    env.insert("YAMUX_COMPRESSION_LEVEL", "5");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "compression");
+    env.insert("YAMUX_COMPRESSION", "zstd");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["compression"], json!({ "zstd": 5 }));
+    env.insert("YAMUX_COMPRESSION", "lz4");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "compression");
+    env.remove("YAMUX_COMPRESSION_LEVEL");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["compression"], "lz4");
+
+    env.insert("YAMUX_EXTENSIONS", "256");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "extensions");
+    env.insert("YAMUX_EXTENSIONS", "3");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["extensions"], 3);
+    env.remove("YAMUX_EXTENSIONS");
+
+    env.insert("YAMUX_PRESET", "low_memory");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["max_connection_receive_window"], 4 * 1024 * 1024);
+    assert_eq!(value["max_num_streams"], 32);
+    env.insert("YAMUX_PRESET", "fast");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "preset");
+    env.remove("YAMUX_PRESET");
+
+    env.insert("YAMUX_STALL_THRESHOLD_MS", "0");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "stall_threshold");
+    env.insert("YAMUX_STALL_THRESHOLD_MS", "10000");
+    let value = serde_json::to_value(from_vars(&env).unwrap()).unwrap();
+    assert_eq!(value["stall_threshold_ms"], 10000);
+    env.remove("YAMUX_STALL_THRESHOLD_MS");
+
+    env.insert("YAMUX_FRAME_BUDGET", "many");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "frame_budget");
+    env.insert("YAMUX_FRAME_BUDGET", "0");
+    let e = from_vars(&env).unwrap_err();
+    assert_eq!(e.setting(), "frame_budget");
+}
+
+fn from_vars(env: &HashMap<&str, &str>) -> Result<Config, ConfigError> {
+    Config::from_vars(env.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+}
+
+#[test]
+fn presets_are_valid_and_interoperate() {
+    let presets = [
+        Preset::LowLatency,
+        Preset::HighThroughput,
+        Preset::LowMemory,
+        Preset::Mobile,
+    ];
+    for preset in presets {
+        Config::preset(preset).validate().unwrap();
+    }
+    let configs = presets
+        .iter()
+        .map(|p| Config::preset(*p))
+        .chain(Some(Config::default()))
+        .collect::<Vec<_>>();
+    for server_config in &configs {
+        for client_config in &configs {
+            Runtime::new().unwrap().block_on(async {
+                let (server, client) = memory_connected_peers(
+                    server_config.clone(),
+                    client_config.clone(),
+                    PipeConfig::default(),
+                );
+                task::spawn(echo_server(server));
+                let (mut control, client) = Control::new(client);
+                task::spawn(noop_server(client));
+
+                let data = vec![0x42; 1024 * 1024];
+                let stream = control.open_stream().await.unwrap();
+                let (mut reader, mut writer) = AsyncReadExt::split(stream);
+                let write = async {
+                    writer.write_all(&data).await.unwrap();
+                    writer.close().await.unwrap();
+                };
+                let mut echoed = Vec::new();
+                let read = reader.read_to_end(&mut echoed);
+                let ((), n) = futures::join!(write, read);
+                assert_eq!(n.unwrap(), data.len());
+            })
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Replays the frame logs in `tests/conformance`, see its README.
+
+use futures::prelude::*;
+use test_harness::*;
+use tokio::{net::TcpStream, runtime::Runtime, task};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use yamux::{Config, Connection, Control, Mode};
+
+#[test]
+fn server_echo() {
+    replay(include_str!("conformance/server_echo.session"))
+}
+
+#[test]
+fn client_open() {
+    replay(include_str!("conformance/client_open.session"))
+}
+
+#[test]
+fn protocol_error() {
+    replay(include_str!("conformance/protocol_error.session"))
+}
+
+#[test]
+fn go_away_error() {
+    replay(include_str!("conformance/go_away_error.session"))
+}
+
+#[test]
+fn unknown_version() {
+    replay(include_str!("conformance/unknown_version.session"))
+}
+
+/// Echo over a live go-yamux server, e.g. a program serving
+/// `yamux.Server(conn, nil)` and echoing each accepted stream, listening at
+/// the address given by `YAMUX_INTEROP_ADDR`.
+#[test]
+#[ignore]
+fn go_yamux_echo() {
+    let addr = std::env::var("YAMUX_INTEROP_ADDR").expect("YAMUX_INTEROP_ADDR is set");
+    Runtime::new().unwrap().block_on(async {
+        let socket = TcpStream::connect(addr).await.unwrap();
+        let connection = Connection::new(socket.compat(), Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        for size in [1, 1024, 1024 * 1024] {
+            let stream = control.open_stream().await.unwrap();
+            let data = vec![0x42; size];
+            let (mut reader, mut writer) = stream.into_split();
+            let write = async {
+                writer.write_all(&data).await.unwrap();
+                writer.close().await.unwrap();
+            };
+            let mut echoed = Vec::new();
+            let read = reader.read_to_end(&mut echoed);
+            let (_, read) = future::join(write, read).await;
+            read.unwrap();
+            assert_eq!(echoed, data);
+        }
+        control.close().await.unwrap();
+    })
+}
+
+/// Replay the given session, panicking on the first deviation.
+fn replay(session: &str) {
+    let mut lines = session
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'));
+    let mode = match lines.next() {
+        Some("mode server") => Mode::Server,
+        Some("mode client") => Mode::Client,
+        other => panic!("invalid mode: {:?}", other),
+    };
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut peer) = raw_peer(Config::default(), mode);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(
+            connection.try_for_each_concurrent(None, |mut stream| async move {
+                {
+                    let (mut r, mut w) = futures::io::AsyncReadExt::split(&mut stream);
+                    let _ = futures::io::copy(&mut r, &mut w).await;
+                }
+                let _ = stream.close().await;
+                Ok(())
+            }),
+        );
+        let mut stream = None;
+
+        for line in lines {
+            let (op, arg) = line.split_at(1);
+            let arg = arg.trim();
+            match (op, arg.split_once(' ').unwrap_or((arg, ""))) {
+                (">", _) => peer.send_bytes(&hex(arg)).await.unwrap(),
+                ("<", ("eof", _)) => {
+                    let frame = peer.recv().await.unwrap();
+                    assert!(frame.is_none(), "{}: got {:?}", line, frame)
+                }
+                ("<", _) => {
+                    let frame = peer.recv().await.unwrap().expect(line);
+                    let mut bytes = frame.encode_header().to_vec();
+                    bytes.extend_from_slice(&frame.body);
+                    assert_eq!(bytes, hex(arg), "{}: got {:?}", line, frame)
+                }
+                ("!", ("open", _)) => stream = Some(control.open_stream().await.unwrap()),
+                ("!", ("write", data)) => {
+                    let s = stream.as_mut().expect("open stream");
+                    s.write_all(&hex(data)).await.unwrap();
+                    s.flush().await.unwrap()
+                }
+                ("!", ("read", data)) => {
+                    let s = stream.as_mut().expect("open stream");
+                    let mut buf = vec![0; hex(data).len()];
+                    s.read_exact(&mut buf).await.unwrap();
+                    assert_eq!(buf, hex(data), "{}", line)
+                }
+                ("!", ("close", _)) => stream.as_mut().expect("open stream").close().await.unwrap(),
+                _ => panic!("invalid line: {}", line),
+            }
+        }
+    })
+}
+
+/// Decode hex digits, ignoring whitespace.
+fn hex(s: &str) -> Vec<u8> {
+    let digits = s
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).expect("hex digit") as u8)
+        .collect::<Vec<_>>();
+    assert!(digits.len() % 2 == 0, "odd number of hex digits: {}", s);
+    digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect()
+}
@@ -0,0 +1,60 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{io::copy_bidirectional, Config, Control};
+
+#[test]
+fn copy_bidirectional_propagates_half_close() {
+    Runtime::new().unwrap().block_on(async {
+        // client -> proxy
+        let (proxy_server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        // proxy -> server
+        let (server, proxy_client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+
+        let (mut client, client_conn) = Control::new(client);
+        task::spawn(noop_server(client_conn));
+        let (mut proxy, proxy_client) = Control::new(proxy_client);
+        task::spawn(noop_server(proxy_client));
+        let (_server, mut server) = Control::new(server);
+        let (_proxy_server, mut proxy_server) = Control::new(proxy_server);
+
+        let proxy = task::spawn(async move {
+            let mut inbound = proxy_server.next().await.unwrap().unwrap();
+            task::spawn(proxy_server.for_each(|_| future::ready(())));
+            let mut outbound = proxy.open_stream().await.unwrap();
+            copy_bidirectional(&mut inbound, &mut outbound)
+                .await
+                .unwrap()
+        });
+
+        let mut stream = client.open_stream().await.unwrap();
+        stream.write_all(&[1; 100_000]).await.unwrap();
+        stream.close().await.unwrap();
+
+        // The server sees the FIN of the client and replies afterwards.
+        let mut inbound = server.next().await.unwrap().unwrap();
+        task::spawn(server.for_each(|_| future::ready(())));
+        let mut buf = Vec::new();
+        inbound.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![1; 100_000]);
+        inbound.write_all(&[2; 50_000]).await.unwrap();
+        inbound.close().await.unwrap();
+
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![2; 50_000]);
+        assert_eq!(proxy.await.unwrap(), (100_000, 50_000));
+    })
+}
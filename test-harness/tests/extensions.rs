@@ -0,0 +1,131 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::{future, Future};
+use std::{pin::pin, task::Poll, time::Duration};
+use test_harness::raw::*;
+use test_harness::*;
+use tokio::runtime::Runtime;
+use yamux::{Config, Connection, Extensions, Mode, OpenMode};
+
+fn config(extensions: u8) -> Config {
+    let mut cfg = Config::default();
+    cfg.set_extensions(Extensions::from_bits(extensions))
+        .set_open_mode(OpenMode::Eager);
+    cfg
+}
+
+/// Drive the given connection until the given future completes.
+async fn drive<T, F>(connection: &mut Connection<T>, f: F) -> F::Output
+where
+    T: futures::AsyncRead + futures::AsyncWrite + Unpin,
+    F: Future,
+{
+    let mut f = pin!(f);
+    future::poll_fn(|cx| {
+        if let Poll::Ready(x) = f.as_mut().poll(cx) {
+            return Poll::Ready(x);
+        }
+        while let Poll::Ready(Some(Ok(_))) = connection.poll_next_inbound(cx) {}
+        Poll::Pending
+    })
+    .await
+}
+
+#[test]
+fn extensions_are_negotiated_on_the_first_syn() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config(0b101), Mode::Server);
+        assert_eq!(connection.negotiated_extensions(), None);
+
+        remote
+            .send(RawFrame::data(1, SYN | 0b110 << 8, vec![1]))
+            .await
+            .unwrap();
+        let _stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let negotiated = Extensions::from_bits(0b100);
+        assert_eq!(connection.negotiated_extensions(), Some(negotiated));
+
+        // Later frames do not change the negotiated extensions.
+        remote
+            .send(RawFrame::data(3, SYN | 0b111 << 8, vec![1]))
+            .await
+            .unwrap();
+        let _stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(connection.negotiated_extensions(), Some(negotiated));
+    })
+}
+
+#[test]
+fn extensions_are_advertised_once_and_acknowledged() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config(0b101), Mode::Client);
+
+        let _s1 = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        let _s2 = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        let syn = drive(&mut connection, remote.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(syn.flags, SYN | 0b101 << 8, "{:?}", syn);
+        let syn = drive(&mut connection, remote.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(syn.flags, SYN, "{:?}", syn);
+        assert_eq!(connection.negotiated_extensions(), None);
+
+        remote
+            .send(RawFrame::window_update(1, ACK | 0b001 << 8, 0))
+            .await
+            .unwrap();
+        drive(
+            &mut connection,
+            tokio::time::sleep(Duration::from_millis(50)),
+        )
+        .await;
+        let negotiated = Extensions::from_bits(0b001);
+        assert_eq!(connection.negotiated_extensions(), Some(negotiated));
+    })
+}
+
+#[test]
+fn nothing_is_negotiated_with_stock_peers() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config(0b101), Mode::Server);
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        let _stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(connection.negotiated_extensions(), Some(Extensions::NONE));
+
+        // Pings carry `SYN` and `ACK` flags too, but do not negotiate.
+        let (mut connection, mut remote) = raw_peer(config(0b101), Mode::Server);
+        remote.send(RawFrame::ping(SYN, 1)).await.unwrap();
+        let pong = drive(&mut connection, remote.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pong, RawFrame::ping(ACK, 1));
+        assert_eq!(connection.negotiated_extensions(), None);
+    })
+}
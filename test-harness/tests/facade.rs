@@ -0,0 +1,146 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use test_harness::*;
+use tokio::{net::TcpStream, runtime::Runtime, task};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use yamux::{client::Connector, server::Listener, Config, ConnectionError, Spawn};
+
+#[test]
+fn listener_and_connector() {
+    Runtime::new().unwrap().block_on(async {
+        let (listener, addr) = bind().await.unwrap();
+        let incoming = stream::unfold(listener, |l| async move {
+            let socket = l.accept().await.map(|(s, _)| s.compat());
+            Some((socket, l))
+        })
+        .boxed();
+        let mut listener = Listener::new(incoming, Config::default(), spawn());
+        let shutdown = listener.shutdown_handle();
+        let server = task::spawn(async move {
+            let mut n = 0;
+            while let Some(mut stream) = listener.accept().await {
+                task::spawn(async move {
+                    let mut buf = Vec::new();
+                    stream.read_to_end(&mut buf).await.unwrap();
+                    stream.write_all(&buf).await.unwrap();
+                    stream.close().await.unwrap();
+                });
+                n += 1;
+            }
+            n
+        });
+
+        let mut connectors = Vec::new();
+        for _ in 0..2 {
+            let dial = TcpStream::connect(addr).map_ok(|s| s.compat());
+            let mut connector = Connector::connect(dial, Config::default(), spawn())
+                .await
+                .unwrap();
+            for i in 0..3u8 {
+                let mut stream = connector.open().await.unwrap();
+                stream.write_all(&[i; 100]).await.unwrap();
+                stream.close().await.unwrap();
+                let mut buf = Vec::new();
+                stream.read_to_end(&mut buf).await.unwrap();
+                assert_eq!(buf, vec![i; 100]);
+            }
+            connectors.push(connector);
+        }
+
+        shutdown.shutdown();
+        assert_eq!(server.await.unwrap(), 6);
+
+        // The connections have been closed by the server.
+        for mut connector in connectors {
+            assert!(connector.accept().await.is_none());
+            assert!(connector.open().await.is_err());
+        }
+    })
+}
+
+#[test]
+fn spawned_connection_handle() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let mut server = server.spawn(spawn());
+        let (mut client, driver) = client.drive();
+        task::spawn(driver);
+
+        let mut outbound = client.open_stream().await.unwrap();
+        outbound.write_all(b"ping").await.unwrap();
+        let mut inbound = server.incoming().next().await.unwrap();
+        let mut buf = [0; 4];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        assert_eq!(client.stats().await.unwrap().num_streams, 1);
+        assert_eq!(server.stats().await.unwrap().num_streams, 1);
+
+        client.close().await.unwrap();
+        assert!(server.incoming().next().await.is_none());
+        assert!(matches!(
+            client.open_stream().await,
+            Err(ConnectionError::Closed)
+        ));
+    })
+}
+
+#[test]
+fn connections_start_with_configured_spawner() {
+    Runtime::new().unwrap().block_on(async {
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let mut cfg = Config::default();
+        cfg.set_spawner({
+            let spawned = spawned.clone();
+            Arc::new(move |f| {
+                spawned.fetch_add(1, Ordering::SeqCst);
+                task::spawn(f);
+            })
+        });
+        let (server, client) = memory_connected_peers(cfg.clone(), cfg, PipeConfig::default());
+        let mut server = server.start().unwrap();
+        let mut client = client.start().unwrap();
+        assert_eq!(spawned.load(Ordering::SeqCst), 2);
+
+        let mut outbound = client.open_stream().await.unwrap();
+        outbound.write_all(b"ping").await.unwrap();
+        let mut inbound = server.incoming().next().await.unwrap();
+        let mut buf = [0; 4];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    })
+}
+
+#[test]
+fn starting_without_spawner_fails() {
+    let (server, client) =
+        memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+    let e = client.start().unwrap_err();
+    assert_eq!(e.setting(), "spawner");
+    assert!(Listener::start(
+        stream::empty::<std::io::Result<tokio_util::compat::Compat<TcpStream>>>(),
+        Config::default()
+    )
+    .is_err());
+    drop(server)
+}
+
+fn spawn() -> Spawn {
+    Arc::new(|f| {
+        task::spawn(f);
+    })
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::SinkExt;
+use std::io;
+use test_harness::raw::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, EncodedFrame, Mode};
+
+fn encode(frame: RawFrame) -> EncodedFrame {
+    EncodedFrame {
+        header: frame.encode_header(),
+        body: frame.body,
+    }
+}
+
+#[test]
+fn injected_pings_are_sent() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let mut sink = connection.frame_sink().unwrap();
+        task::spawn(echo_server(connection));
+
+        sink.send(encode(RawFrame::ping(0, 0xcafe))).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, RawFrame::ping(0, 0xcafe));
+
+        // Pings with the `SYN` flag ask the remote for an answer.
+        sink.send(encode(RawFrame::ping(SYN, 1))).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, RawFrame::ping(SYN, 1));
+    })
+}
+
+#[test]
+fn only_pings_of_the_session_can_be_injected() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, _remote) = raw_peer(Config::default(), Mode::Server);
+        let mut sink = connection.frame_sink().unwrap();
+        task::spawn(echo_server(connection));
+
+        let invalid = [
+            encode(RawFrame::data(1, SYN, vec![1])),
+            encode(RawFrame::window_update(0, 0, 1)),
+            encode(RawFrame::go_away(GO_AWAY_NORMAL)),
+            EncodedFrame {
+                body: vec![1],
+                ..encode(RawFrame::ping(0, 1))
+            },
+        ];
+        for frame in invalid {
+            let e = sink.send(frame).await.unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+    })
+}
+
+#[test]
+fn frame_sink_fails_once_the_connection_is_closed() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, remote) = raw_peer(Config::default(), Mode::Server);
+        let mut sink = connection.frame_sink().unwrap();
+        let handle = task::spawn(echo_server(connection));
+        drop(remote);
+        let _ = handle.await;
+
+        let e = sink.send(encode(RawFrame::ping(0, 1))).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+    })
+}
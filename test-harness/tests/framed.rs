@@ -0,0 +1,127 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use bytes::Bytes;
+use futures::prelude::*;
+use std::{io, time::Duration};
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Control, Mode};
+
+#[test]
+fn framed_stream_preserves_message_boundaries() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let (_server, server) = Control::new(server);
+        task::spawn(server.try_for_each_concurrent(None, |stream| async {
+            let (sink, stream) = stream.into_framed().split();
+            stream.forward(sink).await.unwrap();
+            Ok(())
+        }));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut framed = control.open_stream().await.unwrap().into_framed();
+        let messages = [0, 1, 1000, 300 * 1024]
+            .iter()
+            .map(|&n| Bytes::from(vec![n as u8; n]))
+            .collect::<Vec<_>>();
+        for message in &messages {
+            framed.feed(message.clone()).await.unwrap();
+        }
+        framed.close().await.unwrap();
+
+        let echoed = framed.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(echoed, messages);
+    })
+}
+
+#[test]
+fn framed_stream_enforces_max_message_size() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let (_server, mut server) = Control::new(server);
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut sender = control.open_stream().await.unwrap().into_framed();
+        sender.send(Bytes::from(vec![1; 200])).await.unwrap();
+
+        let mut receiver = server.next().await.unwrap().unwrap().into_framed();
+        task::spawn(server.for_each(|_| future::ready(())));
+        receiver.set_max_message_size(100);
+        let e = receiver.next().await.unwrap().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        sender.set_max_message_size(100);
+        let e = sender.send(Bytes::from(vec![1; 200])).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    })
+}
+
+#[test]
+fn read_exact_timeout_waits_for_all_bytes() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let (_control, mut connection) = Control::new(connection);
+        remote
+            .send(RawFrame::data(1, raw::SYN, b"0123456789".to_vec()))
+            .await
+            .unwrap();
+        let mut stream = connection.next().await.unwrap().unwrap();
+        task::spawn(noop_server(connection));
+
+        let timeout = Duration::from_millis(100);
+        let bytes = stream.read_exact_timeout(4, timeout).await.unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"0123"));
+        let e = stream.read_exact_timeout(100, timeout).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+
+        remote
+            .send(RawFrame::data(1, raw::FIN, b"abc".to_vec()))
+            .await
+            .unwrap();
+        let e = stream.read_exact_timeout(100, timeout).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    })
+}
+
+#[test]
+fn read_until_fin_collects_all_bytes() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let (_control, mut connection) = Control::new(connection);
+        remote
+            .send(RawFrame::data(1, raw::SYN, b"hello".to_vec()))
+            .await
+            .unwrap();
+        remote
+            .send(RawFrame::data(1, raw::FIN, b" world".to_vec()))
+            .await
+            .unwrap();
+        remote
+            .send(RawFrame::data(3, raw::SYN | raw::FIN, vec![0; 17]))
+            .await
+            .unwrap();
+        let mut first = connection.next().await.unwrap().unwrap();
+        let mut second = connection.next().await.unwrap().unwrap();
+        task::spawn(noop_server(connection));
+
+        let bytes = first.read_until_fin(16).await.unwrap();
+        assert_eq!(bytes, Bytes::from_static(b"hello world"));
+        // The stream is only half-closed.
+        first.write_all(b"bye").await.unwrap();
+
+        let e = second.read_until_fin(16).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    })
+}
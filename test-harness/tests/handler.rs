@@ -0,0 +1,112 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Control, Spawn, Stream, StreamHandler, StreamId};
+
+/// Echoes each stream, counting the streams it handled to completion.
+struct Echo(Arc<AtomicUsize>);
+
+impl StreamHandler for Echo {
+    async fn handle(&self, stream: Stream, _: StreamId) {
+        let (mut reader, mut writer) = futures::io::AsyncReadExt::split(stream);
+        futures::io::copy(&mut reader, &mut writer).await.unwrap();
+        writer.close().await.unwrap();
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn tokio_spawn() -> Spawn {
+    Arc::new(|f| {
+        task::spawn(f);
+    })
+}
+
+#[test]
+fn serve_hands_streams_to_handler() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let (_shutdown, serve) = server.serve(Echo(handled.clone()), tokio_spawn());
+        let serve = task::spawn(serve);
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+        for i in 0..3u8 {
+            let mut stream = control.open_stream().await.unwrap();
+            stream.write_all(&[i; 100]).await.unwrap();
+            stream.close().await.unwrap();
+            let mut echoed = Vec::new();
+            stream.read_to_end(&mut echoed).await.unwrap();
+            assert_eq!(echoed, vec![i; 100]);
+        }
+
+        control.close().await.unwrap();
+        serve.await.unwrap();
+        assert_eq!(handled.load(Ordering::SeqCst), 3);
+    })
+}
+
+#[test]
+fn serve_shutdown_waits_for_handlers() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let (shutdown, serve) = server.serve(Echo(handled.clone()), tokio_spawn());
+        let serve = task::spawn(serve);
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+
+        // The stream being handled is still serviced after the shutdown.
+        shutdown.shutdown();
+        stream.write_all(b"world").await.unwrap();
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"world");
+        assert!(!serve.is_finished());
+
+        stream.close().await.unwrap();
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest).await.unwrap();
+        serve.await.unwrap();
+        assert_eq!(handled.load(Ordering::SeqCst), 1);
+    })
+}
+
+#[test]
+fn start_serving_uses_configured_spawner() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_spawner(tokio_spawn());
+        let (server, client) =
+            memory_connected_peers(cfg, Config::default(), PipeConfig::default());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let _shutdown = server.start_serving(Echo(handled.clone())).unwrap();
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        stream.close().await.unwrap();
+        let mut echoed = Vec::new();
+        stream.read_to_end(&mut echoed).await.unwrap();
+        assert_eq!(echoed, b"hello");
+    })
+}
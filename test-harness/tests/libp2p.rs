@@ -0,0 +1,52 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use libp2p_core::muxing::StreamMuxerExt;
+use libp2p_core::upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade, UpgradeInfo};
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::Config;
+
+#[test]
+fn stream_muxer_send_recv() {
+    Runtime::new().unwrap().block_on(async {
+        let (a, b) = memory_pipe(PipeConfig::default());
+        let protocol = Config::default().protocol_info().next().unwrap();
+        assert_eq!(protocol, "/yamux/1.0.0");
+        let mut client = Config::default()
+            .upgrade_outbound(a, protocol)
+            .await
+            .unwrap();
+        let mut server = Config::default()
+            .upgrade_inbound(b, protocol)
+            .await
+            .unwrap();
+
+        let mut outbound = future::poll_fn(|cx| client.poll_outbound_unpin(cx))
+            .await
+            .unwrap();
+        task::spawn(future::poll_fn(move |cx| client.poll_unpin(cx).map(drop)));
+        outbound.write_all(b"hello").await.unwrap();
+
+        // The inbound stream is buffered by `poll` until `poll_inbound` is called.
+        let mut inbound = future::poll_fn(|cx| {
+            let _ = server.poll_unpin(cx);
+            server.poll_inbound_unpin(cx)
+        })
+        .await
+        .unwrap();
+        task::spawn(future::poll_fn(move |cx| server.poll_unpin(cx).map(drop)));
+
+        let mut buf = [0; 5];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    })
+}
@@ -0,0 +1,66 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{metrics::PrometheusExporter, Config, Control};
+
+#[test]
+fn exporter_aggregates_connections() {
+    Runtime::new().unwrap().block_on(async {
+        let mut exporter = PrometheusExporter::new();
+        let mut controls = Vec::new();
+        for _ in 0..2 {
+            let (server, client) =
+                memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+            task::spawn(echo_server(server));
+            let (control, client) = Control::new(client);
+            task::spawn(noop_server(client));
+            exporter.register(control.clone());
+            controls.push(control);
+        }
+        let _s1 = controls[0].open_stream().await.unwrap();
+        let _s2 = controls[0].open_stream().await.unwrap();
+        let _s3 = controls[1].open_stream().await.unwrap();
+
+        let stats = exporter.collect().await;
+        assert_eq!(stats.connections, 2);
+        assert_eq!(stats.streams, 3);
+        assert_eq!(stats.connections_closed, 0);
+
+        controls[1].close().await.unwrap();
+        let text = exporter.render().await;
+        assert!(
+            text.contains("# TYPE yamux_connections gauge\nyamux_connections 1\n"),
+            "{}",
+            text
+        );
+        assert!(text.contains("yamux_streams 2\n"), "{}", text);
+        assert!(
+            text.contains("# TYPE yamux_connections_closed_total counter\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("yamux_connections_closed_total 1\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("# TYPE yamux_lock_contentions_total counter\n"),
+            "{}",
+            text
+        );
+        assert_eq!(exporter.num_connections(), 1);
+
+        // Without a recorder of the `metrics` crate, publishing does nothing.
+        exporter.publish().await;
+    })
+}
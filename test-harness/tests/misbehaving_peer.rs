@@ -0,0 +1,736 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::channel::oneshot;
+use futures::{
+    future, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt, TryStreamExt,
+};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use test_harness::raw::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{
+    BufferOverflow, BufferOverflowPolicy, CloseReason, Config, Connection, ConnectionError,
+    Control, MaxStreamsResponse, Mode, ResetReason, State, Tag, Verdict, WindowUpdateMode,
+    WindowViolationPolicy, DEFAULT_CREDIT,
+};
+
+#[test]
+fn syn_with_invalid_stream_id_is_protocol_error() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        task::spawn(accept_all(connection));
+
+        // Clients must use odd stream IDs.
+        remote
+            .send(RawFrame::window_update(2, SYN, 0))
+            .await
+            .unwrap();
+
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+    })
+}
+
+#[test]
+fn duplicate_syn_is_protocol_error() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+    })
+}
+
+#[test]
+fn first_frame_exceeding_default_credit_is_protocol_error() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        task::spawn(accept_all(connection));
+
+        let body = vec![0; DEFAULT_CREDIT as usize + 1];
+        remote.send(RawFrame::data(1, SYN, body)).await.unwrap();
+
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+    })
+}
+
+#[test]
+fn data_exceeding_window_is_protocol_error() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote
+            .send(RawFrame::data(1, SYN, Vec::new()))
+            .await
+            .unwrap();
+        let body = vec![0; DEFAULT_CREDIT as usize + 1];
+        remote.send(RawFrame::data(1, 0, body)).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+    })
+}
+
+#[test]
+fn data_exceeding_window_resets_stream() {
+    let mut config = Config::default();
+    config.set_window_violation_policy(WindowViolationPolicy::Reset);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(connection.try_collect::<Vec<_>>());
+
+        remote
+            .send(RawFrame::data(1, SYN, Vec::new()))
+            .await
+            .unwrap();
+        let body = vec![0; DEFAULT_CREDIT as usize + 1];
+        remote
+            .send(RawFrame::data(1, 0, body.clone()))
+            .await
+            .unwrap();
+        // Frames in flight after the reset are discarded.
+        remote.send(RawFrame::data(1, 0, body)).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag != TAG_WINDOW_UPDATE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(1), "{:?}", frame);
+
+        // The connection is still alive.
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_PING)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, RawFrame::ping(ACK, 42));
+        assert_eq!(control.stats().await.unwrap().window_violations, 1);
+    })
+}
+
+#[test]
+fn oversized_frame_length_terminates_connection() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let server = task::spawn(accept_all(connection));
+
+        // Only the header is sent, announcing a body beyond the max. buffer size.
+        let mut frame = RawFrame::data(1, SYN, Vec::new());
+        frame.length = u32::MAX;
+        remote.send(frame).await.unwrap();
+
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(ConnectionError::Protocol(_))
+        ));
+    })
+}
+
+#[test]
+fn frame_exceeding_max_frame_body_len_is_protocol_error() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_max_frame_body_len(1024);
+        let (connection, mut remote) = raw_peer(cfg, Mode::Server);
+        let server = task::spawn(accept_all(connection));
+
+        // Within the receive window, but beyond the max. frame body length.
+        remote
+            .send(RawFrame::data(1, SYN, vec![0; 1024]))
+            .await
+            .unwrap();
+        remote
+            .send(RawFrame::data(1, 0, vec![0; 1025]))
+            .await
+            .unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+        match server.await.unwrap() {
+            Err(ConnectionError::Protocol(e)) => assert_eq!(e.reason(), "frame body too large"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    })
+}
+
+#[test]
+fn unknown_version_terminates_connection() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let server = task::spawn(accept_all(connection));
+
+        let mut frame = RawFrame::ping(0, 42);
+        frame.version = 1;
+        remote.send(frame).await.unwrap();
+
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(ConnectionError::Decode { .. })
+        ));
+        assert_eq!(remote.recv().await.unwrap(), None);
+    })
+}
+
+#[test]
+fn protocol_error_carries_context() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let id = connection.id();
+        let server = task::spawn(accept_all(connection));
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+
+        let e = server.await.unwrap().unwrap_err();
+        assert!(e.is_fatal() && !e.is_recoverable());
+        assert_eq!(e.connection(), Some(id));
+        match e {
+            ConnectionError::Protocol(e) => {
+                assert_eq!(e.stream().map(|s| s.val()), Some(1));
+                let header = e.header().unwrap();
+                assert_eq!(header.tag(), Tag::Data);
+                assert_eq!(header.flags().val() & SYN, SYN);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert_eq!(remote.recv().await.unwrap(), None);
+    })
+}
+
+#[test]
+fn go_away_with_error_code_fails_streams() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let id = connection.id();
+
+        remote
+            .send(RawFrame::data(2, SYN, b"hi".to_vec()))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let client = task::spawn(accept_all(connection));
+
+        let mut buf = [0; 2];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hi");
+
+        remote
+            .send(RawFrame::go_away(GO_AWAY_INTERNAL_ERROR))
+            .await
+            .unwrap();
+
+        let e = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+        assert!(matches!(
+            e.into_inner().unwrap().downcast_ref::<ConnectionError>(),
+            Some(ConnectionError::GoAwayReceived { code, .. }) if *code == GO_AWAY_INTERNAL_ERROR
+        ));
+        let e = stream.write_all(b"x").await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+        assert_eq!(
+            stream.close_reason(),
+            Some(CloseReason::GoAway(GO_AWAY_INTERNAL_ERROR))
+        );
+
+        match client.await.unwrap() {
+            Err(e @ ConnectionError::GoAwayReceived { .. }) => {
+                assert!(e.is_fatal());
+                assert_eq!(e.connection(), Some(id));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    })
+}
+
+#[test]
+fn excess_stream_terminates_connection() {
+    // Streams opened with a data frame are rejected with an internal error,
+    // streams opened with a window update with a protocol error.
+    for (tag, code) in [
+        (TAG_DATA, GO_AWAY_INTERNAL_ERROR),
+        (TAG_WINDOW_UPDATE, GO_AWAY_PROTOCOL_ERROR),
+    ] {
+        let mut config = Config::default();
+        config.set_max_num_streams(1);
+
+        Runtime::new().unwrap().block_on(async {
+            let (connection, mut remote) = raw_peer(config, Mode::Server);
+            task::spawn(accept_all(connection));
+
+            remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+            let syn = if tag == TAG_DATA {
+                RawFrame::data(3, SYN, vec![1])
+            } else {
+                RawFrame::window_update(3, SYN, 0)
+            };
+            remote.send(syn).await.unwrap();
+
+            let frame = remote
+                .recv_until(|f| f.tag == TAG_GO_AWAY)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(frame.is_go_away(code), "{:?}", frame);
+        })
+    }
+}
+
+#[test]
+fn excess_stream_is_reset() {
+    let mut config = Config::default();
+    config.set_max_num_streams(1);
+    config.set_max_num_streams_response(MaxStreamsResponse::Reset);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(3, SYN, vec![1])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.stream_id == 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(3), "{:?}", frame);
+
+        // The connection is still alive.
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_PING)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, RawFrame::ping(ACK, 42));
+    })
+}
+
+#[test]
+fn resets_sent_are_counted_by_reason() {
+    let mut config = Config::default();
+    config
+        .set_max_num_streams(1)
+        .set_max_num_streams_response(MaxStreamsResponse::Reset)
+        .set_max_buffer_size(1024);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(connection.try_collect::<Vec<_>>());
+
+        remote
+            .send(RawFrame::data(1, SYN, vec![0; 1024]))
+            .await
+            .unwrap();
+        remote.send(RawFrame::data(3, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(1, 0, vec![1])).await.unwrap();
+
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+        remote.recv_until(|f| f.tag == TAG_PING).await.unwrap();
+
+        let resets = control.stats().await.unwrap().resets_sent;
+        assert_eq!(resets.get(ResetReason::TooManyStreams), 1);
+        assert_eq!(resets.get(ResetReason::BufferOverflow), 1);
+        assert_eq!(resets.total(), 2);
+        let reasons = resets.iter().map(|(r, _)| r.as_str()).collect::<Vec<_>>();
+        assert_eq!(reasons, ["buffer_overflow", "too_many_streams"]);
+    })
+}
+
+#[test]
+fn inbound_and_outbound_streams_are_limited_separately() {
+    let mut config = Config::default();
+    config.set_max_inbound_streams(1);
+    config.set_max_outbound_streams(2);
+    config.set_max_num_streams_response(MaxStreamsResponse::Reset);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        let (mut control, connection) = Control::new(connection);
+        // Keep the inbound streams open.
+        task::spawn(connection.collect::<Vec<_>>());
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(3, SYN, vec![1])).await.unwrap();
+        let frame = remote
+            .recv_until(|f| f.stream_id == 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(3), "{:?}", frame);
+
+        // The inbound stream does not count against the outbound limit.
+        let _first = control.open_stream().await.unwrap();
+        let _second = control.open_stream().await.unwrap();
+        let e = control.open_stream().await.unwrap_err();
+        assert!(matches!(e, ConnectionError::TooManyStreams), "{:?}", e);
+    })
+}
+
+#[test]
+fn max_num_streams_limits_inbound_and_outbound_streams_combined() {
+    let mut config = Config::default();
+    config.set_max_num_streams(2);
+    config.set_max_num_streams_response(MaxStreamsResponse::Reset);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        let (mut control, connection) = Control::new(connection);
+        // Keep the inbound streams open.
+        task::spawn(connection.collect::<Vec<_>>());
+
+        let _outbound = control.open_stream().await.unwrap();
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(3, SYN, vec![1])).await.unwrap();
+        let frame = remote
+            .recv_until(|f| f.stream_id == 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(3), "{:?}", frame);
+
+        let e = control.open_stream().await.unwrap_err();
+        assert!(matches!(e, ConnectionError::TooManyStreams), "{:?}", e);
+    })
+}
+
+#[test]
+fn stream_rejected_by_policy_is_reset() {
+    let mut config = Config::default();
+    config.set_inbound_stream_policy(Arc::new(|id, info| {
+        assert_eq!(info.mode, Mode::Server);
+        if id.val() == 3 {
+            Verdict::ResetStream
+        } else {
+            Verdict::Accept
+        }
+    }));
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote
+            .send(RawFrame::window_update(3, SYN, 0))
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert!(frame.is_rst(3), "{:?}", frame);
+
+        // The connection is still alive.
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, RawFrame::ping(ACK, 42));
+    })
+}
+
+#[test]
+fn stream_rejected_by_policy_terminates_connection() {
+    let mut config = Config::default();
+    config.set_inbound_stream_policy(Arc::new(|_, info| {
+        if info.num_streams >= 1 {
+            Verdict::TerminateConnection
+        } else {
+            Verdict::Accept
+        }
+    }));
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        remote.send(RawFrame::data(3, SYN, vec![1])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_INTERNAL_ERROR), "{:?}", frame);
+    })
+}
+
+#[test]
+fn stream_exceeding_receive_buffer_bytes_is_reset() {
+    let mut config = Config::default();
+    config.set_max_stream_receive_buffer_bytes(1024);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote
+            .send(RawFrame::data(1, SYN, vec![0; 512]))
+            .await
+            .unwrap();
+        remote
+            .send(RawFrame::data(1, 0, vec![0; 512]))
+            .await
+            .unwrap();
+        remote.send(RawFrame::data(1, 0, vec![0; 1])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag != TAG_WINDOW_UPDATE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(1), "{:?}", frame);
+    })
+}
+
+#[test]
+fn stream_exceeding_max_buffer_size_terminates_connection() {
+    let mut config = Config::default();
+    config
+        .set_max_buffer_size(1024)
+        .set_buffer_overflow_policy(BufferOverflowPolicy::GoAway);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Server);
+        let server = task::spawn(accept_all(connection));
+
+        remote
+            .send(RawFrame::data(1, SYN, vec![0; 1024]))
+            .await
+            .unwrap();
+        remote.send(RawFrame::data(1, 0, vec![0; 1])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(GO_AWAY_PROTOCOL_ERROR), "{:?}", frame);
+        match server.await.unwrap() {
+            Err(ConnectionError::Protocol(e)) => {
+                assert_eq!(e.reason(), "buffer of stream grows beyond limit")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    })
+}
+
+#[test]
+fn stream_exceeding_max_buffer_size_is_backpressured() {
+    let overflows = Arc::new(Mutex::new(Vec::new()));
+    let mut config = Config::default();
+    config
+        .set_max_buffer_size(1024)
+        .set_window_update_mode(WindowUpdateMode::OnReceive)
+        .set_buffer_overflow_policy(BufferOverflowPolicy::Backpressure)
+        .set_buffer_overflow_observer({
+            let overflows = overflows.clone();
+            Arc::new(move |o: &BufferOverflow| overflows.lock().unwrap().push(*o))
+        });
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        let (tx, rx) = oneshot::channel();
+        task::spawn(async move {
+            let stream = future::poll_fn(|cx| connection.poll_next_inbound(cx)).await;
+            tx.send(stream.unwrap().unwrap()).unwrap();
+            accept_all(connection).await
+        });
+
+        remote
+            .send(RawFrame::data(1, SYN, vec![1; 1024]))
+            .await
+            .unwrap();
+        remote.send(RawFrame::data(1, 0, vec![2; 1])).await.unwrap();
+
+        // Neither is the stream reset nor is credit granted.
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+        loop {
+            let frame = remote.recv().await.unwrap().unwrap();
+            if frame.tag == TAG_PING {
+                break;
+            }
+            assert!(!frame.is_rst(1), "{:?}", frame);
+            assert!(
+                frame.tag != TAG_WINDOW_UPDATE || frame.length == 0,
+                "{:?}",
+                frame
+            );
+        }
+
+        {
+            let overflows = overflows.lock().unwrap();
+            assert_eq!(overflows.len(), 1);
+            assert_eq!(overflows[0].buffered, 1024);
+            assert_eq!(overflows[0].frame_len, 1);
+            assert_eq!(overflows[0].policy, BufferOverflowPolicy::Backpressure);
+        }
+
+        // No data has been lost.
+        let mut stream = rx.await.unwrap();
+        let mut buf = vec![0; 1025];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert!(buf[..1024].iter().all(|b| *b == 1));
+        assert_eq!(buf[1024], 2);
+    })
+}
+
+#[test]
+fn data_after_fin_resets_stream() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(connection.try_collect::<Vec<_>>());
+
+        remote
+            .send(RawFrame::data(1, SYN | FIN, vec![1]))
+            .await
+            .unwrap();
+        remote.send(RawFrame::data(1, 0, vec![2])).await.unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag != TAG_WINDOW_UPDATE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(1), "{:?}", frame);
+        let resets_sent = control.stats().await.unwrap().resets_sent;
+        assert_eq!(resets_sent.get(ResetReason::DataAfterFin), 1);
+    })
+}
+
+#[test]
+fn data_after_rst_is_discarded() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+
+        remote.send(RawFrame::data(1, SYN, vec![1])).await.unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        task::spawn(accept_all(connection));
+
+        remote.send(RawFrame::data(1, RST, vec![])).await.unwrap();
+        // Written by the remote concurrently with its reset.
+        remote.send(RawFrame::data(1, 0, vec![2])).await.unwrap();
+        remote.send(RawFrame::ping(SYN, 42)).await.unwrap();
+        let frame = remote
+            .recv_until(|f| f.tag == TAG_PING || f.flags & RST != 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.tag, TAG_PING, "{:?}", frame);
+
+        // The stream ends as reset by the remote.
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, [1]);
+    })
+}
+
+#[test]
+fn unknown_stream_is_ignored() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        task::spawn(accept_all(connection));
+
+        remote.send(RawFrame::data(5, 0, vec![1])).await.unwrap();
+        remote
+            .send(RawFrame::window_update(7, 0, 42))
+            .await
+            .unwrap();
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+
+        // The first frame in response is the pong.
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, RawFrame::ping(ACK, 42));
+    })
+}
+
+#[test]
+fn outbound_stream_is_acknowledged_by_ack() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(connection.for_each(|_| future::ready(())));
+
+        let mut stream = control.open_stream().await.unwrap();
+        assert!(stream.is_outbound());
+        assert!(!stream.is_acknowledged());
+        assert_eq!(stream.state(), State::Open);
+
+        stream.write_all(&[1]).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, RawFrame::data(1, SYN, vec![1]));
+
+        remote
+            .send(RawFrame::window_update(1, ACK, 0))
+            .await
+            .unwrap();
+        // A pong is only sent after all preceding frames have been processed.
+        remote.send(RawFrame::ping(0, 42)).await.unwrap();
+        remote.recv_until(|f| f.tag == TAG_PING).await.unwrap();
+        assert!(stream.is_acknowledged());
+
+        stream.close().await.unwrap();
+        assert_eq!(stream.state(), State::SendClosed);
+    })
+}
+
+/// Accept all inbound streams, keeping them alive, until the connection terminates.
+async fn accept_all<T>(mut connection: Connection<T>) -> Result<(), ConnectionError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut streams = Vec::new();
+    loop {
+        match future::poll_fn(|cx| connection.poll_next_inbound(cx)).await {
+            Some(Ok(stream)) => streams.push(stream),
+            Some(Err(e)) => return Err(e),
+            None => return Ok(()),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use std::{sync::Arc, time::Duration};
+use test_harness::*;
+use tokio::{net::TcpStream, runtime::Runtime, task};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use yamux::{
+    pool::{ConnectionPool, RetryPolicy},
+    Config, Connection, ConnectionError, Mode,
+};
+
+#[test]
+fn pool_dials_connections_by_stream_count() {
+    Runtime::new().unwrap().block_on(async {
+        let (listener, addr) = bind().await.unwrap();
+        task::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let connection = Connection::new(socket.compat(), Config::default(), Mode::Server);
+                task::spawn(echo_server(connection));
+            }
+        });
+
+        let connect = Box::new(move || TcpStream::connect(addr).map_ok(|s| s.compat()).boxed());
+        let spawn = Arc::new(|f| {
+            task::spawn(f);
+        });
+        let mut pool = ConnectionPool::new(connect, Config::default(), spawn);
+        pool.set_max_streams_per_connection(2)
+            .set_max_connections(3);
+
+        let mut streams = Vec::new();
+        for i in 0..7u8 {
+            let mut stream = pool.open().await.unwrap();
+            stream.write_all(&[i; 10]).await.unwrap();
+            streams.push(stream);
+        }
+        assert_eq!(pool.num_connections(), 3);
+
+        for (i, mut stream) in streams.into_iter().enumerate() {
+            let mut buf = [0; 10];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, [i as u8; 10]);
+        }
+    })
+}
+
+#[test]
+fn pool_retries_when_streams_are_exhausted() {
+    Runtime::new().unwrap().block_on(async {
+        let (listener, addr) = bind().await.unwrap();
+        task::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let connection = Connection::new(socket.compat(), Config::default(), Mode::Server);
+                task::spawn(echo_server(connection));
+            }
+        });
+
+        let connect = Box::new(move || TcpStream::connect(addr).map_ok(|s| s.compat()).boxed());
+        let spawn = Arc::new(|f| {
+            task::spawn(f);
+        });
+        let mut cfg = Config::default();
+        cfg.set_max_num_streams(1);
+        let mut pool = ConnectionPool::new(connect, cfg, spawn);
+        pool.set_max_connections(1);
+
+        let stream = pool.open().await.unwrap();
+        assert!(matches!(
+            pool.open().await,
+            Err(ConnectionError::TooManyStreams)
+        ));
+
+        let mut policy = RetryPolicy::default();
+        policy
+            .set_initial_delay(Duration::from_millis(10))
+            .set_max_delay(Duration::from_millis(40))
+            .set_max_retries(20);
+        assert_eq!(policy.delay(1), Duration::from_millis(10));
+        assert_eq!(policy.delay(3), Duration::from_millis(40));
+        assert_eq!(policy.delay(100), Duration::from_millis(40));
+        pool.set_retry_policy(policy);
+
+        // The stream is dropped while the pool waits to retry.
+        task::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(stream)
+        });
+        let mut stream = pool.open().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        assert_eq!(pool.num_connections(), 1);
+    })
+}
@@ -0,0 +1,369 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::prelude::*;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{
+    Config, Connect, Connection, Mode, OpenMode, Reconnect, ReconnectEvent, StreamError,
+    WindowUpdateMode, WindowUpdateThreshold, DEFAULT_CREDIT,
+};
+
+#[test]
+fn streams_survive_reconnect() {
+    // Grant credit for every byte received, so that no data remains unconfirmed.
+    let mut config = Config::default();
+    config
+        .set_window_update_mode(WindowUpdateMode::OnReceive)
+        .set_window_update_threshold(WindowUpdateThreshold::Bytes(1));
+
+    Runtime::new().unwrap().block_on(async {
+        let (link, a, b) = breakable_pipe();
+        let (client_sockets, client_connect) = sockets();
+        let (server_sockets, server_connect) = sockets();
+
+        let mut client = Reconnect::new(
+            Connection::new(a, config.clone(), Mode::Client),
+            client_connect,
+        );
+        let mut events = client.events();
+        let mut server = Reconnect::new(Connection::new(b, config, Mode::Server), server_connect);
+        let mut server_events = server.events();
+
+        let mut outbound = future::poll_fn(|cx| client.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        let (inbound_tx, mut inbound_rx) = unbounded();
+        task::spawn(drive(client, inbound_tx.clone()));
+        task::spawn(drive(server, inbound_tx));
+
+        outbound.write_all(b"hello").await.unwrap();
+        let mut inbound = inbound_rx.next().await.unwrap();
+        let mut buf = [0; 5];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        outbound.wait_for_credit(DEFAULT_CREDIT).await.unwrap();
+
+        link.break_link();
+        assert!(matches!(
+            events.next().await,
+            Some(ReconnectEvent::Disconnected(_))
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(ReconnectEvent::Attempt(1))
+        ));
+
+        let (_link, c, d) = breakable_pipe();
+        client_sockets.unbounded_send(c).unwrap();
+        server_sockets.unbounded_send(d).unwrap();
+        assert!(matches!(
+            events.next().await,
+            Some(ReconnectEvent::Reconnected(1))
+        ));
+        while !matches!(
+            server_events.next().await,
+            Some(ReconnectEvent::Reconnected(1))
+        ) {}
+
+        inbound.write_all(b"world").await.unwrap();
+        outbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"world");
+    })
+}
+
+#[test]
+fn streams_with_data_in_flight_are_reset() {
+    Runtime::new().unwrap().block_on(async {
+        let (link, a, b) = breakable_pipe();
+        let (client_sockets, client_connect) = sockets();
+        let (server_sockets, server_connect) = sockets();
+
+        let mut client = Reconnect::new(
+            Connection::new(a, Config::default(), Mode::Client),
+            client_connect,
+        );
+        let mut events = client.events();
+        let server = Reconnect::new(
+            Connection::new(b, Config::default(), Mode::Server),
+            server_connect,
+        );
+
+        let mut outbound = future::poll_fn(|cx| client.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        let (inbound_tx, mut inbound_rx) = unbounded();
+        task::spawn(drive(client, inbound_tx.clone()));
+        task::spawn(drive(server, inbound_tx));
+
+        // The transport fails in the middle of writing a frame.
+        link.break_after(100 * 1024 + 5);
+        let data = vec![7; 200 * 1024];
+        let writer = task::spawn(async move {
+            let result = outbound.write_all(&data).await;
+            (outbound, result)
+        });
+        let mut inbound = inbound_rx.next().await.unwrap();
+
+        let (_link, c, d) = breakable_pipe();
+        client_sockets.unbounded_send(c).unwrap();
+        server_sockets.unbounded_send(d).unwrap();
+        while !matches!(events.next().await, Some(ReconnectEvent::Reconnected(1))) {}
+
+        // Neither side continues with data missing.
+        let (mut outbound, _) = writer.await.unwrap();
+        let e = outbound.write_all(b"more").await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionAborted);
+        assert_eq!(StreamError::from_io(&e), Some(StreamError::Reset));
+        let mut buf = Vec::new();
+        let _ = inbound.read_to_end(&mut buf).await;
+        assert!(inbound.is_closed());
+        assert!(buf.len() < 100 * 1024);
+    })
+}
+
+#[test]
+fn window_growth_does_not_confirm_data() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let mut eager =
+            future::poll_fn(|cx| connection.poll_new_outbound_with_mode(cx, OpenMode::Eager))
+                .await
+                .unwrap();
+        let mut lazy =
+            future::poll_fn(|cx| connection.poll_new_outbound_with_mode(cx, OpenMode::Lazy))
+                .await
+                .unwrap();
+        eager.write_all(b"hello").await.unwrap();
+        lazy.write_all(b"hello").await.unwrap();
+        for _ in 0..3 {
+            drive_until(&mut connection, remote.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        // Both ACKs grow the window and may have been sent before the data
+        // arrived, but only the data of the lazily opened stream was sent
+        // along with its SYN.
+        let credit = 3 * DEFAULT_CREDIT;
+        for id in [1, 3] {
+            let ack = RawFrame::window_update(id, raw::ACK, credit);
+            remote.send(ack).await.unwrap();
+        }
+        let window = DEFAULT_CREDIT + credit - 5;
+        let granted = future::try_join(eager.wait_for_credit(window), lazy.wait_for_credit(window));
+        drive_until(&mut connection, granted).await.unwrap();
+        assert_eq!(connection.reset_streams_in_flight(), 1);
+        assert!(eager.write_all(b"x").await.is_err());
+        lazy.write_all(b"x").await.unwrap();
+    })
+}
+
+#[test]
+fn streams_are_closed_when_giving_up() {
+    Runtime::new().unwrap().block_on(async {
+        let (link, a, b) = breakable_pipe();
+        let (_server_sockets, server_connect) = sockets();
+
+        let connect: Connect<Breakable> =
+            Box::new(|| future::ready(Err(io::ErrorKind::ConnectionRefused.into())).boxed());
+        let mut client =
+            Reconnect::new(Connection::new(a, Config::default(), Mode::Client), connect);
+        client
+            .set_max_attempts(2)
+            .set_retry_delay(Duration::from_millis(10));
+        let events = client.events();
+        let server = Reconnect::new(
+            Connection::new(b, Config::default(), Mode::Server),
+            server_connect,
+        );
+
+        let mut outbound = future::poll_fn(|cx| client.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        let (inbound_tx, _inbound_rx) = unbounded();
+        let client = task::spawn(drive(client, inbound_tx.clone()));
+        task::spawn(drive(server, inbound_tx));
+
+        outbound.write_all(b"hello").await.unwrap();
+        link.break_link();
+
+        let e = client.await.unwrap().unwrap_err();
+        assert!(e.is_fatal());
+
+        let events = events.collect::<Vec<_>>().await;
+        assert!(matches!(
+            events.as_slice(),
+            [
+                ReconnectEvent::Disconnected(_),
+                ReconnectEvent::Attempt(1),
+                ReconnectEvent::AttemptFailed(1, _),
+                ReconnectEvent::Attempt(2),
+                ReconnectEvent::AttemptFailed(2, _),
+                ReconnectEvent::GaveUp,
+            ]
+        ));
+
+        assert!(outbound.write_all(b"x").await.is_err());
+    })
+}
+
+/// Drive the connection until the given future resolves.
+async fn drive_until<T, F>(connection: &mut Connection<T>, f: F) -> F::Output
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    F: Future,
+{
+    let drive = future::poll_fn(|cx| {
+        let _ = connection.poll_next_inbound(cx);
+        Poll::<()>::Pending
+    });
+    match future::select(Box::pin(f), drive).await {
+        future::Either::Left((output, _)) => output,
+        future::Either::Right(((), _)) => unreachable!(),
+    }
+}
+
+/// Drive the connection, forwarding inbound streams, until it fails.
+async fn drive(
+    mut connection: Reconnect<Breakable>,
+    inbound: UnboundedSender<yamux::Stream>,
+) -> yamux::Result<()> {
+    while let Some(stream) = future::poll_fn(|cx| connection.poll_next_inbound(cx)).await {
+        let _ = inbound.unbounded_send(stream?);
+    }
+    Ok(())
+}
+
+/// A [`Connect`] function yielding the I/O resources sent to the returned sender.
+fn sockets() -> (UnboundedSender<Breakable>, Connect<Breakable>) {
+    let (tx, rx) = unbounded();
+    let rx = Arc::new(futures::lock::Mutex::new(rx));
+    let connect: Connect<Breakable> = Box::new(move || {
+        let rx = rx.clone();
+        async move {
+            rx.lock()
+                .await
+                .next()
+                .await
+                .ok_or_else(|| io::ErrorKind::NotConnected.into())
+        }
+        .boxed()
+    });
+    (tx, connect)
+}
+
+/// A [`memory_pipe`] whose endpoints fail once the link is broken.
+fn breakable_pipe() -> (Arc<Link>, Breakable, Breakable) {
+    let (a, b) = memory_pipe(PipeConfig::default());
+    let link = Arc::new(Link::default());
+    let a = Breakable {
+        io: a,
+        link: link.clone(),
+    };
+    let b = Breakable {
+        io: b,
+        link: link.clone(),
+    };
+    (link, a, b)
+}
+
+#[derive(Default)]
+struct Link {
+    broken: AtomicBool,
+    /// The number of bytes which can still be written before the link breaks.
+    budget: Mutex<Option<usize>>,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Link {
+    fn break_after(&self, n: usize) {
+        *self.budget.lock().unwrap() = Some(n)
+    }
+
+    /// Limit a write to the remaining budget, breaking the link once it is spent.
+    fn limit(&self, len: usize) -> usize {
+        let mut budget = self.budget.lock().unwrap();
+        match budget.as_mut() {
+            Some(0) => {
+                drop(budget);
+                self.break_link();
+                0
+            }
+            Some(n) => {
+                let len = std::cmp::min(*n, len);
+                *n -= len;
+                len
+            }
+            None => len,
+        }
+    }
+
+    fn break_link(&self) {
+        self.broken.store(true, Ordering::SeqCst);
+        for w in self.wakers.lock().unwrap().drain(..) {
+            w.wake()
+        }
+    }
+
+    fn check(&self, cx: &mut Context<'_>) -> io::Result<()> {
+        self.wakers.lock().unwrap().push(cx.waker().clone());
+        if self.broken.load(Ordering::SeqCst) {
+            return Err(io::ErrorKind::ConnectionReset.into());
+        }
+        Ok(())
+    }
+}
+
+struct Breakable {
+    io: MemoryEndpoint,
+    link: Arc<Link>,
+}
+
+impl AsyncRead for Breakable {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.link.check(cx)?;
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Breakable {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.link.check(cx)?;
+        let len = self.link.limit(buf.len());
+        self.link.check(cx)?;
+        Pin::new(&mut self.io).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.link.check(cx)?;
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_close(cx)
+    }
+}
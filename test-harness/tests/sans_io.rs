@@ -0,0 +1,64 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Connection, Mode};
+use yamux_core::session::{Event, Mode as SessionMode, SessionState};
+
+/// A [`SessionState`] driven over a memory pipe talks to a [`Connection`].
+#[test]
+fn sans_io_session_interoperates_with_connection() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, mut client) = memory_pipe(PipeConfig::default());
+        task::spawn(echo_server(Connection::new(
+            server,
+            Config::default(),
+            Mode::Server,
+        )));
+
+        let mut session = SessionState::new(SessionMode::Client);
+        let id = session.open_stream().unwrap();
+        let msg = vec![0x42; 300 * 1024];
+        let mut sent = 0;
+        let mut echoed = Vec::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            if sent < msg.len() {
+                sent += session.send(id, &msg[sent..]).unwrap();
+                if sent == msg.len() {
+                    session.close_stream(id).unwrap();
+                }
+            }
+            while let Some(bytes) = session.poll_output() {
+                client.write_all(&bytes).await.unwrap();
+            }
+            let n = client.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed");
+            let mut finished = false;
+            for event in session.handle_input(&buf[..n]).unwrap() {
+                match event {
+                    Event::Data { stream, data } => {
+                        session.consume(stream, data.len() as u32).unwrap();
+                        echoed.extend_from_slice(&data);
+                    }
+                    Event::StreamFinished(_) => finished = true,
+                    _ => {}
+                }
+            }
+            if finished {
+                break;
+            }
+        }
+        assert_eq!(echoed, msg);
+        assert_eq!(session.num_streams(), 0);
+    })
+}
@@ -0,0 +1,81 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use std::{convert::TryFrom, sync::Arc};
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::secured::rustls::{
+    self,
+    pki_types::{PrivateKeyDer, ServerName},
+    ClientConfig, RootCertStore, ServerConfig,
+};
+use yamux::secured::TlsConfig;
+use yamux::{Config, Connection, Control, Mode};
+
+#[test]
+fn echo_over_tls_and_close_cleanly() {
+    let _ = env_logger::try_init();
+    Runtime::new().unwrap().block_on(async {
+        let (client_tls, server_tls) = tls_configs();
+        let (a, b) = memory_pipe(PipeConfig::default());
+
+        let (client, server) = future::try_join(
+            Connection::new_secured(a, client_tls, Config::default(), Mode::Client),
+            Connection::new_secured(b, server_tls, Config::default(), Mode::Server),
+        )
+        .await
+        .unwrap();
+
+        let server = task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        stream.close().await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello");
+
+        control.close().await.unwrap();
+
+        // The server sees the `close_notify` instead of a truncated TLS session.
+        server.await.unwrap().unwrap();
+    })
+}
+
+/// A client and server configuration trusting a self-signed certificate
+/// for `localhost`.
+fn tls_configs() -> (TlsConfig, TlsConfig) {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let key = PrivateKeyDer::Pkcs8(cert.key_pair.serialize_der().into());
+
+    let server = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.cert.der().clone()], key)
+        .unwrap();
+
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.cert.der().clone()).unwrap();
+    let client = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    (
+        TlsConfig::Client(Arc::new(client), ServerName::try_from("localhost").unwrap()),
+        TlsConfig::Server(Arc::new(server)),
+    )
+}
@@ -0,0 +1,123 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::{future, AsyncReadExt};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use test_harness::raw::*;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Control, Mode, StallObserver, StalledStream, State, VirtualClock};
+
+#[derive(Default)]
+struct Stalls(Mutex<Vec<StalledStream>>);
+
+impl StallObserver for Stalls {
+    fn on_stall(&self, stall: &StalledStream) {
+        self.0.lock().unwrap().push(stall.clone())
+    }
+}
+
+impl Stalls {
+    fn take(&self) -> Vec<StalledStream> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+/// Advance the clock and give the connection time to check its streams.
+async fn advance(clock: &VirtualClock, d: Duration) {
+    clock.advance(d);
+    tokio::time::sleep(Duration::from_millis(50)).await
+}
+
+#[test]
+fn unread_data_is_reported_once_per_stall() {
+    let clock = Arc::new(VirtualClock::new());
+    let stalls = Arc::new(Stalls::default());
+    let mut config = Config::default();
+    config
+        .set_stall_detector(Duration::from_secs(10))
+        .set_stall_observer(stalls.clone())
+        .set_clock(clock.clone());
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        remote
+            .send(RawFrame::data(1, SYN, b"hello".to_vec()))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let (_control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // The stream is not read.
+        advance(&clock, Duration::from_secs(10)).await;
+        advance(&clock, Duration::from_secs(10)).await;
+        let reported = stalls.take();
+        assert_eq!(reported.len(), 1, "{:?}", reported);
+        assert_eq!(reported[0].stream, stream.id());
+        assert_eq!(reported[0].state, State::Open);
+        assert_eq!(reported[0].buffered, 5);
+        assert!(!reported[0].reader_waiting);
+        assert!(reported[0].unpolled_for >= Duration::from_secs(10));
+        advance(&clock, Duration::from_secs(10)).await;
+        assert!(stalls.take().is_empty());
+
+        // Polling the stream ends the stall, a new one is reported again.
+        let mut buf = [0; 2];
+        stream.read_exact(&mut buf).await.unwrap();
+        advance(&clock, Duration::from_secs(10)).await;
+        advance(&clock, Duration::from_secs(10)).await;
+        let reported = stalls.take();
+        assert_eq!(reported.len(), 1, "{:?}", reported);
+        assert_eq!(reported[0].buffered, 3);
+    })
+}
+
+#[test]
+fn idle_streams_are_not_reported() {
+    let clock = Arc::new(VirtualClock::new());
+    let stalls = Arc::new(Stalls::default());
+    let mut config = Config::default();
+    config
+        .set_stall_detector(Duration::from_secs(10))
+        .set_stall_observer(stalls.clone())
+        .set_clock(clock.clone());
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        remote
+            .send(RawFrame::data(1, SYN, b"hello".to_vec()))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let (_control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        // A reader waits for data which does not arrive, which is no stall.
+        let reader = task::spawn(async move {
+            let mut buf = [0; 1];
+            stream.read(&mut buf).await
+        });
+        for _ in 0..3 {
+            advance(&clock, Duration::from_secs(10)).await;
+        }
+        assert!(stalls.take().is_empty());
+        reader.abort();
+    })
+}
@@ -0,0 +1,112 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{
+    supervisor::{Supervisor, Usage},
+    Config, ConnectionError, Control,
+};
+
+/// Connect `n` clients to echo servers and register them with the supervisor.
+fn supervised(supervisor: &mut Supervisor, n: usize) -> Vec<Control> {
+    (0..n)
+        .map(|_| {
+            let (server, client) =
+                memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+            task::spawn(echo_server(server));
+            let (control, client) = Control::new(client);
+            task::spawn(noop_server(client));
+            supervisor.register(control.clone());
+            control
+        })
+        .collect()
+}
+
+/// Enforce the limits until the usage satisfies the given predicate.
+async fn enforce_until(supervisor: &mut Supervisor, f: impl Fn(&Usage) -> bool) -> Usage {
+    for _ in 0..100 {
+        let usage = supervisor.enforce().await;
+        if f(&usage) {
+            return usage;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await
+    }
+    panic!("usage did not change in time")
+}
+
+#[test]
+fn supervisor_enforces_global_stream_limit() {
+    Runtime::new().unwrap().block_on(async {
+        let mut supervisor = Supervisor::new();
+        supervisor.set_max_streams(2);
+        let mut controls = supervised(&mut supervisor, 2);
+
+        let mut s1 = controls[0].open_stream().await.unwrap();
+        let _s2 = controls[1].open_stream().await.unwrap();
+        let usage = supervisor.enforce().await;
+        assert_eq!(usage.connections, 2);
+        assert_eq!(usage.streams, 2);
+        assert!(usage.paused);
+
+        for control in &mut controls {
+            assert!(matches!(
+                control.open_stream().await,
+                Err(ConnectionError::TooManyStreams)
+            ));
+        }
+
+        s1.close().await.unwrap();
+        drop(s1);
+        let usage = enforce_until(&mut supervisor, |u| !u.paused).await;
+        assert_eq!(usage.streams, 1);
+        controls[0].open_stream().await.unwrap();
+    })
+}
+
+#[test]
+fn supervisor_enforces_global_buffer_limit() {
+    Runtime::new().unwrap().block_on(async {
+        let mut supervisor = Supervisor::new();
+        supervisor.set_max_buffered_bytes(100);
+        let mut controls = supervised(&mut supervisor, 1);
+
+        // The echoed data is buffered until it is read.
+        let mut stream = controls[0].open_stream().await.unwrap();
+        stream.write_all(&[1; 100]).await.unwrap();
+        let usage = enforce_until(&mut supervisor, |u| u.paused).await;
+        assert_eq!(usage.buffered_bytes, 100);
+        assert!(matches!(
+            controls[0].open_stream().await,
+            Err(ConnectionError::TooManyStreams)
+        ));
+
+        let mut buf = [0; 100];
+        stream.read_exact(&mut buf).await.unwrap();
+        let usage = supervisor.enforce().await;
+        assert_eq!(usage.buffered_bytes, 0);
+        assert!(!usage.paused);
+        controls[0].open_stream().await.unwrap();
+    })
+}
+
+#[test]
+fn supervisor_removes_closed_connections() {
+    Runtime::new().unwrap().block_on(async {
+        let mut supervisor = Supervisor::new();
+        let mut controls = supervised(&mut supervisor, 2);
+        controls[1].close().await.unwrap();
+        let usage = supervisor.enforce().await;
+        assert_eq!(usage.connections, 1);
+        assert_eq!(supervisor.num_connections(), 1);
+    })
+}
@@ -13,15 +13,22 @@ use futures::executor::LocalPool;
 use futures::future::join;
 use futures::io::AsyncReadExt;
 use futures::prelude::*;
-use futures::task::{Spawn, SpawnExt};
+use futures::task::{LocalSpawnExt, Spawn, SpawnExt};
 use quickcheck::{QuickCheck, TestResult};
+use std::io::IoSlice;
 use std::panic::panic_any;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 use test_harness::*;
 use tokio::{runtime::Runtime, task};
-use yamux::{Config, Connection, ConnectionError, Control, Mode};
+use yamux::{
+    AckDeadlineResponse, CloseReason, Config, ConfigUpdate, Connection, ConnectionError, Control,
+    Extensions, MaxStreamsResponse, Mode, OpenMode, ProbeMode, ResetReason, StreamError,
+    StreamSnapshot, VirtualClock, WindowUpdateMode, WindowUpdateThreshold,
+};
 
 #[test]
 fn prop_config_send_recv_single() {
@@ -112,6 +119,129 @@ fn prop_send_recv() {
     QuickCheck::new().tests(1).quickcheck(prop as fn(_) -> _)
 }
 
+#[test]
+fn prop_send_recv_memory_pipe() {
+    fn prop(
+        msgs: Vec<Msg>,
+        TestConfig(cfg1): TestConfig,
+        TestConfig(cfg2): TestConfig,
+    ) -> Result<(), ConnectionError> {
+        let mut pipe = PipeConfig::default();
+        pipe.set_capacity(4096)
+            .set_max_packet_size(1400)
+            .set_latency(Duration::from_millis(1));
+
+        Runtime::new().unwrap().block_on(async move {
+            let (server, client) = memory_connected_peers(cfg1, cfg2, pipe);
+
+            let server = echo_server(server);
+            let client = async {
+                let (control, client) = Control::new(client);
+                task::spawn(noop_server(client));
+                send_on_separate_streams(control, msgs).await?;
+
+                Ok(())
+            };
+
+            futures::future::try_join(server, client).await?;
+
+            Ok(())
+        })
+    }
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop as fn(_, _, _) -> _)
+}
+
+#[test]
+fn prop_send_recv_frame_transport() {
+    fn prop(
+        msgs: Vec<Msg>,
+        TestConfig(cfg1): TestConfig,
+        TestConfig(cfg2): TestConfig,
+    ) -> Result<(), ConnectionError> {
+        Runtime::new().unwrap().block_on(async move {
+            let (server, client) = frame_pipe();
+            let server = Connection::with_transport(server, cfg1, Mode::Server);
+            let client = Connection::with_transport(client, cfg2, Mode::Client);
+
+            let server = echo_server(server);
+            let client = async {
+                let (control, client) = Control::new(client);
+                task::spawn(noop_server(client));
+                send_on_separate_streams(control, msgs).await?;
+
+                Ok(())
+            };
+
+            futures::future::try_join(server, client).await?;
+
+            Ok(())
+        })
+    }
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop as fn(_, _, _) -> _)
+}
+
+#[test]
+fn prop_send_recv_websocket() {
+    fn prop(
+        msgs: Vec<Msg>,
+        TestConfig(cfg1): TestConfig,
+        TestConfig(cfg2): TestConfig,
+    ) -> Result<(), ConnectionError> {
+        Runtime::new().unwrap().block_on(async move {
+            let (server, client) = ws_connected_peers(cfg1, cfg2).await?;
+
+            let server = echo_server(server);
+            let client = async {
+                let (control, client) = Control::new(client);
+                task::spawn(noop_server(client));
+                send_on_separate_streams(control, msgs).await?;
+
+                Ok(())
+            };
+
+            futures::future::try_join(server, client).await?;
+
+            Ok(())
+        })
+    }
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop as fn(_, _, _) -> _)
+}
+
+#[test]
+fn prop_send_recv_messages() {
+    fn prop(
+        msgs: Vec<Msg>,
+        TestConfig(cfg1): TestConfig,
+        TestConfig(cfg2): TestConfig,
+    ) -> Result<(), ConnectionError> {
+        Runtime::new().unwrap().block_on(async move {
+            let (server, client) = messages_connected_peers(cfg1, cfg2);
+
+            let server = echo_server(server);
+            let client = async {
+                let (control, client) = Control::new(client);
+                task::spawn(noop_server(client));
+                send_on_separate_streams(control, msgs).await?;
+
+                Ok(())
+            };
+
+            futures::future::try_join(server, client).await?;
+
+            Ok(())
+        })
+    }
+    QuickCheck::new()
+        .tests(10)
+        .quickcheck(prop as fn(_, _, _) -> _)
+}
+
 #[test]
 fn prop_max_streams() {
     fn prop(n: usize) -> Result<bool, ConnectionError> {
@@ -140,6 +270,204 @@ fn prop_max_streams() {
     QuickCheck::new().tests(7).quickcheck(prop as fn(_) -> _)
 }
 
+#[test]
+fn too_many_streams_is_recoverable() {
+    let mut cfg = Config::default();
+    cfg.set_max_num_streams(2);
+
+    Runtime::new().unwrap().block_on(async move {
+        let (server, client) = connected_peers(cfg.clone(), cfg).await.unwrap();
+
+        task::spawn(echo_server(server));
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut first = control.open_stream().await.unwrap();
+        let _second = control.open_stream().await.unwrap();
+
+        let e = control.open_stream().await.unwrap_err();
+        assert!(matches!(e, ConnectionError::TooManyStreams));
+        assert!(e.is_recoverable() && !e.is_fatal());
+
+        // Once a stream is closed by both sides, a new one can be opened.
+        first.write_all(b"x").await.unwrap();
+        first.close().await.unwrap();
+        let mut buf = Vec::new();
+        first.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"x");
+        drop(first);
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match control.open_stream().await {
+                    Ok(stream) => return stream,
+                    Err(ConnectionError::TooManyStreams) => {
+                        tokio::time::sleep(Duration::from_millis(10)).await
+                    }
+                    Err(e) => panic!("unexpected error: {}", e),
+                }
+            }
+        })
+        .await
+        .unwrap();
+    })
+}
+
+#[test]
+fn resume_connection_over_new_transport() {
+    Runtime::new().unwrap().block_on(async {
+        let (a, b) = memory_pipe(PipeConfig::default());
+        let mut client = Connection::new(a, Config::default(), Mode::Client);
+        let server = Connection::new(b, Config::default(), Mode::Server);
+
+        let mut outbound = future::poll_fn(|cx| client.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        let (inbound_tx, mut inbound_rx) = unbounded();
+        let (stop_client, client) = drive_until_stopped(client, inbound_tx.clone());
+        let (stop_server, server) = drive_until_stopped(server, inbound_tx);
+
+        outbound.write_all(b"hello").await.unwrap();
+        let mut inbound = inbound_rx.next().await.unwrap();
+        let mut buf = [0; 5];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // Detach both connections from the failed transport ...
+        stop_client.send(()).unwrap();
+        stop_server.send(()).unwrap();
+        let (_, client) = client.await.unwrap().into_parts().unwrap();
+        let (_, server) = server.await.unwrap().into_parts().unwrap();
+        assert_eq!(client.streams().len(), 1);
+        assert_eq!(client.streams()[0].id, outbound.id());
+        assert_eq!(server.streams()[0].id, inbound.id());
+
+        // The stream state can be sent to the remote for comparison.
+        let value = serde_json::to_value(&server).unwrap();
+        let streams: Vec<StreamSnapshot> =
+            serde_json::from_value(value["streams"].clone()).unwrap();
+        assert_eq!(streams, server.streams());
+        assert_eq!(value["streams"][0]["state"], "open");
+
+        // ... and resume them over a new one.
+        let (a, b) = memory_pipe(PipeConfig::default());
+        let (inbound_tx, _inbound_rx) = unbounded();
+        let (_stop_client, _) =
+            drive_until_stopped(Connection::from_parts(a, client), inbound_tx.clone());
+        let (_stop_server, _) = drive_until_stopped(Connection::from_parts(b, server), inbound_tx);
+
+        inbound.write_all(b"world").await.unwrap();
+        outbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"world");
+        outbound.close().await.unwrap();
+        let mut rest = Vec::new();
+        inbound.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    })
+}
+
+/// Drive the connection, forwarding inbound streams, until stopped.
+fn drive_until_stopped<T>(
+    mut connection: Connection<T>,
+    inbound: UnboundedSender<yamux::Stream>,
+) -> (
+    futures::channel::oneshot::Sender<()>,
+    task::JoinHandle<Connection<T>>,
+)
+where
+    T: yamux::Transport + Send + 'static,
+{
+    let (stop_tx, mut stop_rx) = futures::channel::oneshot::channel();
+    let handle = task::spawn(async move {
+        future::poll_fn(|cx| {
+            if stop_rx.poll_unpin(cx).is_ready() {
+                return Poll::Ready(());
+            }
+            while let Poll::Ready(Some(stream)) = connection.poll_next_inbound(cx) {
+                inbound.unbounded_send(stream.unwrap()).unwrap();
+            }
+            Poll::Pending
+        })
+        .await;
+        connection
+    });
+    (stop_tx, handle)
+}
+
+#[test]
+fn dropped_streams_are_removed() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut streams = Vec::new();
+        for _ in 0..100 {
+            streams.push(control.open_stream().await.unwrap())
+        }
+        assert_eq!(control.stats().await.unwrap().num_streams, 100);
+
+        // The halves of each stream are dropped concurrently.
+        let threads = streams
+            .into_iter()
+            .map(|stream| {
+                let (reader, writer) = stream.into_split();
+                let thread = std::thread::spawn(move || drop(reader));
+                drop(writer);
+                thread
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap()
+        }
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while control.stats().await.unwrap().num_streams > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await
+            }
+        })
+        .await
+        .unwrap();
+    })
+}
+
+#[test]
+fn max_streams_reset_keeps_connection_alive() {
+    let mut server_cfg = Config::default();
+    server_cfg.set_max_num_streams(1);
+    server_cfg.set_max_num_streams_response(MaxStreamsResponse::Reset);
+
+    Runtime::new().unwrap().block_on(async move {
+        let (server, client) =
+            memory_connected_peers(server_cfg, Config::default(), PipeConfig::default());
+
+        task::spawn(echo_server(server));
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut first = control.open_stream().await.unwrap();
+        send_recv_message(&mut first, Msg(vec![1; 10]))
+            .await
+            .unwrap();
+
+        // The second stream exceeds the server's limit and is reset.
+        let mut second = control.open_stream().await.unwrap();
+        second.write_all(&[2; 10]).await.unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(second.read_to_end(&mut buf).await.unwrap(), 0);
+
+        // The first stream is unaffected.
+        send_recv_message(&mut first, Msg(vec![3; 10]))
+            .await
+            .unwrap();
+        first.close().await.unwrap();
+    });
+}
+
 #[test]
 fn prop_send_recv_half_closed() {
     fn prop(msg: Msg) -> Result<(), ConnectionError> {
@@ -267,6 +595,1626 @@ fn write_deadlock() {
     );
 }
 
+/// A receive window set on an inbound stream before it is acknowledged allows
+/// the remote to write that much data without the stream being read.
+#[test]
+fn inbound_stream_receive_window() {
+    let msg = vec![1u8; 1024 * 1024];
+
+    Runtime::new().unwrap().block_on(async move {
+        let (mut server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let (written_tx, written_rx) = futures::channel::oneshot::channel();
+
+        let len = msg.len();
+        let server = async move {
+            let mut server = stream::poll_fn(move |cx| server.poll_next_inbound(cx));
+            let mut stream = server.next().await.unwrap().unwrap();
+            task::spawn(noop_server(server));
+
+            assert!(stream.set_receive_window(len as u32));
+            assert!(!stream.set_receive_window(len as u32));
+
+            // Only start reading once the client wrote everything.
+            written_rx.await.unwrap();
+            let mut b = vec![0; len];
+            stream.read_exact(&mut b).await.unwrap();
+            b
+        };
+
+        let client = async move {
+            let (mut control, client) = Control::new(client);
+            task::spawn(noop_server(client));
+
+            let mut stream = control.open_stream().await.unwrap();
+            stream.write_all(&msg).await.unwrap();
+            stream.close().await.unwrap();
+            written_tx.send(()).unwrap();
+            msg
+        };
+
+        let (received, sent) = futures::future::join(server, client).await;
+        assert_eq!(received, sent);
+    });
+}
+
+/// With `WindowUpdateMode::Hybrid` both endpoints can finish writing more than
+/// a receive window worth of data before either of them starts reading.
+#[test]
+fn hybrid_mode_write_without_read() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+    let spawner = pool.spawner();
+
+    let msg = vec![1u8; 512 * 1024];
+    let (server_endpoint, client_endpoint) = bounded::channel(("S", 1024), ("C", 1024));
+
+    let mut cfg = Config::default();
+    cfg.set_window_update_mode(WindowUpdateMode::Hybrid);
+
+    let mut server = Connection::new(server_endpoint, cfg.clone(), Mode::Server);
+    let server_msg = msg.clone();
+    let server_spawner = spawner.clone();
+    spawner
+        .spawn_local(async move {
+            let mut server = stream::poll_fn(move |cx| server.poll_next_inbound(cx));
+            let mut stream = server.next().await.unwrap().unwrap();
+            server_spawner.spawn_local(noop_server(server)).unwrap();
+            stream.write_all(&server_msg).await.unwrap();
+            let mut b = vec![0; server_msg.len()];
+            stream.read_exact(&mut b).await.unwrap();
+            assert_eq!(b, server_msg);
+            stream.close().await.unwrap();
+        })
+        .unwrap();
+
+    let client = Connection::new(client_endpoint, cfg, Mode::Client);
+    let (mut ctrl, client) = Control::new(client);
+    spawner.spawn_local(noop_server(client)).unwrap();
+
+    pool.run_until(async move {
+        let mut stream = ctrl.open_stream().await.unwrap();
+        stream.write_all(&msg).await.unwrap();
+        let mut b = vec![0; msg.len()];
+        stream.read_exact(&mut b).await.unwrap();
+        assert_eq!(b, msg);
+        stream.close().await.unwrap();
+    });
+}
+
+/// With `WindowUpdateMode::Hybrid` the connection grants credit if the reader
+/// woken by the frame exhausting the receive window does not come back to read.
+#[test]
+fn hybrid_mode_reader_stalls_as_window_runs_out() {
+    let clock = Arc::new(VirtualClock::new());
+    let mut cfg = Config::default();
+    cfg.set_window_update_mode(WindowUpdateMode::Hybrid)
+        .set_clock(clock.clone());
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(cfg, Mode::Server);
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, Vec::new()))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        task::spawn(noop_server(stream::poll_fn(move |cx| {
+            connection.poll_next_inbound(cx)
+        })));
+
+        // The reader is waiting when the window runs out, but never reads.
+        let mut b = [0; 1];
+        assert!(futures::poll!(stream.read(&mut b)).is_pending());
+        let body = vec![0; yamux::DEFAULT_CREDIT as usize];
+        remote.send(raw::RawFrame::data(1, 0, body)).await.unwrap();
+
+        // No credit is granted right away.
+        remote.send(raw::RawFrame::ping(0, 1)).await.unwrap();
+        let mut credit = 0;
+        loop {
+            let frame = remote.recv().await.unwrap().unwrap();
+            if frame.tag == raw::TAG_PING {
+                break;
+            }
+            if frame.tag == raw::TAG_WINDOW_UPDATE {
+                credit += frame.length
+            }
+        }
+        assert_eq!(credit, 0);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        clock.advance(Duration::from_secs(1));
+        let frame = remote
+            .recv_until(|f| f.tag == raw::TAG_WINDOW_UPDATE && f.length > 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.stream_id, 1);
+        assert_eq!(frame.length, yamux::DEFAULT_CREDIT);
+
+        // The buffered data is still there to read.
+        let mut b = vec![0; yamux::DEFAULT_CREDIT as usize];
+        stream.read_exact(&mut b).await.unwrap();
+    })
+}
+
+#[test]
+fn drain_finishes_existing_streams_and_resets_new_ones() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, b"hello".to_vec()))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let drain = task::spawn(future::poll_fn(move |cx| connection.poll_drain(cx)));
+
+        let frame = remote
+            .recv_until(|f| f.tag == raw::TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(raw::GO_AWAY_NORMAL), "{:?}", frame);
+
+        // New inbound streams are reset.
+        remote
+            .send(raw::RawFrame::data(3, raw::SYN, vec![1]))
+            .await
+            .unwrap();
+        let frame = remote
+            .recv_until(|f| f.stream_id == 3)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(3), "{:?}", frame);
+
+        // The existing stream is still serviced.
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        stream.write_all(b"world").await.unwrap();
+        let frame = remote
+            .recv_until(|f| f.stream_id == 1 && !f.body.is_empty())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.body, b"world");
+
+        // Once the stream is closed by both sides, draining completes.
+        stream.close().await.unwrap();
+        remote
+            .send(raw::RawFrame::data(1, raw::FIN, Vec::new()))
+            .await
+            .unwrap();
+        drain.await.unwrap().unwrap();
+
+        // No second `GoAway` is sent before the connection is closed.
+        let frame = remote
+            .recv_until(|f| f.tag == raw::TAG_GO_AWAY)
+            .await
+            .unwrap();
+        assert_eq!(frame, None);
+    })
+}
+
+#[test]
+fn idle_connection_is_closed() {
+    let timeout = Duration::from_millis(100);
+    let mut config = Config::default();
+    config.set_idle_timeout(timeout);
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, vec![1]))
+            .await
+            .unwrap();
+        let stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        let start = std::time::Instant::now();
+        drop(stream);
+
+        let server = task::spawn(future::poll_fn(move |cx| connection.poll_next_inbound(cx)));
+
+        let frame = remote
+            .recv_until(|f| f.tag == raw::TAG_GO_AWAY)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_go_away(raw::GO_AWAY_NORMAL), "{:?}", frame);
+        assert!(start.elapsed() >= timeout);
+        assert!(server.await.unwrap().is_none());
+    })
+}
+
+#[test]
+fn idle_timeout_works_outside_of_tokio_runtime() {
+    // The `tokio` feature is enabled for this crate, yet timers must not
+    // require a tokio runtime.
+    let mut config = Config::default();
+    config.set_idle_timeout(Duration::from_millis(100));
+
+    futures::executor::block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        let server = future::poll_fn(move |cx| connection.poll_next_inbound(cx));
+        let client = async {
+            let frame = remote
+                .recv_until(|f| f.tag == raw::TAG_GO_AWAY)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(frame.is_go_away(raw::GO_AWAY_NORMAL), "{:?}", frame);
+        };
+        let (inbound, ()) = future::join(server, client).await;
+        assert!(inbound.is_none());
+    })
+}
+
+#[test]
+fn idle_timeout_follows_virtual_clock() {
+    let clock = Arc::new(VirtualClock::new());
+    let mut config = Config::default();
+    config.set_idle_timeout(Duration::from_secs(60));
+    config.set_clock(clock.clone());
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        let server = task::spawn(future::poll_fn(move |cx| connection.poll_next_inbound(cx)));
+
+        // No time passes unless the clock is advanced.
+        remote.send(raw::RawFrame::ping(0, 1)).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::ping(raw::ACK, 1));
+        clock.advance(Duration::from_secs(59));
+        remote.send(raw::RawFrame::ping(0, 2)).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::ping(raw::ACK, 2));
+
+        // The ping counts as activity, restarting the timeout.
+        clock.advance(Duration::from_secs(60));
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert!(frame.is_go_away(raw::GO_AWAY_NORMAL), "{:?}", frame);
+        assert!(server.await.unwrap().is_none());
+    })
+}
+
+#[test]
+fn unanswered_keepalive_ping_fails_connection() {
+    let mut config = Config::default();
+    config.set_liveness_probe(ProbeMode::KeepAlive {
+        interval: Duration::from_millis(50),
+        timeout: Duration::from_millis(100),
+    });
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        let server = task::spawn(future::poll_fn(move |cx| connection.poll_next_inbound(cx)));
+
+        // An answered ping keeps the connection alive.
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::ping(raw::SYN, 1));
+        remote.send(raw::RawFrame::ping(raw::ACK, 1)).await.unwrap();
+
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::ping(raw::SYN, 2));
+        let result = server.await.unwrap();
+        match result {
+            Some(Err(ConnectionError::PeerUnresponsive {
+                ping_unanswered: Some(d),
+                write_blocked: None,
+                ..
+            })) => assert!(d >= Duration::from_millis(100)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    })
+}
+
+#[test]
+fn stalled_writes_fail_connection() {
+    let timeout = Duration::from_millis(100);
+    let mut config = Config::default();
+    config.set_liveness_probe(ProbeMode::WriteStall { timeout });
+
+    Runtime::new().unwrap().block_on(async {
+        let mut pipe = PipeConfig::default();
+        pipe.set_capacity(1024);
+        let (local, _remote) = memory_pipe(pipe);
+        let mut connection = Connection::new(local, config, Mode::Client);
+
+        // The remote never reads.
+        let mut stream = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        stream.write_all(&[1; 64 * 1024]).await.unwrap();
+        let result = future::poll_fn(|cx| connection.poll_next_inbound(cx)).await;
+        match result {
+            Some(Err(ConnectionError::PeerUnresponsive {
+                ping_unanswered: None,
+                write_blocked: Some(d),
+                ..
+            })) => assert!(d >= timeout),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    })
+}
+
+#[test]
+fn seeded_connection_ids_are_reproducible() {
+    let ids = || {
+        let mut config = Config::default();
+        config.set_rng_seed(42);
+        (0..3)
+            .map(|_| {
+                let (connection, _) = raw_peer(config.clone(), Mode::Client);
+                connection.id()
+            })
+            .collect::<Vec<_>>()
+    };
+    let first = ids();
+    assert_eq!(first, ids());
+    assert_ne!(first[0], first[1]);
+}
+
+#[test]
+fn stream_accounting() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        assert_eq!(connection.num_streams(), 0);
+        assert!(connection.can_open_outbound());
+        assert_eq!(connection.remaining_stream_ids(), u32::MAX / 2);
+
+        let stream = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        assert_eq!(connection.num_streams(), 1);
+        assert_eq!(connection.remaining_stream_ids(), u32::MAX / 2 - 1);
+
+        // Once the remote is going away, no new streams can be opened.
+        remote
+            .send(raw::RawFrame::go_away(raw::GO_AWAY_NORMAL))
+            .await
+            .unwrap();
+        future::poll_fn(|cx| {
+            assert!(connection.poll_next_inbound(cx).is_pending());
+            Poll::Ready(())
+        })
+        .await;
+        assert!(!connection.can_open_outbound());
+        assert_eq!(connection.num_streams(), 1);
+
+        drop(stream);
+    })
+}
+
+#[test]
+fn stream_id_is_released_after_closure() {
+    let mut config = Config::default();
+    config.set_stream_id_recycling(true);
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+
+        remote
+            .send(raw::RawFrame::data(
+                1,
+                raw::SYN | raw::FIN | raw::EXT_STREAM_ID_RECYCLING,
+                vec![1],
+            ))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        stream.close().await.unwrap();
+        drop(stream);
+        task::spawn(future::poll_fn(move |cx| connection.poll_next_inbound(cx)));
+
+        let frame = remote
+            .recv_until(|f| f.flags & raw::RELEASE == raw::RELEASE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.tag, raw::TAG_WINDOW_UPDATE);
+        assert_eq!(frame.stream_id, 1);
+    })
+}
+
+#[test]
+fn stream_id_is_not_released_unless_negotiated() {
+    let mut config = Config::default();
+    config.set_stream_id_recycling(true);
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN | raw::FIN, vec![1]))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        stream.close().await.unwrap();
+        drop(stream);
+        task::spawn(future::poll_fn(move |cx| connection.poll_next_inbound(cx)));
+
+        // Give the connection time to garbage collect the stream.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        remote
+            .send(raw::RawFrame::ping(raw::SYN, 42))
+            .await
+            .unwrap();
+
+        let frame = remote
+            .recv_until(|f| f.tag == raw::TAG_PING || f.flags & raw::RELEASE == raw::RELEASE)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, raw::RawFrame::ping(raw::ACK, 42));
+    })
+}
+
+#[test]
+fn coalesce_writes() {
+    let max_delay = Duration::from_millis(100);
+    let mut config = Config::default();
+    config.set_coalesce_writes(max_delay, 90);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        for _ in 0..10 {
+            stream.write_all(&[1; 10]).await.unwrap();
+        }
+
+        // The first frame opens the stream and is not extended.
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, vec![1; 10]));
+        // All other writes are coalesced up to the max. number of bytes.
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, 0, vec![1; 90]));
+
+        // A smaller frame is held back for the max. delay.
+        let start = std::time::Instant::now();
+        stream.write_all(&[2; 10]).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, 0, vec![2; 10]));
+        assert!(start.elapsed() >= max_delay);
+    })
+}
+
+#[test]
+fn vectored_writes_share_a_frame() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        let header = [0; 4];
+        let payload = [1; 16];
+        let mut bufs = [IoSlice::new(&header), IoSlice::new(&payload)];
+        stream.write_all_vectored(&mut bufs).await.unwrap();
+
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(
+            frame,
+            raw::RawFrame::data(1, raw::SYN, [&header[..], &payload[..]].concat())
+        );
+    })
+}
+
+#[test]
+fn stop_sending_fails_remote_writes() {
+    let mut config = Config::default();
+    config.set_extensions(Extensions::STOP_SENDING);
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut server, client) =
+            memory_connected_peers(config.clone(), config, PipeConfig::default());
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        task::spawn(async move {
+            let mut server = stream::poll_fn(move |cx| server.poll_next_inbound(cx));
+            let mut stream = server.next().await.unwrap().unwrap();
+            stream.stop_sending().await.unwrap();
+            // Buffered data is discarded and nothing more can be read.
+            assert_eq!(stream.read(&mut [0; 8]).await.unwrap(), 0);
+            // Writing is not affected.
+            stream.write_all(b"bye").await.unwrap();
+            stream.close().await.unwrap();
+            noop_server(server).await
+        });
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"bye");
+
+        // Writes fail well before the credit is used up.
+        let err = stream.write_all(&[0; 1024]).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    })
+}
+
+#[test]
+fn stop_sending_resets_stream_unless_negotiated() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, vec![1]))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        stream.stop_sending().await.unwrap();
+        task::spawn(future::poll_fn(move |cx| connection.poll_next_inbound(cx)));
+
+        let frame = remote
+            .recv_until(|f| f.stream_id == 1 && f.flags & (raw::RST | raw::STOP_SENDING) != 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(frame.is_rst(1), "{:?}", frame);
+        let err = stream.write_all(b"x").await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+    })
+}
+
+#[test]
+fn tagged_streams_are_dispatched_by_protocol() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        task::spawn(async move {
+            let mut server = stream::poll_fn(move |cx| server.poll_next_inbound(cx));
+            while let Some(Ok(mut stream)) = server.next().await {
+                task::spawn(async move {
+                    let tag = stream.protocol_tag().await.unwrap();
+                    let reply: &[u8] = match tag {
+                        1 => b"one",
+                        2 => b"two",
+                        _ => b"unknown",
+                    };
+                    // The tag is cached and not part of the data.
+                    assert_eq!(stream.protocol_tag().await.unwrap(), tag);
+                    let mut buf = Vec::new();
+                    stream.read_to_end(&mut buf).await.unwrap();
+                    assert_eq!(buf, b"hello");
+                    stream.write_all(reply).await.unwrap();
+                    stream.close().await.unwrap();
+                });
+            }
+        });
+
+        for (tag, reply) in [(1, &b"one"[..]), (2, b"two"), (7, b"unknown")] {
+            let mut stream = control.open_stream_tagged(tag).await.unwrap();
+            assert_eq!(stream.protocol_tag().await.unwrap(), tag);
+            stream.write_all(b"hello").await.unwrap();
+            stream.close().await.unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, reply);
+        }
+    })
+}
+
+#[test]
+fn cloned_write_halves() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        task::spawn(echo_server(server));
+
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let stream = control.open_stream().await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+
+        let writers = (0..10u8).map(|i| {
+            let mut writer = writer.clone();
+            task::spawn(async move {
+                for _ in 0..10 {
+                    writer.write_all(&[i; 100]).await.unwrap();
+                }
+            })
+        });
+        future::try_join_all(writers).await.unwrap();
+        writer.close().await.unwrap();
+
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf.len(), 10 * 10 * 100);
+        // Writes of different write halves are not interleaved.
+        let mut counts = [0; 10];
+        for chunk in buf.chunks(100) {
+            assert!(chunk.iter().all(|b| *b == chunk[0]));
+            counts[chunk[0] as usize] += 1;
+        }
+        assert_eq!(counts, [10; 10]);
+    })
+}
+
+#[test]
+fn max_send_rate() {
+    let mut config = Config::default();
+    config.set_max_send_rate(100_000);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let start = std::time::Instant::now();
+        let mut stream = control.open_stream().await.unwrap();
+        task::spawn(async move { stream.write_all(&[0; 200_000]).await.unwrap() });
+
+        // The first 100 KB are sent right away, the rest within another second.
+        let mut received = 0;
+        while received < 200_000 {
+            let frame = remote.recv().await.unwrap().unwrap();
+            received += frame.body.len();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(950));
+    })
+}
+
+#[test]
+fn send_pacing() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let start = std::time::Instant::now();
+        let mut paced = control.open_stream().await.unwrap();
+        paced.set_send_pacing(Some(50_000));
+        let mut unpaced = control.open_stream().await.unwrap();
+        task::spawn(async move { paced.write_all(&[0; 100_000]).await.unwrap() });
+        task::spawn(async move { unpaced.write_all(&[0; 100_000]).await.unwrap() });
+
+        let mut received = [0; 2];
+        let mut unpaced_done = None;
+        while received != [100_000; 2] {
+            let frame = remote.recv().await.unwrap().unwrap();
+            received[(frame.stream_id / 2) as usize] += frame.body.len();
+            if received[1] == 100_000 && unpaced_done.is_none() {
+                unpaced_done = Some(start.elapsed());
+            }
+        }
+
+        // The unpaced stream is not held up by the paced one, which sends
+        // a burst of one frame and the remainder at the given rate.
+        assert!(unpaced_done.unwrap() < Duration::from_millis(500));
+        assert!(start.elapsed() >= Duration::from_millis(1500));
+    })
+}
+
+#[test]
+fn wait_for_credit() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        assert_eq!(stream.send_credit(), yamux::DEFAULT_CREDIT);
+        assert_eq!(stream.recv_window(), yamux::DEFAULT_CREDIT);
+
+        stream
+            .write_all(&vec![0; yamux::DEFAULT_CREDIT as usize])
+            .await
+            .unwrap();
+        assert_eq!(stream.send_credit(), 0);
+
+        remote
+            .send(raw::RawFrame::window_update(1, raw::ACK, 500))
+            .await
+            .unwrap();
+        let wait = tokio::time::timeout(Duration::from_millis(100), stream.wait_for_credit(1000));
+        assert!(wait.await.is_err());
+
+        remote
+            .send(raw::RawFrame::window_update(1, 0, 1000))
+            .await
+            .unwrap();
+        assert_eq!(stream.wait_for_credit(1000).await.unwrap(), 1500);
+    })
+}
+
+#[test]
+fn stream_metrics() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        let m = stream.metrics();
+        assert_eq!((m.bytes_sent, m.bytes_received, m.buffered), (0, 0, 0));
+
+        stream.write_all(&[1; 10]).await.unwrap();
+        remote
+            .send(raw::RawFrame::data(1, raw::ACK, vec![2; 20]))
+            .await
+            .unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+
+        let m = stream.metrics();
+        assert_eq!((m.bytes_sent, m.bytes_received, m.buffered), (10, 20, 15));
+        assert!(m.buffered_memory >= 20);
+        assert!(m.idle <= m.age);
+
+        // Draining the stream frees its buffer.
+        let mut buf = [0; 15];
+        stream.read_exact(&mut buf).await.unwrap();
+        let m = stream.metrics();
+        assert_eq!((m.buffered, m.buffered_memory), (0, 0));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(stream.metrics().idle >= Duration::from_millis(50));
+    })
+}
+
+#[test]
+fn pings_are_not_delayed_by_queued_data() {
+    let mut config = Config::default();
+    config.set_write_batch_size(1);
+
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(config, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // More data than fits into the pipe is queued by the connection.
+        let mut stream = control.open_stream().await.unwrap();
+        stream
+            .write_all(&vec![0; yamux::DEFAULT_CREDIT as usize])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        remote
+            .send(raw::RawFrame::ping(raw::SYN, 42))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut data = 0;
+        loop {
+            let frame = remote.recv().await.unwrap().unwrap();
+            if frame.tag == raw::TAG_PING {
+                assert_eq!(frame, raw::RawFrame::ping(raw::ACK, 42));
+                break;
+            }
+            data += frame.body.len();
+        }
+        assert!(data < yamux::DEFAULT_CREDIT as usize / 2, "{}", data);
+    })
+}
+
+#[test]
+fn reset_stream_fails_stuck_reader() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let stream = control.open_stream().await.unwrap();
+        let id = stream.id();
+        let (mut reader, mut writer) = stream.into_split();
+        writer.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+
+        // The reader is stuck waiting for data which never arrives.
+        let stuck = task::spawn(async move {
+            let mut buf = [0; 1];
+            reader.read(&mut buf).await
+        });
+
+        assert!(control.reset_stream(id).await.unwrap());
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::RST, Vec::new()));
+
+        let e = stuck.await.unwrap().unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::ConnectionReset);
+        assert_eq!(StreamError::from_io(&e), Some(StreamError::Reset));
+        let e = writer.write_all(b"y").await.unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::ConnectionReset);
+        assert_eq!(StreamError::from_io(&e), Some(StreamError::Reset));
+        let e = writer.flush().await.unwrap_err();
+        assert_eq!(StreamError::from_io(&e), Some(StreamError::Reset));
+    })
+}
+
+#[test]
+fn writes_after_close_fail_with_stream_error() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        stream.close().await.unwrap();
+        let e = stream.write_all(b"again").await.unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe);
+        assert_eq!(StreamError::from_io(&e), Some(StreamError::SendClosed));
+        // Closing again and flushing succeed, and reading is not affected.
+        stream.close().await.unwrap();
+        stream.flush().await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello");
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        control.close().await.unwrap();
+        let e = stream.write_all(b"again").await.unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::WriteZero);
+        assert_eq!(
+            StreamError::from_io(&e),
+            Some(StreamError::ConnectionClosed)
+        );
+    })
+}
+
+#[test]
+fn empty_write_opens_stream() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        assert_eq!(stream.write(&[]).await.unwrap(), 0);
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, Vec::new()));
+
+        // Once the stream is open, empty writes do not send a frame.
+        assert_eq!(stream.write(&[]).await.unwrap(), 0);
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, 0, b"x".to_vec()));
+    })
+}
+
+#[test]
+fn finished_stream_remains_readable() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"request").await.unwrap();
+        // Empty writes do not send a frame.
+        assert_eq!(stream.write(&[]).await.unwrap(), 0);
+        stream.finish().await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"request".to_vec()));
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::FIN, Vec::new()));
+        assert!(stream.is_write_closed());
+
+        let e = stream.write_all(b"more").await.unwrap_err();
+        assert_eq!(StreamError::from_io(&e), Some(StreamError::SendClosed));
+        // Finishing again does not send another FIN.
+        stream.finish().await.unwrap();
+
+        remote
+            .send(raw::RawFrame::data(1, raw::ACK, b"response".to_vec()))
+            .await
+            .unwrap();
+        remote
+            .send(raw::RawFrame::data(1, raw::FIN, Vec::new()))
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"response");
+        assert!(stream.is_closed());
+        stream.finish().await.unwrap();
+
+        let result = tokio::time::timeout(Duration::from_millis(100), remote.recv()).await;
+        assert!(result.is_err(), "{:?}", result);
+    })
+}
+
+#[test]
+fn closed_resolves_once_stream_is_closed_in_both_directions() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.finish().await.unwrap();
+        assert!(stream.is_write_closed());
+        assert!(!stream.is_read_closed());
+
+        let (reader, writer) = stream.into_split();
+        let closed = task::spawn(async move {
+            reader.closed().await;
+            reader
+        });
+        assert!(writer.closed().now_or_never().is_none());
+
+        remote
+            .send(raw::RawFrame::data(1, raw::ACK | raw::FIN, Vec::new()))
+            .await
+            .unwrap();
+        writer.closed().await;
+        let reader = closed.await.unwrap();
+        assert!(reader.is_read_closed());
+        assert!(writer.is_write_closed());
+
+        // A reset closes the stream as well.
+        let stream = control.open_stream().await.unwrap();
+        assert!(stream.closed().now_or_never().is_none());
+        control.reset_stream(stream.id()).await.unwrap();
+        stream.closed().await;
+        assert!(stream.is_closed());
+    })
+}
+
+#[test]
+fn streams_report_close_reason() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, _remote) = raw_peer(Config::default(), Mode::Client);
+        let mut stream = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        assert_eq!(stream.close_reason(), None);
+
+        drop(connection);
+        let mut buf = [0; 1];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(stream.close_reason(), Some(CloseReason::ConnectionDropped));
+
+        let (connection, remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        let driver = task::spawn(noop_server(connection));
+        let mut stream = control.open_stream().await.unwrap();
+
+        drop(remote);
+        driver.await.unwrap();
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(stream.close_reason(), Some(CloseReason::ConnectionClosed));
+    })
+}
+
+#[test]
+fn lazy_stream_open_timeout_starts_with_its_syn() {
+    Runtime::new().unwrap().block_on(async {
+        let timeout = Duration::from_millis(100);
+        let mut cfg = Config::default();
+        cfg.set_stream_open_timeout(timeout);
+        let (connection, mut remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // The remote has not seen the stream yet, so it is not reset.
+        let mut stream = control.open_stream().await.unwrap();
+        tokio::time::sleep(3 * timeout).await;
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+
+        let start = std::time::Instant::now();
+        let mut buf = [0; 1];
+        let e = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= timeout / 2);
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::RST, Vec::new()));
+    })
+}
+
+#[test]
+fn unacknowledged_stream_times_out() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_stream_open_timeout(Duration::from_millis(100));
+        let (connection, mut remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+
+        // The remote never acknowledges the stream.
+        let mut buf = [0; 1];
+        let e = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::TimedOut);
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::RST, Vec::new()));
+
+        // Acknowledged streams are left alone.
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(3, raw::SYN, b"x".to_vec()));
+        remote
+            .send(raw::RawFrame::data(3, raw::ACK, b"y".to_vec()))
+            .await
+            .unwrap();
+        stream.read_exact(&mut buf).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        stream.write_all(b"z").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(3, 0, b"z".to_vec()));
+    })
+}
+
+#[test]
+fn inbound_stream_is_acknowledged_on_deadline() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_inbound_stream_ack_deadline(Duration::from_millis(100));
+        let (connection, mut remote) = raw_peer(cfg, Mode::Server);
+        let (_control, connection) = Control::new(connection);
+        // The inbound streams are kept, but neither read nor written.
+        task::spawn(connection.try_collect::<Vec<_>>());
+
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, b"x".to_vec()))
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::window_update(1, raw::ACK, 0));
+    })
+}
+
+#[test]
+fn inbound_stream_is_reset_on_deadline() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_inbound_stream_ack_deadline(Duration::from_millis(100))
+            .set_ack_deadline_response(AckDeadlineResponse::Reset);
+        let (connection, mut remote) = raw_peer(cfg, Mode::Server);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(connection.try_collect::<Vec<_>>());
+
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, b"x".to_vec()))
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::RST, Vec::new()));
+        let resets = control.stats().await.unwrap().resets_sent;
+        assert_eq!(resets.get(ResetReason::AckDeadline), 1);
+    })
+}
+
+#[test]
+fn ack_backlog_limits_unacknowledged_streams() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_max_ack_backlog(2);
+        let (connection, mut remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut first = control.open_stream().await.unwrap();
+        first.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+
+        // Streams which have not sent their SYN yet do not count.
+        let _lazy = control.open_stream().await.unwrap();
+
+        let mut second = control.open_stream().await.unwrap();
+        second.write_all(b"y").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(5, raw::SYN, b"y".to_vec()));
+
+        // The backlog is full.
+        let mut open = control.clone();
+        let open = task::spawn(async move { open.open_stream().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!open.is_finished());
+
+        remote
+            .send(raw::RawFrame::window_update(1, raw::ACK, 0))
+            .await
+            .unwrap();
+        let mut third = open.await.unwrap().unwrap();
+        third.write_all(b"z").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(7, raw::SYN, b"z".to_vec()));
+
+        // The backlog is full again.
+        let result = tokio::time::timeout(Duration::from_millis(100), control.open_stream()).await;
+        assert!(result.is_err());
+    })
+}
+
+#[test]
+fn peek_does_not_consume_data() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        let (_control, mut connection) = Control::new(connection);
+        remote
+            .send(raw::RawFrame::data(1, raw::SYN, b"GET /".to_vec()))
+            .await
+            .unwrap();
+        remote
+            .send(raw::RawFrame::data(1, raw::FIN, b" HTTP".to_vec()))
+            .await
+            .unwrap();
+        let mut stream = connection.next().await.unwrap().unwrap();
+        task::spawn(noop_server(connection));
+
+        let mut buf = [0; 3];
+        assert_eq!(stream.peek(&mut buf).await.unwrap(), 3);
+        assert_eq!(&buf, b"GET");
+        let mut buf = [0; 16];
+        let n = stream.peek(&mut buf).await.unwrap();
+        assert!(b"GET / HTTP".starts_with(&buf[..n]), "{:?}", &buf[..n]);
+
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"GET / HTTP");
+        assert_eq!(stream.peek(&mut buf).await.unwrap(), 0);
+    })
+}
+
+#[test]
+fn ready_waits_until_a_stream_can_be_opened() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_max_outbound_streams(1);
+        let (connection, _remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        control.ready().await.unwrap();
+        let first = control.open_stream().await.unwrap();
+
+        // The max. number of outbound streams is reached.
+        let mut ready = control.clone();
+        let ready = task::spawn(async move { ready.ready().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!ready.is_finished());
+        drop(first);
+        ready.await.unwrap().unwrap();
+
+        // A paused connection is ready once resumed.
+        let mut update = ConfigUpdate::new();
+        update.set_paused(true);
+        control.update_config(update).await.unwrap();
+        let mut ready = control.clone();
+        let ready = task::spawn(async move { ready.ready().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!ready.is_finished());
+        let mut update = ConfigUpdate::new();
+        update.set_paused(false);
+        control.update_config(update).await.unwrap();
+        ready.await.unwrap().unwrap();
+        let _second = control.open_stream().await.unwrap();
+
+        control.close().await.unwrap();
+        let e = control.ready().await.unwrap_err();
+        assert!(matches!(e, ConnectionError::Closed), "{:?}", e);
+    })
+}
+
+#[test]
+fn open_mode_controls_when_syn_is_sent() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_receive_window(4 * yamux::DEFAULT_CREDIT);
+        let (connection, mut remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // The SYN of a lazily opened stream is sent with its first write,
+        // even with a receive window larger than the default.
+        let mut lazy = control.open_stream_with_mode(OpenMode::Lazy).await.unwrap();
+        let mut eager = control
+            .open_stream_with_mode(OpenMode::Eager)
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        let credit = 3 * yamux::DEFAULT_CREDIT;
+        assert_eq!(frame, raw::RawFrame::window_update(3, raw::SYN, credit));
+
+        lazy.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+        eager.write_all(b"y").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(3, 0, b"y".to_vec()));
+    })
+}
+
+#[test]
+fn idle_stream_sends_keepalives() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+
+        let interval = Duration::from_millis(100);
+        stream.set_keepalive(Some(interval));
+        for _ in 0..2 {
+            let start = std::time::Instant::now();
+            let frame = remote.recv().await.unwrap().unwrap();
+            assert_eq!(frame, raw::RawFrame::data(1, 0, Vec::new()));
+            assert!(start.elapsed() >= interval / 2);
+        }
+
+        stream.set_keepalive(None);
+        let result = tokio::time::timeout(3 * interval, remote.recv()).await;
+        assert!(result.is_err(), "{:?}", result);
+    })
+}
+
+#[test]
+fn lazy_stream_sends_keepalives_after_its_syn() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        let interval = Duration::from_millis(100);
+        stream.set_keepalive(Some(interval));
+        // No keep-alives before the stream has been opened.
+        let result = tokio::time::timeout(2 * interval, remote.recv()).await;
+        assert!(result.is_err(), "{:?}", result);
+
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+        let frame = tokio::time::timeout(10 * interval, remote.recv())
+            .await
+            .expect("keep-alive")
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, 0, Vec::new()));
+    })
+}
+
+#[test]
+fn update_config_applies_to_new_streams() {
+    Runtime::new().unwrap().block_on(async {
+        let (connection, mut remote) = raw_peer(Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut update = ConfigUpdate::new();
+        update
+            .set_receive_window(4 * yamux::DEFAULT_CREDIT)
+            .set_max_num_streams(1);
+        control.update_config(update).await.unwrap();
+
+        let _stream = control
+            .open_stream_with_mode(OpenMode::Eager)
+            .await
+            .unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        let credit = 3 * yamux::DEFAULT_CREDIT;
+        assert_eq!(frame, raw::RawFrame::window_update(1, raw::SYN, credit));
+        assert!(matches!(
+            control.open_stream().await,
+            Err(ConnectionError::TooManyStreams)
+        ));
+
+        control.close().await.unwrap();
+        let result = control.update_config(ConfigUpdate::new()).await;
+        assert!(matches!(result, Err(ConnectionError::Closed)));
+    })
+}
+
+#[test]
+fn connection_receive_window_is_shared_fairly() {
+    Runtime::new().unwrap().block_on(async {
+        const KIB: u32 = 1024;
+        let mut cfg = Config::default();
+        cfg.set_receive_window(1024 * KIB)
+            .set_open_mode(OpenMode::Eager)
+            .set_max_connection_receive_window(7 * 256 * KIB as usize);
+        let (connection, mut remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        // A single stream gets its whole receive window.
+        let _a = control.open_stream().await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::window_update(1, raw::SYN, 768 * KIB));
+
+        // Two streams split the budget evenly.
+        let _b = control.open_stream().await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::window_update(3, raw::SYN, 640 * KIB));
+
+        // A stream with a smaller window gets all of it, the others split the rest.
+        let _c = control.open_stream_with_window(256 * KIB).await.unwrap();
+        let _d = control.open_stream().await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::window_update(5, raw::SYN, 0));
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::window_update(7, raw::SYN, 256 * KIB));
+    })
+}
+
+#[test]
+fn window_update_threshold_controls_when_credit_is_granted() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_window_update_threshold(WindowUpdateThreshold::Bytes(2048));
+        let (connection, mut remote) = raw_peer(cfg, Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"x").await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::data(1, raw::SYN, b"x".to_vec()));
+        remote
+            .send(raw::RawFrame::data(1, raw::ACK, vec![1; 4096]))
+            .await
+            .unwrap();
+
+        // Credit is granted by the first read after the threshold is reached.
+        let mut buf = vec![0; 1024];
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.read_exact(&mut buf).await.unwrap();
+        stream.read_exact(&mut buf).await.unwrap();
+        let frame = remote.recv().await.unwrap().unwrap();
+        assert_eq!(frame, raw::RawFrame::window_update(1, 0, 2048));
+    })
+}
+
+#[test]
+fn close_timeout_bounds_graceful_close() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_close_timeout(Duration::from_millis(100));
+        let mut pipe = PipeConfig::default();
+        pipe.set_capacity(1024);
+        // The server is never polled, i.e. never reads.
+        let (_server, client) = memory_connected_peers(Config::default(), cfg, pipe);
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(&[1; 8 * 1024]).await.unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), control.close()).await;
+        assert!(matches!(result, Ok(Ok(()))), "{:?}", result);
+    })
+}
+
+#[test]
+fn cleanup_report_lists_unsent_data() {
+    Runtime::new().unwrap().block_on(async {
+        let mut pipe = PipeConfig::default();
+        pipe.set_capacity(1024);
+        let (local, remote) = memory_pipe(pipe);
+        let mut connection = Connection::new(local, Config::default(), Mode::Client);
+        let mut remote = RawFrameClient::new(remote);
+
+        let mut stream = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+            .await
+            .unwrap();
+        stream.write_all(&[1; 64 * 1024]).await.unwrap();
+        assert!(connection.cleanup_report().is_none());
+
+        // The remote fails the connection without reading.
+        remote
+            .send(raw::RawFrame::go_away(raw::GO_AWAY_INTERNAL_ERROR))
+            .await
+            .unwrap();
+        let result = future::poll_fn(|cx| connection.poll_next_inbound(cx)).await;
+        assert!(
+            matches!(result, Some(Err(ConnectionError::GoAwayReceived { .. }))),
+            "{:?}",
+            result
+        );
+        let report = connection.cleanup_report().unwrap();
+        assert_eq!(report.unsent_data, vec![stream.id()]);
+        assert!(report.discarded_frames + report.discarded_commands > 0);
+    })
+}
+
+#[test]
+fn push_mode_passes_data_to_inbound_handler() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let (pushed_tx, mut pushed_rx) = unbounded();
+        server.set_inbound_handler(move |id, data| {
+            pushed_tx.unbounded_send((id, data)).unwrap();
+        });
+        let (server_tx, mut server_rx) = unbounded();
+        let (_stop, _server) = drive_until_stopped(server, server_tx);
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut outbound = control.open_stream().await.unwrap();
+        outbound.write_all(b"x").await.unwrap();
+        let mut inbound = server_rx.next().await.unwrap();
+        let mut buf = [0; 1];
+        inbound.read_exact(&mut buf).await.unwrap();
+        inbound.set_push_mode(true);
+
+        // More than the receive window, which is only replenished because the
+        // pushed data counts as consumed.
+        let data = vec![0x42; 1024 * 1024];
+        outbound.write_all(&data).await.unwrap();
+        outbound.close().await.unwrap();
+
+        let mut n = 0;
+        while n < data.len() {
+            let (id, d) = pushed_rx.next().await.unwrap();
+            assert_eq!(id, inbound.id());
+            n += d.len();
+        }
+        assert_eq!(n, data.len());
+
+        // The end of the stream is still reported to the reader.
+        assert_eq!(inbound.read(&mut buf).await.unwrap(), 0);
+    })
+}
+
+#[test]
+fn inbound_handler_can_access_its_stream() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let slot = Arc::new(Mutex::new(None::<yamux::Stream>));
+        let (pushed_tx, mut pushed_rx) = unbounded();
+        let handler_slot = slot.clone();
+        server.set_inbound_handler(move |_, data| {
+            // Locks the stream, which must not be held while calling us.
+            let received = handler_slot
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|s: &yamux::Stream| s.metrics().bytes_received);
+            pushed_tx.unbounded_send((data.len(), received)).unwrap();
+        });
+        let (server_tx, mut server_rx) = unbounded();
+        let (_stop, _server) = drive_until_stopped(server, server_tx);
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut outbound = control.open_stream().await.unwrap();
+        outbound.write_all(b"x").await.unwrap();
+        let mut inbound = server_rx.next().await.unwrap();
+        let mut buf = [0; 1];
+        inbound.read_exact(&mut buf).await.unwrap();
+        inbound.set_push_mode(true);
+        *slot.lock().unwrap() = Some(inbound);
+
+        outbound.write_all(b"pushed").await.unwrap();
+        let (len, received) = tokio::time::timeout(Duration::from_secs(10), pushed_rx.next())
+            .await
+            .expect("handler deadlocked")
+            .unwrap();
+        assert_eq!(len, 6);
+        assert_eq!(received, Some(7));
+    })
+}
+
+#[test]
+fn connection_yields_after_poll_budget() {
+    let mut config = Config::default();
+    config.set_poll_budget(8);
+
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(config, Mode::Server);
+        for nonce in 0..100 {
+            remote
+                .send(raw::RawFrame::ping(raw::SYN, nonce))
+                .await
+                .unwrap();
+        }
+
+        // The pings exceed the poll budget, so the connection yields and
+        // asks to be polled again.
+        let woken = Arc::new(WakeCounter::default());
+        let waker = futures::task::waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert!(connection.poll_next_inbound(&mut cx).is_pending());
+        assert_eq!(woken.0.load(Ordering::SeqCst), 1);
+
+        task::spawn(noop_server(stream::poll_fn(move |cx| {
+            connection.poll_next_inbound(cx)
+        })));
+        for nonce in 0..100 {
+            let frame = remote.recv().await.unwrap().unwrap();
+            assert_eq!(frame, raw::RawFrame::ping(raw::ACK, nonce));
+        }
+    })
+}
+
+#[test]
+fn stream_is_woken_once_per_connection_poll() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut connection, mut remote) = raw_peer(Config::default(), Mode::Server);
+        remote
+            .send(raw::RawFrame::window_update(1, raw::SYN, 0))
+            .await
+            .unwrap();
+        let mut stream = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let woken = Arc::new(WakeCounter::default());
+        let waker = futures::task::waker(woken.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0; 64];
+        assert!(Pin::new(&mut stream)
+            .poll_read(&mut cx, &mut buf)
+            .is_pending());
+
+        for i in 0..10 {
+            remote
+                .send(raw::RawFrame::data(1, 0, vec![i]))
+                .await
+                .unwrap();
+        }
+        let noop = futures::task::noop_waker();
+        assert!(connection
+            .poll_next_inbound(&mut Context::from_waker(&noop))
+            .is_pending());
+        assert_eq!(woken.0.load(Ordering::SeqCst), 1);
+
+        let n = stream.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], (0..10).collect::<Vec<u8>>());
+    })
+}
+
+#[derive(Default)]
+struct WakeCounter(AtomicUsize);
+
+impl futures::task::ArcWake for WakeCounter {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
 /// Send all messages, opening a new stream for each one.
 async fn send_on_separate_streams(
     mut control: Control,
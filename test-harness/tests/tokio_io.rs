@@ -0,0 +1,72 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use futures::prelude::*;
+use http_body_util::BodyExt;
+use hyper::{service::service_fn, Request, Response};
+use std::convert::Infallible;
+use test_harness::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Connection, Control};
+
+#[test]
+fn tokio_io_echo() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut stream = control.open_stream().await.unwrap().into_tokio_io();
+        stream.write_all(&[1; 100_000]).await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, vec![1; 100_000]);
+    })
+}
+
+#[test]
+fn http_over_stream() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        task::spawn(http_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        for path in ["/a", "/b"] {
+            let stream = control.open_stream().await.unwrap().into_tokio_io();
+            let (mut sender, conn) = hyper::client::conn::http1::handshake(stream).await.unwrap();
+            task::spawn(conn);
+
+            let request = Request::get(path).body(String::new()).unwrap();
+            let response = sender.send_request(request).await.unwrap();
+            assert!(response.status().is_success());
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, format!("hello {}", path).as_bytes());
+        }
+    })
+}
+
+/// Serve HTTP/1.1 on each inbound stream of `c`.
+async fn http_server<T: yamux::Transport>(mut c: Connection<T>) {
+    while let Some(Ok(stream)) = future::poll_fn(|cx| c.poll_next_inbound(cx)).await {
+        let service = service_fn(|req: Request<hyper::body::Incoming>| async move {
+            Ok::<_, Infallible>(Response::new(format!("hello {}", req.uri().path())))
+        });
+        task::spawn(
+            hyper::server::conn::http1::Builder::new()
+                .serve_connection(stream.into_tokio_io(), service),
+        );
+    }
+}
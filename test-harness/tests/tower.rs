@@ -0,0 +1,144 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use bytes::Bytes;
+use futures::future::{self, Ready};
+use std::cell::Cell;
+use std::io;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use test_harness::*;
+use tokio::{runtime::Runtime, task, time};
+use tower_service::Service;
+use yamux::{tower, Config, Control};
+
+/// Upper-cases requests, failing empty ones.
+#[derive(Clone)]
+struct Upper;
+
+impl Service<Bytes> for Upper {
+    type Response = Bytes;
+    type Error = &'static str;
+    type Future = Ready<Result<Bytes, &'static str>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Bytes) -> Self::Future {
+        if request.is_empty() {
+            return future::ready(Err("empty request"));
+        }
+        future::ready(Ok(request.to_ascii_uppercase().into()))
+    }
+}
+
+/// Numbers its requests, being neither `Clone` nor `Sync`.
+struct Counter(Cell<usize>);
+
+impl Service<Bytes> for Counter {
+    type Response = Bytes;
+    type Error = &'static str;
+    type Future = Ready<Result<Bytes, &'static str>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: Bytes) -> Self::Future {
+        let n = self.0.get();
+        self.0.set(n + 1);
+        future::ready(Ok(Bytes::from(n.to_string())))
+    }
+}
+
+#[test]
+fn tower_client_calls_remote_service() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let (_shutdown, serve) = server.serve(
+            tower::Server::new(Upper),
+            Arc::new(|f| {
+                task::spawn(f);
+            }),
+        );
+        task::spawn(serve);
+        let (control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut client = tower::Client::new(control);
+        let calls = (0..10).map(|i| client.call(Bytes::from(format!("request {}", i))));
+        let responses = future::try_join_all(calls).await.unwrap();
+        for (i, response) in responses.into_iter().enumerate() {
+            assert_eq!(response, format!("REQUEST {}", i));
+        }
+
+        // A failing service leaves the request without a response.
+        let e = client.call(Bytes::new()).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    })
+}
+
+#[test]
+fn tower_server_shares_service() {
+    Runtime::new().unwrap().block_on(async {
+        let (server, client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        let (_shutdown, serve) = server.serve(
+            tower::Server::new(Counter(Cell::new(0))),
+            Arc::new(|f| {
+                task::spawn(f);
+            }),
+        );
+        task::spawn(serve);
+        let (control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let mut client = tower::Client::new(control);
+        let calls = (0..10).map(|_| client.call(Bytes::new()));
+        let mut responses = future::try_join_all(calls)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| String::from_utf8(r.to_vec()).unwrap().parse().unwrap())
+            .collect::<Vec<usize>>();
+        responses.sort_unstable();
+        assert_eq!(responses, (0..10).collect::<Vec<_>>());
+    })
+}
+
+#[test]
+fn tower_client_is_ready_once_a_stream_can_be_opened() {
+    Runtime::new().unwrap().block_on(async {
+        let mut cfg = Config::default();
+        cfg.set_max_outbound_streams(1);
+        let (connection, _remote) = raw_peer(cfg, yamux::Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut client = tower::Client::new(control.clone());
+        future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
+
+        // The max. number of outbound streams is reached.
+        let first = control.open_stream().await.unwrap();
+        let ready = time::timeout(
+            Duration::from_millis(100),
+            future::poll_fn(|cx| client.poll_ready(cx)),
+        );
+        assert!(ready.await.is_err());
+        drop(first);
+        future::poll_fn(|cx| client.poll_ready(cx)).await.unwrap();
+
+        control.close().await.unwrap();
+        assert!(future::poll_fn(|cx| client.poll_ready(cx)).await.is_err());
+    })
+}
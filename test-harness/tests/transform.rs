@@ -0,0 +1,149 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use bytes::{BufMut, BytesMut};
+use futures::{future, AsyncReadExt, AsyncWriteExt};
+use std::io;
+use test_harness::*;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Connection, ConnectionError, Control, FrameDecodeError, FrameTransform, Mode};
+
+/// A toy cipher, XOR-ing frames with a key stream derived from a frame
+/// counter, which is appended to detect replayed frames.
+struct Xor {
+    key: u8,
+    sent: u64,
+    received: u64,
+}
+
+impl Xor {
+    fn new(key: u8) -> Self {
+        Xor {
+            key,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    fn apply(&self, frame: &mut [u8], counter: u64) {
+        for (i, b) in frame.iter_mut().enumerate() {
+            *b ^= self.key ^ (counter as u8) ^ (i as u8)
+        }
+    }
+}
+
+impl FrameTransform for Xor {
+    fn encrypt_outbound(&mut self, frame: &mut BytesMut) -> io::Result<()> {
+        self.apply(frame, self.sent);
+        frame.put_u64(self.sent);
+        self.sent += 1;
+        Ok(())
+    }
+
+    fn decrypt_inbound(&mut self, frame: &mut BytesMut) -> io::Result<()> {
+        if frame.len() < 8 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame too short",
+            ));
+        }
+        let counter = frame.split_off(frame.len() - 8);
+        if counter[..] != self.received.to_be_bytes() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "replayed frame"));
+        }
+        self.apply(frame, self.received);
+        self.received += 1;
+        Ok(())
+    }
+
+    fn max_overhead(&self) -> usize {
+        8
+    }
+}
+
+#[test]
+fn transformed_frames_round_trip() {
+    Runtime::new().unwrap().block_on(async {
+        let (mut server, mut client) =
+            memory_connected_peers(Config::default(), Config::default(), PipeConfig::default());
+        server.set_frame_transform(Xor::new(42));
+        client.set_frame_transform(Xor::new(42));
+        task::spawn(echo_server(server));
+        let (mut control, client) = Control::new(client);
+        task::spawn(noop_server(client));
+
+        let data = (0..256 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        let stream = control.open_stream().await.unwrap();
+        let (mut reader, mut writer) = AsyncReadExt::split(stream);
+        let write = async {
+            writer.write_all(&data).await.unwrap();
+            writer.close().await.unwrap();
+        };
+        let mut echoed = Vec::new();
+        let read = reader.read_to_end(&mut echoed);
+        let ((), n) = futures::join!(write, read);
+        assert_eq!(n.unwrap(), data.len());
+        assert!(echoed == data);
+    })
+}
+
+#[test]
+fn transformed_frames_hide_data() {
+    Runtime::new().unwrap().block_on(async {
+        let (local, mut remote) = memory_pipe(PipeConfig::default());
+        let mut connection = Connection::new(local, Config::default(), Mode::Client);
+        connection.set_frame_transform(Xor::new(42));
+        let (mut control, connection) = Control::new(connection);
+        task::spawn(noop_server(connection));
+
+        let mut stream = control.open_stream().await.unwrap();
+        stream.write_all(b"secret").await.unwrap();
+
+        // A length prefixed data frame, i.e. header, body and counter.
+        let mut frame = [0; 4 + 12 + 6 + 8];
+        remote.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame[..4], 26u32.to_be_bytes());
+        assert!(!frame.windows(6).any(|w| w == b"secret"));
+        assert_eq!(frame[frame.len() - 8..], 0u64.to_be_bytes());
+    })
+}
+
+#[test]
+fn replayed_frame_fails_connection() {
+    Runtime::new().unwrap().block_on(async {
+        let (local, mut remote) = memory_pipe(PipeConfig::default());
+        let mut connection = Connection::new(local, Config::default(), Mode::Server);
+        connection.set_frame_transform(Xor::new(42));
+
+        // A ping, transformed by the remote and sent twice.
+        let mut frame = BytesMut::from(&[0, 2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 7][..]);
+        Xor::new(42).encrypt_outbound(&mut frame).unwrap();
+        let mut bytes = (frame.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&frame);
+        remote.write_all(&bytes).await.unwrap();
+        remote.write_all(&bytes).await.unwrap();
+
+        let e = future::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            matches!(
+                e,
+                ConnectionError::Decode {
+                    source: FrameDecodeError::Transform(_),
+                    ..
+                }
+            ),
+            "{:?}",
+            e
+        );
+    })
+}
@@ -0,0 +1,53 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Accounting of flow-control credit.
+//!
+//! The receiver of a stream grants the sender credit, i.e. the number of
+//! bytes it may send, starting out with 256 KiB as per the specification.
+//! Each data frame consumes credit (see [`consume`]) and window update
+//! frames grant new credit (see [`replenishable_credit`]).
+
+/// The credit left after receiving or sending a data frame with a body of
+/// `len` bytes, given the credit `window` before.
+///
+/// Returns `None` if the body exceeds the credit, i.e. violates flow control.
+pub fn consume(window: u32, len: u32) -> Option<u32> {
+    window.checked_sub(len)
+}
+
+/// The credit which can be granted to the sender of a stream to restore
+/// its receive window, given the credit `window` it has left and the
+/// number of received bytes which are still `buffered`, i.e. have not been
+/// consumed by the receiver yet.
+///
+/// The credit left may exceed a receive window which shrank after it was
+/// granted, in which case no credit can be granted.
+pub fn replenishable_credit(receive_window: u32, window: u32, buffered: u32) -> u32 {
+    receive_window
+        .saturating_sub(window)
+        .saturating_sub(buffered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_CREDIT: u32 = 256 * 1024;
+
+    #[test]
+    fn replenish_consumed_credit() {
+        let window = consume(DEFAULT_CREDIT, 1000).unwrap();
+        assert_eq!(replenishable_credit(DEFAULT_CREDIT, window, 0), 1000);
+        assert_eq!(replenishable_credit(DEFAULT_CREDIT, window, 400), 600);
+        assert_eq!(replenishable_credit(DEFAULT_CREDIT / 2, window, 0), 0);
+        assert_eq!(consume(window, DEFAULT_CREDIT), None);
+    }
+}
@@ -0,0 +1,565 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! The header of a frame and its encoding.
+
+use core::fmt;
+
+/// The message frame header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header<T> {
+    version: Version,
+    tag: Tag,
+    flags: Flags,
+    stream_id: StreamId,
+    length: Len,
+    _marker: core::marker::PhantomData<T>,
+}
+
+impl<T> fmt::Display for Header<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(Header {:?} {} (len {}) (flags {:?}))",
+            self.tag,
+            self.stream_id,
+            self.length.val(),
+            self.flags.val()
+        )
+    }
+}
+
+impl<T> Header<T> {
+    #[inline]
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    #[inline]
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    #[inline]
+    pub fn stream_id(&self) -> StreamId {
+        self.stream_id
+    }
+
+    #[inline]
+    pub fn len(&self) -> Len {
+        self.length
+    }
+
+    /// Set the length, i.e. the body length of a data frame.
+    pub fn set_len(&mut self, len: u32) {
+        self.length = Len(len)
+    }
+
+    /// Set a compression flag, i.e. `COMPRESS_LZ4` or `COMPRESS_ZSTD`, of a
+    /// data or window update frame.
+    pub fn compression(&mut self, flag: Flags) {
+        debug_assert!(matches!(self.tag, Tag::Data | Tag::WindowUpdate));
+        debug_assert!(flag == COMPRESS_LZ4 || flag == COMPRESS_ZSTD);
+        self.flags.0 |= flag.0
+    }
+
+    /// Advertise the given protocol extensions, one bit per extension, on
+    /// a data or window update frame.
+    pub fn extensions(&mut self, bits: u8) {
+        debug_assert!(matches!(self.tag, Tag::Data | Tag::WindowUpdate));
+        self.flags.0 |= u16::from(bits) << 8
+    }
+
+    /// Arbitrary type cast, use with caution.
+    fn cast<U>(self) -> Header<U> {
+        Header {
+            version: self.version,
+            tag: self.tag,
+            flags: self.flags,
+            stream_id: self.stream_id,
+            length: self.length,
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Introduce this header to the right of a binary header type.
+    pub fn right<U>(self) -> Header<Either<U, T>> {
+        self.cast()
+    }
+
+    /// Introduce this header to the left of a binary header type.
+    pub fn left<U>(self) -> Header<Either<T, U>> {
+        self.cast()
+    }
+}
+
+impl<A: private::Sealed> From<Header<A>> for Header<()> {
+    fn from(h: Header<A>) -> Header<()> {
+        h.cast()
+    }
+}
+
+impl Header<()> {
+    /// The header of a data frame, see [`Header::tag`].
+    pub fn into_data(self) -> Header<Data> {
+        debug_assert_eq!(self.tag, Tag::Data);
+        self.cast()
+    }
+
+    /// The header of a window update frame, see [`Header::tag`].
+    pub fn into_window_update(self) -> Header<WindowUpdate> {
+        debug_assert_eq!(self.tag, Tag::WindowUpdate);
+        self.cast()
+    }
+
+    /// The header of a ping frame, see [`Header::tag`].
+    pub fn into_ping(self) -> Header<Ping> {
+        debug_assert_eq!(self.tag, Tag::Ping);
+        self.cast()
+    }
+}
+
+impl<T: HasSyn> Header<T> {
+    /// Set the `SYN` flag.
+    pub fn syn(&mut self) {
+        self.flags.0 |= SYN.0
+    }
+}
+
+impl<T: HasAck> Header<T> {
+    /// Set the `ACK` flag.
+    pub fn ack(&mut self) {
+        self.flags.0 |= ACK.0
+    }
+}
+
+impl<T: HasFin> Header<T> {
+    /// Set the `FIN` flag.
+    pub fn fin(&mut self) {
+        self.flags.0 |= FIN.0
+    }
+}
+
+impl<T: HasRst> Header<T> {
+    /// Set the `RST` flag.
+    pub fn rst(&mut self) {
+        self.flags.0 |= RST.0
+    }
+}
+
+impl Header<Data> {
+    /// Create a new data frame header.
+    pub fn data(id: StreamId, len: u32) -> Self {
+        Header {
+            version: Version(VERSION),
+            tag: Tag::Data,
+            flags: Flags(0),
+            stream_id: id,
+            length: Len(len),
+            _marker: core::marker::PhantomData,
+        }
+    }
+}
+
+impl Header<WindowUpdate> {
+    /// Create a new window update frame header.
+    pub fn window_update(id: StreamId, credit: u32) -> Self {
+        Header {
+            version: Version(VERSION),
+            tag: Tag::WindowUpdate,
+            flags: Flags(0),
+            stream_id: id,
+            length: Len(credit),
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Set the `RELEASE` flag.
+    pub fn release(&mut self) {
+        self.flags.0 |= RELEASE.0
+    }
+
+    /// Set the `STOP_SENDING` flag.
+    pub fn stop_sending(&mut self) {
+        self.flags.0 |= STOP_SENDING.0
+    }
+
+    /// The credit this window update grants to the remote.
+    pub fn credit(&self) -> u32 {
+        self.length.0
+    }
+}
+
+impl Header<Ping> {
+    /// Create a new ping frame header.
+    pub fn ping(nonce: u32) -> Self {
+        Header {
+            version: Version(VERSION),
+            tag: Tag::Ping,
+            flags: Flags(0),
+            stream_id: StreamId(0),
+            length: Len(nonce),
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    /// The nonce of this ping.
+    pub fn nonce(&self) -> u32 {
+        self.length.0
+    }
+}
+
+impl Header<GoAway> {
+    /// Terminate the session without indicating an error to the remote.
+    pub fn term() -> Self {
+        Self::go_away(GO_AWAY_NORMAL)
+    }
+
+    /// Terminate the session indicating a protocol error to the remote.
+    pub fn protocol_error() -> Self {
+        Self::go_away(GO_AWAY_PROTOCOL_ERROR)
+    }
+
+    /// Terminate the session indicating an internal error to the remote.
+    pub fn internal_error() -> Self {
+        Self::go_away(GO_AWAY_INTERNAL_ERROR)
+    }
+
+    fn go_away(code: u32) -> Self {
+        Header {
+            version: Version(VERSION),
+            tag: Tag::GoAway,
+            flags: Flags(0),
+            stream_id: StreamId(0),
+            length: Len(code),
+            _marker: core::marker::PhantomData,
+        }
+    }
+}
+
+/// Data message type.
+#[derive(Clone, Debug)]
+pub enum Data {}
+
+/// Window update message type.
+#[derive(Clone, Debug)]
+pub enum WindowUpdate {}
+
+/// Ping message type.
+#[derive(Clone, Debug)]
+pub enum Ping {}
+
+/// Go Away message type.
+#[derive(Clone, Debug)]
+pub enum GoAway {}
+
+/// Either of two message types, e.g. `Header<Either<Data, WindowUpdate>>`.
+#[derive(Clone, Debug)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Types which have a `syn` method.
+pub trait HasSyn: private::Sealed {}
+impl HasSyn for Data {}
+impl HasSyn for WindowUpdate {}
+impl HasSyn for Ping {}
+impl<A: HasSyn, B: HasSyn> HasSyn for Either<A, B> {}
+
+/// Types which have an `ack` method.
+pub trait HasAck: private::Sealed {}
+impl HasAck for Data {}
+impl HasAck for WindowUpdate {}
+impl HasAck for Ping {}
+impl<A: HasAck, B: HasAck> HasAck for Either<A, B> {}
+
+/// Types which have a `fin` method.
+pub trait HasFin: private::Sealed {}
+impl HasFin for Data {}
+impl HasFin for WindowUpdate {}
+
+/// Types which have a `rst` method.
+pub trait HasRst: private::Sealed {}
+impl HasRst for Data {}
+impl HasRst for WindowUpdate {}
+
+pub(super) mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::Data {}
+    impl Sealed for super::WindowUpdate {}
+    impl Sealed for super::Ping {}
+    impl Sealed for super::GoAway {}
+    impl<A: Sealed, B: Sealed> Sealed for super::Either<A, B> {}
+}
+
+/// A tag is the runtime representation of a message type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tag {
+    Data,
+    WindowUpdate,
+    Ping,
+    GoAway,
+}
+
+/// The protocol version a message corresponds to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Version(u8);
+
+/// The message length.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Len(u32);
+
+impl Len {
+    #[inline]
+    pub fn val(self) -> u32 {
+        self.0
+    }
+}
+
+pub const CONNECTION_ID: StreamId = StreamId(0);
+
+/// The ID of a stream.
+///
+/// The value 0 denotes no particular stream but the whole session.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StreamId(u32);
+
+impl StreamId {
+    pub fn new(val: u32) -> Self {
+        StreamId(val)
+    }
+
+    pub fn is_server(self) -> bool {
+        self.0 & 1 == 0
+    }
+
+    pub fn is_client(self) -> bool {
+        !self.is_server()
+    }
+
+    pub fn is_session(self) -> bool {
+        self == CONNECTION_ID
+    }
+
+    #[inline]
+    pub fn val(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl nohash_hasher::IsEnabled for StreamId {}
+
+/// Possible flags set on a message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Flags(u16);
+
+impl Flags {
+    #[inline]
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    #[inline]
+    pub fn val(self) -> u16 {
+        self.0
+    }
+
+    /// The protocol extensions advertised by these flags, see `EXTENSIONS`.
+    #[inline]
+    pub fn extensions(self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+}
+
+/// Indicates the start of a new stream.
+pub const SYN: Flags = Flags(1);
+
+/// Acknowledges the start of a new stream.
+pub const ACK: Flags = Flags(2);
+
+/// Indicates the half-closing of a stream.
+pub const FIN: Flags = Flags(4);
+
+/// Indicates an immediate stream reset.
+pub const RST: Flags = Flags(8);
+
+/// Indicates that a stream ID is no longer in use and may be reused.
+///
+/// Not part of the Yamux specification, see `Config::set_stream_id_recycling`.
+pub const RELEASE: Flags = Flags(0x10);
+
+/// Asks the remote to stop sending data on a stream, as it would be discarded.
+///
+/// Not part of the Yamux specification, see `Stream::stop_sending`.
+pub const STOP_SENDING: Flags = Flags(0x20);
+
+/// Offers LZ4 compression when opening or acknowledging a stream, otherwise
+/// indicates an LZ4 compressed data frame body.
+///
+/// Not part of the Yamux specification, see `Config::set_compression`.
+pub const COMPRESS_LZ4: Flags = Flags(0x40);
+
+/// Offers Zstandard compression when opening or acknowledging a stream,
+/// otherwise indicates a Zstandard compressed data frame body.
+///
+/// Not part of the Yamux specification, see `Config::set_compression`.
+pub const COMPRESS_ZSTD: Flags = Flags(0x80);
+
+/// The flags advertising protocol extensions, one bit per extension, on the
+/// first frame an endpoint sends to open or acknowledge a stream.
+///
+/// Not part of the Yamux specification, see `Config::set_extensions`.
+pub const EXTENSIONS: Flags = Flags(0xff00);
+
+/// The protocol version, the only one there is.
+pub const VERSION: u8 = 0;
+
+/// The `GoAway` code of a normal termination.
+pub const GO_AWAY_NORMAL: u32 = 0;
+
+/// The `GoAway` code of a termination because of a protocol error.
+pub const GO_AWAY_PROTOCOL_ERROR: u32 = 1;
+
+/// The `GoAway` code of a termination because of an internal error.
+pub const GO_AWAY_INTERNAL_ERROR: u32 = 2;
+
+/// The serialised header size in bytes.
+pub const HEADER_SIZE: usize = 12;
+
+/// Encode a [`Header`] value.
+#[inline]
+pub fn encode<T>(hdr: &Header<T>) -> [u8; HEADER_SIZE] {
+    let [f0, f1] = hdr.flags.0.to_be_bytes();
+    let [s0, s1, s2, s3] = hdr.stream_id.0.to_be_bytes();
+    let [l0, l1, l2, l3] = hdr.length.0.to_be_bytes();
+    [
+        hdr.version.0,
+        hdr.tag as u8,
+        f0,
+        f1,
+        s0,
+        s1,
+        s2,
+        s3,
+        l0,
+        l1,
+        l2,
+        l3,
+    ]
+}
+
+/// Decode a [`Header`] value.
+#[inline]
+pub fn decode(buf: &[u8; HEADER_SIZE]) -> Result<Header<()>, HeaderDecodeError> {
+    let [version, tag, f0, f1, s0, s1, s2, s3, l0, l1, l2, l3] = *buf;
+
+    if version != VERSION {
+        return Err(HeaderDecodeError::Version(version));
+    }
+
+    let tag = match tag {
+        0 => Tag::Data,
+        1 => Tag::WindowUpdate,
+        2 => Tag::Ping,
+        3 => Tag::GoAway,
+        t => return Err(HeaderDecodeError::Type(t)),
+    };
+
+    Ok(Header {
+        version: Version(version),
+        tag,
+        flags: Flags(u16::from_be_bytes([f0, f1])),
+        stream_id: StreamId(u32::from_be_bytes([s0, s1, s2, s3])),
+        length: Len(u32::from_be_bytes([l0, l1, l2, l3])),
+        _marker: core::marker::PhantomData,
+    })
+}
+
+/// Possible errors while decoding a message frame header.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum HeaderDecodeError {
+    /// Unknown version.
+    Version(u8),
+    /// An unknown frame type.
+    Type(u8),
+}
+
+impl fmt::Display for HeaderDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderDecodeError::Version(v) => write!(f, "unknown version: {}", v),
+            HeaderDecodeError::Type(t) => write!(f, "unknown frame type: {}", t),
+        }
+    }
+}
+
+impl core::error::Error for HeaderDecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::{Arbitrary, Gen, QuickCheck};
+
+    impl Arbitrary for Header<()> {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let tag = *g
+                .choose(&[Tag::Data, Tag::WindowUpdate, Tag::Ping, Tag::GoAway])
+                .unwrap();
+
+            Header {
+                version: Version(VERSION),
+                tag,
+                flags: Flags(Arbitrary::arbitrary(g)),
+                stream_id: StreamId(Arbitrary::arbitrary(g)),
+                length: Len(Arbitrary::arbitrary(g)),
+                _marker: core::marker::PhantomData,
+            }
+        }
+    }
+
+    #[test]
+    fn encode_decode_identity() {
+        fn property(hdr: Header<()>) -> bool {
+            matches!(decode(&encode(&hdr)), Ok(x) if x == hdr)
+        }
+        QuickCheck::new()
+            .tests(10_000)
+            .quickcheck(property as fn(Header<()>) -> bool)
+    }
+
+    #[test]
+    fn decode_rejects_unknown_version_and_type() {
+        let mut buf = encode(&Header::ping(42));
+        buf[0] = 1;
+        assert!(matches!(decode(&buf), Err(HeaderDecodeError::Version(1))));
+        buf[0] = 0;
+        buf[1] = 4;
+        assert!(matches!(decode(&buf), Err(HeaderDecodeError::Type(4))));
+    }
+
+    #[test]
+    fn extensions_use_the_upper_flag_bits() {
+        let mut hdr = Header::data(StreamId::new(1), 0);
+        hdr.syn();
+        hdr.extensions(0b101);
+        assert_eq!(hdr.flags().val(), 0x0501);
+        assert!(hdr.flags().contains(SYN));
+        assert_eq!(hdr.flags().extensions(), 0b101);
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! The meaning of received frames.
+//!
+//! Both [`crate::session::SessionState`] and the `Connection` of the `yamux`
+//! crate dispatch received frames by [`classify`], so that they agree on
+//! which frames open, reset or belong to a stream and which ones violate the
+//! protocol.
+
+use crate::header::{self, Header, StreamId, Tag};
+
+/// How a session is used.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Mode {
+    /// Client to server connection.
+    Client,
+    /// Server to client connection.
+    Server,
+}
+
+impl Mode {
+    /// Whether the remote may open a stream with the given ID, i.e. the
+    /// ID is not the session's and of the remote's parity.
+    pub fn is_remote(self, id: StreamId) -> bool {
+        match self {
+            _ if id.is_session() => false,
+            Mode::Client => id.is_server(),
+            Mode::Server => id.is_client(),
+        }
+    }
+}
+
+/// What a received frame means, regardless of the streams of the session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Inbound {
+    /// A ping with the given nonce, to be answered unless it is for a stream
+    /// which does not exist.
+    Ping(u32),
+    /// The answer to a ping we sent with the given nonce.
+    Pong(u32),
+    /// The remote terminates the session with the given error code.
+    GoAway(u32),
+    /// The remote opens a stream, which is a protocol violation if the
+    /// stream exists.
+    Open(StreamId),
+    /// The remote resets a stream, if it exists.
+    Reset(StreamId),
+    /// A data or window update frame of a stream, if it exists, which
+    /// may be ignored otherwise, e.g. as it was in flight while the stream
+    /// has been reset.
+    Stream(StreamId),
+}
+
+/// Classify a frame received by a session of the given mode.
+///
+/// Fails with the reason if the frame violates the protocol, i.e. the
+/// session must be terminated.
+pub fn classify(mode: Mode, header: &Header<()>) -> Result<Inbound, &'static str> {
+    let flags = header.flags();
+    let id = header.stream_id();
+    match header.tag() {
+        Tag::Ping if flags.contains(header::ACK) => Ok(Inbound::Pong(header.len().val())),
+        Tag::Ping => Ok(Inbound::Ping(header.len().val())),
+        Tag::GoAway => Ok(Inbound::GoAway(header.len().val())),
+        Tag::Data | Tag::WindowUpdate if flags.contains(header::RST) => Ok(Inbound::Reset(id)),
+        Tag::Data | Tag::WindowUpdate if flags.contains(header::SYN) => {
+            if mode.is_remote(id) {
+                Ok(Inbound::Open(id))
+            } else {
+                Err("invalid stream id")
+            }
+        }
+        Tag::Data | Tag::WindowUpdate => Ok(Inbound::Stream(id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_are_opened_with_ids_of_the_remote() {
+        let mut header = Header::data(StreamId::new(1), 0);
+        header.syn();
+        let header = Header::from(header);
+        assert_eq!(
+            classify(Mode::Server, &header),
+            Ok(Inbound::Open(StreamId::new(1)))
+        );
+        assert!(classify(Mode::Client, &header).is_err());
+
+        let mut header = Header::window_update(StreamId::new(0), 0);
+        header.syn();
+        assert!(classify(Mode::Client, &header.into()).is_err());
+    }
+
+    #[test]
+    fn resets_take_precedence() {
+        let mut header = Header::data(StreamId::new(2), 0);
+        header.syn();
+        header.rst();
+        assert_eq!(
+            classify(Mode::Server, &header.into()),
+            Ok(Inbound::Reset(StreamId::new(2)))
+        );
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! The I/O-free core of the [Yamux][1] protocol.
+//!
+//! This crate contains the parts of the protocol which do not depend on any
+//! I/O or runtime, i.e. the encoding of frame headers ([`header`]), the
+//! meaning of received frames ([`inbound`]), the state machine of streams
+//! ([`stream`]) and the accounting of flow-control credit ([`flow`]), along
+//! with a whole session driven by the caller's I/O
+//! ([`session::SessionState`]). It builds without the standard library, only
+//! the `session` module requires an allocator and is thus gated behind the
+//! `alloc` feature (enabled by default), e.g. to run the protocol logic on an
+//! embedded RTOS with an event loop of its own.
+//!
+//! The `yamux` crate builds on it, multiplexing streams over an async I/O
+//! resource.
+//!
+//! [1]: https://github.com/hashicorp/yamux/blob/master/spec.md
+
+#![no_std]
+#![forbid(unsafe_code)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod flow;
+pub mod header;
+pub mod inbound;
+#[cfg(feature = "alloc")]
+pub mod session;
+pub mod stream;
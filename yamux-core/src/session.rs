@@ -0,0 +1,720 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! A session driven by the caller's I/O, see [`SessionState`].
+
+use crate::{
+    flow,
+    header::{self, Header, StreamId, Tag, HEADER_SIZE},
+    inbound::{self, Inbound},
+    stream::State,
+};
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    vec::Vec,
+};
+use core::fmt;
+
+/// The receive window of a stream, as per the specification.
+const RECEIVE_WINDOW: u32 = 256 * 1024;
+
+/// The max. body length of data frames sent.
+const MAX_SEND_SIZE: usize = 16 * 1024;
+
+/// The default max. number of streams, see [`SessionState::set_max_num_streams`].
+pub const DEFAULT_MAX_NUM_STREAMS: usize = 8192;
+
+/// The default max. number of received bytes not yet consumed, see
+/// [`SessionState::set_max_buffered_bytes`].
+pub const DEFAULT_MAX_BUFFERED_BYTES: usize = 16 * 1024 * 1024;
+
+pub use crate::inbound::Mode;
+
+/// What happened as a result of [`SessionState::handle_input`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The remote opened a stream, which has been acknowledged.
+    StreamOpened(StreamId),
+    /// The remote acknowledged a stream we opened.
+    StreamAcknowledged(StreamId),
+    /// Data has been received on a stream.
+    ///
+    /// Once processed, it must be passed to [`SessionState::consume`] to
+    /// grant the remote new credit.
+    Data { stream: StreamId, data: Vec<u8> },
+    /// The remote granted more credit to send on a stream.
+    Credit { stream: StreamId, credit: u32 },
+    /// The remote closed its sending side of a stream.
+    StreamFinished(StreamId),
+    /// The remote reset a stream.
+    StreamReset(StreamId),
+    /// A stream has been reset because the data received and not yet
+    /// consumed exceeds [`SessionState::set_max_buffered_bytes`].
+    BufferOverflow(StreamId),
+    /// The remote answered our ping with the given nonce.
+    Pong(u32),
+    /// The remote terminated the session with the given error code.
+    GoAway(u32),
+}
+
+/// Errors of a [`SessionState`].
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum SessionError {
+    /// A frame header could not be decoded.
+    Decode(header::HeaderDecodeError),
+    /// The remote violated the protocol, for which reason a `GoAway` has
+    /// been queued for sending.
+    Protocol(&'static str),
+    /// The stream does not exist, e.g. because it has been reset.
+    UnknownStream(StreamId),
+    /// The stream has been closed for sending.
+    StreamClosed(StreamId),
+    /// The whole range of stream IDs has been used up.
+    NoMoreStreamIds,
+    /// The session has been terminated, by either side.
+    Terminated,
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Decode(e) => write!(f, "decode error: {}", e),
+            SessionError::Protocol(reason) => write!(f, "protocol error: {}", reason),
+            SessionError::UnknownStream(id) => write!(f, "unknown stream {}", id),
+            SessionError::StreamClosed(id) => write!(f, "stream {} is closed", id),
+            SessionError::NoMoreStreamIds => f.write_str("number of stream ids has been exhausted"),
+            SessionError::Terminated => f.write_str("session has been terminated"),
+        }
+    }
+}
+
+impl core::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            SessionError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The error of [`SessionState::handle_input`], along with the events of the
+/// frames processed before it occurred, which must be handled nonetheless.
+#[derive(Clone, Debug)]
+pub struct InputError {
+    events: Vec<Event>,
+    error: SessionError,
+}
+
+impl InputError {
+    /// The events of the frames processed before the error occurred.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// The error which occurred.
+    pub fn error(&self) -> &SessionError {
+        &self.error
+    }
+
+    /// The events of the frames processed before the error occurred, and
+    /// the error.
+    pub fn into_parts(self) -> (Vec<Event>, SessionError) {
+        (self.events, self.error)
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl core::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The state of a stream of a [`SessionState`].
+#[derive(Debug)]
+struct StreamState {
+    state: State,
+    /// The credit we have left to send.
+    credit: u32,
+    /// The credit the remote has left to send.
+    window: u32,
+    /// The number of received bytes not yet consumed.
+    unconsumed: u32,
+}
+
+impl StreamState {
+    fn new(state: State) -> Self {
+        StreamState {
+            state,
+            credit: RECEIVE_WINDOW,
+            window: RECEIVE_WINDOW,
+            unconsumed: 0,
+        }
+    }
+}
+
+/// A Yamux session which does no I/O itself.
+///
+/// The bytes received from the remote are passed to
+/// [`SessionState::handle_input`], which returns the resulting [`Event`]s,
+/// and the bytes to send to the remote are taken from
+/// [`SessionState::poll_output`], so that the session can be driven by any
+/// I/O layer, e.g. blocking sockets or an event loop of an embedded RTOS.
+///
+/// Streams are opened eagerly, i.e. with a window update frame, inbound
+/// streams are acknowledged right away and the remote is granted new credit
+/// once half of the 256 KiB receive window of a stream has been consumed.
+///
+/// The number of streams and the received data not yet consumed are limited,
+/// see [`SessionState::set_max_num_streams`] and
+/// [`SessionState::set_max_buffered_bytes`]. Received frames are dispatched
+/// by [`inbound::classify`], like those of the `Connection` of the `yamux`
+/// crate. Other than that, the session lacks the features of the
+/// `Connection`, e.g. its
+/// configuration of receive windows, timers for keep-alive pings, idle
+/// connections and unacknowledged streams, protocol extensions and the
+/// policies for misbehaving remotes. Frames to send are queued until taken
+/// by [`SessionState::poll_output`], i.e. the caller must keep taking them.
+#[derive(Debug)]
+pub struct SessionState {
+    mode: Mode,
+    next_id: u32,
+    streams: BTreeMap<StreamId, StreamState>,
+    max_num_streams: usize,
+    /// The number of received bytes of all streams not yet consumed.
+    buffered: usize,
+    max_buffered: usize,
+    /// Received bytes which do not make up a whole frame yet.
+    input: Vec<u8>,
+    /// Encoded frames to send.
+    output: VecDeque<Vec<u8>>,
+    /// Set once either side sent a `GoAway`.
+    terminated: bool,
+}
+
+impl SessionState {
+    pub fn new(mode: Mode) -> Self {
+        SessionState {
+            mode,
+            next_id: match mode {
+                Mode::Client => 1,
+                Mode::Server => 2,
+            },
+            streams: BTreeMap::new(),
+            max_num_streams: DEFAULT_MAX_NUM_STREAMS,
+            buffered: 0,
+            max_buffered: DEFAULT_MAX_BUFFERED_BYTES,
+            input: Vec::new(),
+            output: VecDeque::new(),
+            terminated: false,
+        }
+    }
+
+    /// Set the max. number of streams at once.
+    ///
+    /// A remote opening a stream beyond the limit violates the protocol, i.e.
+    /// the session is terminated.
+    pub fn set_max_num_streams(&mut self, n: usize) -> &mut Self {
+        self.max_num_streams = n;
+        self
+    }
+
+    /// Set the max. number of received bytes of all streams not yet consumed.
+    ///
+    /// A stream receiving data beyond the limit is reset, see
+    /// [`Event::BufferOverflow`].
+    pub fn set_max_buffered_bytes(&mut self, n: usize) -> &mut Self {
+        self.max_buffered = n;
+        self
+    }
+
+    /// The number of open streams.
+    pub fn num_streams(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// The credit left to send on the given stream.
+    pub fn credit(&self, id: StreamId) -> Option<u32> {
+        self.streams.get(&id).map(|s| s.credit)
+    }
+
+    /// Process bytes received from the remote.
+    ///
+    /// Bytes of an incomplete frame are kept until the rest of the frame
+    /// arrives. Frames which must be sent in response, e.g. to acknowledge
+    /// new streams, are queued for [`SessionState::poll_output`]. If a frame
+    /// fails to be processed, the error carries the events of the frames
+    /// processed before.
+    pub fn handle_input(&mut self, bytes: &[u8]) -> Result<Vec<Event>, InputError> {
+        self.input.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        let mut offset = 0;
+        let result = loop {
+            let rest = &self.input[offset..];
+            if rest.len() < HEADER_SIZE {
+                break Ok(());
+            }
+            let mut buf = [0; HEADER_SIZE];
+            buf.copy_from_slice(&rest[..HEADER_SIZE]);
+            let header = match header::decode(&buf) {
+                Ok(header) => header,
+                Err(e) => {
+                    self.terminate(Header::protocol_error());
+                    break Err(SessionError::Decode(e));
+                }
+            };
+            let body_len = match header.tag() {
+                Tag::Data => header.len().val() as usize,
+                _ => 0,
+            };
+            if body_len > RECEIVE_WINDOW as usize {
+                break Err(self.protocol_error("frame body larger than window"));
+            }
+            if rest.len() < HEADER_SIZE + body_len {
+                break Ok(());
+            }
+            let body = rest[HEADER_SIZE..HEADER_SIZE + body_len].to_vec();
+            offset += HEADER_SIZE + body_len;
+            if let Err(e) = self.on_frame(header, body, &mut events) {
+                break Err(e);
+            }
+        };
+        self.input.drain(..offset);
+        match result {
+            Ok(()) => Ok(events),
+            Err(error) => Err(InputError { events, error }),
+        }
+    }
+
+    /// The next encoded frame to send to the remote, if any.
+    pub fn poll_output(&mut self) -> Option<Vec<u8>> {
+        self.output.pop_front()
+    }
+
+    /// Open a new outbound stream.
+    pub fn open_stream(&mut self) -> Result<StreamId, SessionError> {
+        if self.terminated {
+            return Err(SessionError::Terminated);
+        }
+        let id = StreamId::new(self.next_id);
+        self.next_id = self
+            .next_id
+            .checked_add(2)
+            .ok_or(SessionError::NoMoreStreamIds)?;
+        self.streams.insert(id, StreamState::new(State::Open));
+        let mut header = Header::window_update(id, 0);
+        header.syn();
+        self.push(&header, &[]);
+        Ok(id)
+    }
+
+    /// Send as much of the given data on a stream as its credit allows,
+    /// returning the number of bytes sent.
+    pub fn send(&mut self, id: StreamId, data: &[u8]) -> Result<usize, SessionError> {
+        if self.terminated {
+            return Err(SessionError::Terminated);
+        }
+        let stream = self
+            .streams
+            .get_mut(&id)
+            .ok_or(SessionError::UnknownStream(id))?;
+        if !stream.state.can_write() {
+            return Err(SessionError::StreamClosed(id));
+        }
+        let mut sent = 0;
+        while sent < data.len() && stream.credit > 0 {
+            let n = (data.len() - sent)
+                .min(MAX_SEND_SIZE)
+                .min(stream.credit as usize);
+            stream.credit -= n as u32;
+            let header = Header::data(id, n as u32);
+            let mut frame = Vec::with_capacity(HEADER_SIZE + n);
+            frame.extend_from_slice(&header::encode(&header));
+            frame.extend_from_slice(&data[sent..sent + n]);
+            self.output.push_back(frame);
+            sent += n
+        }
+        Ok(sent)
+    }
+
+    /// Mark `n` received bytes of a stream as consumed, granting the remote
+    /// new credit once half of the receive window has been consumed.
+    pub fn consume(&mut self, id: StreamId, n: u32) -> Result<(), SessionError> {
+        let stream = self
+            .streams
+            .get_mut(&id)
+            .ok_or(SessionError::UnknownStream(id))?;
+        let n = n.min(stream.unconsumed);
+        stream.unconsumed -= n;
+        self.buffered -= n as usize;
+        if !stream.state.can_read() {
+            return Ok(());
+        }
+        let credit = flow::replenishable_credit(RECEIVE_WINDOW, stream.window, stream.unconsumed);
+        if credit >= RECEIVE_WINDOW / 2 {
+            stream.window += credit;
+            self.push(&Header::window_update(id, credit), &[]);
+        }
+        Ok(())
+    }
+
+    /// Close the sending side of a stream.
+    pub fn close_stream(&mut self, id: StreamId) -> Result<(), SessionError> {
+        let stream = self
+            .streams
+            .get_mut(&id)
+            .ok_or(SessionError::UnknownStream(id))?;
+        if !stream.state.can_write() {
+            return Err(SessionError::StreamClosed(id));
+        }
+        stream.state = stream.state.transition(State::SendClosed);
+        if stream.state == State::Closed {
+            self.remove_stream(id);
+        }
+        let mut header = Header::data(id, 0);
+        header.fin();
+        self.push(&header, &[]);
+        Ok(())
+    }
+
+    /// Reset a stream.
+    pub fn reset_stream(&mut self, id: StreamId) -> Result<(), SessionError> {
+        self.remove_stream(id)
+            .ok_or(SessionError::UnknownStream(id))?;
+        let mut header = Header::data(id, 0);
+        header.rst();
+        self.push(&header, &[]);
+        Ok(())
+    }
+
+    /// Send a ping with the given nonce, answered by an [`Event::Pong`].
+    pub fn ping(&mut self, nonce: u32) {
+        let mut header = Header::ping(nonce);
+        header.syn();
+        self.push(&header, &[])
+    }
+
+    /// Terminate the session without indicating an error to the remote.
+    pub fn go_away(&mut self) {
+        if !self.terminated {
+            self.terminate(Header::term())
+        }
+    }
+
+    fn on_frame(
+        &mut self,
+        header: Header<()>,
+        body: Vec<u8>,
+        events: &mut Vec<Event>,
+    ) -> Result<(), SessionError> {
+        let flags = header.flags();
+        let id = match inbound::classify(self.mode, &header) {
+            Ok(Inbound::Ping(nonce)) => {
+                if header.stream_id().is_session() || self.streams.contains_key(&header.stream_id())
+                {
+                    let mut pong = Header::ping(nonce);
+                    pong.ack();
+                    self.push(&pong, &[])
+                }
+                return Ok(());
+            }
+            Ok(Inbound::Pong(nonce)) => {
+                events.push(Event::Pong(nonce));
+                return Ok(());
+            }
+            Ok(Inbound::GoAway(code)) => {
+                self.terminated = true;
+                events.push(Event::GoAway(code));
+                return Ok(());
+            }
+            Ok(Inbound::Reset(id)) => {
+                if self.remove_stream(id).is_some() {
+                    events.push(Event::StreamReset(id))
+                }
+                return Ok(());
+            }
+            Ok(Inbound::Open(id)) => {
+                if self.streams.contains_key(&id) {
+                    return Err(self.protocol_error("stream already exists"));
+                }
+                if self.terminated {
+                    let mut header = Header::data(id, 0);
+                    header.rst();
+                    self.push(&header, &[]);
+                    return Ok(());
+                }
+                if self.streams.len() >= self.max_num_streams {
+                    return Err(self.protocol_error("max. number of streams exceeded"));
+                }
+                self.streams.insert(id, StreamState::new(State::Open));
+                let mut ack = Header::window_update(id, 0);
+                ack.ack();
+                self.push(&ack, &[]);
+                events.push(Event::StreamOpened(id));
+                id
+            }
+            Ok(Inbound::Stream(id)) => id,
+            Err(reason) => return Err(self.protocol_error(reason)),
+        };
+
+        let stream = match self.streams.get_mut(&id) {
+            Some(stream) => stream,
+            // Frames in flight after the stream has been reset.
+            None => return Ok(()),
+        };
+        if flags.contains(header::ACK) {
+            events.push(Event::StreamAcknowledged(id));
+        }
+        match header.tag() {
+            Tag::Data => {
+                stream.window = match flow::consume(stream.window, body.len() as u32) {
+                    Some(window) => window,
+                    None => return Err(self.protocol_error("frame body larger than window")),
+                };
+                if !body.is_empty() && stream.state.can_read() {
+                    if self.buffered + body.len() > self.max_buffered {
+                        self.remove_stream(id);
+                        let mut header = Header::data(id, 0);
+                        header.rst();
+                        self.push(&header, &[]);
+                        events.push(Event::BufferOverflow(id));
+                        return Ok(());
+                    }
+                    self.buffered += body.len();
+                    stream.unconsumed += body.len() as u32;
+                    events.push(Event::Data {
+                        stream: id,
+                        data: body,
+                    });
+                }
+            }
+            _ => {
+                let credit = header.len().val();
+                stream.credit = stream.credit.saturating_add(credit);
+                if credit > 0 {
+                    events.push(Event::Credit { stream: id, credit });
+                }
+            }
+        }
+        if flags.contains(header::FIN) && stream.state.can_read() {
+            stream.state = stream.state.transition(State::RecvClosed);
+            if stream.state == State::Closed {
+                self.remove_stream(id);
+            }
+            events.push(Event::StreamFinished(id));
+        }
+        Ok(())
+    }
+
+    /// Remove a stream, whose data not yet consumed no longer counts towards
+    /// the max. number of buffered bytes.
+    fn remove_stream(&mut self, id: StreamId) -> Option<StreamState> {
+        let stream = self.streams.remove(&id)?;
+        self.buffered -= stream.unconsumed as usize;
+        Some(stream)
+    }
+
+    /// Terminate the session because of a protocol violation of the remote.
+    fn protocol_error(&mut self, reason: &'static str) -> SessionError {
+        self.terminate(Header::protocol_error());
+        SessionError::Protocol(reason)
+    }
+
+    fn terminate(&mut self, header: Header<header::GoAway>) {
+        self.terminated = true;
+        self.push(&header, &[])
+    }
+
+    fn push<T>(&mut self, header: &Header<T>, body: &[u8]) {
+        let mut frame = Vec::with_capacity(HEADER_SIZE + body.len());
+        frame.extend_from_slice(&header::encode(header));
+        frame.extend_from_slice(body);
+        self.output.push_back(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pass all output of `from` to `to`.
+    fn transfer(from: &mut SessionState, to: &mut SessionState) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(bytes) = from.poll_output() {
+            events.extend(to.handle_input(&bytes).unwrap())
+        }
+        events
+    }
+
+    #[test]
+    fn exchange_data() {
+        let mut client = SessionState::new(Mode::Client);
+        let mut server = SessionState::new(Mode::Server);
+
+        let id = client.open_stream().unwrap();
+        assert_eq!(client.send(id, b"hello").unwrap(), 5);
+        client.close_stream(id).unwrap();
+        let events = transfer(&mut client, &mut server);
+        assert_eq!(
+            events,
+            [
+                Event::StreamOpened(id),
+                Event::Data {
+                    stream: id,
+                    data: b"hello".to_vec()
+                },
+                Event::StreamFinished(id),
+            ]
+        );
+
+        assert_eq!(server.send(id, b"world").unwrap(), 5);
+        server.close_stream(id).unwrap();
+        assert_eq!(server.num_streams(), 0);
+        let events = transfer(&mut server, &mut client);
+        assert_eq!(
+            events,
+            [
+                Event::StreamAcknowledged(id),
+                Event::Data {
+                    stream: id,
+                    data: b"world".to_vec()
+                },
+                Event::StreamFinished(id),
+            ]
+        );
+        assert_eq!(client.num_streams(), 0);
+    }
+
+    #[test]
+    fn credit_is_granted_once_data_is_consumed() {
+        let mut client = SessionState::new(Mode::Client);
+        let mut server = SessionState::new(Mode::Server);
+
+        let id = client.open_stream().unwrap();
+        let data = alloc::vec![0; RECEIVE_WINDOW as usize + 1];
+        assert_eq!(client.send(id, &data).unwrap(), RECEIVE_WINDOW as usize);
+        assert_eq!(client.credit(id), Some(0));
+        transfer(&mut client, &mut server);
+
+        server.consume(id, RECEIVE_WINDOW).unwrap();
+        let events = transfer(&mut server, &mut client);
+        assert!(events.contains(&Event::Credit {
+            stream: id,
+            credit: RECEIVE_WINDOW
+        }));
+        assert_eq!(client.send(id, &data[..1]).unwrap(), 1);
+    }
+
+    #[test]
+    fn partial_input_is_buffered() {
+        let mut client = SessionState::new(Mode::Client);
+        let mut server = SessionState::new(Mode::Server);
+
+        client.ping(7);
+        let ping = client.poll_output().unwrap();
+        assert!(server.handle_input(&ping[..5]).unwrap().is_empty());
+        assert!(server.handle_input(&ping[5..]).unwrap().is_empty());
+        assert_eq!(transfer(&mut server, &mut client), [Event::Pong(7)]);
+    }
+
+    #[test]
+    fn protocol_violation_terminates_session() {
+        let mut server = SessionState::new(Mode::Server);
+        let mut header = Header::data(StreamId::new(2), 0);
+        header.syn();
+        let e = server.handle_input(&header::encode(&header)).unwrap_err();
+        assert!(matches!(e.error(), SessionError::Protocol(_)));
+        let go_away = server.poll_output().unwrap();
+        assert_eq!(go_away[1], Tag::GoAway as u8);
+        assert!(matches!(
+            server.open_stream(),
+            Err(SessionError::Terminated)
+        ));
+    }
+
+    #[test]
+    fn excess_streams_terminate_session() {
+        let mut client = SessionState::new(Mode::Client);
+        let mut server = SessionState::new(Mode::Server);
+        server.set_max_num_streams(2);
+
+        client.open_stream().unwrap();
+        client.open_stream().unwrap();
+        transfer(&mut client, &mut server);
+        client.open_stream().unwrap();
+        let syn = client.poll_output().unwrap();
+        let e = server.handle_input(&syn).unwrap_err();
+        assert!(matches!(e.error(), SessionError::Protocol(_)));
+    }
+
+    #[test]
+    fn events_before_an_error_are_returned_with_it() {
+        let mut client = SessionState::new(Mode::Client);
+        let mut server = SessionState::new(Mode::Server);
+
+        let id = client.open_stream().unwrap();
+        client.send(id, b"hello").unwrap();
+        let mut input = Vec::new();
+        while let Some(bytes) = client.poll_output() {
+            input.extend(bytes)
+        }
+        let mut header = Header::data(StreamId::new(2), 0);
+        header.syn();
+        input.extend(header::encode(&header));
+
+        let (events, e) = server.handle_input(&input).unwrap_err().into_parts();
+        assert!(matches!(e, SessionError::Protocol(_)));
+        assert_eq!(
+            events,
+            [
+                Event::StreamOpened(id),
+                Event::Data {
+                    stream: id,
+                    data: b"hello".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn buffer_overflow_resets_stream() {
+        let mut client = SessionState::new(Mode::Client);
+        let mut server = SessionState::new(Mode::Server);
+        server.set_max_buffered_bytes(10);
+
+        let a = client.open_stream().unwrap();
+        let b = client.open_stream().unwrap();
+        client.send(a, &[1; 8]).unwrap();
+        client.send(b, &[2; 4]).unwrap();
+        let events = transfer(&mut client, &mut server);
+        assert_eq!(events.last(), Some(&Event::BufferOverflow(b)));
+        assert_eq!(server.num_streams(), 1);
+        let events = transfer(&mut server, &mut client);
+        assert_eq!(events.last(), Some(&Event::StreamReset(b)));
+
+        // Consumed data no longer counts towards the limit.
+        server.consume(a, 8).unwrap();
+        client.send(a, &[1; 10]).unwrap();
+        let events = transfer(&mut client, &mut server);
+        assert_eq!(
+            events,
+            [Event::Data {
+                stream: a,
+                data: alloc::vec![1; 10]
+            }]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! The state machine of a stream.
+
+/// The state of a Yamux stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Open bidirectionally.
+    Open,
+    /// Open for incoming messages.
+    SendClosed,
+    /// Open for outgoing messages.
+    RecvClosed,
+    /// Closed (terminal state).
+    Closed,
+}
+
+impl State {
+    /// Can we receive messages over this stream?
+    pub fn can_read(self) -> bool {
+        !matches!(self, State::RecvClosed | State::Closed)
+    }
+
+    /// Can we send messages over this stream?
+    pub fn can_write(self) -> bool {
+        !matches!(self, State::SendClosed | State::Closed)
+    }
+
+    /// The state after closing the direction(s) `next` denotes, e.g. a
+    /// stream whose sending side closed (`SendClosed`) is `Closed` once
+    /// its receiving side closes (`RecvClosed`) too.
+    pub fn transition(self, next: State) -> State {
+        use self::State::*;
+
+        match (self, next) {
+            (Closed, _) => Closed,
+            (Open, _) => next,
+            (RecvClosed, Closed) => Closed,
+            (RecvClosed, Open) => RecvClosed,
+            (RecvClosed, RecvClosed) => RecvClosed,
+            (RecvClosed, SendClosed) => Closed,
+            (SendClosed, Closed) => Closed,
+            (SendClosed, Open) => SendClosed,
+            (SendClosed, RecvClosed) => Closed,
+            (SendClosed, SendClosed) => SendClosed,
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Throughput and latency over an in-memory connection for the matrix of
+//! window update mode, split send size and receive window settings.
+
+use constrained_connection::{new_unconstrained_connection, Endpoint};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::{channel::mpsc, future, io::AsyncReadExt, prelude::*};
+use std::sync::Arc;
+use tokio::{runtime::Runtime, task};
+use yamux::{Config, Connection, Control, Mode, WindowUpdateMode};
+
+criterion_group!(benches, throughput, latency);
+criterion_main!(benches);
+
+const WINDOW_UPDATE_MODES: [WindowUpdateMode; 3] = [
+    WindowUpdateMode::OnRead,
+    WindowUpdateMode::OnReceive,
+    WindowUpdateMode::Hybrid,
+];
+
+const SPLIT_SEND_SIZES: [usize; 3] = [4 * 1024, 16 * 1024, 64 * 1024];
+
+const RECEIVE_WINDOWS: [u32; 2] = [256 * 1024, 1024 * 1024];
+
+#[derive(Debug, Clone)]
+struct Bytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
+
+/// All combinations of settings, each with a name to identify it.
+fn configs() -> Vec<(String, Config)> {
+    let mut configs = Vec::new();
+    for mode in WINDOW_UPDATE_MODES.iter() {
+        for split_send_size in SPLIT_SEND_SIZES.iter() {
+            for receive_window in RECEIVE_WINDOWS.iter() {
+                let mut c = Config::default();
+                c.set_window_update_mode(*mode);
+                c.set_split_send_size(*split_send_size);
+                c.set_receive_window(*receive_window);
+                let name = format!(
+                    "{:?}/split{}KiB/window{}KiB",
+                    mode,
+                    split_send_size / 1024,
+                    receive_window / 1024
+                );
+                configs.push((name, c))
+            }
+        }
+    }
+    configs
+}
+
+fn throughput(c: &mut Criterion) {
+    let data = Bytes(Arc::new(vec![0x42; 64 * 1024]));
+
+    let mut group = c.benchmark_group("throughput");
+    group.sample_size(10);
+
+    for (name, config) in configs() {
+        for (nstreams, nmessages) in [(1, 100), (1000, 1)].iter() {
+            let data = data.clone();
+            let rt = Runtime::new().unwrap();
+
+            group.throughput(Throughput::Bytes(
+                (nstreams * nmessages * data.0.len()) as u64,
+            ));
+            group.bench_function(
+                BenchmarkId::from_parameter(format!("{}/#streams{}", name, nstreams)),
+                |b| {
+                    b.iter(|| {
+                        let (server, client) = new_unconstrained_connection();
+                        rt.block_on(oneway(
+                            *nstreams,
+                            *nmessages,
+                            data.clone(),
+                            config.clone(),
+                            server,
+                            client,
+                        ))
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn latency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("latency");
+    group.sample_size(10);
+
+    for (name, config) in configs() {
+        let rt = Runtime::new().unwrap();
+
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let (server, client) = new_unconstrained_connection();
+                rt.block_on(ping_pong(100, config.clone(), server, client))
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Send `nmessages` messages on each of `nstreams` streams from client to server.
+async fn oneway(
+    nstreams: usize,
+    nmessages: usize,
+    data: Bytes,
+    config: Config,
+    server: Endpoint,
+    client: Endpoint,
+) {
+    let msg_len = data.0.len();
+    let (tx, rx) = mpsc::unbounded();
+
+    let server_config = config.clone();
+    let server = async move {
+        let mut connection = Connection::new(server, server_config, Mode::Server);
+
+        while let Some(Ok(mut stream)) = stream::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .next()
+            .await
+        {
+            let tx = tx.clone();
+
+            task::spawn(async move {
+                let mut n = 0;
+                let mut b = vec![0; msg_len];
+
+                // Receive `nmessages` messages.
+                for _ in 0..nmessages {
+                    stream.read_exact(&mut b[..]).await.unwrap();
+                    n += b.len();
+                }
+
+                tx.unbounded_send(n).expect("unbounded_send");
+                stream.close().await.unwrap();
+            });
+        }
+    };
+    task::spawn(server);
+
+    let conn = Connection::new(client, config, Mode::Client);
+    let (mut ctrl, conn) = Control::new(conn);
+
+    task::spawn(conn.for_each(|r| {
+        r.unwrap();
+        future::ready(())
+    }));
+
+    for _ in 0..nstreams {
+        let data = data.clone();
+        let mut ctrl = ctrl.clone();
+        task::spawn(async move {
+            let mut stream = ctrl.open_stream().await.unwrap();
+
+            // Send `nmessages` messages.
+            for _ in 0..nmessages {
+                stream.write_all(data.as_ref()).await.unwrap();
+            }
+
+            stream.close().await.unwrap();
+        });
+    }
+
+    let n = rx
+        .take(nstreams)
+        .fold(0, |acc, n| future::ready(acc + n))
+        .await;
+    assert_eq!(n, nstreams * nmessages * msg_len);
+    ctrl.close().await.expect("close");
+}
+
+/// Exchange `nrounds` small messages back and forth on a single stream.
+async fn ping_pong(nrounds: usize, config: Config, server: Endpoint, client: Endpoint) {
+    let server_config = config.clone();
+    let server = async move {
+        let mut connection = Connection::new(server, server_config, Mode::Server);
+
+        while let Some(Ok(mut stream)) = stream::poll_fn(|cx| connection.poll_next_inbound(cx))
+            .next()
+            .await
+        {
+            task::spawn(async move {
+                let mut b = [0; 64];
+                for _ in 0..nrounds {
+                    stream.read_exact(&mut b[..]).await.unwrap();
+                    stream.write_all(&b[..]).await.unwrap();
+                }
+                stream.close().await.unwrap();
+            });
+        }
+    };
+    task::spawn(server);
+
+    let conn = Connection::new(client, config, Mode::Client);
+    let (mut ctrl, conn) = Control::new(conn);
+
+    task::spawn(conn.for_each(|r| {
+        r.unwrap();
+        future::ready(())
+    }));
+
+    let mut stream = ctrl.open_stream().await.unwrap();
+    let mut b = [0x42; 64];
+    for _ in 0..nrounds {
+        stream.write_all(&b[..]).await.unwrap();
+        stream.read_exact(&mut b[..]).await.unwrap();
+    }
+    stream.close().await.unwrap();
+    ctrl.close().await.expect("close");
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Encoding the headers of the control frames sent most often, i.e. pongs and
+//! the empty FIN and RST frames closing streams, compared to patching the
+//! varying field into a pre-encoded template.
+//!
+//! Headers are encoded into arrays on the stack without allocating, and both
+//! take about the same time, so `frame::Io` encodes every header instead of
+//! caching templates. Run this to check before adding such a cache.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use yamux::codec::{encode_header, Header, StreamId, HEADER_SIZE};
+
+criterion_group!(benches, pong, fin, rst);
+criterion_main!(benches);
+
+/// Number of frames per iteration.
+const N: u32 = 1024;
+
+fn pong(c: &mut Criterion) {
+    let template = encode_header(&pong_header(0));
+    bench(c, "pong", pong_header, |nonce| {
+        let mut buf = template;
+        buf[8..12].copy_from_slice(&nonce.to_be_bytes());
+        buf
+    })
+}
+
+fn fin(c: &mut Criterion) {
+    let template = encode_header(&fin_header(0));
+    bench(c, "fin", fin_header, |id| {
+        let mut buf = template;
+        buf[4..8].copy_from_slice(&id.to_be_bytes());
+        buf
+    })
+}
+
+fn rst(c: &mut Criterion) {
+    let template = encode_header(&rst_header(0));
+    bench(c, "rst", rst_header, |id| {
+        let mut buf = template;
+        buf[4..8].copy_from_slice(&id.to_be_bytes());
+        buf
+    })
+}
+
+fn pong_header(nonce: u32) -> Header<()> {
+    let mut header = Header::ping(nonce);
+    header.ack();
+    header.into()
+}
+
+fn fin_header(id: u32) -> Header<()> {
+    let mut header = Header::data(StreamId::new(id), 0);
+    header.fin();
+    header.into()
+}
+
+fn rst_header(id: u32) -> Header<()> {
+    let mut header = Header::data(StreamId::new(id), 0);
+    header.rst();
+    header.into()
+}
+
+fn bench(
+    c: &mut Criterion,
+    name: &str,
+    header: impl Fn(u32) -> Header<()>,
+    patch: impl Fn(u32) -> [u8; HEADER_SIZE],
+) {
+    let mut group = c.benchmark_group(format!("control_frames/{}", name));
+    group.throughput(Throughput::Elements(u64::from(N)));
+    group.bench_function("encode", |b| {
+        b.iter(|| {
+            for i in 0..N {
+                black_box(encode_header(&header(black_box(i))));
+            }
+        })
+    });
+    group.bench_function("template", |b| {
+        b.iter(|| {
+            for i in 0..N {
+                black_box(patch(black_box(i)));
+            }
+        })
+    });
+    group.finish();
+}
@@ -0,0 +1,68 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Encoding and decoding of frame headers, which happens once per frame and
+//! thus dominates for small messages.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use yamux::codec::{decode_header, encode_header, Header, HEADER_SIZE};
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
+
+/// Number of headers per iteration.
+const N: usize = 1024;
+
+/// Encoded headers of all frame types with varying stream IDs and lengths.
+fn headers() -> Vec<[u8; HEADER_SIZE]> {
+    (0..N as u32)
+        .map(|i| {
+            let mut buf = [0; HEADER_SIZE];
+            buf[1] = (i % 4) as u8;
+            buf[2..4].copy_from_slice(&((i % 8) as u16).to_be_bytes());
+            buf[4..8].copy_from_slice(&i.to_be_bytes());
+            buf[8..12].copy_from_slice(&(i * 16).to_be_bytes());
+            buf
+        })
+        .collect()
+}
+
+fn encode(c: &mut Criterion) {
+    let headers = headers()
+        .iter()
+        .map(|b| decode_header(b).unwrap())
+        .collect::<Vec<Header<()>>>();
+
+    let mut group = c.benchmark_group("header");
+    group.throughput(Throughput::Elements(N as u64));
+    group.bench_function("encode", |b| {
+        b.iter(|| {
+            for h in &headers {
+                black_box(encode_header(black_box(h)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let headers = headers();
+
+    let mut group = c.benchmark_group("header");
+    group.throughput(Throughput::Elements(N as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            for h in &headers {
+                black_box(decode_header(black_box(h)).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
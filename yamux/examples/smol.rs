@@ -0,0 +1,47 @@
+//! Echo a message over a Yamux stream, using the smol runtime.
+//!
+//! Run with `cargo run --example smol --features async-io` to also drive
+//! the connection's timers by the smol reactor.
+
+use futures::{future, stream, AsyncReadExt, AsyncWriteExt, StreamExt, TryStreamExt};
+use smol::net::{TcpListener, TcpStream};
+use yamux::{Config, Connection, Control, Mode};
+
+fn main() -> anyhow::Result<()> {
+    smol::block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        smol::spawn(async move {
+            let (socket, _) = listener.accept().await?;
+            let mut connection = Connection::new(socket, Config::default(), Mode::Server);
+            stream::poll_fn(|cx| connection.poll_next_inbound(cx))
+                .try_for_each_concurrent(None, |mut stream| async move {
+                    {
+                        let (mut reader, mut writer) = AsyncReadExt::split(&mut stream);
+                        futures::io::copy(&mut reader, &mut writer).await?;
+                    }
+                    stream.close().await?;
+                    Ok(())
+                })
+                .await?;
+            anyhow::Ok(())
+        })
+        .detach();
+
+        let socket = TcpStream::connect(addr).await?;
+        let connection = Connection::new(socket, Config::default(), Mode::Client);
+        let (mut control, connection) = Control::new(connection);
+        smol::spawn(connection.for_each(|_| future::ready(()))).detach();
+
+        let mut stream = control.open_stream().await?;
+        stream.write_all(b"hello from smol").await?;
+        stream.close().await?;
+        let mut echo = String::new();
+        stream.read_to_string(&mut echo).await?;
+        println!("{}", echo);
+
+        control.close().await?;
+        Ok(())
+    })
+}
@@ -0,0 +1,519 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Yamux over blocking sockets, without an async runtime, see [`Connection`].
+
+use crate::{frame::header::StreamId, Mode};
+use nohash_hasher::IntMap;
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use yamux_core::session::{Event, SessionError, SessionState};
+
+/// The size of the buffer the service thread reads into.
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+/// How long closing a connection waits for the remote to close the socket.
+const LINGER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A Yamux connection over a blocking [`TcpStream`].
+///
+/// A service thread reads from the socket and dispatches received frames to
+/// the connection's [`Stream`]s, which implement [`Read`] and [`Write`] and
+/// block until data or send credit is available. Frames are written by a
+/// second service thread, so that reading never waits for the remote to
+/// read, which might in turn wait for us.
+///
+/// The protocol is implemented by [`SessionState`] of `yamux-core`, i.e.
+/// unlike [`crate::Connection`] this connection is not configurable and has
+/// no timers, e.g. for keep-alive pings or idle timeouts. The number of
+/// streams and the received data not yet read are limited by the defaults
+/// of [`SessionState`], a stream whose data exceeds the limit is reset.
+///
+/// Dropping the connection, like [`Connection::close`], terminates the
+/// session and shuts the socket down. Once the connection is closed, for
+/// whatever reason, reads and writes of its streams fail with an error
+/// carrying the reason, e.g. the [`SessionError`] of a remote violating the
+/// protocol.
+pub struct Connection {
+    shared: Arc<Shared>,
+    socket: TcpStream,
+    reader: Option<thread::JoinHandle<()>>,
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("mode", &self.shared.mode)
+            .finish()
+    }
+}
+
+/// A stream of a blocking [`Connection`].
+///
+/// Like [`TcpStream`], [`Read`] and [`Write`] are also implemented for
+/// `&Stream`, to read and write from different threads at the same time.
+///
+/// Dropping the stream closes it for sending, discarding any data received
+/// afterwards.
+pub struct Stream {
+    id: StreamId,
+    shared: Arc<Shared>,
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Stream").field("id", &self.id).finish()
+    }
+}
+
+/// The state shared between a [`Connection`], its [`Stream`]s and the
+/// service thread.
+struct Shared {
+    mode: Mode,
+    inner: Mutex<Inner>,
+    /// Notified whenever `inner` changed.
+    changed: Condvar,
+    /// Notified whenever the session queued frames to send or `inner` has
+    /// been closed, for the writer thread.
+    output: Condvar,
+}
+
+struct Inner {
+    session: SessionState,
+    streams: IntMap<StreamId, Buffer>,
+    /// Inbound streams not yet accepted.
+    incoming: VecDeque<StreamId>,
+    /// Set once the session has been terminated or the socket failed. The
+    /// frames queued by then are still written.
+    closed: Option<CloseReason>,
+    /// Set once the reader thread ended, i.e. the remote closed the socket.
+    eof: bool,
+}
+
+/// Why a [`Connection`] has been closed.
+#[derive(Clone, Debug)]
+enum CloseReason {
+    /// The connection has been closed or dropped.
+    Local,
+    /// The remote terminated the session with the given error code.
+    GoAway(u32),
+    /// The session failed, e.g. because the remote violated the protocol.
+    Session(SessionError),
+    /// Reading from or writing to the socket failed, or the remote closed it.
+    Io(io::ErrorKind, String),
+}
+
+/// The received data of a [`Stream`].
+#[derive(Default)]
+struct Buffer {
+    data: VecDeque<u8>,
+    finished: bool,
+    reset: bool,
+}
+
+impl Connection {
+    /// Run a Yamux session over the given socket, spawning its service threads.
+    pub fn new(socket: TcpStream, mode: Mode) -> io::Result<Self> {
+        let reader = socket.try_clone()?;
+        let control = socket.try_clone()?;
+        let session = SessionState::new(mode);
+        let shared = Arc::new(Shared {
+            mode,
+            inner: Mutex::new(Inner {
+                session,
+                streams: IntMap::default(),
+                incoming: VecDeque::new(),
+                closed: None,
+                eof: false,
+            }),
+            changed: Condvar::new(),
+            output: Condvar::new(),
+        });
+        let writer = thread::Builder::new()
+            .name("yamux-blocking-writer".into())
+            .spawn({
+                let shared = shared.clone();
+                move || shared.write(socket)
+            })?;
+        let reader = thread::Builder::new().name("yamux-blocking".into()).spawn({
+            let shared = shared.clone();
+            move || shared.serve(reader)
+        });
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(e) => {
+                shared.close(CloseReason::Local);
+                let _ = writer.join();
+                return Err(e);
+            }
+        };
+        Ok(Connection {
+            shared,
+            socket: control,
+            reader: Some(reader),
+            writer: Some(writer),
+        })
+    }
+
+    /// Open a new outbound stream.
+    pub fn open_stream(&self) -> io::Result<Stream> {
+        let id = {
+            let mut inner = self.shared.inner.lock();
+            if let Some(reason) = &inner.closed {
+                return Err(reason.to_error());
+            }
+            let id = inner.session.open_stream().map_err(into_io_error)?;
+            inner.streams.insert(id, Buffer::default());
+            id
+        };
+        self.shared.output.notify_one();
+        Ok(Stream {
+            id,
+            shared: self.shared.clone(),
+        })
+    }
+
+    /// Wait for the next inbound stream.
+    ///
+    /// Returns `None` once the connection is closed.
+    pub fn accept(&self) -> Option<Stream> {
+        let mut inner = self.shared.inner.lock();
+        loop {
+            if let Some(id) = inner.incoming.pop_front() {
+                return Some(Stream {
+                    id,
+                    shared: self.shared.clone(),
+                });
+            }
+            if inner.closed.is_some() {
+                return None;
+            }
+            self.shared.changed.wait(&mut inner)
+        }
+    }
+
+    /// Terminate the session and wait for the service threads to end.
+    pub fn close(mut self) -> io::Result<()> {
+        self.terminate();
+        Ok(())
+    }
+
+    fn terminate(&mut self) {
+        self.shared.inner.lock().session.go_away();
+        // The writer sends the `GoAway` and shuts the socket down for
+        // writing.
+        self.shared.close(CloseReason::Local);
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        // Closing the socket while the remote still sends, e.g. window updates
+        // for the data it reads, makes it answer with a RST, upon which the
+        // remote may discard data we sent but it did not read yet. Hence the
+        // reader keeps reading until the remote closes the socket too.
+        let deadline = Instant::now() + LINGER_TIMEOUT;
+        let mut inner = self.shared.inner.lock();
+        while !inner.eof {
+            if self
+                .shared
+                .changed
+                .wait_until(&mut inner, deadline)
+                .timed_out()
+            {
+                log::debug!("blocking connection: remote did not close the socket");
+                break;
+            }
+        }
+        drop(inner);
+        let _ = self.socket.shutdown(Shutdown::Read);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.terminate()
+    }
+}
+
+impl Stream {
+    pub fn id(&self) -> StreamId {
+        self.id
+    }
+
+    /// Close the stream for sending.
+    ///
+    /// Data may still be received until the remote closes the stream too.
+    pub fn close(&self) -> io::Result<()> {
+        self.shared
+            .inner
+            .lock()
+            .session
+            .close_stream(self.id)
+            .map_err(into_io_error)?;
+        self.shared.output.notify_one();
+        Ok(())
+    }
+}
+
+impl Read for &Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut inner = self.shared.inner.lock();
+        loop {
+            let Inner {
+                session, streams, ..
+            } = &mut *inner;
+            let buffer = streams.entry(self.id).or_default();
+            if !buffer.data.is_empty() {
+                let n = buffer.data.len().min(buf.len());
+                for (b, d) in buf.iter_mut().zip(buffer.data.drain(..n)) {
+                    *b = d
+                }
+                // The stream is unknown to the session once closed by both sides.
+                let _ = session.consume(self.id, n as u32);
+                drop(inner);
+                self.shared.output.notify_one();
+                return Ok(n);
+            }
+            if buffer.reset {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            if buffer.finished {
+                return Ok(0);
+            }
+            if let Some(reason) = &inner.closed {
+                return Err(reason.to_error());
+            }
+            self.shared.changed.wait(&mut inner)
+        }
+    }
+}
+
+impl Write for &Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut inner = self.shared.inner.lock();
+        loop {
+            if inner.streams.get(&self.id).is_some_and(|b| b.reset) {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            if let Some(reason) = &inner.closed {
+                return Err(reason.to_error());
+            }
+            let n = inner.session.send(self.id, buf).map_err(into_io_error)?;
+            if n > 0 {
+                drop(inner);
+                self.shared.output.notify_one();
+                return Ok(n);
+            }
+            self.shared.changed.wait(&mut inner)
+        }
+    }
+
+    /// Frames are handed to the writer thread as soon as they are sent, i.e.
+    /// this does nothing.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let mut inner = self.shared.inner.lock();
+        inner.streams.remove(&self.id);
+        if inner.session.close_stream(self.id).is_ok() {
+            drop(inner);
+            self.shared.output.notify_one();
+        }
+    }
+}
+
+impl Shared {
+    /// Read from the socket until it fails or is closed by the remote.
+    ///
+    /// Frames to send in response are left to the writer thread, i.e. this
+    /// never blocks on writing.
+    fn serve(&self, mut socket: TcpStream) {
+        let mut buf = vec![0; READ_BUF_SIZE];
+        let mut failed = false;
+        let reason = loop {
+            let n = match socket.read(&mut buf) {
+                Ok(0) => {
+                    let msg = "connection closed by remote";
+                    break CloseReason::Io(io::ErrorKind::UnexpectedEof, msg.into());
+                }
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::debug!("blocking connection: read error: {}", e);
+                    break CloseReason::Io(e.kind(), e.to_string());
+                }
+            };
+            // After a session error, the input is discarded until the remote
+            // closes the socket in response to our `GoAway`.
+            if failed {
+                continue;
+            }
+            let mut inner = self.inner.lock();
+            let (events, error) = match inner.session.handle_input(&buf[..n]) {
+                Ok(events) => (events, None),
+                Err(e) => {
+                    let (events, e) = e.into_parts();
+                    (events, Some(e))
+                }
+            };
+            // The frames before a failing one have been processed nonetheless.
+            for event in events {
+                inner.on_event(event)
+            }
+            drop(inner);
+            if let Some(e) = error {
+                log::debug!("blocking connection: {}", e);
+                failed = true;
+                // The writer sends the `GoAway` queued for a protocol error.
+                self.close(CloseReason::Session(e));
+                continue;
+            }
+            self.changed.notify_all();
+            self.output.notify_one();
+        };
+        self.inner.lock().eof = true;
+        self.close(reason)
+    }
+
+    /// Write the frames queued by the session until it is closed.
+    fn write(&self, mut socket: TcpStream) {
+        let mut inner = self.inner.lock();
+        loop {
+            let frames = std::iter::from_fn(|| inner.session.poll_output()).collect::<Vec<_>>();
+            if frames.is_empty() {
+                if inner.closed.is_some() {
+                    break;
+                }
+                self.output.wait(&mut inner);
+                continue;
+            }
+            // Write without holding the lock, so that streams can queue more.
+            drop(inner);
+            let result = frames.iter().try_for_each(|frame| socket.write_all(frame));
+            inner = self.inner.lock();
+            if let Err(e) = result {
+                log::debug!("blocking connection: write error: {}", e);
+                inner
+                    .closed
+                    .get_or_insert(CloseReason::Io(e.kind(), e.to_string()));
+                self.changed.notify_all();
+                break;
+            }
+        }
+        drop(inner);
+        // The reader keeps reading until the remote closes the socket too.
+        let _ = socket.shutdown(Shutdown::Write);
+    }
+
+    /// Mark the connection as closed for the given reason, unless it has
+    /// been closed before, and wake everyone waiting for it.
+    fn close(&self, reason: CloseReason) {
+        self.inner.lock().closed.get_or_insert(reason);
+        self.changed.notify_all();
+        self.output.notify_one();
+    }
+}
+
+impl Inner {
+    fn on_event(&mut self, event: Event) {
+        match event {
+            Event::StreamOpened(id) => {
+                self.streams.insert(id, Buffer::default());
+                self.incoming.push_back(id)
+            }
+            Event::Data { stream, data } => match self.streams.get_mut(&stream) {
+                Some(buffer) => buffer.data.extend(data),
+                // The stream has been dropped.
+                None => {
+                    let _ = self.session.consume(stream, data.len() as u32);
+                }
+            },
+            Event::StreamFinished(id) => {
+                if let Some(buffer) = self.streams.get_mut(&id) {
+                    buffer.finished = true
+                }
+            }
+            Event::StreamReset(id) | Event::BufferOverflow(id) => {
+                if let Some(buffer) = self.streams.get_mut(&id) {
+                    buffer.reset = true
+                }
+            }
+            Event::GoAway(code) => {
+                self.closed.get_or_insert(CloseReason::GoAway(code));
+            }
+            Event::StreamAcknowledged(_) | Event::Credit { .. } | Event::Pong(_) => {}
+        }
+    }
+}
+
+impl CloseReason {
+    /// The error reads and writes of the connection fail with.
+    fn to_error(&self) -> io::Error {
+        match self {
+            CloseReason::Local => {
+                io::Error::new(io::ErrorKind::NotConnected, "connection is closed")
+            }
+            CloseReason::GoAway(code) => io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                format!("remote terminated the session with code {}", code),
+            ),
+            CloseReason::Session(e) => into_io_error(e.clone()),
+            CloseReason::Io(kind, msg) => io::Error::new(*kind, msg.clone()),
+        }
+    }
+}
+
+fn into_io_error(e: SessionError) -> io::Error {
+    let kind = match e {
+        SessionError::UnknownStream(_) => io::ErrorKind::ConnectionReset,
+        SessionError::StreamClosed(_) => io::ErrorKind::BrokenPipe,
+        SessionError::Terminated => io::ErrorKind::NotConnected,
+        SessionError::Decode(_) | SessionError::Protocol(_) => io::ErrorKind::InvalidData,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, e)
+}
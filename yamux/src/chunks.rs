@@ -8,17 +8,28 @@
 // at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
 // at https://opensource.org/licenses/MIT.
 
+use crate::frame::INLINE_BODY_LEN;
 use std::{collections::VecDeque, io};
 
+/// The capacity of a chunk allocated for a small frame body, to which the
+/// small bodies of the following frames are appended.
+const SMALL_CHUNK_CAPACITY: usize = 4 * INLINE_BODY_LEN;
+
 /// A sequence of [`Chunk`] values.
 ///
 /// [`Chunks::len`] considers all [`Chunk`] elements and computes the total
 /// result, i.e. the length of all bytes, by summing up the lengths of all
-/// [`Chunk`] elements.
+/// [`Chunk`] elements. [`Chunks::memory`] likewise sums up the memory held
+/// by all [`Chunk`] elements.
 #[derive(Debug)]
 pub(crate) struct Chunks {
     seq: VecDeque<Chunk>,
     len: usize,
+    mem: usize,
+    /// A read chunk of small bodies, kept for reuse by [`Chunks::push_body`].
+    spare: Option<Vec<u8>>,
+    /// Whether bytes have been added since the last [`Chunks::shrink`].
+    pushed: bool,
 }
 
 impl Chunks {
@@ -27,6 +38,9 @@ impl Chunks {
         Chunks {
             seq: VecDeque::new(),
             len: 0,
+            mem: 0,
+            spare: None,
+            pushed: false,
         }
     }
 
@@ -35,27 +49,117 @@ impl Chunks {
         self.len - self.seq.front().map(|c| c.offset()).unwrap_or(0)
     }
 
+    /// Whether there are no bytes yet-to-be-read.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bytes allocated by all `Chunk`s.
+    ///
+    /// In addition to [`Chunks::len`], this includes bytes which have already
+    /// been read from the first chunk and the spare capacity of each chunk,
+    /// e.g. of a small frame body read into a pooled buffer.
+    pub(crate) fn memory(&self) -> usize {
+        self.mem
+    }
+
     /// Add another chunk of bytes to the end.
     pub(crate) fn push(&mut self, x: Vec<u8>) {
         self.len += x.len();
         if !x.is_empty() {
+            self.pushed = true;
+            self.mem += x.capacity();
             self.seq.push_back(Chunk {
                 cursor: io::Cursor::new(x),
             })
         }
     }
 
+    /// Add a frame body to the end.
+    ///
+    /// A small body is copied to the spare capacity of the last chunk, or of
+    /// a new one, so that small frames do not need one allocation each, and
+    /// is then returned for reuse.
+    pub(crate) fn push_body(&mut self, mut body: Vec<u8>) -> Option<Vec<u8>> {
+        if body.is_empty() || body.len() > INLINE_BODY_LEN {
+            self.push(body);
+            return None;
+        }
+        match self.seq.back_mut() {
+            Some(last) if last.spare_capacity() >= body.len() => {
+                last.cursor.get_mut().extend_from_slice(&body);
+                self.len += body.len();
+                self.pushed = true
+            }
+            _ => {
+                let mut chunk = self
+                    .spare
+                    .take()
+                    .unwrap_or_else(|| Vec::with_capacity(SMALL_CHUNK_CAPACITY));
+                chunk.extend_from_slice(&body);
+                self.push(chunk)
+            }
+        }
+        body.clear();
+        Some(body)
+    }
+
+    /// The number of bytes [`Chunks::push_body`] adds to [`Chunks::memory`]
+    /// for a body of the given length and capacity.
+    pub(crate) fn memory_to_push(&self, len: usize, capacity: usize) -> usize {
+        if len == 0 {
+            0
+        } else if len > INLINE_BODY_LEN {
+            capacity
+        } else if self.seq.back().is_some_and(|c| c.spare_capacity() >= len) {
+            0
+        } else {
+            SMALL_CHUNK_CAPACITY
+        }
+    }
+
     /// Remove and return the first chunk.
     pub(crate) fn pop(&mut self) -> Option<Chunk> {
         let chunk = self.seq.pop_front();
         self.len -= chunk.as_ref().map(|c| c.len() + c.offset()).unwrap_or(0);
+        self.mem -= chunk.as_ref().map(|c| c.capacity()).unwrap_or(0);
         chunk
     }
 
+    /// Release the memory of the sequence itself and of the spare chunk if
+    /// there are no chunks and none have been added since the last call, so
+    /// that idle streams do not hold on to it.
+    pub(crate) fn shrink(&mut self) {
+        if !std::mem::take(&mut self.pushed) && self.seq.is_empty() {
+            self.seq = VecDeque::new();
+            self.spare = None
+        }
+    }
+
+    /// Remove the first chunk once it has been read, keeping a chunk of small
+    /// bodies for reuse.
+    pub(crate) fn drop_front(&mut self) {
+        if let Some(chunk) = self.seq.pop_front() {
+            self.len -= chunk.len() + chunk.offset();
+            self.mem -= chunk.capacity();
+            if self.spare.is_none() && chunk.capacity() == SMALL_CHUNK_CAPACITY {
+                let mut v = chunk.into_vec();
+                v.clear();
+                self.spare = Some(v)
+            }
+        }
+    }
+
     /// Get a mutable reference to the first chunk.
     pub(crate) fn front_mut(&mut self) -> Option<&mut Chunk> {
         self.seq.front_mut()
     }
+
+    /// Iterate over the chunks, e.g. to look at their bytes without
+    /// consuming them.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Chunk> {
+        self.seq.iter()
+    }
 }
 
 /// A `Chunk` wraps a `std::io::Cursor<Vec<u8>>`.
@@ -78,6 +182,16 @@ impl Chunk {
         self.cursor.get_ref().len() - self.offset()
     }
 
+    /// The number of bytes allocated by this `Chunk`.
+    pub(crate) fn capacity(&self) -> usize {
+        self.cursor.get_ref().capacity()
+    }
+
+    /// The number of bytes which can be appended without reallocating.
+    fn spare_capacity(&self) -> usize {
+        self.capacity() - self.cursor.get_ref().len()
+    }
+
     /// The sum of bytes that the cursor has been `advance`d over.
     pub(crate) fn offset(&self) -> usize {
         self.cursor.position() as usize
@@ -0,0 +1,87 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Opening streams over a dialed connection, see [`Connector`].
+
+use crate::{
+    Config, ConfigError, Connection, Control, Handle, Mode, Result, ShutdownHandle, Spawn, Stream,
+    Transport,
+};
+use futures::StreamExt;
+use std::{future::Future, io};
+
+/// A client [`Connection`] driven in a task of its own, which opens
+/// streams via [`Connector::open`].
+///
+/// Dropping the connector closes the connection, see [`Connector::shutdown_handle`].
+#[derive(Debug)]
+pub struct Connector {
+    handle: Handle,
+}
+
+impl Connector {
+    /// Dial with the given future and create a connection over the
+    /// resulting socket, see [`Connector::new`].
+    pub async fn connect<F, T>(dial: F, cfg: Config, spawn: Spawn) -> io::Result<Self>
+    where
+        F: Future<Output = io::Result<T>>,
+        T: Transport + Send + 'static,
+    {
+        let socket = dial.await?;
+        Ok(Connector::new(socket, cfg, spawn))
+    }
+
+    /// Create a connection over the given socket, spawning the task driving
+    /// it with `spawn`.
+    pub fn new<T>(socket: T, cfg: Config, spawn: Spawn) -> Self
+    where
+        T: Transport + Send + 'static,
+    {
+        let connection = Connection::new(socket, cfg, Mode::Client);
+        log::debug!("{}: connected", connection.id());
+        Connector {
+            handle: connection.spawn(spawn),
+        }
+    }
+
+    /// Create a connection over the given socket, spawning the task driving
+    /// it with the spawner of `cfg`, see [`Config::set_spawner`].
+    ///
+    /// Fails if no spawner has been configured.
+    pub fn start<T>(socket: T, cfg: Config) -> std::result::Result<Self, ConfigError>
+    where
+        T: Transport + Send + 'static,
+    {
+        let spawn = cfg.spawner()?;
+        Ok(Connector::new(socket, cfg, spawn))
+    }
+
+    /// Open a new stream to the remote.
+    pub async fn open(&mut self) -> Result<Stream> {
+        self.handle.open_stream().await
+    }
+
+    /// Accept the next stream opened by the remote.
+    ///
+    /// Returns `None` once the connection is closed.
+    pub async fn accept(&mut self) -> Option<Stream> {
+        self.handle.incoming().next().await
+    }
+
+    /// A [`Control`] of the connection, e.g. to open streams from other tasks.
+    pub fn control(&self) -> Control {
+        self.handle.control()
+    }
+
+    /// A handle to shut down the connection.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.handle.shutdown_handle()
+    }
+}
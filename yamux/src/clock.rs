@@ -0,0 +1,158 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    task::Waker,
+    time::{Duration, Instant},
+};
+
+/// A source of time for the timers of a connection, e.g. idle and stream
+/// open timeouts, keep-alives and rate limits, see [`crate::Config::set_clock`].
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// Wake the given waker once [`Clock::now`] reached the given deadline.
+    fn wake_at(&self, deadline: Instant, waker: Waker);
+}
+
+/// A [`Clock`] which only advances when told to, to run connections with
+/// timers deterministically in tests.
+///
+/// The clock starts at the time it is created and moves forward by
+/// [`VirtualClock::advance`] only.
+pub struct VirtualClock {
+    state: Mutex<VirtualState>,
+}
+
+struct VirtualState {
+    now: Instant,
+    start: Instant,
+    /// The wakers of timers which are not due yet, one per task.
+    sleepers: Vec<(Instant, Waker)>,
+}
+
+impl VirtualClock {
+    /// A new clock, standing at the current system time.
+    pub fn new() -> Self {
+        let now = Instant::now();
+        VirtualClock {
+            state: Mutex::new(VirtualState {
+                now,
+                start: now,
+                sleepers: Vec::new(),
+            }),
+        }
+    }
+
+    /// The time the clock advanced since it was created.
+    pub fn elapsed(&self) -> Duration {
+        let state = self.state.lock();
+        state.now - state.start
+    }
+
+    /// Advance the clock by the given duration, waking all timers which
+    /// are due by then.
+    pub fn advance(&self, d: Duration) {
+        let due = {
+            let mut state = self.state.lock();
+            state.now += d;
+            let now = state.now;
+            let (due, pending) = std::mem::take(&mut state.sleepers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+            state.sleepers = pending;
+            due
+        };
+        for (_, waker) in due {
+            waker.wake()
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        VirtualClock::new()
+    }
+}
+
+impl fmt::Debug for VirtualClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("VirtualClock")
+            .field("elapsed", &(state.now - state.start))
+            .field("sleepers", &state.sleepers.len())
+            .finish()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.state.lock().now
+    }
+
+    fn wake_at(&self, deadline: Instant, waker: Waker) {
+        let mut state = self.state.lock();
+        if deadline <= state.now {
+            drop(state);
+            return waker.wake();
+        }
+        // Timers register their waker whenever they are polled. Waking a
+        // task polls its timers again, which then register their deadlines
+        // anew, so only the earliest deadline of each task is kept.
+        if let Some(sleeper) = state.sleepers.iter_mut().find(|(_, w)| w.will_wake(&waker)) {
+            sleeper.0 = std::cmp::min(sleeper.0, deadline);
+            return;
+        }
+        state.sleepers.push((deadline, waker))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::{waker, ArcWake};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl ArcWake for CountingWaker {
+        fn wake_by_ref(this: &Arc<Self>) {
+            this.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn repeated_polls_register_one_sleeper() {
+        let clock = VirtualClock::new();
+        let task = Arc::new(CountingWaker::default());
+        let other = Arc::new(CountingWaker::default());
+        let now = clock.now();
+        for _ in 0..100 {
+            clock.wake_at(now + Duration::from_secs(2), waker(task.clone()));
+            clock.wake_at(now + Duration::from_secs(1), waker(task.clone()));
+        }
+        clock.wake_at(now + Duration::from_secs(2), waker(other.clone()));
+        assert_eq!(clock.state.lock().sleepers.len(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(task.0.load(Ordering::SeqCst), 1);
+        assert_eq!(other.0.load(Ordering::SeqCst), 0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(other.0.load(Ordering::SeqCst), 1);
+        assert!(clock.state.lock().sleepers.is_empty());
+    }
+}
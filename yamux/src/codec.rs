@@ -0,0 +1,128 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Encoding and decoding of Yamux frames.
+//!
+//! This module is meant for tools which operate on the wire format directly,
+//! e.g. traffic inspection or conformance testing, and is only available with
+//! the `codec` feature. A [`Codec`] can be used with
+//! [`tokio_util::codec::Framed`] to read and write [`Frame`]s.
+
+use bytes::{Buf, BytesMut};
+use std::{convert::TryInto, io};
+use tokio_util::codec::{Decoder, Encoder};
+
+pub use crate::frame::header::{
+    decode as decode_header, encode as encode_header, Data, Flags, GoAway, Header,
+    HeaderDecodeError, Len, Ping, StreamId, Tag, WindowUpdate, ACK, COMPRESS_LZ4, COMPRESS_ZSTD,
+    CONNECTION_ID, FIN, HEADER_SIZE, RELEASE, RST, STOP_SENDING, SYN,
+};
+pub use crate::frame::{Frame, FrameDecodeError};
+
+/// A [`Decoder`] and [`Encoder`] of [`Frame`]s.
+#[derive(Debug)]
+pub struct Codec {
+    max_body_len: usize,
+    /// The header of a data frame whose body has not been received completely.
+    header: Option<Header<()>>,
+}
+
+impl Codec {
+    /// Create a codec which rejects data frames with bodies larger than
+    /// `max_body_len` bytes.
+    pub fn new(max_body_len: usize) -> Self {
+        Codec {
+            max_body_len,
+            header: None,
+        }
+    }
+}
+
+impl Decoder for Codec {
+    type Item = Frame<()>;
+    type Error = FrameDecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let header = match self.header.take() {
+            Some(header) => header,
+            None => {
+                if src.len() < HEADER_SIZE {
+                    src.reserve(HEADER_SIZE - src.len());
+                    return Ok(None);
+                }
+                let buf = src[..HEADER_SIZE].try_into().expect("HEADER_SIZE bytes");
+                let header = decode_header(buf)?;
+                src.advance(HEADER_SIZE);
+                if header.tag() != Tag::Data {
+                    return Ok(Some(Frame::new(header)));
+                }
+                header
+            }
+        };
+
+        let body_len = header.len().val() as usize;
+
+        if body_len > self.max_body_len {
+            return Err(FrameDecodeError::FrameTooLarge(body_len));
+        }
+
+        if src.len() < body_len {
+            src.reserve(body_len - src.len());
+            self.header = Some(header);
+            return Ok(None);
+        }
+
+        let body = src.split_to(body_len).to_vec();
+        Ok(Some(Frame::from_parts(header, body)))
+    }
+}
+
+impl<T> Encoder<Frame<T>> for Codec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Frame<T>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (header, body) = frame.into_parts();
+        dst.reserve(HEADER_SIZE + body.len());
+        dst.extend_from_slice(&encode_header(&header));
+        dst.extend_from_slice(&body);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::QuickCheck;
+
+    #[test]
+    fn encode_decode_identity() {
+        fn property(frames: Vec<Frame<()>>) -> bool {
+            let mut codec = Codec::new(4096);
+            let mut buf = BytesMut::new();
+            for f in &frames {
+                codec.encode(f.clone(), &mut buf).unwrap();
+            }
+            // Feed the encoded frames byte by byte.
+            let mut src = BytesMut::new();
+            let mut decoded = Vec::new();
+            for b in buf {
+                src.extend_from_slice(&[b]);
+                if let Some(f) = codec.decode(&mut src).unwrap() {
+                    decoded.push(f)
+                }
+            }
+            decoded == frames
+        }
+
+        QuickCheck::new()
+            .tests(1_000)
+            .quickcheck(property as fn(Vec<Frame<()>>) -> bool)
+    }
+}
@@ -0,0 +1,695 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Validation of [`Config`]s, loading them from the environment and, with
+//! the `serde` feature, from configuration files, and updating the
+//! configuration of live connections.
+
+use crate::{
+    consts, error::ConfigError, AckDeadlineResponse, BufferOverflowPolicy, Compression, Config,
+    Extensions, MaxStreamsResponse, OpenMode, Preset, ProbeMode, WindowUpdateMode,
+    WindowUpdateThreshold, WindowViolationPolicy, DEFAULT_CREDIT,
+};
+use std::{str::FromStr, time::Duration};
+
+/// The prefix of the environment variables read by [`Config::from_env`].
+const ENV_PREFIX: &str = "YAMUX_";
+
+impl Config {
+    /// Create a configuration with the settings of the given preset, starting
+    /// from the default configuration.
+    ///
+    /// | Setting | `LowLatency` | `HighThroughput` | `LowMemory` | `Mobile` |
+    /// |---|---|---|---|---|
+    /// | receive window | 1 MiB | 16 MiB | 256 KiB | 512 KiB |
+    /// | max. buffer size | 1 MiB | 16 MiB | 256 KiB | 1 MiB |
+    /// | max. connection receive window | | | 4 MiB | |
+    /// | max. number of streams | | | 256 | |
+    /// | split send size | 4 KiB | 64 KiB | 8 KiB | 16 KiB |
+    /// | window update mode | hybrid | hybrid | on read | hybrid |
+    /// | window update threshold | 1/4 | 1/2 | 1/2 | 1/2 |
+    /// | write coalescing | | | | 5 ms, 16 KiB |
+    /// | liveness probe | keep-alive every 10 s | | | both, every 30 s |
+    /// | idle timeout | | | | 5 min |
+    /// | stream open timeout | | | | 30 s |
+    /// | buffer pool capacity | | 64 | | |
+    /// | write batch size | 1 | 64 | 4 | |
+    ///
+    /// Settings not listed keep their defaults. The liveness probes time out
+    /// after twice the interval. Each preset interoperates with remotes using
+    /// another preset or the default configuration. Individual settings may
+    /// be adjusted afterwards.
+    pub fn preset(preset: Preset) -> Config {
+        let mut cfg = Config::default();
+        match preset {
+            Preset::LowLatency => {
+                cfg.set_receive_window(1024 * 1024)
+                    .set_max_buffer_size(1024 * 1024)
+                    .set_split_send_size(4 * 1024)
+                    .set_window_update_mode(WindowUpdateMode::Hybrid)
+                    .set_window_update_threshold(WindowUpdateThreshold::Fraction(0.25))
+                    .set_liveness_probe(ProbeMode::KeepAlive {
+                        interval: Duration::from_secs(10),
+                        timeout: Duration::from_secs(20),
+                    })
+                    .set_write_batch_size(1);
+            }
+            Preset::HighThroughput => {
+                cfg.set_receive_window(16 * 1024 * 1024)
+                    .set_max_buffer_size(16 * 1024 * 1024)
+                    .set_split_send_size(64 * 1024)
+                    .set_window_update_mode(WindowUpdateMode::Hybrid)
+                    .set_buffer_pool(64)
+                    .set_write_batch_size(64);
+            }
+            Preset::LowMemory => {
+                cfg.set_receive_window(DEFAULT_CREDIT)
+                    .set_max_buffer_size(DEFAULT_CREDIT as usize)
+                    .set_max_connection_receive_window(4 * 1024 * 1024)
+                    .set_max_num_streams(256)
+                    .set_split_send_size(8 * 1024)
+                    .set_write_batch_size(4);
+            }
+            Preset::Mobile => {
+                cfg.set_receive_window(512 * 1024)
+                    .set_split_send_size(16 * 1024)
+                    .set_window_update_mode(WindowUpdateMode::Hybrid)
+                    .set_coalesce_writes(Duration::from_millis(5), 16 * 1024)
+                    .set_liveness_probe(ProbeMode::Both {
+                        interval: Duration::from_secs(30),
+                        timeout: Duration::from_secs(60),
+                    })
+                    .set_idle_timeout(Duration::from_secs(5 * 60))
+                    .set_stream_open_timeout(Duration::from_secs(30));
+            }
+        }
+        cfg
+    }
+
+    /// Check the settings for values the setters would reject, e.g. a
+    /// receive window < 256 KiB, and for inconsistent combinations.
+    ///
+    /// A configuration built with the setters only fails validation if its
+    /// split send size exceeds the max. frame body length, as a remote with
+    /// the same configuration would reject such frames.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.receive_window < consts::DEFAULT_RECEIVE_WINDOW {
+            let reason = format!(
+                "{} < {} bytes",
+                self.receive_window,
+                consts::DEFAULT_RECEIVE_WINDOW
+            );
+            return Err(ConfigError::new("receive_window", reason));
+        }
+        if self.split_send_size == 0 || self.split_send_size > consts::MAX_FRAME_BODY_LEN {
+            let reason = "must be > 0 and <= the max. frame body length of the protocol";
+            return Err(ConfigError::new("split_send_size", reason));
+        }
+        if self.max_frame_body_len > Some(consts::MAX_FRAME_BODY_LEN) {
+            let reason = "exceeds the max. frame body length of the protocol";
+            return Err(ConfigError::new("max_frame_body_len", reason));
+        }
+        if let Some(max) = self.max_frame_body_len {
+            if self.split_send_size > max {
+                let reason = format!(
+                    "{} bytes exceed the max. frame body length of {} bytes",
+                    self.split_send_size, max
+                );
+                return Err(ConfigError::new("split_send_size", reason));
+            }
+        }
+        match self.window_update_threshold {
+            WindowUpdateThreshold::Fraction(f) if !(f > 0.0 && f <= 1.0) => {
+                let reason = format!("fraction {} not in (0, 1]", f);
+                return Err(ConfigError::new("window_update_threshold", reason));
+            }
+            WindowUpdateThreshold::Bytes(0) => {
+                return Err(ConfigError::new("window_update_threshold", "must be > 0"));
+            }
+            _ => {}
+        }
+        if self.max_ack_backlog == Some(0) {
+            return Err(ConfigError::new("max_ack_backlog", "must be > 0"));
+        }
+        if let Some((_, max_bytes)) = self.coalesce_writes {
+            if max_bytes == 0 || max_bytes > u32::MAX as usize {
+                let reason = "max. bytes must be > 0 and <= u32::MAX";
+                return Err(ConfigError::new("coalesce_writes", reason));
+            }
+        }
+        if self.max_send_rate == Some(0) {
+            return Err(ConfigError::new("max_send_rate", "must be > 0"));
+        }
+        if let Some(mode) = self.liveness_probe {
+            if mode.ping_interval() == Some(Duration::ZERO) || mode.timeout().is_zero() {
+                return Err(ConfigError::new("liveness_probe", "durations must be > 0"));
+            }
+        }
+        #[cfg(feature = "stall-detector")]
+        if self.stall_threshold == Some(Duration::ZERO) {
+            return Err(ConfigError::new("stall_threshold", "must be > 0"));
+        }
+        if !self.compression.is_valid() {
+            return Err(ConfigError::new("compression", "unsupported level"));
+        }
+        for (setting, n) in [
+            ("frame_budget", self.frame_budget),
+            ("poll_budget", self.poll_budget),
+            ("write_batch_size", self.write_batch_size),
+        ] {
+            if n == 0 {
+                return Err(ConfigError::new(setting, "must be > 0"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Create a configuration from environment variables, starting from the
+    /// default configuration, or the preset given by `YAMUX_PRESET`, e.g.
+    /// `YAMUX_PRESET=low_memory`, and validate it.
+    ///
+    /// Each setting is read from the variable of its name in upper case with
+    /// the prefix `YAMUX_`, e.g. `YAMUX_RECEIVE_WINDOW`. Durations are given
+    /// in milliseconds by the variables `YAMUX_IDLE_TIMEOUT_MS`,
+    /// `YAMUX_STREAM_OPEN_TIMEOUT_MS`, `YAMUX_INBOUND_STREAM_ACK_DEADLINE_MS`,
+    /// `YAMUX_CLOSE_TIMEOUT_MS`, `YAMUX_STALL_THRESHOLD_MS` (with the
+    /// `stall-detector` feature) and, along with
+    /// `YAMUX_COALESCE_WRITES_MAX_BYTES`, `YAMUX_COALESCE_WRITES_MAX_DELAY_MS`.
+    /// Enum settings are given in snake case, e.g.
+    /// `YAMUX_WINDOW_UPDATE_MODE=on_receive`,
+    /// `YAMUX_BUFFER_OVERFLOW_POLICY=backpressure`, `YAMUX_LIVENESS_PROBE=both`
+    /// along with `YAMUX_LIVENESS_PROBE_TIMEOUT_MS` and, unless the mode is
+    /// `write_stall`, `YAMUX_LIVENESS_PROBE_INTERVAL_MS`, or `YAMUX_COMPRESSION=zstd`
+    /// along with `YAMUX_COMPRESSION_LEVEL`. `YAMUX_EXTENSIONS` is the bitmask
+    /// of [`Config::set_extensions`], e.g. `5`. `YAMUX_WINDOW_UPDATE_THRESHOLD`
+    /// is a fraction if it contains a decimal point, e.g. `0.25`, and a number
+    /// of bytes otherwise. Other variables are ignored.
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Config::from_vars(std::env::vars())
+    }
+
+    /// Read a configuration from the given name-value pairs, as
+    /// [`Config::from_env`] does from the environment variables.
+    pub fn from_vars<I>(vars: I) -> Result<Config, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let vars = vars.into_iter().collect::<Vec<_>>();
+        let preset = format!("{}PRESET", ENV_PREFIX);
+        let mut cfg = match vars.iter().find(|(name, _)| *name == preset) {
+            Some((_, preset)) => Config::preset(match preset.as_str() {
+                "low_latency" => Preset::LowLatency,
+                "high_throughput" => Preset::HighThroughput,
+                "low_memory" => Preset::LowMemory,
+                "mobile" => Preset::Mobile,
+                _ => return Err(invalid("preset", preset)),
+            }),
+            None => Config::default(),
+        };
+        let mut coalesce_delay = None;
+        let mut coalesce_bytes = None;
+        let mut probe_mode = None;
+        let mut probe_interval = None;
+        let mut probe_timeout = None;
+        let mut compression = None;
+        let mut compression_level = None;
+        for (name, value) in vars {
+            let name = match name.strip_prefix(ENV_PREFIX) {
+                Some(name) => name.to_ascii_lowercase(),
+                None => continue,
+            };
+            match name.as_str() {
+                "receive_window" => cfg.receive_window = parse("receive_window", &value)?,
+                "max_buffer_size" => cfg.max_buffer_size = parse("max_buffer_size", &value)?,
+                "max_stream_receive_buffer_bytes" => {
+                    let n = parse("max_stream_receive_buffer_bytes", &value)?;
+                    cfg.max_stream_receive_buffer_bytes = Some(n)
+                }
+                "max_connection_receive_window" => {
+                    let n = parse("max_connection_receive_window", &value)?;
+                    cfg.max_connection_receive_window = Some(n)
+                }
+                "max_frame_body_len" => {
+                    cfg.max_frame_body_len = Some(parse("max_frame_body_len", &value)?)
+                }
+                "max_num_streams" => cfg.max_num_streams = parse("max_num_streams", &value)?,
+                "max_inbound_streams" => {
+                    cfg.max_inbound_streams = Some(parse("max_inbound_streams", &value)?)
+                }
+                "max_outbound_streams" => {
+                    cfg.max_outbound_streams = Some(parse("max_outbound_streams", &value)?)
+                }
+                "max_num_streams_grace" => {
+                    cfg.max_num_streams_grace = parse("max_num_streams_grace", &value)?
+                }
+                "max_num_streams_response" => {
+                    cfg.max_num_streams_response = match value.as_str() {
+                        "go_away" => MaxStreamsResponse::GoAway,
+                        "reset" => MaxStreamsResponse::Reset,
+                        _ => return Err(invalid("max_num_streams_response", &value)),
+                    }
+                }
+                "window_violation_policy" => {
+                    cfg.window_violation_policy = match value.as_str() {
+                        "go_away" => WindowViolationPolicy::GoAway,
+                        "reset" => WindowViolationPolicy::Reset,
+                        _ => return Err(invalid("window_violation_policy", &value)),
+                    }
+                }
+                "buffer_overflow_policy" => {
+                    cfg.buffer_overflow_policy = match value.as_str() {
+                        "reset" => BufferOverflowPolicy::Reset,
+                        "go_away" => BufferOverflowPolicy::GoAway,
+                        "backpressure" => BufferOverflowPolicy::Backpressure,
+                        _ => return Err(invalid("buffer_overflow_policy", &value)),
+                    }
+                }
+                "reset_log_budget" => cfg.reset_log_budget = parse("reset_log_budget", &value)?,
+                "window_update_mode" => {
+                    cfg.window_update_mode = match value.as_str() {
+                        "on_receive" => WindowUpdateMode::OnReceive,
+                        "on_read" => WindowUpdateMode::OnRead,
+                        "hybrid" => WindowUpdateMode::Hybrid,
+                        _ => return Err(invalid("window_update_mode", &value)),
+                    }
+                }
+                "window_update_threshold" => {
+                    cfg.window_update_threshold = if value.contains('.') {
+                        let f = parse("window_update_threshold", &value)?;
+                        WindowUpdateThreshold::Fraction(f)
+                    } else {
+                        WindowUpdateThreshold::Bytes(parse("window_update_threshold", &value)?)
+                    }
+                }
+                "read_after_close" => cfg.read_after_close = parse("read_after_close", &value)?,
+                "split_send_size" => cfg.split_send_size = parse("split_send_size", &value)?,
+                "idle_timeout_ms" => cfg.idle_timeout = Some(millis("idle_timeout", &value)?),
+                "stream_open_timeout_ms" => {
+                    cfg.stream_open_timeout = Some(millis("stream_open_timeout", &value)?)
+                }
+                "inbound_stream_ack_deadline_ms" => {
+                    let d = millis("inbound_stream_ack_deadline", &value)?;
+                    cfg.inbound_stream_ack_deadline = Some(d)
+                }
+                "ack_deadline_response" => {
+                    cfg.ack_deadline_response = match value.as_str() {
+                        "ack" => AckDeadlineResponse::Ack,
+                        "reset" => AckDeadlineResponse::Reset,
+                        _ => return Err(invalid("ack_deadline_response", &value)),
+                    }
+                }
+                "close_timeout_ms" => cfg.close_timeout = Some(millis("close_timeout", &value)?),
+                #[cfg(feature = "stall-detector")]
+                "stall_threshold_ms" => {
+                    cfg.stall_threshold = Some(millis("stall_threshold", &value)?)
+                }
+                "open_mode" => {
+                    cfg.open_mode = match value.as_str() {
+                        "lazy" => OpenMode::Lazy,
+                        "eager" => OpenMode::Eager,
+                        _ => return Err(invalid("open_mode", &value)),
+                    }
+                }
+                "max_ack_backlog" => cfg.max_ack_backlog = Some(parse("max_ack_backlog", &value)?),
+                "stream_id_recycling" => {
+                    cfg.stream_id_recycling = parse("stream_id_recycling", &value)?
+                }
+                "buffer_pool" => cfg.buffer_pool = parse("buffer_pool", &value)?,
+                "coalesce_writes_max_delay_ms" => {
+                    coalesce_delay = Some(millis("coalesce_writes", &value)?)
+                }
+                "coalesce_writes_max_bytes" => {
+                    coalesce_bytes = Some(parse("coalesce_writes", &value)?)
+                }
+                "max_send_rate" => cfg.max_send_rate = Some(parse("max_send_rate", &value)?),
+                "liveness_probe" => probe_mode = Some(value),
+                "liveness_probe_interval_ms" => {
+                    probe_interval = Some(millis("liveness_probe", &value)?)
+                }
+                "liveness_probe_timeout_ms" => {
+                    probe_timeout = Some(millis("liveness_probe", &value)?)
+                }
+                "compression" => compression = Some(value),
+                "compression_level" => compression_level = Some(parse("compression", &value)?),
+                "extensions" => {
+                    cfg.extensions = Extensions::from_bits(parse("extensions", &value)?)
+                }
+                "frame_budget" => cfg.frame_budget = parse("frame_budget", &value)?,
+                "poll_budget" => cfg.poll_budget = parse("poll_budget", &value)?,
+                "write_batch_size" => cfg.write_batch_size = parse("write_batch_size", &value)?,
+                "rng_seed" => {
+                    cfg.set_rng_seed(parse("rng_seed", &value)?);
+                }
+                _ => {}
+            }
+        }
+        match (coalesce_delay, coalesce_bytes) {
+            (Some(delay), Some(bytes)) => cfg.coalesce_writes = Some((delay, bytes)),
+            (None, None) => {}
+            _ => {
+                let reason = "both the max. delay and the max. bytes must be given";
+                return Err(ConfigError::new("coalesce_writes", reason));
+            }
+        }
+        match (probe_mode, probe_timeout) {
+            (Some(mode), Some(timeout)) => {
+                cfg.liveness_probe = Some(probe_mode_from_parts(&mode, probe_interval, timeout)?)
+            }
+            (None, None) if probe_interval.is_none() => {}
+            _ => {
+                let reason = "both the mode and the timeout must be given";
+                return Err(ConfigError::new("liveness_probe", reason));
+            }
+        }
+        if let Some(name) = compression {
+            cfg.compression = compression_from_parts(&name, compression_level)?
+        } else if compression_level.is_some() {
+            return Err(ConfigError::new(
+                "compression",
+                "the algorithm must be given",
+            ));
+        }
+        cfg.validate()?;
+        Ok(cfg)
+    }
+}
+
+/// Changes to the configuration of a live connection, applied via
+/// [`Control::update_config`](crate::Control::update_config) or
+/// [`Connection::update_config`](crate::Connection::update_config).
+///
+/// Settings which are not set keep their current values. Existing streams
+/// keep the settings they were created with.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigUpdate {
+    receive_window: Option<u32>,
+    max_num_streams: Option<usize>,
+    max_inbound_streams: Option<usize>,
+    max_outbound_streams: Option<usize>,
+    max_send_rate: Option<Option<u64>>,
+    paused: Option<bool>,
+}
+
+impl ConfigUpdate {
+    pub fn new() -> Self {
+        ConfigUpdate::default()
+    }
+
+    /// Set the receive window of new streams (must be >= 256 KiB), see
+    /// [`Config::set_receive_window`].
+    ///
+    /// # Panics
+    ///
+    /// If the given receive window is < 256 KiB.
+    pub fn set_receive_window(&mut self, n: u32) -> &mut Self {
+        assert!(n >= DEFAULT_CREDIT);
+        self.receive_window = Some(n);
+        self
+    }
+
+    /// Set the max. number of streams, see [`Config::set_max_num_streams`].
+    ///
+    /// Lowering it does not close existing streams, but no new streams are
+    /// opened or accepted until their number drops below the new limit.
+    pub fn set_max_num_streams(&mut self, n: usize) -> &mut Self {
+        self.max_num_streams = Some(n);
+        self
+    }
+
+    /// Set the max. number of inbound streams, see
+    /// [`Config::set_max_inbound_streams`] and
+    /// [`ConfigUpdate::set_max_num_streams`].
+    pub fn set_max_inbound_streams(&mut self, n: usize) -> &mut Self {
+        self.max_inbound_streams = Some(n);
+        self
+    }
+
+    /// Set the max. number of outbound streams, see
+    /// [`Config::set_max_outbound_streams`] and
+    /// [`ConfigUpdate::set_max_num_streams`].
+    pub fn set_max_outbound_streams(&mut self, n: usize) -> &mut Self {
+        self.max_outbound_streams = Some(n);
+        self
+    }
+
+    /// Set the max. send rate in bytes per second (must be > 0), or remove
+    /// the limit, see [`Config::set_max_send_rate`].
+    ///
+    /// # Panics
+    ///
+    /// If the given rate is 0.
+    pub fn set_max_send_rate(&mut self, bytes_per_sec: Option<u64>) -> &mut Self {
+        assert!(bytes_per_sec != Some(0));
+        self.max_send_rate = Some(bytes_per_sec);
+        self
+    }
+
+    /// Pause or resume opening and accepting new streams.
+    ///
+    /// While paused, opening a stream fails with
+    /// [`ConnectionError::TooManyStreams`](crate::ConnectionError::TooManyStreams)
+    /// and streams opened by the remote are reset. Existing streams are not
+    /// affected.
+    pub fn set_paused(&mut self, paused: bool) -> &mut Self {
+        self.paused = Some(paused);
+        self
+    }
+
+    /// Apply the update to the given configuration.
+    pub(crate) fn apply(&self, cfg: &mut Config) {
+        if let Some(n) = self.receive_window {
+            cfg.receive_window = n
+        }
+        if let Some(n) = self.max_num_streams {
+            cfg.max_num_streams = n
+        }
+        if let Some(n) = self.max_inbound_streams {
+            cfg.max_inbound_streams = Some(n)
+        }
+        if let Some(n) = self.max_outbound_streams {
+            cfg.max_outbound_streams = Some(n)
+        }
+        if let Some(rate) = self.max_send_rate {
+            cfg.max_send_rate = rate
+        }
+    }
+
+    /// Whether to pause or resume opening and accepting new streams, if
+    /// the update changes it.
+    pub(crate) fn paused(&self) -> Option<bool> {
+        self.paused
+    }
+
+    /// Whether the update changes the max. send rate.
+    pub(crate) fn changes_max_send_rate(&self) -> bool {
+        self.max_send_rate.is_some()
+    }
+}
+
+fn parse<T: FromStr>(setting: &'static str, value: &str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| invalid(setting, value))
+}
+
+fn millis(setting: &'static str, value: &str) -> Result<Duration, ConfigError> {
+    parse(setting, value).map(Duration::from_millis)
+}
+
+/// Assemble a [`ProbeMode`] from its name in snake case and its durations.
+fn probe_mode_from_parts(
+    mode: &str,
+    interval: Option<Duration>,
+    timeout: Duration,
+) -> Result<ProbeMode, ConfigError> {
+    let interval = || {
+        interval.ok_or_else(|| {
+            ConfigError::new(
+                "liveness_probe",
+                format!("mode {:?} requires an interval", mode),
+            )
+        })
+    };
+    match mode {
+        "keep_alive" => Ok(ProbeMode::KeepAlive {
+            interval: interval()?,
+            timeout,
+        }),
+        "write_stall" => Ok(ProbeMode::WriteStall { timeout }),
+        "both" => Ok(ProbeMode::Both {
+            interval: interval()?,
+            timeout,
+        }),
+        _ => Err(invalid("liveness_probe", mode)),
+    }
+}
+
+/// Assemble a [`Compression`] from its name in snake case and its level.
+fn compression_from_parts(name: &str, level: Option<i32>) -> Result<Compression, ConfigError> {
+    match (name, level) {
+        ("none", None) => Ok(Compression::None),
+        #[cfg(feature = "lz4")]
+        ("lz4", None) => Ok(Compression::Lz4),
+        #[cfg(feature = "zstd")]
+        ("zstd", level) => Ok(Compression::Zstd(
+            level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+        )),
+        (_, Some(_)) => {
+            let reason = format!("algorithm {:?} has no level", name);
+            Err(ConfigError::new("compression", reason))
+        }
+        _ => Err(invalid("compression", name)),
+    }
+}
+
+fn invalid(setting: &'static str, value: &str) -> ConfigError {
+    ConfigError::new(setting, format!("invalid value {:?}", value))
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use crate::Config;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    // The derived implementations, generated with `remote = "Self"`, are
+    // wrapped to validate deserialized configurations.
+
+    impl Serialize for Config {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Config::serialize(self, serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Config {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let cfg = Config::deserialize(deserializer)?;
+            cfg.validate().map_err(D::Error::custom)?;
+            Ok(cfg)
+        }
+    }
+}
+
+/// (De)serialization of optional durations as milliseconds.
+#[cfg(feature = "serde")]
+pub(crate) mod opt_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(crate) fn serialize<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        match d {
+            Some(d) => s.serialize_some(&(d.as_millis() as u64)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_millis))
+    }
+}
+
+/// (De)serialization of the write coalescing settings as a struct with
+/// the max. delay in milliseconds.
+#[cfg(feature = "serde")]
+pub(crate) mod coalesce_writes {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct CoalesceWrites {
+        max_delay_ms: u64,
+        max_bytes: usize,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        c: &Option<(Duration, usize)>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        c.map(|(delay, max_bytes)| CoalesceWrites {
+            max_delay_ms: delay.as_millis() as u64,
+            max_bytes,
+        })
+        .serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<(Duration, usize)>, D::Error> {
+        let c = Option::<CoalesceWrites>::deserialize(d)?;
+        Ok(c.map(|c| (Duration::from_millis(c.max_delay_ms), c.max_bytes)))
+    }
+}
+
+/// (De)serialization of the liveness probe as a struct with the mode in
+/// snake case and its durations in milliseconds.
+#[cfg(feature = "serde")]
+pub(crate) mod liveness_probe {
+    use crate::ProbeMode;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct LivenessProbe {
+        mode: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        interval_ms: Option<u64>,
+        timeout_ms: u64,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(p: &Option<ProbeMode>, s: S) -> Result<S::Ok, S::Error> {
+        p.map(|p| LivenessProbe {
+            mode: match p {
+                ProbeMode::KeepAlive { .. } => "keep_alive",
+                ProbeMode::WriteStall { .. } => "write_stall",
+                ProbeMode::Both { .. } => "both",
+            }
+            .to_string(),
+            interval_ms: p.ping_interval().map(|d| d.as_millis() as u64),
+            timeout_ms: p.timeout().as_millis() as u64,
+        })
+        .serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<ProbeMode>, D::Error> {
+        match Option::<LivenessProbe>::deserialize(d)? {
+            Some(p) => super::probe_mode_from_parts(
+                &p.mode,
+                p.interval_ms.map(Duration::from_millis),
+                Duration::from_millis(p.timeout_ms),
+            )
+            .map(Some)
+            .map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// (De)serialization of the seeded generator of connection IDs as its seed.
+#[cfg(feature = "serde")]
+pub(crate) mod rng_seed {
+    use crate::connection::SeededIds;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        ids: &Option<SeededIds>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        ids.as_ref().map(|ids| ids.seed).serialize(s)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<SeededIds>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(SeededIds::new))
+    }
+}
@@ -26,8 +26,9 @@
 // it pushes incoming data to the `Stream`'s buffer or increases the sending
 // credit if the remote has sent us a corresponding `Frame::<WindowUpdate>`.
 // Updating a `Stream`'s state acquires a `Mutex`, which every `Stream` has
-// around its `Shared` state. While blocking, we make sure the lock is only
-// held for brief moments and *never* while doing I/O. The only contention is
+// around its `Shared` state, and the received data is behind a second one,
+// which is always locked after the first. While blocking, we make sure the
+// locks are only held for brief moments and *never* while doing I/O. The only contention is
 // between the `Connection` and a single `Stream`, which should resolve
 // quickly. Ideally, we could use `futures::lock::Mutex` but it does not offer
 // a poll-based API as of futures-preview 0.3.0-alpha.19, which makes it
@@ -81,53 +82,139 @@
 //   `StreamCommand::Flush` so that `Stream`s can trigger a flush, which
 //   they would have to when they run out of credit, or else a series of
 //   send operations might never finish.
-// - If Rust gets async destructors, the `garbage_collect()` method can be
-//   removed. Instead a `Stream` would send a `StreamCommand::Dropped(..)`
-//   or something similar and the removal logic could happen within regular
-//   command processing instead of having to scan the whole collection of
-//   `Stream`s on each loop iteration, which is not great.
+// - If Rust gets async destructors, the fallback scan of `garbage_collect()`
+//   can be removed. `Stream`s already send a best-effort
+//   `StreamCommand::Dropped(..)` when dropped, which is handled within
+//   regular command processing, but scanning all `Stream`s from time to time
+//   catches any which did not get theirs through.
 
+mod budget;
 mod cleanup;
 mod closing;
+mod compression;
+mod extensions;
+#[cfg(feature = "frame-sink")]
+mod frame_sink;
+#[cfg(feature = "framed")]
+mod framed;
+mod liveness;
+mod pending;
+mod rate_limit;
+mod recycle;
+mod resets;
+mod snapshot;
+#[cfg(feature = "stall-detector")]
+mod stall;
 mod stream;
+#[cfg(feature = "tokio-io")]
+mod tokio_io;
 
+#[cfg(feature = "transform")]
+use crate::frame::FrameTransform;
 use crate::Result;
 use crate::{
-    error::ConnectionError,
-    frame::header::{self, Data, GoAway, Header, Ping, StreamId, Tag, WindowUpdate, CONNECTION_ID},
-    frame::{self, Frame},
-    Config, WindowUpdateMode, DEFAULT_CREDIT, MAX_COMMAND_BACKLOG,
+    error::{ConnectionError, ProtocolError},
+    frame::header::{
+        self, Data, Either, GoAway, Header, Ping, StreamId, Tag, WindowUpdate, CONNECTION_ID,
+    },
+    frame::{self, Frame, FrameDecodeError},
+    policy::{BufferOverflow, ConnectionInfo, Verdict},
+    timer::{Delay, Time, Timer},
+    transport::{FrameTransport, Frames, Transport},
+    AckDeadlineResponse, BufferOverflowPolicy, Config, ConfigUpdate, Extensions,
+    MaxStreamsResponse, OpenMode, Spawn, WindowUpdateMode, WindowViolationPolicy, DEFAULT_CREDIT,
+    HYBRID_FALLBACK_DELAY,
 };
+use budget::ReceiveBudget;
 use cleanup::Cleanup;
+pub use cleanup::CleanupReport;
 use closing::Closing;
-use futures::{channel::mpsc, future::Either, prelude::*, sink::SinkExt, stream::Fuse};
+use extensions::Negotiation;
+use futures::{channel::mpsc, prelude::*, ready, sink::SinkExt, stream::Fuse};
+use liveness::{Liveness, Probe};
 use nohash_hasher::IntMap;
-use std::collections::VecDeque;
-use std::task::Context;
+use parking_lot::Mutex;
+use pending::PendingFrames;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rate_limit::RateLimit;
+use recycle::Recycler;
+use resets::LogBudget;
+pub use resets::{ResetReason, ResetsSent};
+use std::task::{Context, Waker};
+use std::time::{Duration, Instant};
 use std::{fmt, sync::Arc, task::Poll};
+use yamux_core::{
+    flow,
+    inbound::{self, Inbound},
+};
 
-pub use stream::{Packet, State, Stream};
+#[cfg(feature = "frame-sink")]
+pub use frame_sink::FrameSink;
+#[cfg(feature = "framed")]
+pub use framed::FramedStream;
+pub use snapshot::{Snapshot, StreamSnapshot};
+#[cfg(feature = "stall-detector")]
+pub(crate) use stall::StallObserverRef;
+#[cfg(feature = "stall-detector")]
+pub use stall::{StallObserver, StalledStream};
+use stream::Reset;
+pub use stream::{CloseReason, Packet, ReadHalf, State, Stream, StreamMetrics, WriteHalf};
+#[cfg(feature = "tokio-io")]
+pub use tokio_io::TokioStream;
 
-/// How the connection is used.
-#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
-pub enum Mode {
-    /// Client to server connection.
-    Client,
-    /// Server to client connection.
-    Server,
-}
+pub use yamux_core::inbound::Mode;
 
 /// The connection identifier.
 ///
-/// Randomly generated, this is mainly intended to improve log output.
-#[derive(Clone, Copy)]
-pub(crate) struct Id(u32);
+/// Randomly generated, this is mainly intended to improve log output and
+/// to attribute errors to connections.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Id(u32);
 
 impl Id {
     /// Create a random connection ID.
     pub(crate) fn random() -> Self {
         Id(rand::random())
     }
+
+    /// Create the ID of a new connection with the given configuration, see
+    /// [`Config::set_rng_seed`].
+    pub(crate) fn new(config: &Config) -> Self {
+        match &config.seeded_ids {
+            Some(ids) => Id(ids.rng.lock().gen()),
+            None => Id::random(),
+        }
+    }
+}
+
+/// The random number generator of a [`Config`] with a seed, e.g. for
+/// connection IDs.
+#[derive(Clone)]
+pub(crate) struct SeededIds {
+    pub(crate) seed: u64,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl SeededIds {
+    pub(crate) fn new(seed: u64) -> Self {
+        SeededIds {
+            seed,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// Call `f` with the generator, e.g. to draw the jitter of a delay.
+    pub(crate) fn with_rng<R>(&self, f: impl FnOnce(&mut StdRng) -> R) -> R {
+        f(&mut self.rng.lock())
+    }
+}
+
+impl fmt::Debug for SeededIds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeededIds")
+            .field("seed", &self.seed)
+            .finish()
+    }
 }
 
 impl fmt::Debug for Id {
@@ -144,13 +231,323 @@ impl fmt::Display for Id {
 
 #[derive(Debug)]
 pub struct Connection<T> {
+    id: Id,
     inner: ConnectionState<T>,
+    /// Whether to detach from the transport when it fails, see [`Reconnect`](crate::Reconnect).
+    detach_on_failure: bool,
+    /// The state of the connection after it detached from the failed transport.
+    detached: Option<Box<Snapshot>>,
+    /// What was discarded once the connection failed.
+    cleanup_report: Option<Box<CleanupReport>>,
+}
+
+/// A summary of the state of a [`Connection`], see [`Connection::stats`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// See [`Connection::num_streams`].
+    pub num_streams: usize,
+    /// See [`Connection::can_open_outbound`].
+    pub can_open_outbound: bool,
+    /// See [`Connection::remaining_stream_ids`].
+    pub remaining_stream_ids: u32,
+    /// The number of frames of the remote whose body exceeded the receive
+    /// window of their stream, see [`Config::set_window_violation_policy`].
+    pub window_violations: u64,
+    /// The sum of [`StreamMetrics::lock_contentions`] of all streams of the
+    /// connection, including the streams which have been removed already.
+    pub lock_contentions: u64,
+    /// The number of received bytes buffered by the streams of the
+    /// connection which have not been read yet.
+    pub buffered_bytes: usize,
+    /// The number of RSTs sent to the remote, by reason.
+    pub resets_sent: ResetsSent,
+}
+
+impl<F: FrameTransport> Connection<Frames<F>> {
+    /// Create a connection over a transport which sends and receives whole
+    /// frames instead of bytes (see [`FrameTransport`]).
+    pub fn with_transport(transport: F, cfg: Config, mode: Mode) -> Self {
+        Connection::new(Frames::new(transport), cfg, mode)
+    }
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
+impl<T: Transport> Connection<T> {
     pub fn new(socket: T, cfg: Config, mode: Mode) -> Self {
+        let active = Active::new(socket, cfg, mode);
         Self {
-            inner: ConnectionState::Active(Active::new(socket, cfg, mode)),
+            id: active.id,
+            inner: ConnectionState::Active(active),
+            detach_on_failure: false,
+            detached: None,
+            cleanup_report: None,
+        }
+    }
+
+    /// Resume a connection detached via [`Connection::into_parts`] over a
+    /// new I/O resource.
+    pub fn from_parts(socket: T, snapshot: Snapshot) -> Self {
+        log::debug!("{}: resuming connection", snapshot.id);
+        Self {
+            id: snapshot.id,
+            inner: ConnectionState::Active(Active::from_snapshot(socket, snapshot)),
+            detach_on_failure: false,
+            detached: None,
+            cleanup_report: None,
+        }
+    }
+
+    /// Detach the connection from its I/O resource, e.g. after a transient
+    /// failure of the transport, to resume it via [`Connection::from_parts`]
+    /// over a new one.
+    ///
+    /// All streams remain open and frames not yet written to the I/O resource
+    /// are sent once resumed. However, frames which have been written to the
+    /// I/O resource, including a partially written one, may not have reached
+    /// the remote, and frames in flight from the remote are lost. Unless both
+    /// endpoints agree on the state of their streams, e.g. by exchanging and
+    /// comparing [`Snapshot::streams`] out of band (serializable with the
+    /// `serde` feature), the flow control state of streams diverges and data
+    /// is lost. [`Connection::reset_streams_in_flight`] resets the streams
+    /// affected instead. Resuming must be coordinated with the remote, which
+    /// detaches and resumes its connection likewise.
+    ///
+    /// Fails if the connection is no longer active.
+    pub fn into_parts(mut self) -> Result<(T, Snapshot)> {
+        match std::mem::replace(&mut self.inner, ConnectionState::Closed) {
+            ConnectionState::Active(active) => active.into_snapshot(),
+            _ => Err(ConnectionError::Closed),
+        }
+    }
+
+    /// Detach from the transport when it fails instead of closing all streams.
+    pub(crate) fn set_detach_on_failure(&mut self, detach: bool) {
+        self.detach_on_failure = detach
+    }
+
+    /// Close the connection once the given cleanup completed.
+    fn finish_cleanup(&mut self, cleanup: &mut Cleanup) {
+        let report = cleanup.take_report();
+        log::debug!("{}: cleaned up: {:?}", self.id, report);
+        self.cleanup_report = Some(Box::new(report));
+        self.inner = ConnectionState::Closed;
+    }
+
+    /// Take the state of the connection if it detached from the failed transport.
+    pub(crate) fn take_detached(&mut self) -> Option<Snapshot> {
+        self.detached.take().map(|s| *s)
+    }
+
+    /// What was discarded when the connection failed, i.e. the commands of
+    /// its streams and the frames which were never sent.
+    ///
+    /// `None` unless the connection failed with an error and finished
+    /// cleaning up, i.e. after the error was returned.
+    pub fn cleanup_report(&self) -> Option<&CleanupReport> {
+        self.cleanup_report.as_deref()
+    }
+
+    /// The identifier of this connection, as used in log output and errors.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// The number of streams currently open on this connection.
+    pub fn num_streams(&self) -> usize {
+        match &self.inner {
+            ConnectionState::Active(active) => active.streams.len(),
+            _ => 0,
+        }
+    }
+
+    /// Whether a new outbound stream can be opened right now, i.e. the
+    /// connection is active, neither side is going away, the max. number of
+    /// outbound streams has not been reached and stream IDs are still available.
+    pub fn can_open_outbound(&self) -> bool {
+        match &self.inner {
+            ConnectionState::Active(active) => {
+                active.accepts_new_streams()
+                    && !active.paused
+                    && !active.max_outbound_streams_reached()
+                    && active.remaining_stream_ids() > 0
+            }
+            _ => false,
+        }
+    }
+
+    /// The number of outbound streams which can still be opened before
+    /// running out of stream IDs (see [`ConnectionError::NoMoreStreamIds`]).
+    pub fn remaining_stream_ids(&self) -> u32 {
+        match &self.inner {
+            ConnectionState::Active(active) => active.remaining_stream_ids(),
+            _ => 0,
+        }
+    }
+
+    /// The protocol extensions both endpoints advertised, see
+    /// [`Config::set_extensions`].
+    ///
+    /// `None` until the remote opened or acknowledged a stream, or if the
+    /// connection is no longer active. Empty for remotes which do not know
+    /// about extensions.
+    pub fn negotiated_extensions(&self) -> Option<Extensions> {
+        match &self.inner {
+            ConnectionState::Active(active) => active.extensions.negotiated(),
+            _ => None,
+        }
+    }
+
+    /// The spawner of [`Config::set_spawner`], unless none has been set or
+    /// the connection is no longer active.
+    pub(crate) fn spawner(&self) -> Option<Spawn> {
+        match &self.inner {
+            ConnectionState::Active(active) => active.config.spawner.as_ref().map(|s| s.0.clone()),
+            _ => None,
+        }
+    }
+
+    /// A summary of the state of this connection.
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            num_streams: self.num_streams(),
+            can_open_outbound: self.can_open_outbound(),
+            remaining_stream_ids: self.remaining_stream_ids(),
+            window_violations: match &self.inner {
+                ConnectionState::Active(active) => active.window_violations,
+                _ => 0,
+            },
+            lock_contentions: match &self.inner {
+                ConnectionState::Active(active) => {
+                    let open: u64 = active.streams.values().map(|s| s.lock_contentions()).sum();
+                    active.lock_contentions + open
+                }
+                _ => 0,
+            },
+            buffered_bytes: match &self.inner {
+                ConnectionState::Active(active) => {
+                    active.streams.values().map(|s| s.buffer().len()).sum()
+                }
+                _ => 0,
+            },
+            resets_sent: match &self.inner {
+                ConnectionState::Active(active) => active.resets_sent,
+                _ => ResetsSent::default(),
+            },
+        }
+    }
+
+    /// Set the handler of inbound data of streams in push mode, see
+    /// [`Stream::set_push_mode`].
+    ///
+    /// The handler is called with the data of each received frame as soon as
+    /// it arrives, instead of buffering it for the stream to read. The data is
+    /// considered consumed, i.e. new credit is granted to the remote without
+    /// waiting for the stream to be read. The handler is called while polling
+    /// the connection and must not block, though it may access the stream.
+    pub fn set_inbound_handler<F>(&mut self, f: F)
+    where
+        F: FnMut(StreamId, Vec<u8>) + Send + 'static,
+    {
+        if let ConnectionState::Active(active) = &mut self.inner {
+            active.inbound_handler = Some(Box::new(f))
+        }
+    }
+
+    /// Transform all frames sent and received over the I/O resource, e.g. to
+    /// encrypt them, see [`FrameTransform`] (requires the `transform` feature).
+    ///
+    /// Transformed frames are written with a length prefix, so the remote must
+    /// use a corresponding transform. The transform must be set before the
+    /// connection is first polled. It does not apply to connections over a
+    /// [`FrameTransport`].
+    #[cfg(feature = "transform")]
+    pub fn set_frame_transform<F: FrameTransform>(&mut self, transform: F) {
+        if let ConnectionState::Active(active) = &mut self.inner {
+            active.socket.get_mut().set_transform(Box::new(transform))
+        }
+    }
+
+    /// A [`FrameSink`] to inject frames into this connection, e.g. pings
+    /// carrying out-of-band messages (requires the `frame-sink` feature).
+    ///
+    /// Fails with [`ConnectionError::Closed`] if the connection is not active.
+    #[cfg(feature = "frame-sink")]
+    pub fn frame_sink(&self) -> Result<FrameSink> {
+        match &self.inner {
+            ConnectionState::Active(active) => {
+                Ok(FrameSink::new(active.id, active.stream_sender.clone()))
+            }
+            _ => Err(ConnectionError::Closed),
+        }
+    }
+
+    /// Change settings of this connection, see [`ConfigUpdate`].
+    ///
+    /// Returns `false` if the connection is not active.
+    pub fn update_config(&mut self, update: &ConfigUpdate) -> bool {
+        match &mut self.inner {
+            ConnectionState::Active(active) => {
+                active.update_config(update);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Reset the stream with the given ID, regardless of the task owning it.
+    ///
+    /// A `RST` is sent to the remote the next time the connection is polled
+    /// and the local [`Stream`] is closed, discarding buffered data and failing
+    /// all further reads and writes with [`std::io::ErrorKind::ConnectionReset`].
+    /// Returns `false` if there is no such stream or the connection is not active.
+    pub fn reset_stream(&mut self, id: StreamId) -> bool {
+        match &mut self.inner {
+            ConnectionState::Active(active) => active.reset_stream(id, Reset::Requested),
+            _ => false,
+        }
+    }
+
+    /// Reset all streams whose frames may have been lost with the transport
+    /// the connection was detached from, see [`Connection::into_parts`].
+    ///
+    /// Yamux neither acknowledges data nor retransmits it. A stream is reset
+    /// if it sent data the remote has not granted credit for again, if the
+    /// remote has not acknowledged its SYN yet or if it is half-closed by us,
+    /// since the remote may never have received its FIN. Credit granted along
+    /// with the ACK of a stream may grow the remote's window instead, so the
+    /// ACK only confirms the data sent along with the SYN. Reads and writes of
+    /// those streams fail with [`std::io::ErrorKind::ConnectionAborted`] and
+    /// the remote receives a `RST` once the connection is polled. The resets
+    /// are counted as [`ResetReason::TransportLost`].
+    ///
+    /// Streams whose data has been lost on the way to us are reset by the
+    /// remote, given it calls this method after resuming as well. Returns the
+    /// number of streams reset.
+    pub fn reset_streams_in_flight(&mut self) -> usize {
+        match &mut self.inner {
+            ConnectionState::Active(active) => active.reset_streams_in_flight(),
+            _ => 0,
+        }
+    }
+
+    /// Poll whether a new outbound stream can be opened.
+    ///
+    /// Resolves once opening a stream would neither fail because the max.
+    /// number of streams is reached (see [`Config::set_max_num_streams`] and
+    /// [`Config::set_max_outbound_streams`])
+    /// or the connection is paused (see [`ConfigUpdate::set_paused`]), nor
+    /// wait for the ACK backlog (see [`Config::set_max_ack_backlog`]). Fails
+    /// if no stream can be opened anymore, i.e. the connection is going away,
+    /// closed or out of stream IDs.
+    ///
+    /// As with [`futures::Sink::poll_ready`], the connection has to be polled
+    /// for progress meanwhile, e.g. via [`Connection::poll_next_inbound`].
+    /// Readiness is not reserved, i.e. opening a stream may still fail if
+    /// streams have been opened in between.
+    pub fn poll_ready_outbound(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match &mut self.inner {
+            ConnectionState::Active(active) => active.poll_ready_outbound(cx),
+            _ => Poll::Ready(Err(ConnectionError::Closed)),
         }
     }
 
@@ -158,18 +555,73 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
     ///
     /// This function will fail if the current state does not allow opening new outbound streams.
     pub fn poll_new_outbound(&mut self, cx: &mut Context<'_>) -> Poll<Result<Stream>> {
+        self.poll_new_outbound_inner(cx, None, None)
+    }
+
+    /// Poll for a new outbound stream with the given receive window (must be >= 256 KiB),
+    /// instead of the receive window of the connection's [`Config`].
+    ///
+    /// This function will fail if the current state does not allow opening new outbound streams.
+    ///
+    /// # Panics
+    ///
+    /// If the given receive window is < 256 KiB.
+    pub fn poll_new_outbound_with_window(
+        &mut self,
+        cx: &mut Context<'_>,
+        receive_window: u32,
+    ) -> Poll<Result<Stream>> {
+        assert!(receive_window >= DEFAULT_CREDIT);
+        self.poll_new_outbound_inner(cx, Some(receive_window), None)
+    }
+
+    /// Poll for a new outbound stream whose SYN is sent according to the given
+    /// [`OpenMode`], instead of the open mode of the connection's [`Config`].
+    ///
+    /// This function will fail if the current state does not allow opening new outbound streams.
+    pub fn poll_new_outbound_with_mode(
+        &mut self,
+        cx: &mut Context<'_>,
+        mode: OpenMode,
+    ) -> Poll<Result<Stream>> {
+        self.poll_new_outbound_inner(cx, None, Some(mode))
+    }
+
+    pub(crate) fn poll_new_outbound_inner(
+        &mut self,
+        cx: &mut Context<'_>,
+        receive_window: Option<u32>,
+        mode: Option<OpenMode>,
+    ) -> Poll<Result<Stream>> {
         loop {
             match std::mem::replace(&mut self.inner, ConnectionState::Poisoned) {
-                ConnectionState::Active(mut active) => match active.new_outbound() {
-                    Ok(stream) => {
-                        self.inner = ConnectionState::Active(active);
-                        return Poll::Ready(Ok(stream));
-                    }
-                    Err(e) => {
-                        self.inner = ConnectionState::Cleanup(active.cleanup(e));
-                        continue;
+                ConnectionState::Active(active) if !active.accepts_new_streams() => {
+                    log::debug!("{}: no new streams while going away", active.id);
+                    self.inner = ConnectionState::Active(active);
+                    return Poll::Ready(Err(ConnectionError::Closed));
+                }
+                ConnectionState::Active(mut active) if active.ack_backlog_exceeded() => {
+                    log::debug!("{}: waiting for ACKs of outbound streams", active.id);
+                    active.open_waker = Some(cx.waker().clone());
+                    self.inner = ConnectionState::Active(active);
+                    return Poll::Pending;
+                }
+                ConnectionState::Active(mut active) => {
+                    match active.new_outbound(receive_window, mode) {
+                        Ok(stream) => {
+                            self.inner = ConnectionState::Active(active);
+                            return Poll::Ready(Ok(stream));
+                        }
+                        Err(e) if e.is_recoverable() => {
+                            self.inner = ConnectionState::Active(active);
+                            return Poll::Ready(Err(e));
+                        }
+                        Err(e) => {
+                            self.inner = ConnectionState::Cleanup(active.cleanup(e));
+                            continue;
+                        }
                     }
-                },
+                }
                 ConnectionState::Closing(mut inner) => match inner.poll_unpin(cx) {
                     Poll::Ready(Ok(())) => {
                         self.inner = ConnectionState::Closed;
@@ -186,7 +638,7 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
                 },
                 ConnectionState::Cleanup(mut inner) => match inner.poll_unpin(cx) {
                     Poll::Ready(e) => {
-                        self.inner = ConnectionState::Closed;
+                        self.finish_cleanup(&mut inner);
                         return Poll::Ready(Err(e));
                     }
                     Poll::Pending => {
@@ -214,11 +666,27 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
                         self.inner = ConnectionState::Active(active);
                         return Poll::Ready(Some(Ok(stream)));
                     }
+                    Poll::Ready(Err(e))
+                        if self.detach_on_failure && active.is_transport_failure(&e) =>
+                    {
+                        log::debug!("{}: detaching from failed transport: {}", active.id, e);
+                        self.inner = ConnectionState::Closed;
+                        match active.into_snapshot() {
+                            Ok((_, snapshot)) => self.detached = Some(Box::new(snapshot)),
+                            Err(e) => return Poll::Ready(Some(Err(e))),
+                        }
+                        return Poll::Ready(Some(Err(e)));
+                    }
                     Poll::Ready(Err(e)) => {
                         self.inner = ConnectionState::Cleanup(active.cleanup(e));
                         continue;
                     }
                     Poll::Pending => {
+                        if active.poll_idle_timeout(cx).is_ready() {
+                            log::debug!("{}: closing idle connection", active.id);
+                            self.inner = ConnectionState::Closing(active.close());
+                            continue;
+                        }
                         self.inner = ConnectionState::Active(active);
                         return Poll::Pending;
                     }
@@ -239,11 +707,11 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
                 },
                 ConnectionState::Cleanup(mut cleanup) => match cleanup.poll_unpin(cx) {
                     Poll::Ready(ConnectionError::Closed) => {
-                        self.inner = ConnectionState::Closed;
+                        self.finish_cleanup(&mut cleanup);
                         return Poll::Ready(None);
                     }
                     Poll::Ready(other) => {
-                        self.inner = ConnectionState::Closed;
+                        self.finish_cleanup(&mut cleanup);
                         return Poll::Ready(Some(Err(other)));
                     }
                     Poll::Pending => {
@@ -260,6 +728,59 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
         }
     }
 
+    /// Gracefully drain the connection.
+    ///
+    /// Announces to the remote that no new streams will be accepted, resets any
+    /// further inbound streams, but keeps servicing existing streams until they
+    /// are all closed, at which point the connection is closed and this function
+    /// completes. While draining, no new outbound streams can be opened either.
+    pub fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            match std::mem::replace(&mut self.inner, ConnectionState::Poisoned) {
+                ConnectionState::Active(mut active) => match active.poll_drain(cx) {
+                    Poll::Ready(Ok(())) => {
+                        self.inner = ConnectionState::Closing(active.close());
+                    }
+                    Poll::Ready(Err(e)) => {
+                        self.inner = ConnectionState::Cleanup(active.cleanup(e));
+                    }
+                    Poll::Pending => {
+                        self.inner = ConnectionState::Active(active);
+                        return Poll::Pending;
+                    }
+                },
+                ConnectionState::Closing(mut inner) => match inner.poll_unpin(cx)? {
+                    Poll::Ready(()) => {
+                        self.inner = ConnectionState::Closed;
+                    }
+                    Poll::Pending => {
+                        self.inner = ConnectionState::Closing(inner);
+                        return Poll::Pending;
+                    }
+                },
+                ConnectionState::Cleanup(mut cleanup) => match cleanup.poll_unpin(cx) {
+                    Poll::Ready(ConnectionError::Closed) => {
+                        self.finish_cleanup(&mut cleanup);
+                        return Poll::Ready(Ok(()));
+                    }
+                    Poll::Ready(other) => {
+                        self.finish_cleanup(&mut cleanup);
+                        return Poll::Ready(Err(other));
+                    }
+                    Poll::Pending => {
+                        self.inner = ConnectionState::Cleanup(cleanup);
+                        return Poll::Pending;
+                    }
+                },
+                ConnectionState::Closed => {
+                    self.inner = ConnectionState::Closed;
+                    return Poll::Ready(Ok(()));
+                }
+                ConnectionState::Poisoned => unreachable!(),
+            }
+        }
+    }
+
     /// Close the connection.
     pub fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
         loop {
@@ -279,7 +800,7 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
                 ConnectionState::Cleanup(mut cleanup) => match cleanup.poll_unpin(cx) {
                     Poll::Ready(reason) => {
                         log::warn!("Failure while closing connection: {}", reason);
-                        self.inner = ConnectionState::Closed;
+                        self.finish_cleanup(&mut cleanup);
                         return Poll::Ready(Ok(()));
                     }
                     Poll::Pending => {
@@ -302,7 +823,9 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
 impl<T> Drop for Connection<T> {
     fn drop(&mut self) {
         match &mut self.inner {
-            ConnectionState::Active(active) => active.drop_all_streams(),
+            ConnectionState::Active(active) => {
+                active.drop_all_streams(CloseReason::ConnectionDropped)
+            }
             ConnectionState::Closing(_) => {}
             ConnectionState::Cleanup(_) => {}
             ConnectionState::Closed => {}
@@ -311,6 +834,9 @@ impl<T> Drop for Connection<T> {
     }
 }
 
+// A connection spends most of its life in `Active`, so boxing it would only
+// add an indirection to every poll.
+#[allow(clippy::large_enum_variant)]
 enum ConnectionState<T> {
     /// The connection is alive and healthy.
     Active(Active<T>),
@@ -348,10 +874,134 @@ struct Active<T> {
     socket: Fuse<frame::Io<T>>,
     next_id: u32,
     streams: IntMap<StreamId, Stream>,
+    /// The number of `streams` opened by us.
+    num_outbound: usize,
+    /// The number of `poll_frames` iterations until the next fallback scan
+    /// for dropped streams, see `garbage_collect`.
+    gc_countdown: usize,
     stream_sender: mpsc::Sender<StreamCommand>,
     stream_receiver: mpsc::Receiver<StreamCommand>,
     dropped_streams: Vec<StreamId>,
-    pending_frames: VecDeque<Frame<()>>,
+    pending_frames: PendingFrames,
+    /// We are draining, i.e. we have sent a `GoAway` and no longer accept new streams.
+    draining: bool,
+    /// The remote has sent a `GoAway` and no longer accepts new streams.
+    remote_draining: bool,
+    /// New streams are neither opened nor accepted, see [`ConfigUpdate::set_paused`].
+    paused: bool,
+    idle: Option<Idle>,
+    open_timeout: Option<Box<OpenTimeout>>,
+    /// See [`Config::set_inbound_stream_ack_deadline`].
+    ack_deadline: Option<Box<OpenTimeout>>,
+    /// Running as long as streams may be due a keep-alive, see
+    /// [`Stream::set_keepalive`].
+    keepalive: Option<Delay>,
+    /// Running while the receive window of a stream is exhausted just as its
+    /// reader is woken, given [`WindowUpdateMode::Hybrid`].
+    hybrid_fallback: Option<Delay>,
+    /// Waiting to open a stream once the ACK backlog drops below its limit,
+    /// see [`Config::set_max_ack_backlog`], or for another stream to be
+    /// opened at all, see [`Connection::poll_ready_outbound`].
+    open_waker: Option<Waker>,
+    /// Set if stream ID recycling is enabled.
+    recycler: Option<Box<Recycler>>,
+    /// See [`Config::set_max_connection_receive_window`].
+    receive_budget: Option<Box<ReceiveBudget>>,
+    /// See [`Config::set_extensions`].
+    extensions: Negotiation,
+    coalesce: Option<Box<Coalesce>>,
+    rate_limit: Option<Box<RateLimit>>,
+    liveness: Option<Box<Liveness>>,
+    #[cfg(feature = "stall-detector")]
+    stall_detector: Option<Box<stall::StallDetector>>,
+    /// Set once we sent a `GoAway` because of the given protocol violation.
+    terminated: Option<Box<ProtocolError>>,
+    /// The number of frames which exceeded the receive window of their stream.
+    window_violations: u64,
+    /// The lock contentions of removed streams, see
+    /// [`ConnectionStats::lock_contentions`].
+    lock_contentions: u64,
+    /// See [`ConnectionStats::resets_sent`].
+    resets_sent: ResetsSent,
+    reset_log: LogBudget,
+    /// Receives the data of streams in push mode, see [`Connection::set_inbound_handler`].
+    inbound_handler: Option<InboundHandler>,
+    /// The readers and writers of streams to wake once the current poll
+    /// returns, so that a stream which receives several frames in one poll
+    /// is woken once and the wakers run without the stream's lock held.
+    wakeups: Vec<Waker>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+/// A handler of the data of streams in push mode.
+pub(crate) type InboundHandler = Box<dyn FnMut(StreamId, Vec<u8>) + Send>;
+
+/// Detection of idle connections, see [`Config::set_idle_timeout`].
+struct Idle {
+    timeout: Duration,
+    /// The last time a frame was sent or received.
+    last_activity: Instant,
+    timer: Delay,
+}
+
+impl Idle {
+    fn new(timeout: Duration, time: &Time) -> Self {
+        Idle {
+            timeout,
+            last_activity: time.now(),
+            timer: time.delay(timeout),
+        }
+    }
+}
+
+/// Detection of outbound streams the remote does not acknowledge, see
+/// [`Config::set_stream_open_timeout`], or of inbound streams the application
+/// does not acknowledge, see [`Config::set_inbound_stream_ack_deadline`].
+struct OpenTimeout {
+    timeout: Duration,
+    /// Running as long as there may be unacknowledged streams.
+    timer: Option<Delay>,
+}
+
+/// Coalescing of data frames, see [`Config::set_coalesce_writes`].
+struct Coalesce {
+    max_delay: Duration,
+    max_bytes: usize,
+    /// Started when holding back a pending data frame.
+    timer: Option<Delay>,
+}
+
+impl Coalesce {
+    fn new(max_delay: Duration, max_bytes: usize) -> Self {
+        Coalesce {
+            max_delay,
+            max_bytes,
+            timer: None,
+        }
+    }
+
+    /// Can the given pending frame be extended with more data?
+    fn is_extensible(&self, frame: &Frame<()>) -> bool {
+        let flags = frame.header().flags();
+        frame.header().tag() == Tag::Data
+            && !flags.contains(header::SYN)
+            && !flags.contains(header::FIN)
+            && !flags.contains(header::RST)
+            && !flags.contains(header::COMPRESS_LZ4)
+            && !flags.contains(header::COMPRESS_ZSTD)
+            && (frame.header().len().val() as usize) < self.max_bytes
+    }
+
+    /// Can the given frame be appended to the given pending frame?
+    fn can_append(&self, pending: &Frame<()>, frame: &Frame<Either<Data, WindowUpdate>>) -> bool {
+        let len = pending.header().len().val() as usize + frame.header().len().val() as usize;
+        self.is_extensible(pending)
+            && frame.header().tag() == Tag::Data
+            && frame.header().flags().val() == 0
+            && frame.header().stream_id() == pending.header().stream_id()
+            && len <= self.max_bytes
+    }
 }
 
 /// `Stream` to `Connection` commands.
@@ -361,6 +1011,13 @@ pub(crate) enum StreamCommand {
     SendFrame(Frame<Either<Data, WindowUpdate>>),
     /// Close a stream.
     CloseStream { id: StreamId, ack: bool },
+    /// Keep-alives have been enabled for a stream.
+    KeepAlive,
+    /// All handles of a stream but the connection's have been dropped.
+    Dropped(StreamId),
+    /// A frame of a [`FrameSink`] should be sent to the remote.
+    #[cfg(feature = "frame-sink")]
+    InjectFrame(Frame<()>),
 }
 
 /// Possible actions as a result of incoming frame handling.
@@ -374,10 +1031,19 @@ enum Action {
     Update(Frame<WindowUpdate>),
     /// A ping should be answered.
     Ping(Frame<Ping>),
-    /// A stream should be reset.
-    Reset(Frame<Data>),
-    /// The connection should be terminated.
-    Terminate(Frame<GoAway>),
+    /// A stream should be reset for the given reason.
+    Reset(Frame<Data>, ResetReason),
+    /// The connection should be terminated because of the given error.
+    Terminate(Frame<GoAway>, ProtocolError),
+}
+
+impl Action {
+    /// Terminate the connection because of a protocol violation of the remote,
+    /// triggered by the frame with the given header.
+    fn protocol_error(connection: Id, reason: &'static str, header: impl Into<Header<()>>) -> Self {
+        let error = ProtocolError::new(connection, reason).with_header(header);
+        Action::Terminate(Frame::protocol_error(), error)
+    }
 }
 
 impl<T> fmt::Debug for Active<T> {
@@ -403,117 +1069,787 @@ impl<T> fmt::Display for Active<T> {
     }
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
+impl<T: Transport> Active<T> {
     /// Create a new `Connection` from the given I/O resource.
     fn new(socket: T, cfg: Config, mode: Mode) -> Self {
-        let id = Id::random();
-        log::debug!("new connection: {} ({:?})", id, mode);
-        let (stream_sender, stream_receiver) = mpsc::channel(MAX_COMMAND_BACKLOG);
-        let socket = frame::Io::new(id, socket, cfg.max_buffer_size).fuse();
+        let snapshot = Snapshot::new(cfg, mode);
+        log::debug!("new connection: {} ({:?})", snapshot.id, mode);
+        Active::from_snapshot(socket, snapshot)
+    }
+
+    /// Resume a `Connection` from the given snapshot over the given I/O resource.
+    fn from_snapshot(socket: T, snapshot: Snapshot) -> Self {
+        let Snapshot {
+            id,
+            mode,
+            config: cfg,
+            next_id,
+            streams,
+            stream_sender,
+            stream_receiver,
+            dropped_streams,
+            pending_frames,
+            draining,
+            remote_draining,
+            paused,
+            recycler,
+            extensions,
+            inbound_handler,
+        } = snapshot;
+        let max_frame_body_len = cfg.max_frame_body_len.unwrap_or(cfg.max_buffer_size);
+        let socket = frame::Io::new(id, socket, max_frame_body_len)
+            .with_buffer_pool(cfg.buffer_pool, cfg.split_send_size)
+            .with_write_batch(cfg.write_batch_size)
+            .with_recorder(cfg.frame_recorder.clone())
+            .fuse();
+        let idle = cfg
+            .idle_timeout
+            .map(|timeout| Idle::new(timeout, &cfg.time));
+        let reset_log = LogBudget::new(cfg.reset_log_budget, cfg.time.now());
+        let open_timeout = cfg.stream_open_timeout.map(|timeout| {
+            Box::new(OpenTimeout {
+                timeout,
+                // Streams of a resumed connection may still be unacknowledged.
+                timer: if streams.0.is_empty() {
+                    None
+                } else {
+                    Some(cfg.time.delay(timeout))
+                },
+            })
+        });
+        let ack_deadline = cfg.inbound_stream_ack_deadline.map(|timeout| {
+            Box::new(OpenTimeout {
+                timeout,
+                timer: if streams.0.is_empty() {
+                    None
+                } else {
+                    Some(cfg.time.delay(timeout))
+                },
+            })
+        });
+        let coalesce = cfg
+            .coalesce_writes
+            .map(|(max_delay, max_bytes)| Box::new(Coalesce::new(max_delay, max_bytes)));
+        let rate_limit = cfg.max_send_rate.map(|rate| {
+            let capacity = std::cmp::max(rate, cfg.split_send_size as u64);
+            Box::new(RateLimit::new(rate, capacity, cfg.time.clone()))
+        });
+        let liveness = cfg
+            .liveness_probe
+            .map(|mode| Box::new(Liveness::new(mode, cfg.time.clone())));
+        let receive_budget = cfg.max_connection_receive_window.map(|budget| {
+            let mut budget = ReceiveBudget::new(budget);
+            for stream in streams.0.values() {
+                let mut shared = stream.shared();
+                // Shares of the connection we resume from are void.
+                shared.fair_share = shared
+                    .state()
+                    .can_read()
+                    .then(|| budget.add(shared.receive_window));
+            }
+            Box::new(budget)
+        });
+        #[cfg(feature = "stall-detector")]
+        let stall_detector = cfg.stall_threshold.map(|threshold| {
+            let observer = cfg.stall_observer.clone();
+            Box::new(stall::StallDetector::new(threshold, observer, &cfg.time))
+        });
         Active {
             id,
             mode,
-            config: Arc::new(cfg),
+            config: cfg,
             socket,
-            streams: IntMap::default(),
+            num_outbound: streams.0.values().filter(|s| s.is_outbound()).count(),
+            gc_countdown: 0,
+            streams: streams.into_inner(),
             stream_sender,
             stream_receiver,
-            next_id: match mode {
-                Mode::Client => 1,
-                Mode::Server => 2,
-            },
-            dropped_streams: Vec::new(),
-            pending_frames: VecDeque::default(),
+            next_id,
+            dropped_streams,
+            pending_frames,
+            draining,
+            remote_draining,
+            paused,
+            idle,
+            open_timeout,
+            ack_deadline,
+            keepalive: None,
+            hybrid_fallback: None,
+            open_waker: None,
+            recycler,
+            receive_budget,
+            extensions,
+            coalesce,
+            rate_limit,
+            liveness,
+            #[cfg(feature = "stall-detector")]
+            stall_detector,
+            terminated: None,
+            window_violations: 0,
+            lock_contentions: 0,
+            resets_sent: ResetsSent::default(),
+            reset_log,
+            inbound_handler,
+            wakeups: Vec::new(),
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("connection", conn.id = %id, conn.mode = ?mode),
+        }
+    }
+
+    /// Whether the error is a failure of the transport, rather than of the
+    /// remote, so that the connection could be resumed over a new transport.
+    fn is_transport_failure(&self, e: &ConnectionError) -> bool {
+        match e {
+            ConnectionError::Io(_) | ConnectionError::PeerUnresponsive { .. } => true,
+            ConnectionError::Decode { source, .. } => matches!(source, FrameDecodeError::Io(_)),
+            // The transport reached EOF without the remote going away.
+            ConnectionError::Closed => !self.remote_draining && self.terminated.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Detach the connection from its I/O resource.
+    fn into_snapshot(mut self) -> Result<(T, Snapshot)> {
+        if let Some(e) = self.terminated.take() {
+            self.drop_all_streams(CloseReason::ProtocolError);
+            return Err(ConnectionError::Protocol(*e));
+        }
+        let (socket, unwritten) = self.socket.into_inner().into_parts();
+        for frame in unwritten.into_iter().rev() {
+            self.pending_frames.push_front(frame)
         }
+        let snapshot = Snapshot {
+            id: self.id,
+            mode: self.mode,
+            config: self.config,
+            next_id: self.next_id,
+            streams: snapshot::Streams(std::mem::take(&mut self.streams)),
+            stream_sender: self.stream_sender,
+            stream_receiver: self.stream_receiver,
+            dropped_streams: self.dropped_streams,
+            pending_frames: self.pending_frames,
+            draining: self.draining,
+            remote_draining: self.remote_draining,
+            paused: self.paused,
+            recycler: self.recycler,
+            extensions: self.extensions,
+            inbound_handler: self.inbound_handler,
+        };
+        Ok((socket, snapshot))
     }
 
     /// Gracefully close the connection to the remote.
     fn close(self) -> Closing<T> {
-        Closing::new(self.stream_receiver, self.pending_frames, self.socket)
+        let timeout = self.config.close_timeout.map(|d| self.config.time.delay(d));
+        Closing::new(
+            self.stream_receiver,
+            self.pending_frames,
+            self.socket,
+            self.draining,
+            timeout,
+        )
     }
 
     /// Cleanup all our resources.
     ///
     /// This should be called in the context of an unrecoverable error on the connection.
     fn cleanup(mut self, error: ConnectionError) -> Cleanup {
-        self.drop_all_streams();
+        self.drop_all_streams(CloseReason::from(&error));
 
-        Cleanup::new(self.stream_receiver, error)
+        let mut report = CleanupReport::default();
+        for header in self.socket.get_ref().unwritten_headers() {
+            report.discarded_frames += 1;
+            report.on_frame(&header)
+        }
+        for frame in self.pending_frames.iter() {
+            report.discarded_frames += 1;
+            report.on_frame(frame.header())
+        }
+        Cleanup::new(self.stream_receiver, error, report)
     }
 
     fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<Stream>> {
-        loop {
-            self.garbage_collect();
+        let result = self.poll_frames(cx);
+        for w in self.wakeups.drain(..) {
+            w.wake()
+        }
+        result
+    }
 
-            if self.socket.poll_ready_unpin(cx).is_ready() {
-                if let Some(frame) = self.pending_frames.pop_front() {
-                    self.socket.start_send_unpin(frame)?;
-                    continue;
-                }
+    fn poll_frames(&mut self, cx: &mut Context<'_>) -> Poll<Result<Stream>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
+        // The number of frames sent and received in the current round. Each
+        // direction gets at most `frame_budget` frames per round.
+        let budget = self.config.frame_budget;
+        let mut sent = 0;
+        let mut received = 0;
+        let mut iterations = 0;
+
+        self.poll_stream_open_timeout(cx);
+        self.poll_inbound_ack_deadline(cx);
+        self.poll_stream_keepalive(cx);
+        #[cfg(feature = "stall-detector")]
+        if let Some(detector) = self.stall_detector.as_mut() {
+            detector.poll(cx, self.id, self.streams.values(), &self.config.time)
+        }
+
+        loop {
+            // Do not starve other tasks of the executor.
+            if iterations == self.config.poll_budget {
+                log::trace!("{}: poll budget exhausted, yielding", self.id);
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
             }
+            iterations += 1;
 
-            match self.socket.poll_flush_unpin(cx)? {
-                Poll::Ready(()) => {}
-                Poll::Pending => {}
+            // Dropped streams are removed on their `StreamCommand::Dropped`.
+            // As a fallback, all streams are scanned once per as many
+            // iterations as there are streams, i.e. at amortised constant cost.
+            if self.gc_countdown == 0 {
+                self.garbage_collect();
+                self.gc_countdown = self.streams.len()
+            } else {
+                self.gc_countdown -= 1
             }
 
-            match self.stream_receiver.poll_next_unpin(cx) {
-                Poll::Ready(Some(StreamCommand::SendFrame(frame))) => {
-                    self.on_send_frame(frame);
-                    continue;
-                }
-                Poll::Ready(Some(StreamCommand::CloseStream { id, ack })) => {
-                    self.on_close_stream(id, ack);
+            if sent < budget
+                && self.socket.poll_ready_unpin(cx).is_ready()
+                && !self.hold_back_frame(cx)
+                && !self.is_rate_limited(cx)
+            {
+                if let Some(mut frame) = self.pending_frames.pop_front() {
+                    let local = self.config.advertised_extensions();
+                    self.extensions.advertise(local, frame.header_mut());
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        stream.id = frame.header().stream_id().val(),
+                        frame.tag = ?frame.header().tag(),
+                        frame.flags = frame.header().flags().val(),
+                        frame.len = frame.header().len().val(),
+                        "frame sent"
+                    );
+                    // Keep-alive pings do not keep an idle connection open.
+                    let is_ping = frame.header().tag() == Tag::Ping
+                        && frame.header().flags().contains(header::SYN);
+                    self.socket.start_send_unpin(frame)?;
+                    if !is_ping {
+                        self.on_activity();
+                    }
+                    sent += 1;
                     continue;
                 }
-                Poll::Ready(None) => {
-                    debug_assert!(false, "Only closed during shutdown")
-                }
-                Poll::Pending => {}
             }
 
-            match self.socket.poll_next_unpin(cx) {
-                Poll::Ready(Some(frame)) => {
-                    if let Some(stream) = self.on_frame(frame?)? {
-                        return Poll::Ready(Ok(stream));
+            match self.socket.poll_flush_unpin(cx)? {
+                Poll::Ready(()) => {
+                    if let Some(liveness) = self.liveness.as_mut() {
+                        liveness.on_write_unblocked()
+                    }
+                    if self.pending_frames.is_empty() {
+                        if let Some(e) = self.terminated.take() {
+                            return Poll::Ready(Err(ConnectionError::Protocol(*e)));
+                        }
                     }
-                    continue;
                 }
-                Poll::Ready(None) => {
-                    return Poll::Ready(Err(ConnectionError::Closed));
+                Poll::Pending => {
+                    if let Some(liveness) = self.liveness.as_mut() {
+                        liveness.on_write_blocked()
+                    }
                 }
-                Poll::Pending => {}
             }
 
-            // If we make it this far, at least one of the above must have registered a waker.
-            return Poll::Pending;
-        }
-    }
+            // Commands are only accepted while their frames can be sent.
+            if sent < budget {
+                match self.stream_receiver.poll_next_unpin(cx) {
+                    Poll::Ready(Some(StreamCommand::SendFrame(frame))) => {
+                        let id = frame.header().stream_id();
+                        let is_syn = frame.header().flags().contains(header::SYN);
+                        self.on_send_frame(frame);
+                        if is_syn {
+                            self.on_syn_sent(id, cx)
+                        }
+                        continue;
+                    }
+                    Poll::Ready(Some(StreamCommand::CloseStream { id, ack })) => {
+                        self.on_close_stream(id, ack);
+                        continue;
+                    }
+                    Poll::Ready(Some(StreamCommand::KeepAlive)) => {
+                        self.keepalive = Some(self.config.time.delay(Duration::ZERO));
+                        self.poll_stream_keepalive(cx);
+                        continue;
+                    }
+                    Poll::Ready(Some(StreamCommand::Dropped(id))) => {
+                        self.on_stream_dropped(id);
+                        continue;
+                    }
+                    #[cfg(feature = "frame-sink")]
+                    Poll::Ready(Some(StreamCommand::InjectFrame(frame))) => {
+                        self.pending_frames.push_back(frame);
+                        continue;
+                    }
+                    Poll::Ready(None) => {
+                        // We hold a sender ourselves, so the channel has been
+                        // closed, which only happens when shutting down.
+                        log::debug!("{}: stream command channel closed", self.id);
+                        return Poll::Ready(Err(ConnectionError::Closed));
+                    }
+                    Poll::Pending => {
+                        // All commands of garbage collected streams have been processed.
+                        if self.release_dropped_ids() {
+                            continue;
+                        }
+                    }
+                }
+            }
 
-    fn new_outbound(&mut self) -> Result<Stream> {
-        if self.streams.len() >= self.config.max_num_streams {
-            log::error!("{}: maximum number of streams reached", self.id);
+            // Once terminated, further inbound frames are of no interest.
+            if self.terminated.is_none() && received < budget {
+                match self.socket.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(frame))) => {
+                        received += 1;
+                        if let Some(stream) = self.on_frame(frame)? {
+                            return Poll::Ready(Ok(stream));
+                        }
+                        continue;
+                    }
+                    Poll::Ready(Some(Err(FrameDecodeError::FrameTooLarge(len)))) => {
+                        log::error!("{}: frame body too large ({} bytes)", self.id, len);
+                        let e = ProtocolError::new(self.id, "frame body too large");
+                        self.pending_frames
+                            .push_back(Frame::protocol_error().into());
+                        self.terminated = Some(Box::new(e));
+                        continue;
+                    }
+                    Poll::Ready(Some(Err(source))) => {
+                        return Poll::Ready(Err(ConnectionError::Decode {
+                            connection: self.id,
+                            source,
+                        }));
+                    }
+                    Poll::Ready(None) => {
+                        return Poll::Ready(Err(ConnectionError::Closed));
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            // A direction exhausted its budget, start the next round.
+            if sent >= budget || received >= budget {
+                sent = 0;
+                received = 0;
+                continue;
+            }
+
+            // The deadlines of the liveness probe may have changed in this round.
+            if self.poll_liveness(cx)? {
+                continue;
+            }
+
+            // The fallback of `WindowUpdateMode::Hybrid` may have been armed in this round.
+            if self.poll_hybrid_fallback(cx) {
+                continue;
+            }
+
+            // If we make it this far, at least one of the above must have registered a waker.
+            return Poll::Pending;
+        }
+    }
+
+    /// Drain the connection until all streams are closed.
+    ///
+    /// Upon first invocation a `GoAway` frame is enqueued, after which inbound
+    /// streams are reset.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if !self.draining {
+            log::debug!("{}: draining", self.id);
+            self.draining = true;
+            self.pending_frames.push_back(Frame::term().into());
+        }
+
+        match self.poll(cx) {
+            Poll::Ready(Ok(_)) => unreachable!("no inbound streams are accepted while draining"),
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => {}
+        }
+
+        if self
+            .streams
+            .values()
+            .all(|s| s.shared().state() == State::Closed)
+        {
+            log::debug!("{}: drained", self.id);
+            return Poll::Ready(Ok(()));
+        }
+
+        Poll::Pending
+    }
+
+    /// Poll whether the connection has been idle, i.e. without any streams and
+    /// without any frames sent or received, for longer than the idle timeout.
+    ///
+    /// Never ready if no idle timeout is configured.
+    fn poll_idle_timeout(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let idle = match self.idle.as_mut() {
+            Some(idle) => idle,
+            None => return Poll::Pending,
+        };
+        loop {
+            ready!(idle.timer.poll_unpin(cx));
+            let elapsed = self.config.time.elapsed(idle.last_activity);
+            if !self.streams.is_empty() {
+                idle.timer.restart(idle.timeout);
+            } else if elapsed < idle.timeout {
+                idle.timer.restart(idle.timeout - elapsed);
+            } else {
+                return Poll::Ready(());
+            }
+        }
+    }
+
+    /// Reset the outbound streams which the remote has not acknowledged
+    /// within the stream open timeout, if any.
+    fn poll_stream_open_timeout(&mut self, cx: &mut Context<'_>) {
+        loop {
+            let t = match self.open_timeout.as_mut() {
+                Some(t) => t,
+                None => return,
+            };
+            let timeout = t.timeout;
+            match t.timer.as_mut() {
+                Some(timer) => {
+                    if timer.poll_unpin(cx).is_pending() {
+                        return;
+                    }
+                }
+                None => return,
+            }
+            let mut expired = Vec::new();
+            let mut next: Option<Duration> = None;
+            for (id, stream) in &self.streams {
+                let shared = stream.shared();
+                if shared.acknowledged || shared.state() == State::Closed {
+                    continue;
+                }
+                // The remote cannot acknowledge a lazily opened stream before
+                // its SYN has been sent.
+                let syn_sent = match shared.syn_sent {
+                    Some(syn_sent) => syn_sent,
+                    None => continue,
+                };
+                let elapsed = self.config.time.elapsed(syn_sent);
+                if elapsed >= timeout {
+                    expired.push(*id)
+                } else {
+                    next = Some(next.map_or(timeout - elapsed, |n| n.min(timeout - elapsed)))
+                }
+            }
+            for id in expired {
+                log::debug!("{}/{}: stream not acknowledged in time", self.id, id);
+                self.reset_stream(id, Reset::OpenTimeout);
+            }
+            let t = self.open_timeout.as_mut().expect("open timeout");
+            match (next, t.timer.as_mut()) {
+                (Some(d), Some(timer)) => timer.restart(d),
+                _ => t.timer = None,
+            }
+        }
+    }
+
+    /// Acknowledge or reset the inbound streams which the application has not
+    /// acknowledged within the deadline, if any.
+    fn poll_inbound_ack_deadline(&mut self, cx: &mut Context<'_>) {
+        loop {
+            let t = match self.ack_deadline.as_mut() {
+                Some(t) => t,
+                None => return,
+            };
+            let timeout = t.timeout;
+            match t.timer.as_mut() {
+                Some(timer) => {
+                    if timer.poll_unpin(cx).is_pending() {
+                        return;
+                    }
+                }
+                None => return,
+            }
+            let mut expired = Vec::new();
+            let mut next: Option<Duration> = None;
+            for (id, stream) in &self.streams {
+                if stream.is_outbound() || !stream.shared().ack_pending() {
+                    continue;
+                }
+                let elapsed = self.config.time.elapsed(stream.shared().created);
+                if elapsed >= timeout {
+                    expired.push(*id)
+                } else {
+                    next = Some(next.map_or(timeout - elapsed, |n| n.min(timeout - elapsed)))
+                }
+            }
+            for id in expired {
+                match self.config.ack_deadline_response {
+                    AckDeadlineResponse::Ack => {
+                        // The ACK may have been sent since, along with a frame of the stream.
+                        if self.streams[&id].shared().take_ack() {
+                            log::debug!("{}/{}: acknowledging stream on deadline", self.id, id);
+                            let mut frame = Frame::window_update(id, 0);
+                            frame.header_mut().ack();
+                            self.streams[&id]
+                                .shared()
+                                .announce_compression(frame.header_mut());
+                            self.pending_frames.push_back(frame.into());
+                        }
+                    }
+                    AckDeadlineResponse::Reset => {
+                        self.reset_stream(id, Reset::AckDeadline);
+                    }
+                }
+            }
+            let t = self.ack_deadline.as_mut().expect("ack deadline");
+            match (next, t.timer.as_mut()) {
+                (Some(d), Some(timer)) => timer.restart(d),
+                _ => t.timer = None,
+            }
+        }
+    }
+
+    /// Send an empty data frame over each stream which has been idle for
+    /// its keep-alive interval, if any.
+    fn poll_stream_keepalive(&mut self, cx: &mut Context<'_>) {
+        loop {
+            match self.keepalive.as_mut() {
+                Some(timer) => {
+                    if timer.poll_unpin(cx).is_pending() {
+                        return;
+                    }
+                }
+                None => return,
+            }
+            let mut next: Option<Duration> = None;
+            for (id, stream) in &self.streams {
+                let mut shared = stream.shared();
+                let due = match shared.next_keepalive() {
+                    Some(due) => due,
+                    None => continue,
+                };
+                let due = if due.is_zero() {
+                    log::trace!("{}/{}: sending keep-alive", self.id, id);
+                    let mut frame = Frame::data(*id, Vec::new()).expect("empty body").left();
+                    shared.add_flag(frame.header_mut());
+                    shared.on_sent(0);
+                    self.pending_frames.push_back(frame.into());
+                    shared.keepalive().expect("keep-alive interval")
+                } else {
+                    due
+                };
+                next = Some(next.map_or(due, |n| n.min(due)))
+            }
+            match (next, self.keepalive.as_mut()) {
+                (Some(d), Some(timer)) => timer.restart(d),
+                _ => self.keepalive = None,
+            }
+        }
+    }
+
+    /// Grant credit on behalf of the streams whose receive window is exhausted
+    /// while they are not being read, given [`WindowUpdateMode::Hybrid`].
+    ///
+    /// Once the window is exhausted no more data arrives, i.e. `on_data` does
+    /// not check again if the stream's reader was woken but does not read.
+    ///
+    /// Returns whether a window update has been enqueued.
+    fn poll_hybrid_fallback(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut enqueued = false;
+        loop {
+            match self.hybrid_fallback.as_mut() {
+                Some(timer) => {
+                    if timer.poll_unpin(cx).is_pending() {
+                        return enqueued;
+                    }
+                }
+                None => return enqueued,
+            }
+            let mut again = false;
+            for (id, stream) in &self.streams {
+                let mut shared = stream.shared();
+                if shared.window != 0 || !shared.state().can_read() {
+                    continue;
+                }
+                if shared.reader.is_some() && !shared.window_update_blocked {
+                    // The stream is about to send a window update itself.
+                    again = true;
+                    continue;
+                }
+                if let Some(credit) = shared.next_fallback_window_update(stream.buffer().len()) {
+                    log::debug!(
+                        "{}/{}: stream is not being read, sending window update",
+                        self.id,
+                        id
+                    );
+                    shared.window += credit;
+                    self.pending_frames
+                        .push_back(Frame::window_update(*id, credit).into());
+                    enqueued = true;
+                }
+            }
+            match (again, self.hybrid_fallback.as_mut()) {
+                (true, Some(timer)) => timer.restart(HYBRID_FALLBACK_DELAY),
+                _ => self.hybrid_fallback = None,
+            }
+        }
+    }
+
+    /// Send a keep-alive ping or fail if the remote is unresponsive, as
+    /// configured via [`Config::set_liveness_probe`].
+    ///
+    /// Returns whether a ping has been enqueued.
+    fn poll_liveness(&mut self, cx: &mut Context<'_>) -> Result<bool> {
+        let liveness = match self.liveness.as_mut() {
+            Some(liveness) => liveness,
+            None => return Ok(false),
+        };
+        match liveness.poll(cx) {
+            Poll::Ready(Probe::Ping(frame)) => {
+                log::trace!("{}: sending keep-alive ping", self.id);
+                // Pings do not wait for frames which are already pending.
+                self.pending_frames.push_front(frame.into());
+                Ok(true)
+            }
+            Poll::Ready(Probe::Unresponsive {
+                ping_unanswered,
+                write_blocked,
+            }) => {
+                log::debug!("{}: remote is unresponsive", self.id);
+                Err(ConnectionError::PeerUnresponsive {
+                    connection: self.id,
+                    ping_unanswered,
+                    write_blocked,
+                })
+            }
+            Poll::Pending => Ok(false),
+        }
+    }
+
+    /// Whether the given extensions have been negotiated with the remote, see
+    /// [`Config::set_extensions`].
+    fn is_negotiated(&self, extensions: Extensions) -> bool {
+        self.extensions
+            .negotiated()
+            .is_some_and(|negotiated| negotiated.contains(extensions))
+    }
+
+    fn on_activity(&mut self) {
+        if let Some(idle) = self.idle.as_mut() {
+            idle.last_activity = self.config.time.now();
+        }
+    }
+
+    fn update_config(&mut self, update: &ConfigUpdate) {
+        log::debug!("{}: updating config: {:?}", self.id, update);
+        // Existing streams keep sharing the previous configuration.
+        let mut cfg = Config::clone(&self.config);
+        update.apply(&mut cfg);
+        if let Some(paused) = update.paused() {
+            self.paused = paused
+        }
+        // The limits of outbound streams may have been lifted.
+        if let Some(w) = self.open_waker.take() {
+            w.wake()
+        }
+        if update.changes_max_send_rate() {
+            self.rate_limit = cfg.max_send_rate.map(|rate| {
+                let capacity = std::cmp::max(rate, cfg.split_send_size as u64);
+                Box::new(RateLimit::new(rate, capacity, cfg.time.clone()))
+            });
+        }
+        self.config = Arc::new(cfg);
+    }
+
+    /// See [`Connection::poll_ready_outbound`].
+    fn poll_ready_outbound(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if !self.accepts_new_streams() {
+            return Poll::Ready(Err(ConnectionError::Closed));
+        }
+        if self.remaining_stream_ids() == 0 {
+            return Poll::Ready(Err(ConnectionError::NoMoreStreamIds));
+        }
+        if self.paused || self.max_outbound_streams_reached() || self.ack_backlog_exceeded() {
+            log::trace!("{}: not ready to open a stream", self.id);
+            self.open_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Whether the max. number of streams or of outbound streams is reached.
+    fn max_outbound_streams_reached(&self) -> bool {
+        self.streams.len() >= self.config.max_num_streams
+            || self
+                .config
+                .max_outbound_streams
+                .is_some_and(|max| self.num_outbound >= max)
+    }
+
+    /// Can new streams be opened on this connection?
+    fn accepts_new_streams(&self) -> bool {
+        !self.draining && !self.remote_draining
+    }
+
+    /// Whether the max. number of unacknowledged outbound streams is reached.
+    fn ack_backlog_exceeded(&self) -> bool {
+        let max = match self.config.max_ack_backlog {
+            Some(max) => max,
+            None => return false,
+        };
+        let backlog = self
+            .streams
+            .values()
+            .filter(|s| s.is_outbound() && s.shared().awaits_ack())
+            .count();
+        backlog >= max
+    }
+
+    fn new_outbound(
+        &mut self,
+        receive_window: Option<u32>,
+        mode: Option<OpenMode>,
+    ) -> Result<Stream> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
+        if self.max_outbound_streams_reached() {
+            log::error!("{}: maximum number of outbound streams reached", self.id);
+            return Err(ConnectionError::TooManyStreams);
+        }
+
+        if self.paused {
+            log::debug!("{}: paused, not opening a new stream", self.id);
             return Err(ConnectionError::TooManyStreams);
         }
 
         log::trace!("{}: creating new outbound stream", self.id);
 
         let id = self.next_stream_id()?;
-        let extra_credit = self.config.receive_window - DEFAULT_CREDIT;
-
-        if extra_credit > 0 {
-            let mut frame = Frame::window_update(id, extra_credit);
-            frame.header_mut().syn();
-            log::trace!("{}/{}: sending initial {}", self.id, id, frame.header());
-            self.pending_frames.push_back(frame.into());
-        }
+        let receive_window = receive_window.unwrap_or(self.config.receive_window);
+        let mode = mode.unwrap_or(self.config.open_mode);
 
         let stream = {
             let config = self.config.clone();
             let sender = self.stream_sender.clone();
-            let window = self.config.receive_window;
-            let mut stream = Stream::new(id, self.id, config, window, DEFAULT_CREDIT, sender);
-            if extra_credit == 0 {
+            let mut stream = Stream::new(
+                id,
+                self.id,
+                config,
+                DEFAULT_CREDIT,
+                DEFAULT_CREDIT,
+                sender,
+                true,
+            );
+            stream.shared().receive_window = receive_window;
+            stream.shared().compression_offered = self.is_negotiated(Extensions::COMPRESSION);
+            if mode == OpenMode::Lazy {
                 stream.set_flag(stream::Flag::Syn)
             }
             stream
@@ -521,20 +1857,132 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
 
         log::debug!("{}: new outbound {} of {}", self.id, stream, self);
         self.streams.insert(id, stream.clone());
+        self.num_outbound += 1;
+        self.claim_receive_budget(&stream);
+
+        // A lazily opened stream starts out with the default window, any
+        // extra credit is granted by a later window update.
+        if mode == OpenMode::Eager {
+            let mut shared = stream.shared();
+            let credit = shared
+                .effective_receive_window()
+                .saturating_sub(DEFAULT_CREDIT);
+            shared.window += credit;
+            shared.syn_sent = Some(self.config.time.now());
+            let mut frame = Frame::window_update(id, credit);
+            frame.header_mut().syn();
+            shared.announce_compression(frame.header_mut());
+            log::trace!("{}/{}: sending initial {}", self.id, id, frame.header());
+            self.pending_frames.push_back(frame.into());
+            drop(shared);
+            self.arm_open_timeout();
+        }
 
         Ok(stream)
     }
 
     fn on_send_frame(&mut self, frame: Frame<Either<Data, WindowUpdate>>) {
-        log::trace!(
-            "{}/{}: sending: {}",
-            self.id,
-            frame.header().stream_id(),
-            frame.header()
-        );
+        let id = frame.header().stream_id();
+        if self.streams.get(&id).is_some_and(|s| s.shared().is_reset()) {
+            // Written before the reset, which discards queued frames likewise.
+            log::trace!("{}/{}: discarding frame of reset stream", self.id, id);
+            return;
+        }
+        if frame.header().flags().contains(header::STOP_SENDING)
+            && !self.is_negotiated(Extensions::STOP_SENDING)
+        {
+            // The remote would ignore the flag and keep sending.
+            log::debug!("{}/{}: stop sending is not negotiated", self.id, id);
+            self.reset_stream(id, Reset::StopSending);
+            return;
+        }
+        log::trace!("{}/{}: sending: {}", self.id, id, frame.header());
+        if let (Some(coalesce), Some(pending)) =
+            (self.coalesce.as_ref(), self.pending_frames.last_data_mut())
+        {
+            if coalesce.can_append(pending, &frame) {
+                pending.append(frame);
+                return;
+            }
+        }
         self.pending_frames.push_back(frame.into());
     }
 
+    /// Start the stream open timeout and arm the keep-alive timer once a
+    /// lazily opened stream sent its SYN, as the latter has been disarmed if
+    /// no other stream has keep-alives.
+    fn on_syn_sent(&mut self, id: StreamId, cx: &mut Context<'_>) {
+        let has_keepalive = match self.streams.get(&id) {
+            Some(stream) => {
+                let mut shared = stream.shared();
+                shared.syn_sent = Some(self.config.time.now());
+                shared.keepalive().is_some()
+            }
+            None => return,
+        };
+        if self.open_timeout.is_some() {
+            self.arm_open_timeout();
+            self.poll_stream_open_timeout(cx);
+        }
+        if has_keepalive && self.keepalive.is_none() {
+            self.keepalive = Some(self.config.time.delay(Duration::ZERO));
+            self.poll_stream_keepalive(cx);
+        }
+    }
+
+    /// Start the timer of the stream open timeout, unless it is running.
+    fn arm_open_timeout(&mut self) {
+        if let Some(t) = self.open_timeout.as_mut() {
+            if t.timer.is_none() {
+                t.timer = Some(self.config.time.delay(t.timeout))
+            }
+        }
+    }
+
+    /// Whether to hold back sending the next pending frame, in order to
+    /// coalesce it with subsequent writes.
+    fn hold_back_frame(&mut self, cx: &mut Context<'_>) -> bool {
+        let coalesce = match self.coalesce.as_mut() {
+            Some(coalesce) => coalesce,
+            None => return false,
+        };
+        let hold_back = self.pending_frames.len() == 1
+            && self
+                .pending_frames
+                .front()
+                .is_some_and(|f| coalesce.is_extensible(f));
+        if !hold_back {
+            coalesce.timer = None;
+            return false;
+        }
+        let max_delay = coalesce.max_delay;
+        let time = &self.config.time;
+        let timer = coalesce.timer.get_or_insert_with(|| time.delay(max_delay));
+        if timer.poll_unpin(cx).is_ready() {
+            coalesce.timer = None;
+            return false;
+        }
+        true
+    }
+
+    /// Whether sending the next pending frame has to wait for the send rate
+    /// limit. Otherwise the frame is accounted for, as it is sent right away.
+    fn is_rate_limited(&mut self, cx: &mut Context<'_>) -> bool {
+        let rate_limit = match self.rate_limit.as_mut() {
+            Some(rate_limit) => rate_limit,
+            None => return false,
+        };
+        let len = match self.pending_frames.front() {
+            Some(frame) if frame.header().tag() == Tag::Data => frame.header().len().val(),
+            _ => return false,
+        };
+        if rate_limit.poll_take(u64::from(len), cx).is_pending() {
+            log::trace!("{}: send rate limited", self.id);
+            return true;
+        }
+        false
+    }
+
     fn on_close_stream(&mut self, id: StreamId, ack: bool) {
         log::trace!("{}/{}: sending close", self.id, id);
         self.pending_frames
@@ -549,11 +1997,62 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
     /// if one was opened by the remote.
     fn on_frame(&mut self, frame: Frame<()>) -> Result<Option<Stream>> {
         log::trace!("{}: received: {}", self.id, frame.header());
-        let action = match frame.header().tag() {
-            Tag::Data => self.on_data(frame.into_data()),
-            Tag::WindowUpdate => self.on_window_update(&frame.into_window_update()),
-            Tag::Ping => self.on_ping(&frame.into_ping()),
-            Tag::GoAway => return Err(ConnectionError::Closed),
+        // Pongs of keep-alive pings do not keep an idle connection open.
+        let is_pong =
+            frame.header().tag() == Tag::Ping && frame.header().flags().contains(header::ACK);
+        if !is_pong {
+            self.on_activity();
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            stream.id = frame.header().stream_id().val(),
+            frame.tag = ?frame.header().tag(),
+            frame.flags = frame.header().flags().val(),
+            frame.len = frame.header().len().val(),
+            "frame received"
+        );
+        if let Some(negotiated) = self
+            .extensions
+            .on_received(self.config.advertised_extensions(), frame.header())
+        {
+            log::debug!("{}: negotiated extensions: {:?}", self.id, negotiated);
+        }
+        let action = match inbound::classify(self.mode, frame.header()) {
+            Ok(Inbound::Ping(nonce)) => self.on_ping(frame.header().stream_id(), nonce),
+            Ok(Inbound::Pong(nonce)) => {
+                if let Some(liveness) = self.liveness.as_mut() {
+                    liveness.on_pong(nonce)
+                }
+                Action::None
+            }
+            Ok(Inbound::GoAway(code)) => {
+                if code != 0 {
+                    log::debug!("{}: remote terminated with error {}", self.id, code);
+                    for s in self.streams.values() {
+                        let mut shared = s.shared();
+                        shared.go_away = Some(code);
+                        shared.wake_all();
+                    }
+                    return Err(ConnectionError::GoAwayReceived {
+                        connection: self.id,
+                        code,
+                    });
+                }
+                // A normal termination only means that the remote no longer
+                // accepts new streams. Existing streams remain usable until the
+                // remote closes the connection.
+                log::debug!("{}: remote is going away", self.id);
+                self.remote_draining = true;
+                Action::None
+            }
+            Ok(inbound) if frame.header().tag() == Tag::Data => {
+                self.on_data(inbound, frame.into_data())
+            }
+            Ok(inbound) => self.on_window_update(inbound, &frame.into_window_update()),
+            Err(reason) => {
+                log::error!("{}: {}: {}", self.id, reason, frame.header());
+                Action::protocol_error(self.id, reason, frame.header().clone())
+            }
         };
         match action {
             Action::None => {}
@@ -563,6 +2062,11 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
                     log::trace!("{}/{}: sending update", self.id, f.header().stream_id());
                     self.pending_frames.push_back(f.into());
                 }
+                if let Some(t) = self.ack_deadline.as_mut() {
+                    if t.timer.is_none() {
+                        t.timer = Some(self.config.time.delay(t.timeout))
+                    }
+                }
                 return Ok(Some(stream));
             }
             Action::Update(f) => {
@@ -573,81 +2077,99 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
                 log::trace!("{}/{}: pong", self.id, f.header().stream_id());
                 self.pending_frames.push_back(f.into());
             }
-            Action::Reset(f) => {
-                log::trace!("{}/{}: sending reset", self.id, f.header().stream_id());
+            Action::Reset(f, reason) => {
+                self.on_reset_sent(f.header().stream_id(), reason);
                 self.pending_frames.push_back(f.into());
             }
-            Action::Terminate(f) => {
+            Action::Terminate(f, e) => {
                 log::trace!("{}: sending term", self.id);
                 self.pending_frames.push_back(f.into());
+                self.terminated = Some(Box::new(e));
             }
         }
 
         Ok(None)
     }
 
-    fn on_data(&mut self, frame: Frame<Data>) -> Action {
+    fn on_data(&mut self, inbound: Inbound, mut frame: Frame<Data>) -> Action {
         let stream_id = frame.header().stream_id();
 
-        if frame.header().flags().contains(header::RST) {
-            // stream reset
-            if let Some(s) = self.streams.get_mut(&stream_id) {
-                let mut shared = s.shared();
-                shared.update_state(self.id, stream_id, State::Closed);
-                if let Some(w) = shared.reader.take() {
-                    w.wake()
-                }
-                if let Some(w) = shared.writer.take() {
-                    w.wake()
-                }
-            }
+        if let Inbound::Reset(_) = inbound {
+            self.on_reset_received(stream_id);
             return Action::None;
         }
 
         let is_finish = frame.header().flags().contains(header::FIN); // half-close
 
-        if frame.header().flags().contains(header::SYN) {
-            // new stream
-            if !self.is_valid_remote_id(stream_id, Tag::Data) {
-                log::error!("{}: invalid stream id {}", self.id, stream_id);
-                return Action::Terminate(Frame::protocol_error());
-            }
+        if let Inbound::Open(_) = inbound {
             if frame.body().len() > DEFAULT_CREDIT as usize {
                 log::error!(
                     "{}/{}: 1st body of stream exceeds default credit",
                     self.id,
                     stream_id
                 );
-                return Action::Terminate(Frame::protocol_error());
+                return Action::protocol_error(
+                    self.id,
+                    "1st body of stream exceeds default credit",
+                    frame.header().clone(),
+                );
             }
             if self.streams.contains_key(&stream_id) {
                 log::error!("{}/{}: stream already exists", self.id, stream_id);
-                return Action::Terminate(Frame::protocol_error());
+                return Action::protocol_error(
+                    self.id,
+                    "stream already exists",
+                    frame.header().clone(),
+                );
+            }
+            if self.draining || self.paused {
+                let mut header = Header::data(stream_id, 0);
+                header.rst();
+                return Action::Reset(Frame::new(header), ResetReason::NotAccepting);
             }
-            if self.streams.len() == self.config.max_num_streams {
-                log::error!("{}: maximum number of streams reached", self.id);
-                return Action::Terminate(Frame::internal_error());
+            if let Some(action) = self
+                .reject_if_too_many_streams(frame.header().clone().into(), Frame::internal_error())
+            {
+                return action;
+            }
+            if let Some(action) = self.reject_by_policy(frame.header().clone().into()) {
+                return action;
             }
             let mut stream = {
                 let config = self.config.clone();
                 let credit = DEFAULT_CREDIT;
                 let sender = self.stream_sender.clone();
-                Stream::new(stream_id, self.id, config, credit, credit, sender)
+                Stream::new(stream_id, self.id, config, credit, credit, sender, false)
             };
+            self.streams.insert(stream_id, stream.clone());
+            self.claim_receive_budget(&stream);
             let mut window_update = None;
             {
                 let mut shared = stream.shared();
+                if self.config.compression.is_offered(frame.header().flags())
+                    && self.is_negotiated(Extensions::COMPRESSION)
+                {
+                    shared.compression = self.config.compression
+                }
                 if is_finish {
                     shared.update_state(self.id, stream_id, State::RecvClosed);
                 }
                 shared.window = shared.window.saturating_sub(frame.body_len());
-                shared.buffer.push(frame.into_body());
+                stream.on_received(&mut shared, frame.body_len());
+                let buffered = {
+                    let mut buffer = stream.buffer();
+                    if let Some(body) = buffer.push_body(frame.into_body()) {
+                        self.socket.get_mut().recycle(body)
+                    }
+                    buffer.len()
+                };
 
                 if matches!(self.config.window_update_mode, WindowUpdateMode::OnReceive) {
-                    if let Some(credit) = shared.next_window_update() {
+                    if let Some(credit) = shared.next_window_update(buffered) {
                         shared.window += credit;
                         let mut frame = Frame::window_update(stream_id, credit);
                         frame.header_mut().ack();
+                        shared.announce_compression(frame.header_mut());
                         window_update = Some(frame)
                     }
                 }
@@ -655,45 +2177,199 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
             if window_update.is_none() {
                 stream.set_flag(stream::Flag::Ack)
             }
-            self.streams.insert(stream_id, stream.clone());
             return Action::New(stream, window_update);
         }
 
         if let Some(stream) = self.streams.get_mut(&stream_id) {
             let mut shared = stream.shared();
-            if frame.body().len() > shared.window as usize {
+            let flags = frame.header().flags();
+            if flags.contains(header::ACK) {
+                shared.on_ack();
+                if let Some(w) = self.open_waker.take() {
+                    w.wake()
+                }
+                if shared.compression_offered && self.config.compression.is_offered(flags) {
+                    shared.compression = self.config.compression
+                }
+            } else if flags.contains(header::COMPRESS_LZ4) || flags.contains(header::COMPRESS_ZSTD)
+            {
+                // The decompressed body must fit into the window, as credit is
+                // accounted in uncompressed bytes.
+                let body = shared
+                    .compression
+                    .flag()
+                    .filter(|flag| flags.contains(*flag))
+                    .and_then(|_| {
+                        let max_len = shared.window as usize;
+                        shared.compression.decompress(frame.body(), max_len)
+                    });
+                match body {
+                    Some(body) => frame.set_body(body),
+                    None => {
+                        log::error!("{}/{}: failed to decompress data frame", self.id, stream_id);
+                        return Action::protocol_error(
+                            self.id,
+                            "failed to decompress data frame",
+                            frame.header().clone(),
+                        );
+                    }
+                }
+            }
+            if flow::consume(shared.window, frame.body_len()).is_none() {
                 log::error!(
                     "{}/{}: frame body larger than window of stream",
                     self.id,
                     stream_id
                 );
-                return Action::Terminate(Frame::protocol_error());
+                match self.config.window_violation_policy {
+                    WindowViolationPolicy::GoAway => {
+                        return Action::protocol_error(
+                            self.id,
+                            "frame body larger than window of stream",
+                            frame.header().clone(),
+                        )
+                    }
+                    WindowViolationPolicy::Reset => {
+                        // Frames in flight after the reset are discarded below.
+                        if shared.state() == State::Closed {
+                            return Action::None;
+                        }
+                        self.window_violations += 1;
+                        stream.reset(&mut shared, Reset::WindowViolation);
+                        let mut header = Header::data(stream_id, 0);
+                        header.rst();
+                        return Action::Reset(Frame::new(header), ResetReason::WindowViolation);
+                    }
+                }
+            }
+            if !shared.state().can_read() {
+                // Frames may still be in flight after we stopped reading, the
+                // stream was reset or closed in both directions, otherwise the
+                // remote half-closed it before. Empty frames, e.g. a repeated
+                // FIN, are harmless.
+                if shared.stopped_reading
+                    || shared.is_reset()
+                    || shared.state() == State::Closed
+                    || frame.body_len() == 0
+                {
+                    log::trace!(
+                        "{}/{}: discarding data, stream can no longer be read",
+                        self.id,
+                        stream_id
+                    );
+                    shared.window = shared.window.saturating_sub(frame.body_len());
+                    return Action::None;
+                }
+                log::debug!("{}/{}: data after fin", self.id, stream_id);
+                stream.reset(&mut shared, Reset::DataAfterFin);
+                let mut header = Header::data(stream_id, 0);
+                header.rst();
+                return Action::Reset(Frame::new(header), ResetReason::DataAfterFin);
             }
             if is_finish {
                 shared.update_state(self.id, stream_id, State::RecvClosed);
             }
+            if shared.push_mode && self.inbound_handler.is_some() {
+                shared.window = shared.window.saturating_sub(frame.body_len());
+                stream.on_received(&mut shared, frame.body_len());
+                if is_finish {
+                    shared.wake_reader();
+                }
+                // The data has been consumed, grant new credit right away.
+                let update = shared
+                    .next_window_update(stream.buffer().len())
+                    .map(|credit| {
+                        shared.window += credit;
+                        Frame::window_update(stream_id, credit)
+                    });
+                // The handler may access the stream, so it must not be called
+                // while holding its lock.
+                drop(shared);
+                if let (true, Some(handler)) = (frame.body_len() > 0, self.inbound_handler.as_mut())
+                {
+                    handler(stream_id, frame.into_body());
+                }
+                return update.map_or(Action::None, Action::Update);
+            }
+            let mut buffer = stream.buffer();
             let max_buffer_size = self.config.max_buffer_size;
-            if shared.buffer.len() >= max_buffer_size {
+            let exceeds_memory = self
+                .config
+                .max_stream_receive_buffer_bytes
+                .is_some_and(|max| {
+                    let len = frame.body().len();
+                    buffer.memory() + buffer.memory_to_push(len, frame.body_capacity()) > max
+                });
+            if buffer.len() >= max_buffer_size || exceeds_memory {
                 log::error!(
                     "{}/{}: buffer of stream grows beyond limit",
                     self.id,
                     stream_id
                 );
-                let mut header = Header::data(stream_id, 0);
-                header.rst();
-                return Action::Reset(Frame::new(header));
+                let policy = self.config.buffer_overflow_policy;
+                if let Some(observer) = &self.config.buffer_overflow_observer {
+                    (observer.0)(&BufferOverflow {
+                        connection: self.id,
+                        stream: stream_id,
+                        buffered: buffer.len(),
+                        frame_len: frame.body_len() as usize,
+                        policy,
+                    })
+                }
+                match policy {
+                    BufferOverflowPolicy::Reset => {
+                        let mut header = Header::data(stream_id, 0);
+                        header.rst();
+                        return Action::Reset(Frame::new(header), ResetReason::BufferOverflow);
+                    }
+                    BufferOverflowPolicy::GoAway => {
+                        return Action::protocol_error(
+                            self.id,
+                            "buffer of stream grows beyond limit",
+                            frame.header().clone(),
+                        )
+                    }
+                    // The frame is within the window, withhold credit below.
+                    BufferOverflowPolicy::Backpressure => shared.backpressured = true,
+                }
             }
             shared.window = shared.window.saturating_sub(frame.body_len());
-            shared.buffer.push(frame.into_body());
+            stream.on_received(&mut shared, frame.body_len());
+            if let Some(body) = buffer.push_body(frame.into_body()) {
+                self.socket.get_mut().recycle(body)
+            }
+            let buffered = buffer.len();
+            drop(buffer);
+            let is_reading = shared.reader.is_some() && !shared.window_update_blocked;
             if let Some(w) = shared.reader.take() {
-                w.wake()
+                self.wakeups.push(w)
             }
-            if matches!(self.config.window_update_mode, WindowUpdateMode::OnReceive) {
-                if let Some(credit) = shared.next_window_update() {
-                    shared.window += credit;
-                    let frame = Frame::window_update(stream_id, credit);
-                    return Action::Update(frame);
+            let credit = match self.config.window_update_mode {
+                _ if shared.backpressured => None,
+                WindowUpdateMode::OnReceive => shared.next_window_update(buffered),
+                WindowUpdateMode::Hybrid if !is_reading => {
+                    let credit = shared.next_fallback_window_update(buffered);
+                    if credit.is_some() {
+                        log::debug!(
+                            "{}/{}: stream is not being read, sending window update",
+                            self.id,
+                            stream_id
+                        );
+                    }
+                    credit
                 }
+                WindowUpdateMode::Hybrid => {
+                    // The woken reader may not come back to read, check again later.
+                    if shared.window == 0 && self.hybrid_fallback.is_none() {
+                        self.hybrid_fallback = Some(self.config.time.delay(HYBRID_FALLBACK_DELAY))
+                    }
+                    None
+                }
+                WindowUpdateMode::OnRead => None,
+            };
+            if let Some(credit) = credit {
+                shared.window += credit;
+                return Action::Update(Frame::window_update(stream_id, credit));
             }
         } else {
             log::trace!(
@@ -714,46 +2390,56 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
         Action::None
     }
 
-    fn on_window_update(&mut self, frame: &Frame<WindowUpdate>) -> Action {
+    fn on_window_update(&mut self, inbound: Inbound, frame: &Frame<WindowUpdate>) -> Action {
         let stream_id = frame.header().stream_id();
 
-        if frame.header().flags().contains(header::RST) {
-            // stream reset
-            if let Some(s) = self.streams.get_mut(&stream_id) {
-                let mut shared = s.shared();
-                shared.update_state(self.id, stream_id, State::Closed);
-                if let Some(w) = shared.reader.take() {
-                    w.wake()
-                }
-                if let Some(w) = shared.writer.take() {
-                    w.wake()
-                }
-            }
+        if frame.header().flags().contains(header::RELEASE) {
+            self.on_release(stream_id);
+            return Action::None;
+        }
+
+        if let Inbound::Reset(_) = inbound {
+            self.on_reset_received(stream_id);
             return Action::None;
         }
 
         let is_finish = frame.header().flags().contains(header::FIN); // half-close
 
-        if frame.header().flags().contains(header::SYN) {
-            // new stream
-            if !self.is_valid_remote_id(stream_id, Tag::WindowUpdate) {
-                log::error!("{}: invalid stream id {}", self.id, stream_id);
-                return Action::Terminate(Frame::protocol_error());
-            }
+        if let Inbound::Open(_) = inbound {
             if self.streams.contains_key(&stream_id) {
                 log::error!("{}/{}: stream already exists", self.id, stream_id);
-                return Action::Terminate(Frame::protocol_error());
+                return Action::protocol_error(
+                    self.id,
+                    "stream already exists",
+                    frame.header().clone(),
+                );
             }
-            if self.streams.len() == self.config.max_num_streams {
-                log::error!("{}: maximum number of streams reached", self.id);
-                return Action::Terminate(Frame::protocol_error());
+            if self.draining || self.paused {
+                let mut header = Header::data(stream_id, 0);
+                header.rst();
+                return Action::Reset(Frame::new(header), ResetReason::NotAccepting);
+            }
+            if let Some(action) = self
+                .reject_if_too_many_streams(frame.header().clone().into(), Frame::protocol_error())
+            {
+                return action;
+            }
+            if let Some(action) = self.reject_by_policy(frame.header().clone().into()) {
+                return action;
             }
             let stream = {
                 let credit = frame.header().credit() + DEFAULT_CREDIT;
                 let config = self.config.clone();
                 let sender = self.stream_sender.clone();
-                let mut stream =
-                    Stream::new(stream_id, self.id, config, DEFAULT_CREDIT, credit, sender);
+                let mut stream = Stream::new(
+                    stream_id,
+                    self.id,
+                    config,
+                    DEFAULT_CREDIT,
+                    credit,
+                    sender,
+                    false,
+                );
                 stream.set_flag(stream::Flag::Ack);
                 stream
             };
@@ -762,19 +2448,42 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
                     .shared()
                     .update_state(self.id, stream_id, State::RecvClosed);
             }
+            if frame.header().flags().contains(header::STOP_SENDING) {
+                stream.shared().stop_sending = true;
+            }
+            if self.config.compression.is_offered(frame.header().flags())
+                && self.is_negotiated(Extensions::COMPRESSION)
+            {
+                stream.shared().compression = self.config.compression
+            }
             self.streams.insert(stream_id, stream.clone());
+            self.claim_receive_budget(&stream);
             return Action::New(stream, None);
         }
 
         if let Some(stream) = self.streams.get_mut(&stream_id) {
             let mut shared = stream.shared();
-            shared.credit += frame.header().credit();
+            let is_ack = frame.header().flags().contains(header::ACK);
+            if is_ack {
+                shared.on_ack();
+                if let Some(w) = self.open_waker.take() {
+                    w.wake()
+                }
+                if shared.compression_offered
+                    && self.config.compression.is_offered(frame.header().flags())
+                {
+                    shared.compression = self.config.compression
+                }
+            }
+            shared.on_credit(frame.header().credit(), is_ack);
             if is_finish {
                 shared.update_state(self.id, stream_id, State::RecvClosed);
             }
-            if let Some(w) = shared.writer.take() {
-                w.wake()
+            if frame.header().flags().contains(header::STOP_SENDING) {
+                log::debug!("{}/{}: remote stopped reading", self.id, stream_id);
+                shared.stop_sending = true;
             }
+            self.wakeups.extend(shared.take_writers());
         } else {
             log::trace!(
                 "{}/{}: window update for unknown stream, possibly dropped earlier: {:?}",
@@ -794,22 +2503,16 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
         Action::None
     }
 
-    fn on_ping(&mut self, frame: &Frame<Ping>) -> Action {
-        let stream_id = frame.header().stream_id();
-        if frame.header().flags().contains(header::ACK) {
-            // pong
-            return Action::None;
-        }
+    fn on_ping(&mut self, stream_id: StreamId, nonce: u32) -> Action {
         if stream_id == CONNECTION_ID || self.streams.contains_key(&stream_id) {
-            let mut hdr = Header::ping(frame.header().nonce());
+            let mut hdr = Header::ping(nonce);
             hdr.ack();
             return Action::Ping(Frame::new(hdr));
         }
         log::trace!(
-            "{}/{}: ping for unknown stream, possibly dropped earlier: {:?}",
+            "{}/{}: ping for unknown stream, possibly dropped earlier",
             self.id,
-            stream_id,
-            frame
+            stream_id
         );
         // We do not consider this a protocol violation and thus do not send a stream reset because
         // we may still be processing pending `StreamCommand`s of this stream that were sent before
@@ -821,12 +2524,94 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
         Action::None
     }
 
+    fn reset_streams_in_flight(&mut self) -> usize {
+        let ids = self
+            .streams
+            .iter()
+            .filter(|(_, s)| s.shared().may_be_in_flight(s.is_outbound()))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in &ids {
+            log::debug!("{}/{}: frames may have been lost", self.id, id);
+            self.reset_stream(*id, Reset::TransportLost);
+        }
+        ids.len()
+    }
+
+    fn reset_stream(&mut self, id: StreamId, reset: Reset) -> bool {
+        let stream = match self.streams.get(&id) {
+            Some(s) => s,
+            None => return false,
+        };
+        if let Some(w) = self.open_waker.take() {
+            w.wake()
+        }
+        if stream.shared().reset(self.id, id, reset) != State::Closed {
+            let mut header = Header::data(id, 0);
+            header.rst();
+            self.pending_frames.push_back(Frame::new(header).into());
+            let reason = match reset {
+                Reset::Requested => ResetReason::Requested,
+                Reset::OpenTimeout => ResetReason::OpenTimeout,
+                Reset::AckDeadline => ResetReason::AckDeadline,
+                Reset::WindowViolation => ResetReason::WindowViolation,
+                Reset::StopSending => ResetReason::StopSending,
+                Reset::DataAfterFin => ResetReason::DataAfterFin,
+                Reset::TransportLost => ResetReason::TransportLost,
+            };
+            self.on_reset_sent(id, reason);
+        }
+        true
+    }
+
+    /// Count a RST sent to the remote for the given reason and log it as a
+    /// warning, unless the budget of [`Config::set_reset_log_budget`] is
+    /// exhausted or the reset has been requested.
+    fn on_reset_sent(&mut self, id: StreamId, reason: ResetReason) {
+        self.resets_sent.add(reason);
+        if reason == ResetReason::Requested {
+            log::debug!("{}/{}: resetting stream: {}", self.id, id, reason);
+            return;
+        }
+        match self.reset_log.take(self.config.time.now()) {
+            Some(0) => log::warn!("{}/{}: resetting stream: {}", self.id, id, reason),
+            Some(n) => log::warn!(
+                "{}/{}: resetting stream: {} ({} more resets not logged)",
+                self.id,
+                id,
+                reason,
+                n
+            ),
+            None => log::debug!("{}/{}: resetting stream: {}", self.id, id, reason),
+        }
+    }
+
+    /// The number of IDs left for which [`Active::next_stream_id`] succeeds.
+    fn remaining_stream_ids(&self) -> u32 {
+        let recycled = self
+            .recycler
+            .as_ref()
+            .map_or(0, |r| r.num_free().min(u32::MAX as usize) as u32);
+        // `next_stream_id` fails unless the ID following the proposed one is valid.
+        let last = u32::MAX - 2;
+        if self.next_id > last {
+            return recycled;
+        }
+        ((last - self.next_id) / 2 + 1).saturating_add(recycled)
+    }
+
     fn next_stream_id(&mut self) -> Result<StreamId> {
         let proposed = StreamId::new(self.next_id);
-        self.next_id = self
-            .next_id
-            .checked_add(2)
-            .ok_or(ConnectionError::NoMoreStreamIds)?;
+        self.next_id = match self.next_id.checked_add(2) {
+            Some(next_id) => next_id,
+            None => {
+                return self
+                    .recycler
+                    .as_mut()
+                    .and_then(|r| r.pop_free())
+                    .ok_or(ConnectionError::NoMoreStreamIds)
+            }
+        };
         match self.mode {
             Mode::Client => assert!(proposed.is_client()),
             Mode::Server => assert!(proposed.is_server()),
@@ -834,36 +2619,217 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
         Ok(proposed)
     }
 
-    // Check if the given stream ID is valid w.r.t. the provided tag and our connection mode.
-    fn is_valid_remote_id(&self, id: StreamId, tag: Tag) -> bool {
-        if tag == Tag::Ping || tag == Tag::GoAway {
-            return id.is_session();
+    /// The remote released the given stream ID.
+    fn on_release(&mut self, id: StreamId) {
+        if !self.is_negotiated(Extensions::STREAM_ID_RECYCLING) {
+            log::debug!(
+                "{}/{}: ignoring release, recycling is not negotiated",
+                self.id,
+                id
+            );
+            return;
         }
-        match self.mode {
-            Mode::Client => id.is_server(),
-            Mode::Server => id.is_client(),
+        let recycler = match self.recycler.as_mut() {
+            Some(recycler) => recycler,
+            None => {
+                log::debug!(
+                    "{}/{}: ignoring release, recycling is disabled",
+                    self.id,
+                    id
+                );
+                return;
+            }
+        };
+        // Only IDs we have handed out can be released by the remote.
+        let is_ours = match self.mode {
+            Mode::Client => id.is_client(),
+            Mode::Server => id.is_server(),
+        };
+        if !is_ours || id.is_session() || id.val() >= self.next_id {
+            log::debug!("{}/{}: ignoring release of unknown stream id", self.id, id);
+            return;
+        }
+        log::trace!("{}/{}: released by remote", self.id, id);
+        recycler.release_remote(id)
+    }
+
+    /// Release the IDs of garbage collected streams.
+    ///
+    /// Must only be called if no `StreamCommand`s are pending, since these
+    /// may still refer to the streams. Returns `true` if frames were enqueued.
+    ///
+    /// Unless the remote agreed to recycle stream IDs, the IDs are forgotten
+    /// instead, i.e. never reused.
+    fn release_dropped_ids(&mut self) -> bool {
+        let is_negotiated = self.is_negotiated(Extensions::STREAM_ID_RECYCLING);
+        let recycler = match self.recycler.as_mut() {
+            Some(recycler) => recycler,
+            None => return false,
+        };
+        if !is_negotiated {
+            recycler.take_dropped();
+            return false;
+        }
+        let mut enqueued = false;
+        for id in recycler.take_dropped() {
+            let is_remote = match self.mode {
+                Mode::Client => id.is_server(),
+                Mode::Server => id.is_client(),
+            };
+            if is_remote {
+                log::trace!("{}/{}: sending release", self.id, id);
+                self.pending_frames
+                    .push_back(Frame::release_stream(id).into());
+                enqueued = true;
+            } else {
+                recycler.release_local(id)
+            }
+        }
+        enqueued
+    }
+
+    /// The number of streams opened by the remote.
+    fn num_inbound_streams(&self) -> usize {
+        self.streams.len() - self.num_outbound
+    }
+
+    /// Check if accepting the inbound stream of the given header would exceed the
+    /// max. number of streams or of inbound streams (plus grace margin) and if so,
+    /// return the action to reject it with, as per the configured
+    /// [`MaxStreamsResponse`].
+    ///
+    /// The connection is terminated with the given `go_away` frame, which
+    /// differs depending on the frame opening the stream.
+    fn reject_if_too_many_streams(
+        &self,
+        header: Header<()>,
+        go_away: Frame<GoAway>,
+    ) -> Option<Action> {
+        let stream_id = header.stream_id();
+        let grace = self.config.max_num_streams_grace;
+        let exceeds = |num, max: usize| num >= max.saturating_add(grace);
+        if !exceeds(self.streams.len(), self.config.max_num_streams)
+            && !self
+                .config
+                .max_inbound_streams
+                .is_some_and(|max| exceeds(self.num_inbound_streams(), max))
+        {
+            return None;
+        }
+        match self.config.max_num_streams_response {
+            MaxStreamsResponse::GoAway => {
+                log::error!("{}: maximum number of inbound streams reached", self.id);
+                let error =
+                    ProtocolError::new(self.id, "maximum number of inbound streams exceeded")
+                        .with_header(header);
+                Some(Action::Terminate(go_away, error))
+            }
+            MaxStreamsResponse::Reset => {
+                let mut header = Header::data(stream_id, 0);
+                header.rst();
+                Some(Action::Reset(
+                    Frame::new(header),
+                    ResetReason::TooManyStreams,
+                ))
+            }
+        }
+    }
+
+    /// Check the inbound stream of the given header against the policy of
+    /// [`Config::set_inbound_stream_policy`] and if it is not accepted,
+    /// return the action to reject it with.
+    fn reject_by_policy(&self, header: Header<()>) -> Option<Action> {
+        let policy = self.config.inbound_stream_policy.as_ref()?;
+        let stream_id = header.stream_id();
+        let info = ConnectionInfo {
+            connection: self.id,
+            mode: self.mode,
+            num_streams: self.streams.len(),
+            num_inbound_streams: self.num_inbound_streams(),
+        };
+        match (policy.0)(stream_id, &info) {
+            Verdict::Accept => None,
+            Verdict::ResetStream => {
+                let mut header = Header::data(stream_id, 0);
+                header.rst();
+                Some(Action::Reset(
+                    Frame::new(header),
+                    ResetReason::RejectedByPolicy,
+                ))
+            }
+            Verdict::TerminateConnection => {
+                log::error!("{}/{}: stream rejected by policy", self.id, stream_id);
+                let error = ProtocolError::new(self.id, "inbound stream rejected by policy")
+                    .with_header(header);
+                Some(Action::Terminate(Frame::internal_error(), error))
+            }
+        }
+    }
+
+    /// Close a stream the remote reset, if it exists.
+    fn on_reset_received(&mut self, id: StreamId) {
+        if let Some(s) = self.streams.get_mut(&id) {
+            let mut shared = s.shared();
+            shared.update_state(self.id, id, State::Closed);
+            shared.wake_all();
+        }
+    }
+
+    /// Remove the stream of the given `StreamCommand::Dropped` if it has
+    /// indeed been dropped.
+    fn on_stream_dropped(&mut self, id: StreamId) {
+        if self.streams.get(&id).is_some_and(|s| s.is_dropped()) {
+            self.dropped_streams.push(id);
+            self.remove_dropped_streams()
         }
     }
 
-    /// Remove stale streams and create necessary messages to be sent to the remote.
+    /// Remove all dropped streams, as a fallback for streams whose
+    /// `StreamCommand::Dropped` got lost.
+    ///
+    /// The scan also releases the memory of empty receive buffers, which
+    /// keep it while the stream is read, see `Chunks::shrink`.
     fn garbage_collect(&mut self) {
+        for stream in self.streams.values() {
+            if stream.is_dropped() {
+                self.dropped_streams.push(stream.id())
+            } else {
+                stream.buffer().shrink()
+            }
+        }
+        self.remove_dropped_streams()
+    }
+
+    /// Remove the streams of `dropped_streams` and create necessary messages
+    /// to be sent to the remote.
+    fn remove_dropped_streams(&mut self) {
+        if self.dropped_streams.is_empty() {
+            return;
+        }
         let conn_id = self.id;
         let win_update_mode = self.config.window_update_mode;
-        for stream in self.streams.values_mut() {
-            if stream.strong_count() > 1 {
-                continue;
-            }
+        let mut dropped_streams = std::mem::take(&mut self.dropped_streams);
+        for stream_id in dropped_streams.drain(..) {
+            let stream = match self.streams.remove(&stream_id) {
+                Some(stream) => stream,
+                None => continue,
+            };
             log::trace!("{}: removing dropped {}", conn_id, stream);
-            let stream_id = stream.id();
+            self.lock_contentions += stream.lock_contentions();
             let frame = {
                 let mut shared = stream.shared();
+                if let (Some(budget), Some(share)) =
+                    (self.receive_budget.as_mut(), shared.fair_share.take())
+                {
+                    budget.remove(share)
+                }
                 let frame = match shared.update_state(conn_id, stream_id, State::Closed) {
                     // The stream was dropped without calling `poll_close`.
                     // We reset the stream to inform the remote of the closure.
                     State::Open => {
                         let mut header = Header::data(stream_id, 0);
                         header.rst();
-                        Some(Frame::new(header))
+                        Some((Frame::new(header), Some(ResetReason::Dropped)))
                     }
                     // The stream was dropped without calling `poll_close`.
                     // We have already received a FIN from remote and send one
@@ -871,19 +2837,19 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
                     State::RecvClosed => {
                         let mut header = Header::data(stream_id, 0);
                         header.fin();
-                        Some(Frame::new(header))
+                        Some((Frame::new(header), None))
                     }
                     // The stream was properly closed. We either already have
                     // or will at some later point send our FIN frame.
                     // The remote may be out of credit though and blocked on
                     // writing more data. We may need to reset the stream.
                     State::SendClosed => {
-                        if win_update_mode == WindowUpdateMode::OnRead && shared.window == 0 {
+                        if win_update_mode != WindowUpdateMode::OnReceive && shared.window == 0 {
                             // The remote may be waiting for a window update
                             // which we will never send, so reset the stream now.
                             let mut header = Header::data(stream_id, 0);
                             header.rst();
-                            Some(Frame::new(header))
+                            Some((Frame::new(header), Some(ResetReason::DroppedWithoutCredit)))
                         } else {
                             // The remote has either still credit or will be given more
                             // (due to an enqueued window update or because the update
@@ -899,38 +2865,167 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Active<T> {
                     // remote end has already done so in the past.
                     State::Closed => None,
                 };
-                if let Some(w) = shared.reader.take() {
-                    w.wake()
-                }
-                if let Some(w) = shared.writer.take() {
-                    w.wake()
-                }
+                shared.wake_all();
                 frame
             };
-            if let Some(f) = frame {
-                log::trace!("{}/{}: sending: {}", self.id, stream_id, f.header());
-                self.pending_frames.push_back(f.into());
+            if let Some((f, reset)) = frame {
+                log::trace!("{}/{}: sending: {}", conn_id, stream_id, f.header());
+                // Data written before the stream was dropped is still sent.
+                self.pending_frames.push_back_in_order(f.into());
+                if let Some(reason) = reset {
+                    self.on_reset_sent(stream_id, reason)
+                }
+            }
+            if stream.is_outbound() {
+                self.num_outbound -= 1
+            }
+            if let Some(w) = self.open_waker.take() {
+                w.wake()
+            }
+            if let Some(recycler) = self.recycler.as_mut() {
+                recycler.on_dropped(stream_id)
             }
-            self.dropped_streams.push(stream_id)
         }
-        for id in self.dropped_streams.drain(..) {
-            self.streams.remove(&id);
+        self.dropped_streams = dropped_streams;
+    }
+
+    /// Count a new stream towards the receive budget of the connection, see
+    /// [`Config::set_max_connection_receive_window`].
+    fn claim_receive_budget(&mut self, stream: &Stream) {
+        if let Some(budget) = self.receive_budget.as_mut() {
+            let mut shared = stream.shared();
+            shared.fair_share = Some(budget.add(shared.receive_window))
         }
     }
 }
 
 impl<T> Active<T> {
-    /// Close and drop all `Stream`s and wake any pending `Waker`s.
-    fn drop_all_streams(&mut self) {
+    /// Close and drop all `Stream`s for the given reason and wake any pending `Waker`s.
+    fn drop_all_streams(&mut self, reason: CloseReason) {
         for (id, s) in self.streams.drain() {
             let mut shared = s.shared();
-            shared.update_state(self.id, id, State::Closed);
-            if let Some(w) = shared.reader.take() {
-                w.wake()
-            }
-            if let Some(w) = shared.writer.take() {
-                w.wake()
+            if shared.update_state(self.id, id, State::Closed) != State::Closed {
+                shared.close_reason.get_or_insert(reason);
             }
+            shared.wake_all();
+        }
+        self.num_outbound = 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::{AsyncRead, AsyncWrite};
+    use std::{io, pin::Pin};
+
+    /// An I/O resource which never receives any data.
+    struct Silent;
+
+    impl AsyncRead for Silent {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context,
+            _: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for Silent {
+        fn poll_write(self: Pin<&mut Self>, _: &mut Context, b: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(Ok(b.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
         }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn closed_command_channel_closes_connection() {
+        futures::executor::block_on(async {
+            let mut connection = Connection::new(Silent, Config::default(), Mode::Client);
+            let mut stream = future::poll_fn(|cx| connection.poll_new_outbound(cx))
+                .await
+                .unwrap();
+            match &mut connection.inner {
+                ConnectionState::Active(active) => active.stream_receiver.close(),
+                state => panic!("unexpected state: {:?}", state),
+            }
+
+            let next = future::poll_fn(|cx| connection.poll_next_inbound(cx)).await;
+            assert!(next.is_none(), "{:?}", next);
+            assert!(matches!(connection.inner, ConnectionState::Closed));
+            assert!(stream.write_all(b"x").await.is_err());
+        })
+    }
+
+    #[test]
+    fn released_stream_id_is_reused_once_fresh_ids_are_exhausted() {
+        use futures::{AsyncReadExt, StreamExt};
+        use tokio_util::compat::TokioAsyncReadCompatExt;
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            let (server, client) =
+                futures::future::join(async { listener.accept().await.unwrap().0 }, async {
+                    tokio::net::TcpStream::connect(address).await.unwrap()
+                })
+                .await;
+
+            let mut config = Config::default();
+            config.set_stream_id_recycling(true);
+            let mut server = Connection::new(server.compat(), config.clone(), Mode::Server);
+            let mut client = Connection::new(client.compat(), config, Mode::Client);
+
+            // Leave a single fresh ID.
+            match &mut client.inner {
+                ConnectionState::Active(active) => active.next_id = u32::MAX - 2,
+                state => panic!("unexpected state: {:?}", state),
+            }
+            assert_eq!(client.remaining_stream_ids(), 1);
+
+            // Echo every inbound stream.
+            tokio::spawn(async move {
+                while let Some(Ok(stream)) =
+                    future::poll_fn(|cx| server.poll_next_inbound(cx)).await
+                {
+                    tokio::spawn(async move {
+                        let (mut reader, mut writer) = AsyncReadExt::split(stream);
+                        futures::io::copy(&mut reader, &mut writer).await.unwrap();
+                        writer.close().await.unwrap();
+                    });
+                }
+            });
+            let (mut control, client) = crate::Control::new(client);
+            tokio::spawn(client.for_each(|_| future::ready(())));
+
+            let echo = |mut stream: Stream| async move {
+                stream.write_all(b"ping").await.unwrap();
+                stream.close().await.unwrap();
+                let mut buf = Vec::new();
+                stream.read_to_end(&mut buf).await.unwrap();
+                assert_eq!(buf, b"ping");
+            };
+
+            let stream = control.open_stream().await.unwrap();
+            assert_eq!(stream.id().val(), u32::MAX - 2);
+            echo(stream).await;
+
+            // Fresh IDs are exhausted, so the ID of the previous stream is
+            // reused once both endpoints released it.
+            while control.stats().await.unwrap().remaining_stream_ids == 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await
+            }
+            let stream = control.open_stream().await.unwrap();
+            assert_eq!(stream.id().val(), u32::MAX - 2);
+            echo(stream).await;
+        })
     }
 }
@@ -0,0 +1,124 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// The receive budget of a connection (see `Config::set_max_connection_receive_window`).
+//
+// The budget is divided among the streams using max-min fairness, i.e. each
+// stream gets the min. of its receive window and a common level, the largest
+// one at which the sum of the shares does not exceed the budget. The level is
+// shared with the streams, so that opening or dropping a stream only updates
+// a histogram of the receive windows and recomputes the level from it, at a
+// cost proportional to the number of distinct receive windows rather than to
+// the number of streams.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub(crate) struct ReceiveBudget {
+    budget: usize,
+    /// The number of streams per receive window.
+    demands: BTreeMap<u32, usize>,
+    num_streams: usize,
+    level: Arc<AtomicU32>,
+}
+
+/// The share of a stream in the receive budget of its connection.
+#[derive(Debug)]
+pub(crate) struct FairShare {
+    demand: u32,
+    level: Arc<AtomicU32>,
+}
+
+impl FairShare {
+    /// The max. receive window of the stream.
+    pub(crate) fn get(&self) -> u32 {
+        std::cmp::min(self.demand, self.level.load(Ordering::Relaxed))
+    }
+}
+
+impl ReceiveBudget {
+    pub(crate) fn new(budget: usize) -> Self {
+        ReceiveBudget {
+            budget,
+            demands: BTreeMap::new(),
+            num_streams: 0,
+            level: Arc::new(AtomicU32::new(u32::MAX)),
+        }
+    }
+
+    /// Count a stream with the given receive window towards the budget.
+    pub(crate) fn add(&mut self, demand: u32) -> FairShare {
+        *self.demands.entry(demand).or_default() += 1;
+        self.num_streams += 1;
+        self.update();
+        FairShare {
+            demand,
+            level: self.level.clone(),
+        }
+    }
+
+    /// No longer count the stream of the given share towards the budget.
+    pub(crate) fn remove(&mut self, share: FairShare) {
+        // Shares of a connection this one was resumed from are void.
+        if !Arc::ptr_eq(&share.level, &self.level) {
+            return;
+        }
+        if let Some(n) = self.demands.get_mut(&share.demand) {
+            *n -= 1;
+            if *n == 0 {
+                self.demands.remove(&share.demand);
+            }
+            self.num_streams -= 1;
+            self.update();
+        }
+    }
+
+    /// Recompute the level, given streams in ascending order of their
+    /// receive windows: as long as a receive window fits into an even split
+    /// of what is left, the streams get all of it.
+    fn update(&mut self) {
+        let mut remaining = self.budget;
+        let mut n = self.num_streams;
+        let mut level = u32::MAX;
+        for (&demand, &count) in &self.demands {
+            if (demand as usize).saturating_mul(n) > remaining {
+                // Less than `demand`, i.e. fits into a `u32`. If the budget
+                // does not even cover a byte per stream, this is 0.
+                level = (remaining / n) as u32;
+                break;
+            }
+            remaining -= demand as usize * count;
+            n -= count;
+        }
+        self.level.store(level, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_never_exceed_budget() {
+        let mut budget = ReceiveBudget::new(1000);
+        let a = budget.add(100);
+        let b = budget.add(800);
+        let c = budget.add(800);
+        assert_eq!((a.get(), b.get(), c.get()), (100, 450, 450));
+        budget.remove(a);
+        assert_eq!((b.get(), c.get()), (500, 500));
+
+        let shares = (0..1001).map(|_| budget.add(1)).collect::<Vec<_>>();
+        let total = shares.iter().map(FairShare::get).sum::<u32>() + b.get() + c.get();
+        assert!(total <= 1000, "{}", total);
+    }
+}
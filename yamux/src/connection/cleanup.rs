@@ -1,4 +1,5 @@
 use crate::connection::StreamCommand;
+use crate::frame::header::{Header, StreamId, Tag};
 use crate::ConnectionError;
 use futures::channel::mpsc;
 use futures::{ready, StreamExt};
@@ -6,32 +7,66 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+/// What was discarded when a connection failed, see
+/// [`Connection::cleanup_report`](crate::Connection::cleanup_report).
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// The number of commands of streams, e.g. frames to send, which the
+    /// connection had not processed yet.
+    pub discarded_commands: usize,
+    /// The number of frames which had not been completely written to the
+    /// I/O resource.
+    pub discarded_frames: usize,
+    /// The streams whose data was discarded by either, in ascending order.
+    pub unsent_data: Vec<StreamId>,
+}
+
+impl CleanupReport {
+    /// Record a discarded frame with the given header.
+    pub(crate) fn on_frame<T>(&mut self, header: &Header<T>) {
+        if header.tag() == Tag::Data && header.len().val() > 0 {
+            if let Err(i) = self.unsent_data.binary_search(&header.stream_id()) {
+                self.unsent_data.insert(i, header.stream_id())
+            }
+        }
+    }
+}
+
 /// A [`Future`] that cleans up resources in case of an error.
 #[must_use]
 pub struct Cleanup {
     state: State,
     stream_receiver: mpsc::Receiver<StreamCommand>,
     error: Option<ConnectionError>,
+    report: CleanupReport,
 }
 
 impl Cleanup {
     pub(crate) fn new(
         stream_receiver: mpsc::Receiver<StreamCommand>,
         error: ConnectionError,
+        report: CleanupReport,
     ) -> Self {
         Self {
             state: State::ClosingStreamReceiver,
             stream_receiver,
             error: Some(error),
+            report,
         }
     }
+
+    /// Take the report of what was discarded, complete once cleanup is.
+    pub(crate) fn take_report(&mut self) -> CleanupReport {
+        std::mem::take(&mut self.report)
+    }
 }
 
 impl Future for Cleanup {
     type Output = ConnectionError;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.get_mut();
+        let this = self.get_mut();
 
         loop {
             match this.state {
@@ -44,7 +79,13 @@ impl Future for Cleanup {
                     this.stream_receiver.close();
 
                     match ready!(this.stream_receiver.poll_next_unpin(cx)) {
+                        // Dropping a stream is not a command which is lost.
+                        Some(StreamCommand::Dropped(_)) => {}
                         Some(cmd) => {
+                            this.report.discarded_commands += 1;
+                            if let StreamCommand::SendFrame(frame) = &cmd {
+                                this.report.on_frame(frame.header())
+                            }
                             drop(cmd);
                         }
                         None => {
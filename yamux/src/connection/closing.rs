@@ -1,11 +1,12 @@
-use crate::connection::StreamCommand;
+use crate::connection::{pending::PendingFrames, StreamCommand};
 use crate::frame;
 use crate::frame::Frame;
+use crate::timer::Delay;
+use crate::transport::Transport;
 use crate::Result;
 use futures::channel::mpsc;
 use futures::stream::Fuse;
-use futures::{ready, AsyncRead, AsyncWrite, SinkExt, StreamExt};
-use std::collections::VecDeque;
+use futures::{ready, FutureExt, SinkExt, StreamExt};
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -14,37 +15,56 @@ use std::task::{Context, Poll};
 #[must_use]
 pub struct Closing<T> {
     state: State,
+    /// Whether a `GoAway` has already been sent, e.g. because the connection was drained.
+    term_sent: bool,
     stream_receiver: mpsc::Receiver<StreamCommand>,
-    pending_frames: VecDeque<Frame<()>>,
+    pending_frames: PendingFrames,
     socket: Fuse<frame::Io<T>>,
+    /// Fires when closing is to be abandoned, see [`crate::Config::set_close_timeout`].
+    timeout: Option<Delay>,
 }
 
 impl<T> Closing<T>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Transport,
 {
     pub(crate) fn new(
         stream_receiver: mpsc::Receiver<StreamCommand>,
-        pending_frames: VecDeque<Frame<()>>,
+        pending_frames: PendingFrames,
         socket: Fuse<frame::Io<T>>,
+        term_sent: bool,
+        timeout: Option<Delay>,
     ) -> Self {
         Self {
             state: State::ClosingStreamReceiver,
+            term_sent,
             stream_receiver,
             pending_frames,
             socket,
+            timeout,
         }
     }
 }
 
 impl<T> Future for Closing<T>
 where
-    T: AsyncRead + AsyncWrite + Unpin,
+    T: Transport,
 {
     type Output = Result<()>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.get_mut();
+        let this = self.get_mut();
+
+        if let Some(timeout) = this.timeout.as_mut() {
+            if timeout.poll_unpin(cx).is_ready() {
+                log::debug!(
+                    "close timed out, discarding {} pending frames",
+                    this.pending_frames.len()
+                );
+                this.pending_frames.clear();
+                return Poll::Ready(Ok(()));
+            }
+        }
 
         loop {
             match this.state {
@@ -60,14 +80,21 @@ where
                         Some(StreamCommand::SendFrame(frame)) => {
                             this.pending_frames.push_back(frame.into())
                         }
+                        #[cfg(feature = "frame-sink")]
+                        Some(StreamCommand::InjectFrame(frame)) => {
+                            this.pending_frames.push_back(frame)
+                        }
                         Some(StreamCommand::CloseStream { id, ack }) => this
                             .pending_frames
                             .push_back(Frame::close_stream(id, ack).into()),
+                        Some(StreamCommand::KeepAlive) | Some(StreamCommand::Dropped(_)) => {}
                         None => this.state = State::SendingTermFrame,
                     }
                 }
                 State::SendingTermFrame => {
-                    this.pending_frames.push_back(Frame::term().into());
+                    if !this.term_sent {
+                        this.pending_frames.push_back(Frame::term().into());
+                    }
                     this.state = State::FlushingPendingFrames;
                 }
                 State::FlushingPendingFrames => {
@@ -79,6 +106,8 @@ where
                     }
                 }
                 State::ClosingSocket => {
+                    // Closing a TLS stream sends its `close_notify` alert, so
+                    // this has to happen after all frames have been written.
                     ready!(this.socket.poll_close_unpin(cx))?;
 
                     return Poll::Ready(Ok(()));
@@ -0,0 +1,94 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Compression of data frame bodies, see `Config::set_compression`.
+//
+// The compression flags have two meanings. On frames carrying `SYN` or `ACK`
+// they announce the algorithm the sender is configured with, the bodies of
+// such frames are never compressed. On all other data frames they indicate
+// that the body is compressed with the algorithm. A stream compresses once
+// both sides announced the same algorithm.
+
+use crate::frame::header::Flags;
+#[cfg(feature = "lz4")]
+use crate::frame::header::COMPRESS_LZ4;
+#[cfg(feature = "zstd")]
+use crate::frame::header::COMPRESS_ZSTD;
+use crate::Compression;
+
+impl Compression {
+    /// Is this a supported algorithm and level?
+    pub(crate) fn is_valid(&self) -> bool {
+        match *self {
+            Compression::None => true,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => true,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => zstd::compression_level_range().contains(&level),
+        }
+    }
+
+    /// The flag announcing, respectively indicating, this algorithm.
+    pub(crate) fn flag(&self) -> Option<Flags> {
+        match self {
+            Compression::None => None,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Some(COMPRESS_LZ4),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => Some(COMPRESS_ZSTD),
+        }
+    }
+
+    /// Whether a frame opening or acknowledging a stream with the given flags
+    /// announces this algorithm.
+    pub(crate) fn is_offered(&self, flags: Flags) -> bool {
+        self.flag().is_some_and(|flag| flags.contains(flag))
+    }
+
+    /// Compress the given body, unless compression does not shrink it.
+    #[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+    pub(crate) fn compress(&self, body: &[u8]) -> Option<Vec<u8>> {
+        match *self {
+            Compression::None => None,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => shrunk(body, lz4_flex::block::compress_prepend_size(body)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => {
+                let compressed = zstd::bulk::compress(body, level).ok()?;
+                shrunk(body, compressed)
+            }
+        }
+    }
+
+    /// Decompress the given body, which must not decompress to more than
+    /// `max_len` bytes.
+    #[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+    pub(crate) fn decompress(&self, body: &[u8], max_len: usize) -> Option<Vec<u8>> {
+        match *self {
+            Compression::None => None,
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                let (len, compressed) = body.split_first_chunk::<4>()?;
+                let len = u32::from_le_bytes(*len) as usize;
+                if len > max_len {
+                    return None;
+                }
+                lz4_flex::block::decompress(compressed, len).ok()
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(_) => zstd::bulk::decompress(body, max_len).ok(),
+        }
+    }
+}
+
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+fn shrunk(body: &[u8], compressed: Vec<u8>) -> Option<Vec<u8>> {
+    (compressed.len() < body.len()).then_some(compressed)
+}
@@ -0,0 +1,65 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Negotiation of protocol extensions, see `Config::set_extensions`.
+//
+// Each endpoint advertises its extensions with the `EXTENSIONS` flags of the
+// first data or window update frame it sends with `SYN` or `ACK`. Whichever
+// endpoint opens the first stream, the other one acknowledges it, so both
+// learn of the other's extensions with the first stream. The first such frame
+// received from the remote settles the negotiated extensions. Stock peers never
+// set these flags, so nothing is negotiated with them.
+
+use crate::frame::header::{self, Header, Tag};
+use crate::Extensions;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Negotiation {
+    /// Whether we advertised our extensions.
+    advertised: bool,
+    /// The extensions both endpoints advertised, once the remote did.
+    negotiated: Option<Extensions>,
+}
+
+impl Negotiation {
+    pub(crate) fn negotiated(&self) -> Option<Extensions> {
+        self.negotiated
+    }
+
+    /// Advertise the given extensions if the given frame header is the first
+    /// one to open or acknowledge a stream.
+    pub(crate) fn advertise<T>(&mut self, local: Extensions, header: &mut Header<T>) {
+        if !self.advertised && opens_or_acks(header) {
+            self.advertised = true;
+            header.extensions(local.bits())
+        }
+    }
+
+    /// Settle the negotiated extensions if the given frame header is the
+    /// first one of the remote to open or acknowledge a stream.
+    pub(crate) fn on_received<T>(
+        &mut self,
+        local: Extensions,
+        header: &Header<T>,
+    ) -> Option<Extensions> {
+        if self.negotiated.is_some() || !opens_or_acks(header) {
+            return None;
+        }
+        let remote = Extensions::from_bits(header.flags().extensions());
+        let negotiated = local.intersection(remote);
+        self.negotiated = Some(negotiated);
+        Some(negotiated)
+    }
+}
+
+fn opens_or_acks<T>(header: &Header<T>) -> bool {
+    matches!(header.tag(), Tag::Data | Tag::WindowUpdate)
+        && (header.flags().contains(header::SYN) || header.flags().contains(header::ACK))
+}
@@ -0,0 +1,95 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use super::{Id, StreamCommand};
+use crate::frame::header::{self, Tag, CONNECTION_ID};
+use crate::frame::Frame;
+use crate::{ConnectionError, EncodedFrame};
+use futures::{channel::mpsc, Sink};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Injects frames into a [`Connection`](crate::Connection), see
+/// [`Connection::frame_sink`](crate::Connection::frame_sink) (requires the
+/// `frame-sink` feature).
+///
+/// This is an expert API, e.g. to piggyback small out-of-band messages on
+/// pings with vendor specific flags or nonces, without opening a stream.
+/// Only pings of the session, i.e. with stream ID 0, can be injected, as
+/// any other frame would interfere with the state the connection keeps.
+/// Pings with the `SYN` flag are answered by the remote, pings without
+/// are merely received.
+///
+/// Frames are subject to the same backpressure as the frames of streams and
+/// are sent in order with the other control frames of the connection.
+#[derive(Debug, Clone)]
+pub struct FrameSink {
+    conn: Id,
+    sender: mpsc::Sender<StreamCommand>,
+}
+
+impl FrameSink {
+    pub(crate) fn new(conn: Id, sender: mpsc::Sender<StreamCommand>) -> Self {
+        FrameSink { conn, sender }
+    }
+
+    fn closed_err(&self) -> io::Error {
+        log::debug!("{}: connection is closed", self.conn);
+        io::Error::new(io::ErrorKind::BrokenPipe, ConnectionError::Closed)
+    }
+}
+
+impl Sink<EncodedFrame> for FrameSink {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.sender.poll_ready(cx).map_err(|_| this.closed_err())
+    }
+
+    /// Send the given frame.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the frame is not a ping
+    /// of the session.
+    fn start_send(self: Pin<&mut Self>, frame: EncodedFrame) -> io::Result<()> {
+        let this = self.get_mut();
+        let header = header::decode(&frame.header)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if header.tag() != Tag::Ping || header.stream_id() != CONNECTION_ID {
+            let msg = "only pings of the session can be injected";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        if !frame.body.is_empty() {
+            let msg = "pings have no body";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        log::trace!("{}: injecting: {}", this.conn, header);
+        this.sender
+            .start_send(StreamCommand::InjectFrame(Frame::new(header)))
+            .map_err(|_| this.closed_err())
+    }
+
+    /// Frames are sent by the connection, i.e. flushing only checks that it
+    /// is still alive.
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.sender.is_closed() {
+            return Poll::Ready(Err(self.closed_err()));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().sender.disconnect();
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use super::Stream;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::{future, io::AsyncWrite, ready, FutureExt, Sink};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// The size of the length prefix of a message.
+const PREFIX_SIZE: usize = 4;
+
+/// The default max. message size, see [`FramedStream::set_max_message_size`].
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// A [`Stream`] carrying messages, see [`Stream::into_framed`].
+///
+/// Each message is prefixed with its length as a big-endian `u32`. Messages
+/// are received via [`futures::Stream`] and sent via [`Sink`], where sent
+/// messages are buffered until the sink is flushed or the next message is
+/// sent.
+#[derive(Debug)]
+pub struct FramedStream {
+    stream: Stream,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    max_message_size: usize,
+}
+
+impl Stream {
+    /// Convert the stream into a [`FramedStream`], which sends and receives
+    /// length-prefixed messages (requires the `framed` feature).
+    pub fn into_framed(self) -> FramedStream {
+        FramedStream {
+            stream: self,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Read exactly `len` bytes within the given duration (requires the
+    /// `framed` feature).
+    ///
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] if the remote closes the
+    /// stream before, and with [`io::ErrorKind::TimedOut`] once the duration
+    /// elapsed. In both cases, the bytes read so far are discarded.
+    pub async fn read_exact_timeout(&mut self, len: usize, timeout: Duration) -> io::Result<Bytes> {
+        let mut timer = self.config().time.delay(timeout);
+        let mut buf = BytesMut::with_capacity(len);
+        future::poll_fn(|cx| {
+            while buf.len() < len {
+                let max = len - buf.len();
+                match self.poll_read_with(cx, max, |chunk| buf.extend_from_slice(chunk)) {
+                    Poll::Ready(Ok(0)) => {
+                        return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()))
+                    }
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => {
+                        ready!(timer.poll_unpin(cx));
+                        log::debug!("{}/{}: read timed out", self.conn(), self.id());
+                        return Poll::Ready(Err(io::ErrorKind::TimedOut.into()));
+                    }
+                }
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await?;
+        Ok(buf.freeze())
+    }
+
+    /// Read until the remote closes the stream, i.e. sends its FIN, but at
+    /// most `max_size` bytes (requires the `framed` feature).
+    ///
+    /// Only the remote's side of the stream is closed afterwards, i.e. a
+    /// response can still be written. Fails with [`io::ErrorKind::InvalidData`]
+    /// if the remote sends more than `max_size` bytes, and with the error of
+    /// the read if the stream is reset.
+    pub async fn read_until_fin(&mut self, max_size: usize) -> io::Result<Bytes> {
+        let mut buf = BytesMut::new();
+        future::poll_fn(|cx| loop {
+            // Read one byte more than allowed to tell whether there is more.
+            let max = max_size.saturating_add(1) - buf.len();
+            buf.reserve(std::cmp::min(self.buffer().len(), max));
+            let n = ready!(self.poll_read_with(cx, max, |chunk| buf.extend_from_slice(chunk)))?;
+            if n == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            if buf.len() > max_size {
+                let e = io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream data exceeds the max. size",
+                );
+                return Poll::Ready(Err(e));
+            }
+        })
+        .await?;
+        Ok(buf.freeze())
+    }
+}
+
+impl FramedStream {
+    /// Set the max. size of messages sent and received (must be <= `u32::MAX`),
+    /// 1 MiB by default.
+    ///
+    /// Sending a larger message fails with [`io::ErrorKind::InvalidInput`],
+    /// receiving one with [`io::ErrorKind::InvalidData`].
+    ///
+    /// # Panics
+    ///
+    /// If the given size exceeds `u32::MAX`.
+    pub fn set_max_message_size(&mut self, n: usize) -> &mut Self {
+        assert!(n <= u32::MAX as usize);
+        self.max_message_size = n;
+        self
+    }
+
+    pub fn get_ref(&self) -> &Stream {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut Stream {
+        &mut self.stream
+    }
+
+    /// The underlying stream, discarding any buffered data.
+    pub fn into_inner(self) -> Stream {
+        self.stream
+    }
+
+    /// Write out all buffered messages.
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_buf.is_empty() {
+            let n = ready!(Pin::new(&mut self.stream).poll_write(cx, &self.write_buf))?;
+            if n == 0 {
+                let msg = "failed to write whole message";
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::WriteZero, msg)));
+            }
+            self.write_buf.advance(n)
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Take the next complete message out of the read buffer, if any.
+    fn decode(&mut self) -> io::Result<Option<Bytes>> {
+        if self.read_buf.len() < PREFIX_SIZE {
+            return Ok(None);
+        }
+        let mut prefix = [0; PREFIX_SIZE];
+        prefix.copy_from_slice(&self.read_buf[..PREFIX_SIZE]);
+        let len = u32::from_be_bytes(prefix) as usize;
+        if len > self.max_message_size {
+            let msg = format!("message of {} bytes exceeds max. message size", len);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        if self.read_buf.len() < PREFIX_SIZE + len {
+            self.read_buf
+                .reserve(PREFIX_SIZE + len - self.read_buf.len());
+            return Ok(None);
+        }
+        self.read_buf.advance(PREFIX_SIZE);
+        Ok(Some(self.read_buf.split_to(len).freeze()))
+    }
+}
+
+impl futures::Stream for FramedStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(message) = this.decode()? {
+                return Poll::Ready(Some(Ok(message)));
+            }
+            let read_buf = &mut this.read_buf;
+            let n = ready!(this
+                .stream
+                .poll_read_with(cx, usize::MAX, |chunk| read_buf.extend_from_slice(chunk)))?;
+            if n == 0 {
+                if this.read_buf.is_empty() {
+                    return Poll::Ready(None);
+                }
+                let msg = "stream closed within a message";
+                return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg))));
+            }
+        }
+    }
+}
+
+impl Sink<Bytes> for FramedStream {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_write_buf(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Bytes) -> io::Result<()> {
+        let this = self.get_mut();
+        if message.len() > this.max_message_size {
+            let msg = format!(
+                "message of {} bytes exceeds max. message size",
+                message.len()
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        this.write_buf.reserve(PREFIX_SIZE + message.len());
+        this.write_buf.put_u32(message.len() as u32);
+        this.write_buf.put(message);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.stream).poll_close(cx)
+    }
+}
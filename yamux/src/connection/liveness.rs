@@ -0,0 +1,167 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use crate::frame::{
+    header::{Header, Ping},
+    Frame,
+};
+use crate::timer::{Delay, Time};
+use crate::ProbeMode;
+use futures::{ready, FutureExt};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Detection of unresponsive remotes, see [`crate::Config::set_liveness_probe`].
+///
+/// Keep-alive pings are sent every interval, one at a time, and must be
+/// answered within the timeout. Writes to the I/O resource, as reported via
+/// [`Liveness::on_write_blocked`], must not be blocked for longer than the
+/// timeout.
+#[derive(Debug)]
+pub(crate) struct Liveness {
+    mode: ProbeMode,
+    /// The nonce of the last ping sent.
+    nonce: u32,
+    /// The nonce and send time of the ping awaiting its pong, if any.
+    ping: Option<(u32, Instant)>,
+    /// When the next ping is due, unless one is awaiting its pong.
+    next_ping: Instant,
+    /// Since when writes to the I/O resource have been blocked, if they are.
+    write_blocked_since: Option<Instant>,
+    /// Running until the earliest of the above deadlines.
+    timer: Option<Delay>,
+    /// When `timer` fires.
+    due: Option<Instant>,
+    time: Time,
+}
+
+/// The outcome of a probe.
+#[derive(Debug)]
+pub(crate) enum Probe {
+    /// A keep-alive ping should be sent.
+    Ping(Frame<Ping>),
+    /// The remote is unresponsive.
+    Unresponsive {
+        ping_unanswered: Option<Duration>,
+        write_blocked: Option<Duration>,
+    },
+}
+
+impl Liveness {
+    pub(crate) fn new(mode: ProbeMode, time: Time) -> Self {
+        let now = time.now();
+        let mut liveness = Liveness {
+            mode,
+            nonce: 0,
+            ping: None,
+            next_ping: now + mode.ping_interval().unwrap_or_default(),
+            write_blocked_since: None,
+            timer: None,
+            due: None,
+            time,
+        };
+        liveness.reschedule();
+        liveness
+    }
+
+    /// Poll for the next ping to send, or whether the remote is unresponsive.
+    pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Probe> {
+        loop {
+            match self.timer.as_mut() {
+                Some(timer) => ready!(timer.poll_unpin(cx)),
+                None => return Poll::Pending,
+            }
+            self.timer = None;
+            self.due = None;
+
+            let now = self.time.now();
+            let timeout = self.mode.timeout();
+            let ping_unanswered = self
+                .ping
+                .map(|(_, sent)| now.saturating_duration_since(sent))
+                .filter(|d| *d >= timeout);
+            let write_blocked = self
+                .write_blocked_since
+                .map(|since| now.saturating_duration_since(since));
+            let write_stalled =
+                self.mode.detects_write_stall() && write_blocked.is_some_and(|d| d >= timeout);
+            if ping_unanswered.is_some() || write_stalled {
+                return Poll::Ready(Probe::Unresponsive {
+                    ping_unanswered,
+                    write_blocked,
+                });
+            }
+
+            let mut ping = None;
+            if let Some(interval) = self.mode.ping_interval() {
+                if self.ping.is_none() && now >= self.next_ping {
+                    self.nonce = self.nonce.wrapping_add(1);
+                    self.ping = Some((self.nonce, now));
+                    self.next_ping = now + interval;
+                    let mut header = Header::ping(self.nonce);
+                    header.syn();
+                    ping = Some(Frame::new(header));
+                }
+            }
+            self.reschedule();
+            if let Some(frame) = ping {
+                return Poll::Ready(Probe::Ping(frame));
+            }
+        }
+    }
+
+    /// A pong has been received.
+    pub(crate) fn on_pong(&mut self, nonce: u32) {
+        if self.ping.is_some_and(|(n, _)| n == nonce) {
+            self.ping = None;
+            self.schedule(self.next_ping)
+        }
+    }
+
+    /// Writes to the I/O resource are blocked.
+    pub(crate) fn on_write_blocked(&mut self) {
+        if self.write_blocked_since.is_none() {
+            let now = self.time.now();
+            self.write_blocked_since = Some(now);
+            if self.mode.detects_write_stall() {
+                self.schedule(now + self.mode.timeout())
+            }
+        }
+    }
+
+    /// All frames have been written to the I/O resource.
+    pub(crate) fn on_write_unblocked(&mut self) {
+        self.write_blocked_since = None
+    }
+
+    /// Run the timer until the earliest deadline.
+    fn reschedule(&mut self) {
+        let timeout = self.mode.timeout();
+        match self.ping {
+            Some((_, sent)) => self.schedule(sent + timeout),
+            None if self.mode.ping_interval().is_some() => self.schedule(self.next_ping),
+            None => {}
+        }
+        if let Some(since) = self.write_blocked_since {
+            if self.mode.detects_write_stall() {
+                self.schedule(since + timeout)
+            }
+        }
+    }
+
+    /// Run the timer until the given deadline, unless it fires earlier.
+    fn schedule(&mut self, deadline: Instant) {
+        if self.due.is_none_or(|due| deadline < due) {
+            let now = self.time.now();
+            self.timer = Some(self.time.delay(deadline.saturating_duration_since(now)));
+            self.due = Some(deadline)
+        }
+    }
+}
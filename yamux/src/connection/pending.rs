@@ -0,0 +1,119 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// The frames waiting to be sent.
+//
+// Control frames are sent before data frames, so that pings, window updates
+// and resets are not delayed behind megabytes of queued data:
+//
+// 1. Frames opening a stream count as control frames. As the first frame of
+//    their stream, no other frame of the stream is overtaken by them, and
+//    no control frame of the stream overtakes them in turn.
+// 2. Window updates releasing a stream ID (see `Config::set_stream_id_recycling`)
+//    stay in order with data frames, since they must follow all other frames
+//    of their stream. So do `GoAway`s, which conclude the frames sent.
+// 3. A reset discards the data frames of its stream which are still queued,
+//    since the remote would not accept them anyway.
+// 4. The reset of a stream which has been dropped stays in order with data
+//    frames, since the data written before the drop is still to be delivered.
+
+use crate::frame::{
+    header::{self, Tag},
+    Frame,
+};
+use std::collections::VecDeque;
+
+#[derive(Debug, Default)]
+pub(crate) struct PendingFrames {
+    control: VecDeque<Frame<()>>,
+    data: VecDeque<Frame<()>>,
+}
+
+impl PendingFrames {
+    /// Enqueue a frame after the frames of its kind.
+    pub(crate) fn push_back(&mut self, frame: Frame<()>) {
+        if is_control(&frame) {
+            self.discard_reset_data(&frame);
+            self.control.push_back(frame)
+        } else {
+            self.data.push_back(frame)
+        }
+    }
+
+    /// Enqueue a frame before the frames of its kind, e.g. a ping which
+    /// should not wait for other control frames.
+    pub(crate) fn push_front(&mut self, frame: Frame<()>) {
+        if is_control(&frame) {
+            self.discard_reset_data(&frame);
+            self.control.push_front(frame)
+        } else {
+            self.data.push_front(frame)
+        }
+    }
+
+    /// Enqueue a frame after all data frames, regardless of its kind, e.g.
+    /// the reset of a dropped stream (see above).
+    pub(crate) fn push_back_in_order(&mut self, frame: Frame<()>) {
+        self.data.push_back(frame)
+    }
+
+    /// Dequeue the next frame to send.
+    pub(crate) fn pop_front(&mut self) -> Option<Frame<()>> {
+        self.control.pop_front().or_else(|| self.data.pop_front())
+    }
+
+    /// The next frame to send.
+    pub(crate) fn front(&self) -> Option<&Frame<()>> {
+        self.control.front().or_else(|| self.data.front())
+    }
+
+    /// The last data frame, e.g. to append another one to.
+    pub(crate) fn last_data_mut(&mut self) -> Option<&mut Frame<()>> {
+        self.data.back_mut()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.control.len() + self.data.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.data.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.control.clear();
+        self.data.clear()
+    }
+
+    /// The frames in the order they are sent.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Frame<()>> {
+        self.control.iter().chain(self.data.iter())
+    }
+
+    /// Discard the queued data frames of the stream the given frame resets.
+    fn discard_reset_data(&mut self, frame: &Frame<()>) {
+        let header = frame.header();
+        if header.tag() == Tag::Data && header.flags().contains(header::RST) {
+            let stream_id = header.stream_id();
+            self.data.retain(|f| f.header().stream_id() != stream_id)
+        }
+    }
+}
+
+/// Whether the given frame is sent before data frames, see above.
+fn is_control(frame: &Frame<()>) -> bool {
+    let flags = frame.header().flags();
+    match frame.header().tag() {
+        Tag::Ping => true,
+        Tag::WindowUpdate => !flags.contains(header::RELEASE),
+        Tag::Data => flags.contains(header::RST) || flags.contains(header::SYN),
+        Tag::GoAway => false,
+    }
+}
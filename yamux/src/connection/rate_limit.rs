@@ -0,0 +1,77 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use crate::timer::{Delay, Time};
+use futures::FutureExt;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// A token bucket limiting the rate at which bytes are sent, see
+/// [`crate::Config::set_max_send_rate`] and [`crate::Stream::set_send_pacing`].
+///
+/// The bucket holds up to `capacity` bytes worth of tokens and is refilled
+/// at `rate` bytes per second.
+#[derive(Debug)]
+pub(crate) struct RateLimit {
+    rate: u64,
+    capacity: u64,
+    tokens: f64,
+    last_refill: Instant,
+    /// Started when waiting for enough tokens.
+    timer: Option<Delay>,
+    time: Time,
+}
+
+impl RateLimit {
+    pub(crate) fn new(rate: u64, capacity: u64, time: Time) -> Self {
+        RateLimit {
+            rate,
+            capacity,
+            tokens: capacity as f64,
+            last_refill: time.now(),
+            timer: None,
+            time,
+        }
+    }
+
+    /// Take the tokens for sending `n` bytes (or a full bucket, if less) once
+    /// they are available.
+    pub(crate) fn poll_take(&mut self, n: u64, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if let Some(timer) = self.timer.as_mut() {
+                if timer.poll_unpin(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                self.timer = None;
+            }
+            match self.take(n) {
+                None => return Poll::Ready(()),
+                Some(wait) => self.timer = Some(self.time.delay(wait)),
+            }
+        }
+    }
+
+    /// Take the tokens for sending `n` bytes if available, otherwise return
+    /// the time until they will be.
+    fn take(&mut self, n: u64) -> Option<Duration> {
+        let now = self.time.now();
+        let refill = now.duration_since(self.last_refill).as_secs_f64() * self.rate as f64;
+        self.tokens = f64::min(self.tokens + refill, self.capacity as f64);
+        self.last_refill = now;
+        let n = std::cmp::min(n, self.capacity) as f64;
+        if self.tokens >= n {
+            self.tokens -= n;
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            (n - self.tokens) / self.rate as f64,
+        ))
+    }
+}
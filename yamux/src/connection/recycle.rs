@@ -0,0 +1,79 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Bookkeeping for the recycling of stream IDs (see `Config::set_stream_id_recycling`).
+//
+// A stream ID may only be reused once neither endpoint will send or expect
+// any more frames for the stream it previously identified:
+//
+// 1. When a stream is garbage collected, its ID is not released right away,
+//    since `StreamCommand`s of the stream may still be enqueued. Only once
+//    the command channel has been drained the ID is released.
+// 2. Releasing the ID of a stream the remote opened means sending a window
+//    update frame with the `RELEASE` flag, after which we never send another
+//    frame for the stream.
+// 3. Releasing the ID of a stream we opened means waiting for the remote to
+//    release it as well. Once both endpoints did, the ID is free for reuse.
+
+use crate::frame::header::StreamId;
+use nohash_hasher::IntSet;
+use std::collections::VecDeque;
+
+#[derive(Debug, Default)]
+pub(crate) struct Recycler {
+    /// IDs of garbage collected streams which have not been released yet.
+    dropped: Vec<StreamId>,
+    /// Our IDs the remote released, but we did not yet.
+    released_by_remote: IntSet<StreamId>,
+    /// Our IDs we released, but the remote did not yet.
+    released_by_us: IntSet<StreamId>,
+    /// Our IDs released by both endpoints.
+    free: VecDeque<StreamId>,
+}
+
+impl Recycler {
+    /// A stream has been garbage collected.
+    pub(crate) fn on_dropped(&mut self, id: StreamId) {
+        self.dropped.push(id)
+    }
+
+    /// Take the IDs of all garbage collected streams to release them.
+    pub(crate) fn take_dropped(&mut self) -> Vec<StreamId> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// We released one of our IDs.
+    pub(crate) fn release_local(&mut self, id: StreamId) {
+        if self.released_by_remote.remove(&id) {
+            self.free.push_back(id)
+        } else {
+            self.released_by_us.insert(id);
+        }
+    }
+
+    /// The remote released one of our IDs.
+    pub(crate) fn release_remote(&mut self, id: StreamId) {
+        if self.released_by_us.remove(&id) {
+            self.free.push_back(id)
+        } else {
+            self.released_by_remote.insert(id);
+        }
+    }
+
+    /// Take an ID for reuse.
+    pub(crate) fn pop_free(&mut self) -> Option<StreamId> {
+        self.free.pop_front()
+    }
+
+    /// The number of IDs available for reuse.
+    pub(crate) fn num_free(&self) -> usize {
+        self.free.len()
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Why a connection sent a RST to the remote, see [`ResetsSent`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResetReason {
+    /// The stream was reset via [`crate::Connection::reset_stream`] or
+    /// [`crate::Control::reset_stream`].
+    Requested,
+    /// The remote did not acknowledge the stream in time, see
+    /// [`crate::Config::set_stream_open_timeout`].
+    OpenTimeout,
+    /// The application did not acknowledge an inbound stream in time, see
+    /// [`crate::Config::set_inbound_stream_ack_deadline`].
+    AckDeadline,
+    /// The stream was dropped before it was closed.
+    Dropped,
+    /// The stream was dropped after it was closed, while the remote was out of
+    /// credit and would otherwise wait for a window update forever.
+    DroppedWithoutCredit,
+    /// The remote sent more data than the receive window of the stream, see
+    /// [`crate::Config::set_window_violation_policy`].
+    WindowViolation,
+    /// The buffer of the stream grew beyond its limit, see
+    /// [`crate::Config::set_buffer_overflow_policy`].
+    BufferOverflow,
+    /// The remote opened a stream while the connection did not accept
+    /// streams, e.g. while paused or draining.
+    NotAccepting,
+    /// The remote opened more streams than allowed, see
+    /// [`crate::Config::set_max_num_streams_response`].
+    TooManyStreams,
+    /// The remote opened a stream rejected by the policy of
+    /// [`crate::Config::set_inbound_stream_policy`].
+    RejectedByPolicy,
+    /// The stream stopped reading via [`crate::Stream::stop_sending`], but the
+    /// remote does not support `STOP_SENDING`.
+    StopSending,
+    /// The remote sent data after half-closing the stream.
+    DataAfterFin,
+    /// Data or the opening or closing of the stream may have been lost with
+    /// a failed transport, see [`crate::Connection::reset_streams_in_flight`].
+    TransportLost,
+}
+
+impl ResetReason {
+    const ALL: [ResetReason; 13] = [
+        ResetReason::Requested,
+        ResetReason::OpenTimeout,
+        ResetReason::AckDeadline,
+        ResetReason::Dropped,
+        ResetReason::DroppedWithoutCredit,
+        ResetReason::WindowViolation,
+        ResetReason::BufferOverflow,
+        ResetReason::NotAccepting,
+        ResetReason::TooManyStreams,
+        ResetReason::RejectedByPolicy,
+        ResetReason::StopSending,
+        ResetReason::DataAfterFin,
+        ResetReason::TransportLost,
+    ];
+
+    /// The machine-readable name of the reason in snake case, e.g. `buffer_overflow`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResetReason::Requested => "requested",
+            ResetReason::OpenTimeout => "open_timeout",
+            ResetReason::AckDeadline => "ack_deadline",
+            ResetReason::Dropped => "dropped",
+            ResetReason::DroppedWithoutCredit => "dropped_without_credit",
+            ResetReason::WindowViolation => "window_violation",
+            ResetReason::BufferOverflow => "buffer_overflow",
+            ResetReason::NotAccepting => "not_accepting",
+            ResetReason::TooManyStreams => "too_many_streams",
+            ResetReason::RejectedByPolicy => "rejected_by_policy",
+            ResetReason::StopSending => "stop_sending",
+            ResetReason::DataAfterFin => "data_after_fin",
+            ResetReason::TransportLost => "transport_lost",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl fmt::Display for ResetReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The number of RSTs a connection sent, by reason, see
+/// [`crate::ConnectionStats::resets_sent`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResetsSent([u64; ResetReason::ALL.len()]);
+
+impl ResetsSent {
+    /// The number of RSTs sent for the given reason.
+    pub fn get(&self, reason: ResetReason) -> u64 {
+        self.0[reason.index()]
+    }
+
+    /// The number of RSTs sent for any reason.
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// The reasons for which RSTs have been sent, along with their number.
+    pub fn iter(&self) -> impl Iterator<Item = (ResetReason, u64)> + '_ {
+        ResetReason::ALL
+            .iter()
+            .map(move |r| (*r, self.get(*r)))
+            .filter(|(_, n)| *n > 0)
+    }
+
+    pub(crate) fn add(&mut self, reason: ResetReason) {
+        self.0[reason.index()] += 1
+    }
+}
+
+/// Limits how many messages are logged per second, see
+/// [`crate::Config::set_reset_log_budget`].
+#[derive(Debug)]
+pub(crate) struct LogBudget {
+    max: u32,
+    used: u32,
+    /// The number of messages not logged since the last one.
+    suppressed: u64,
+    since: Instant,
+}
+
+impl LogBudget {
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    pub(crate) fn new(max: u32, now: Instant) -> Self {
+        LogBudget {
+            max,
+            used: 0,
+            suppressed: 0,
+            since: now,
+        }
+    }
+
+    /// Whether a message may be logged at the given time, along with the
+    /// number of messages suppressed since the last one.
+    pub(crate) fn take(&mut self, now: Instant) -> Option<u64> {
+        if now.saturating_duration_since(self.since) >= Self::INTERVAL {
+            self.since = now;
+            self.used = 0
+        }
+        if self.used < self.max {
+            self.used += 1;
+            Some(std::mem::take(&mut self.suppressed))
+        } else {
+            self.suppressed += 1;
+            None
+        }
+    }
+}
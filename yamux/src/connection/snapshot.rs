@@ -0,0 +1,244 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use super::{
+    extensions::Negotiation, pending::PendingFrames, recycle::Recycler, Id, InboundHandler, Mode,
+    State, Stream, StreamCommand,
+};
+use crate::{frame::header::StreamId, Config, MAX_COMMAND_BACKLOG};
+use futures::channel::mpsc;
+use nohash_hasher::IntMap;
+use std::{fmt, sync::Arc};
+
+/// The state of a [`Connection`](crate::Connection) detached from its
+/// transport, see [`Connection::into_parts`](crate::Connection::into_parts).
+///
+/// The [`Stream`]s of the connection remain usable and continue once the
+/// snapshot is resumed via [`Connection::from_parts`](crate::Connection::from_parts).
+///
+/// With the `serde` feature, a snapshot serializes to its ID, mode, number of
+/// pending frames and the [`StreamSnapshot`]s of its streams, e.g. to send
+/// them to the remote for comparison. The snapshot itself owns the live
+/// streams and can therefore not be deserialized.
+pub struct Snapshot {
+    pub(crate) id: Id,
+    pub(crate) mode: Mode,
+    pub(crate) config: Arc<Config>,
+    pub(crate) next_id: u32,
+    pub(crate) streams: Streams,
+    pub(crate) stream_sender: mpsc::Sender<StreamCommand>,
+    pub(crate) stream_receiver: mpsc::Receiver<StreamCommand>,
+    pub(crate) dropped_streams: Vec<StreamId>,
+    pub(crate) pending_frames: PendingFrames,
+    pub(crate) draining: bool,
+    pub(crate) remote_draining: bool,
+    pub(crate) paused: bool,
+    pub(crate) recycler: Option<Box<Recycler>>,
+    pub(crate) extensions: Negotiation,
+    pub(crate) inbound_handler: Option<InboundHandler>,
+}
+
+impl Snapshot {
+    /// The state of a new connection.
+    pub(crate) fn new(config: Config, mode: Mode) -> Self {
+        let (stream_sender, stream_receiver) = mpsc::channel(MAX_COMMAND_BACKLOG);
+        let recycler = if config.stream_id_recycling {
+            Some(Box::default())
+        } else {
+            None
+        };
+        Snapshot {
+            id: Id::new(&config),
+            mode,
+            config: Arc::new(config),
+            next_id: match mode {
+                Mode::Client => 1,
+                Mode::Server => 2,
+            },
+            streams: Streams(IntMap::default()),
+            stream_sender,
+            stream_receiver,
+            dropped_streams: Vec::new(),
+            pending_frames: PendingFrames::default(),
+            draining: false,
+            remote_draining: false,
+            paused: false,
+            recycler,
+            extensions: Negotiation::default(),
+            inbound_handler: None,
+        }
+    }
+
+    /// The identifier of the connection.
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// The mode of the connection.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The number of frames which have not been passed to the transport yet
+    /// and will be sent once resumed.
+    pub fn pending_frames(&self) -> usize {
+        self.pending_frames.len()
+    }
+
+    /// The flow control state of all streams, ordered by stream ID.
+    pub fn streams(&self) -> Vec<StreamSnapshot> {
+        let mut streams = self
+            .streams
+            .0
+            .iter()
+            .map(|(id, s)| {
+                let shared = s.shared();
+                StreamSnapshot {
+                    id: *id,
+                    state: shared.state(),
+                    window: shared.window,
+                    credit: shared.credit,
+                    buffered: s.buffer().len(),
+                }
+            })
+            .collect::<Vec<_>>();
+        streams.sort_by_key(|s| s.id);
+        streams
+    }
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("id", &self.id)
+            .field("mode", &self.mode)
+            .field("streams", &self.streams.0.len())
+            .field("pending_frames", &self.pending_frames.len())
+            .finish()
+    }
+}
+
+/// The streams of a [`Snapshot`], which are closed if it is dropped instead
+/// of being resumed.
+pub(crate) struct Streams(pub(crate) IntMap<StreamId, Stream>);
+
+impl Streams {
+    pub(crate) fn into_inner(mut self) -> IntMap<StreamId, Stream> {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Drop for Streams {
+    fn drop(&mut self) {
+        for (id, s) in self.0.drain() {
+            let mut shared = s.shared();
+            shared.update_state(s.conn(), id, State::Closed);
+            shared.wake_all();
+        }
+    }
+}
+
+/// The flow control state of a stream in a [`Snapshot`].
+///
+/// With the `serde` feature, it implements `Serialize` and `Deserialize`,
+/// with the state in snake case, e.g. `send_closed`.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamSnapshot {
+    /// The stream ID.
+    pub id: StreamId,
+    /// The state of the stream.
+    pub state: State,
+    /// The remaining number of bytes the remote may send.
+    pub window: u32,
+    /// The remaining number of bytes we may send.
+    pub credit: u32,
+    /// The number of received bytes which have not been read yet.
+    pub buffered: usize,
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::{Snapshot, StreamSnapshot};
+    use crate::{connection::Mode, frame::header::StreamId, State};
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize)]
+    struct SnapshotRepr {
+        id: String,
+        mode: &'static str,
+        pending_frames: usize,
+        streams: Vec<StreamSnapshot>,
+    }
+
+    impl Serialize for Snapshot {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            SnapshotRepr {
+                id: self.id.to_string(),
+                mode: match self.mode {
+                    Mode::Client => "client",
+                    Mode::Server => "server",
+                },
+                pending_frames: self.pending_frames(),
+                streams: self.streams(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct StreamSnapshotRepr {
+        id: u32,
+        state: String,
+        window: u32,
+        credit: u32,
+        buffered: usize,
+    }
+
+    impl Serialize for StreamSnapshot {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            StreamSnapshotRepr {
+                id: self.id.val(),
+                state: match self.state {
+                    State::Open => "open",
+                    State::SendClosed => "send_closed",
+                    State::RecvClosed => "recv_closed",
+                    State::Closed => "closed",
+                }
+                .to_string(),
+                window: self.window,
+                credit: self.credit,
+                buffered: self.buffered,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for StreamSnapshot {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let s = StreamSnapshotRepr::deserialize(deserializer)?;
+            let state = match s.state.as_str() {
+                "open" => State::Open,
+                "send_closed" => State::SendClosed,
+                "recv_closed" => State::RecvClosed,
+                "closed" => State::Closed,
+                other => return Err(D::Error::custom(format!("invalid state {:?}", other))),
+            };
+            Ok(StreamSnapshot {
+                id: StreamId::new(s.id),
+                state,
+                window: s.window,
+                credit: s.credit,
+                buffered: s.buffered,
+            })
+        }
+    }
+}
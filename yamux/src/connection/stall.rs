@@ -0,0 +1,135 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use super::{Id, State, Stream};
+use crate::frame::header::StreamId;
+use crate::timer::{Delay, Time, Timer};
+use futures::FutureExt;
+use std::{fmt, sync::Arc, task::Context, time::Duration};
+
+/// Receives the streams a connection detects as stalled, see
+/// [`crate::Config::set_stall_detector`].
+///
+/// The observer is called while polling the connection and must not block.
+pub trait StallObserver: Send + Sync {
+    /// A stream stalled.
+    fn on_stall(&self, stall: &StalledStream);
+}
+
+/// A stream which has not been polled for longer than the threshold of the
+/// stall detector, even though it could make progress.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct StalledStream {
+    /// The connection of the stream.
+    pub connection: Id,
+    /// The stalled stream.
+    pub stream: StreamId,
+    /// The state of the stream.
+    pub state: State,
+    /// The number of bytes received but not read.
+    pub buffered: usize,
+    /// The number of bytes which can be sent.
+    pub credit: u32,
+    /// Whether a task waits to read, i.e. whether its waker has been lost
+    /// if `buffered` is > 0.
+    pub reader_waiting: bool,
+    /// The number of tasks waiting to write, whose wakers have been lost
+    /// if `credit` is > 0.
+    pub writers_waiting: usize,
+    /// For how long the stream has not been polled to read or write.
+    pub unpolled_for: Duration,
+}
+
+/// A [`StallObserver`] of a [`crate::Config`].
+#[derive(Clone)]
+pub(crate) struct StallObserverRef(pub(crate) Arc<dyn StallObserver>);
+
+impl fmt::Debug for StallObserverRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("StallObserver")
+    }
+}
+
+/// Detection of stalled streams, see [`crate::Config::set_stall_detector`].
+///
+/// The streams are checked whenever the threshold elapsed, i.e. a stall is
+/// detected within twice the threshold.
+#[derive(Debug)]
+pub(crate) struct StallDetector {
+    threshold: Duration,
+    observer: Option<StallObserverRef>,
+    timer: Delay,
+}
+
+impl StallDetector {
+    pub(crate) fn new(
+        threshold: Duration,
+        observer: Option<StallObserverRef>,
+        time: &Time,
+    ) -> Self {
+        StallDetector {
+            threshold,
+            observer,
+            timer: time.delay(threshold),
+        }
+    }
+
+    /// Check the given streams for stalls whenever the threshold elapsed.
+    pub(crate) fn poll<'a>(
+        &mut self,
+        cx: &mut Context<'_>,
+        connection: Id,
+        streams: impl Iterator<Item = &'a Stream> + Clone,
+        time: &Time,
+    ) {
+        while self.timer.poll_unpin(cx).is_ready() {
+            for stream in streams.clone() {
+                if let Some(stall) = self.check(connection, stream, time) {
+                    match &self.observer {
+                        Some(observer) => observer.0.on_stall(&stall),
+                        None => log::warn!(
+                            "{}/{}: stream stalled: {:?}",
+                            connection,
+                            stall.stream,
+                            stall
+                        ),
+                    }
+                }
+            }
+            self.timer.restart(self.threshold)
+        }
+    }
+
+    /// Whether the given stream stalled since it has last been checked.
+    fn check(&self, connection: Id, stream: &Stream, time: &Time) -> Option<StalledStream> {
+        let mut shared = stream.shared();
+        let unpolled_for = time.elapsed(shared.last_polled);
+        if shared.stall_reported || unpolled_for < self.threshold {
+            return None;
+        }
+        let buffered = stream.buffer().len();
+        let writers_waiting = shared.writers_waiting();
+        if buffered == 0 && (shared.credit == 0 || writers_waiting == 0) {
+            return None;
+        }
+        shared.stall_reported = true;
+        Some(StalledStream {
+            connection,
+            stream: stream.id(),
+            state: shared.state(),
+            buffered,
+            credit: shared.credit,
+            reader_waiting: shared.reader.is_some(),
+            writers_waiting,
+            unpolled_for,
+        })
+    }
+}
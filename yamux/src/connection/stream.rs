@@ -10,50 +10,82 @@
 
 use crate::{
     chunks::Chunks,
-    connection::{self, StreamCommand},
+    connection::{self, budget::FairShare, rate_limit::RateLimit, StreamCommand},
+    error::{ConnectionError, StreamError},
     frame::{
-        header::{Data, Header, StreamId, WindowUpdate},
+        header::{self, Data, Either, Header, StreamId, WindowUpdate},
         Frame,
     },
-    Config, WindowUpdateMode,
+    sync::{self, Mutex, MutexGuard},
+    Compression, Config, WindowUpdateMode, DEFAULT_CREDIT,
 };
 use futures::{
     channel::mpsc,
-    future::Either,
-    io::{AsyncRead, AsyncWrite},
+    future,
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, IoSlice},
     ready,
 };
-use parking_lot::{Mutex, MutexGuard};
 use std::convert::TryInto;
 use std::{
     fmt, io,
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
     sync::Arc,
     task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
+use yamux_core::flow;
 
-/// The state of a Yamux stream.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub enum State {
-    /// Open bidirectionally.
-    Open,
-    /// Open for incoming messages.
-    SendClosed,
-    /// Open for outgoing messages.
-    RecvClosed,
-    /// Closed (terminal state).
-    Closed,
+pub use yamux_core::stream::State;
+
+/// Counters of a [`Stream`], see [`Stream::metrics`].
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct StreamMetrics {
+    /// The number of bytes written to the stream.
+    pub bytes_sent: u64,
+    /// The number of bytes received from the remote.
+    pub bytes_received: u64,
+    /// The number of received bytes which have not been read yet.
+    pub buffered: usize,
+    /// The number of bytes allocated to buffer received data, including
+    /// spare capacity, which is 0 once all received data has been read.
+    pub buffered_memory: usize,
+    /// The time since the stream was created.
+    pub age: Duration,
+    /// The time since data was last sent or received.
+    pub idle: Duration,
+    /// The number of times the stream or its connection had to wait for the
+    /// other to release a lock of the state they share.
+    pub lock_contentions: u64,
 }
 
-impl State {
-    /// Can we receive messages over this stream?
-    pub fn can_read(self) -> bool {
-        !matches!(self, State::RecvClosed | State::Closed)
-    }
+/// Why the connection closed a [`Stream`], see [`Stream::close_reason`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The [`crate::Connection`] was dropped.
+    ConnectionDropped,
+    /// The underlying I/O resource was closed without the remote going away.
+    ConnectionClosed,
+    /// The remote terminated the connection with a `GoAway` frame carrying
+    /// the given error code.
+    GoAway(u32),
+    /// The connection was terminated because of a protocol violation of the
+    /// remote, see [`crate::ProtocolError`].
+    ProtocolError,
+    /// The connection failed otherwise, e.g. with an I/O error.
+    ConnectionFailed,
+}
 
-    /// Can we send messages over this stream?
-    pub fn can_write(self) -> bool {
-        !matches!(self, State::SendClosed | State::Closed)
+impl From<&ConnectionError> for CloseReason {
+    fn from(e: &ConnectionError) -> Self {
+        match e {
+            ConnectionError::Closed => CloseReason::ConnectionClosed,
+            ConnectionError::GoAwayReceived { code, .. } => CloseReason::GoAway(*code),
+            ConnectionError::Protocol(_) => CloseReason::ProtocolError,
+            _ => CloseReason::ConnectionFailed,
+        }
     }
 }
 
@@ -75,13 +107,65 @@ pub(crate) enum Flag {
 ///
 /// `Stream` implements [`AsyncRead`] and [`AsyncWrite`] and also
 /// [`futures::stream::Stream`].
+///
+/// If the remote terminates the connection with a `GoAway` error code, reads
+/// (once buffered data is consumed) and writes fail with an [`io::Error`] of
+/// kind [`io::ErrorKind::ConnectionAborted`], wrapping
+/// [`ConnectionError::GoAwayReceived`]. A normal `GoAway` only prevents new
+/// streams, existing ones remain usable.
+///
+/// If the stream is reset via [`crate::Connection::reset_stream`] or
+/// [`crate::Control::reset_stream`], buffered data is discarded and reads and
+/// writes fail with an [`io::Error`] of kind [`io::ErrorKind::ConnectionReset`].
+/// An outbound stream which the remote does not acknowledge in time (see
+/// [`crate::Config::set_stream_open_timeout`]) is reset as well, but reads and
+/// writes fail with kind [`io::ErrorKind::TimedOut`] instead.
+///
+/// # Half-close
+///
+/// [`Stream::finish`], like [`futures::AsyncWriteExt::close`], signals the end
+/// of the data written to the stream by sending a `FIN`, i.e. a data frame
+/// without a body, but keeps the stream open for reading:
+///
+/// | After                   | Reads                        | Writes                 |
+/// |-------------------------|------------------------------|------------------------|
+/// | `finish`                | return data until remote EOF | fail with `SendClosed` |
+/// | remote `finish`         | return EOF once data is read | succeed                |
+/// | both `finish`           | return EOF once data is read | fail with `SendClosed` |
+/// | reset                   | fail with `Reset`            | fail with `Reset`      |
+///
+/// Empty writes do not send a frame, unless the stream has not been opened
+/// yet, in which case they send the `SYN` opening it.
+///
+/// Other than after a `GoAway`, failed operations carry a [`StreamError`],
+/// telling apart writes after the stream has been closed, writes to a reset
+/// stream and writes after the connection has been closed.
+///
+/// # Cancellation safety
+///
+/// All operations of a `Stream` are cancellation-safe, i.e. a future, e.g. of
+/// [`futures::AsyncReadExt::read`], may be dropped in a `select!` without
+/// losing data or leaving the stream in an inconsistent state: Each poll
+/// either completes its operation, e.g. queues a frame and deducts its credit,
+/// or has no effect other than registering its waker. Futures completing
+/// multiple operations, e.g. [`futures::AsyncWriteExt::write_all`], may be
+/// cancelled in between them though, i.e. after having written part of the data.
+/// [`Stream::write_all_vectored`] advances the given slices, so that they tell
+/// what has not been written.
 pub struct Stream {
     id: StreamId,
     conn: connection::Id,
     config: Arc<Config>,
     sender: mpsc::Sender<StreamCommand>,
-    flag: Flag,
-    shared: Arc<Mutex<Shared>>,
+    outbound: bool,
+    shared: sync::Arc<SharedState>,
+    /// The application protocol tag, once written or read.
+    protocol_tag: Option<u16>,
+    /// The bytes of the protocol tag read so far, so that reading the tag
+    /// can be cancelled without losing them.
+    protocol_tag_read: Option<u8>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl fmt::Debug for Stream {
@@ -99,6 +183,28 @@ impl fmt::Display for Stream {
     }
 }
 
+impl Drop for Stream {
+    fn drop(&mut self) {
+        let handles = {
+            let mut shared = self.shared();
+            shared.handles -= 1;
+            shared.handles
+        };
+        if handles != 1 {
+            return;
+        }
+        // Only the connection's handle is left, tell it to remove the stream.
+        // Should our slot in the command channel be taken, a new sender has a
+        // guaranteed one. If the connection is gone, there is nothing to remove.
+        let cmd = StreamCommand::Dropped(self.id);
+        if let Err(e) = self.sender.try_send(cmd) {
+            if e.is_full() {
+                let _ = self.sender.clone().try_send(e.into_inner());
+            }
+        }
+    }
+}
+
 impl Stream {
     pub(crate) fn new(
         id: StreamId,
@@ -107,14 +213,23 @@ impl Stream {
         window: u32,
         credit: u32,
         sender: mpsc::Sender<StreamCommand>,
+        outbound: bool,
     ) -> Self {
+        let mut shared = Shared::new(window, credit, config.clone());
+        // We acknowledge inbound streams ourselves.
+        shared.acknowledged = !outbound;
         Stream {
             id,
             conn,
-            config: config.clone(),
+            config,
             sender,
-            flag: Flag::None,
-            shared: Arc::new(Mutex::new(Shared::new(window, credit, config))),
+            outbound,
+            shared: sync::Arc::new(SharedState::new(shared)),
+            protocol_tag: None,
+            protocol_tag_read: None,
+            // Streams are created while the span of their connection is entered.
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("stream", stream.id = id.val()),
         }
     }
 
@@ -123,84 +238,552 @@ impl Stream {
         self.id
     }
 
+    /// The connection this stream belongs to.
+    pub(crate) fn conn(&self) -> connection::Id {
+        self.conn
+    }
+
+    /// The configuration of the connection this stream belongs to.
+    #[cfg(feature = "framed")]
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Was this stream opened by us?
+    pub fn is_outbound(&self) -> bool {
+        self.outbound
+    }
+
+    /// Has the remote acknowledged this stream?
+    ///
+    /// Inbound streams are always considered acknowledged.
+    pub fn is_acknowledged(&self) -> bool {
+        self.shared().acknowledged
+    }
+
+    /// The current state of this stream.
+    pub fn state(&self) -> State {
+        self.shared().state()
+    }
+
+    /// The number of bytes which can currently be written without waiting
+    /// for the remote to grant more credit.
+    pub fn send_credit(&self) -> u32 {
+        self.shared().credit
+    }
+
+    /// The number of bytes the remote can currently send without waiting for
+    /// us to grant more credit.
+    pub fn recv_window(&self) -> u32 {
+        self.shared().window
+    }
+
+    /// Pass data received from now on to the inbound handler of the connection
+    /// (see [`crate::Connection::set_inbound_handler`]) instead of buffering it.
+    ///
+    /// Data buffered before can still be read. Reads report the end of the
+    /// stream once the remote closed it and the buffer is empty. Without an
+    /// inbound handler, data is buffered as usual.
+    pub fn set_push_mode(&mut self, b: bool) {
+        self.shared().push_mode = b
+    }
+
+    /// The number of bytes sent and received, the number of buffered bytes
+    /// and the times since creation and last activity of this stream.
+    pub fn metrics(&self) -> StreamMetrics {
+        let shared = self.shared();
+        let buffer = self.buffer();
+        StreamMetrics {
+            bytes_sent: self.shared.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.shared.bytes_received.load(Ordering::Relaxed),
+            buffered: buffer.len(),
+            buffered_memory: buffer.memory(),
+            age: shared.config.time.elapsed(shared.created),
+            idle: shared.config.time.elapsed(shared.last_activity),
+            lock_contentions: self.lock_contentions(),
+        }
+    }
+
+    /// Why the connection closed this stream, if it did.
+    ///
+    /// Once a read or write reports the end of the stream or fails because
+    /// the connection went away, e.g. was dropped or failed, this tells why.
+    /// Returns `None` as long as the connection is alive, including after the
+    /// stream has been closed or reset regularly.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        let shared = self.shared();
+        shared
+            .close_reason
+            .or(shared.go_away.map(CloseReason::GoAway))
+    }
+
+    /// Write all of the given slices, gathering them into as few frames as
+    /// possible, see [`AsyncWrite::poll_write_vectored`].
+    ///
+    /// The slices are advanced past the written bytes, i.e. if the write
+    /// fails, they hold what has not been written.
+    pub async fn write_all_vectored(&mut self, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+        IoSlice::advance_slices(&mut bufs, 0);
+        while !bufs.is_empty() {
+            let n = self.write_vectored(bufs).await?;
+            if n == 0 {
+                return Err(self.connection_closed_err());
+            }
+            IoSlice::advance_slices(&mut bufs, n);
+        }
+        Ok(())
+    }
+
+    /// Wait until at least `min_bytes` can be written without waiting for the
+    /// remote to grant more credit and return the available credit.
+    ///
+    /// Note that the remote may never grant more credit than its receive
+    /// window, which is at least 256 KiB.
+    pub async fn wait_for_credit(&mut self, min_bytes: u32) -> io::Result<u32> {
+        future::poll_fn(|cx| self.poll_wait_for_credit(cx, min_bytes)).await
+    }
+
+    /// Poll-based version of [`Stream::wait_for_credit`].
+    pub fn poll_wait_for_credit(
+        &mut self,
+        cx: &mut Context,
+        min_bytes: u32,
+    ) -> Poll<io::Result<u32>> {
+        let mut shared = self.shared();
+        shared.on_polled();
+        if let Some(e) = self.write_err(&shared) {
+            return Poll::Ready(Err(e));
+        }
+        if shared.credit < min_bytes {
+            shared.register_writer(cx.waker());
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(shared.credit))
+    }
+
+    /// Split this stream into a read half and a write half.
+    ///
+    /// Unlike splitting via [`futures::AsyncReadExt::split`], both halves can
+    /// be used without a lock and the [`WriteHalf`] can be cloned, e.g. to
+    /// let multiple tasks write to the stream.
+    pub fn into_split(self) -> (ReadHalf, WriteHalf) {
+        (ReadHalf(self.clone()), WriteHalf(self))
+    }
+
+    /// Signal the end of the data written to this stream, i.e. half-close it,
+    /// while it remains open for reading, e.g. to end a request and read the
+    /// response.
+    ///
+    /// Unlike [`futures::AsyncWriteExt::close`], finishing a stream which has
+    /// already been finished does nothing. Fails if the stream has been reset.
+    pub async fn finish(&mut self) -> io::Result<()> {
+        future::poll_fn(|cx| self.poll_finish(cx)).await
+    }
+
+    /// Poll-based version of [`Stream::finish`].
+    pub fn poll_finish(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        ready!(Pin::new(&mut *self).poll_flush(cx))?;
+        if !self.shared().state().can_write() {
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(self).poll_close(cx)
+    }
+
+    /// Whether this stream has been closed for writing, by us or by a reset.
     pub fn is_write_closed(&self) -> bool {
-        matches!(self.shared().state(), State::SendClosed)
+        !self.shared().state().can_write()
+    }
+
+    /// Whether this stream has been closed for reading, by the remote or by
+    /// a reset.
+    ///
+    /// Data received before may still be buffered and readable.
+    pub fn is_read_closed(&self) -> bool {
+        !self.shared().state().can_read()
     }
 
+    /// Whether this stream has been closed in both directions or reset.
     pub fn is_closed(&self) -> bool {
         matches!(self.shared().state(), State::Closed)
     }
 
+    /// Wait until this stream has been closed in both directions, reset or
+    /// closed along with its connection, without reading from it.
+    ///
+    /// Any number of tasks may wait, e.g. via clones of the halves of a
+    /// [`Stream::into_split`]. See [`Stream::close_reason`] for why the
+    /// connection closed the stream, if it did.
+    pub async fn closed(&self) {
+        future::poll_fn(|cx| self.poll_closed(cx)).await
+    }
+
+    /// Poll-based version of [`Stream::closed`].
+    pub fn poll_closed(&self, cx: &mut Context) -> Poll<()> {
+        let mut shared = self.shared();
+        if shared.state() == State::Closed || shared.go_away.is_some() {
+            return Poll::Ready(());
+        }
+        let closing = &mut shared.extras_mut().closing;
+        if !closing.iter().any(|w| w.will_wake(cx.waker())) {
+            closing.push(cx.waker().clone())
+        }
+        Poll::Pending
+    }
+
+    /// Set the receive window of this inbound stream (must be >= 256 KiB).
+    ///
+    /// This only has an effect as long as the stream has not been acknowledged
+    /// yet, i.e. before anything has been sent over it, in which case the
+    /// resulting credit is granted to the remote along with the ACK. Returns
+    /// `false` if the stream has already been acknowledged.
+    ///
+    /// # Panics
+    ///
+    /// If the given receive window is < 256 KiB.
+    pub fn set_receive_window(&mut self, n: u32) -> bool {
+        assert!(n >= DEFAULT_CREDIT);
+
+        let mut shared = self.shared.lock();
+
+        if shared.flag != Flag::Ack {
+            return false;
+        }
+
+        let buffer_len: u32 = self.buffer().len().try_into().unwrap_or(u32::MAX);
+        let credit = n.saturating_sub(buffer_len).saturating_sub(shared.window);
+
+        if credit > 0 {
+            let mut frame = Frame::window_update(self.id, credit).right();
+            frame.header_mut().ack();
+            shared.announce_compression(frame.header_mut());
+            // Nothing has been sent over this stream yet, thus our guaranteed
+            // slot in the command channel is available.
+            if self
+                .sender
+                .try_send(StreamCommand::SendFrame(frame))
+                .is_err()
+            {
+                return false;
+            }
+            shared.window += credit;
+            shared.flag = Flag::None;
+        }
+
+        shared.receive_window = n;
+        true
+    }
+
+    /// Limit the rate at which data is written to this stream to the given
+    /// number of bytes per second (must be > 0), or remove the limit.
+    ///
+    /// Writes are delayed until the data may be sent, without using up credit
+    /// in the meantime. This is independent of the connection's send rate
+    /// limit (see [`Config::set_max_send_rate`](crate::Config::set_max_send_rate)).
+    ///
+    /// # Panics
+    ///
+    /// If the given rate is 0.
+    pub fn set_send_pacing(&mut self, bytes_per_sec: Option<u64>) {
+        assert!(bytes_per_sec != Some(0));
+        let capacity = self.config.split_send_size as u64;
+        let mut shared = self.shared();
+        let pacing =
+            bytes_per_sec.map(|rate| RateLimit::new(rate, capacity, self.config.time.clone()));
+        if pacing.is_some() || shared.extras.is_some() {
+            shared.extras_mut().pacing = pacing
+        }
+        shared.wake_writers();
+    }
+
+    /// Send an empty data frame whenever no data has been sent or received
+    /// over this stream for the given interval (must be > 0), or stop doing so.
+    ///
+    /// This keeps the stream alive in middleboxes which track the idle time
+    /// of individual streams, e.g. relays carrying each stream separately.
+    /// Keep-alives are only sent while the stream can be written to and once
+    /// it has been opened, i.e. not before the SYN of a lazily opened stream.
+    ///
+    /// # Panics
+    ///
+    /// If the given interval is 0.
+    pub fn set_keepalive(&mut self, interval: Option<Duration>) {
+        assert!(interval != Some(Duration::ZERO));
+        let mut shared = self.shared();
+        if interval.is_some() || shared.extras.is_some() {
+            shared.extras_mut().keepalive = interval
+        }
+        drop(shared);
+        if interval.is_some() {
+            // A new sender has a guaranteed slot in the command channel. If
+            // the connection is gone, there is nothing to keep alive.
+            let _ = self.sender.clone().try_send(StreamCommand::KeepAlive);
+        }
+    }
+
+    /// Tell the remote that any further data on this stream will be discarded.
+    ///
+    /// The read side of this stream is closed and data buffered so far is
+    /// dropped. The remote's writes fail immediately instead of using up the
+    /// remaining credit, whereas our own write side is not affected.
+    ///
+    /// This is an extension to the Yamux specification, signalled by a window
+    /// update frame with the `STOP_SENDING` (`0x20`) flag. Unless the remote
+    /// advertised [`crate::Extensions::STOP_SENDING`], the stream is reset
+    /// instead, which closes our write side as well.
+    pub async fn stop_sending(&mut self) -> io::Result<()> {
+        future::poll_fn(|cx| self.poll_stop_sending(cx)).await
+    }
+
+    /// Poll-based version of [`Stream::stop_sending`].
+    pub fn poll_stop_sending(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        if !self.shared().state().can_read() {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self
+            .sender
+            .poll_ready(cx)
+            .map_err(|_| self.connection_closed_err())?);
+        let mut shared = self.shared.lock();
+        let mut frame = Frame::stop_sending(self.id).right();
+        shared.add_flag(frame.header_mut());
+        log::trace!("{}/{}: stop sending", self.conn, self.id);
+        let cmd = StreamCommand::SendFrame(frame);
+        self.sender
+            .start_send(cmd)
+            .map_err(|_| self.connection_closed_err())?;
+        shared.update_state(self.conn, self.id, State::RecvClosed);
+        shared.stopped_reading = true;
+        *self.buffer() = Chunks::new();
+        Poll::Ready(Ok(()))
+    }
+
+    /// The application protocol tag of this stream, i.e. the protocol ID the
+    /// remote opened it with via [`crate::Control::open_stream_tagged`].
+    ///
+    /// The tag is read from the stream on the first call, i.e. the first 2
+    /// bytes of an inbound stream are consumed, and cached for later calls.
+    /// Streams opened without a tag must therefore not be asked for one. For
+    /// outbound streams, the tag they were opened with is returned.
+    pub async fn protocol_tag(&mut self) -> io::Result<u16> {
+        future::poll_fn(|cx| self.poll_protocol_tag(cx)).await
+    }
+
+    /// Poll-based version of [`Stream::protocol_tag`].
+    pub fn poll_protocol_tag(&mut self, cx: &mut Context) -> Poll<io::Result<u16>> {
+        loop {
+            if let Some(tag) = self.protocol_tag {
+                return Poll::Ready(Ok(tag));
+            }
+            let mut byte = [0];
+            if ready!(Pin::new(&mut *self).poll_read(cx, &mut byte))? == 0 {
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            match self.protocol_tag_read.take() {
+                None => self.protocol_tag_read = Some(byte[0]),
+                Some(first) => self.protocol_tag = Some(u16::from_be_bytes([first, byte[0]])),
+            }
+        }
+    }
+
+    /// Copy received data into `buf` without consuming it, i.e. the data is
+    /// read again by the next read, e.g. to sniff the protocol of a stream
+    /// before handing it on.
+    ///
+    /// Waits until data has been received and returns the number of bytes
+    /// copied, at most the length of `buf`, or 0 at the end of the stream.
+    /// Only the data received so far is copied, which may be less than
+    /// requested. As the data is not consumed, no credit is granted to the
+    /// remote, i.e. more than the receive window can not be peeked at.
+    pub async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_peek(cx, buf)).await
+    }
+
+    /// Poll-based version of [`Stream::peek`].
+    pub fn poll_peek(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if !self.config.read_after_close && self.sender.is_closed() {
+            if let Some(code) = self.shared().go_away {
+                return Poll::Ready(Err(self.go_away_err(code)));
+            }
+            return Poll::Ready(Ok(0));
+        }
+
+        self.shared().on_polled();
+        loop {
+            let mut n = 0;
+            for chunk in self.buffer().iter() {
+                if n == buf.len() {
+                    break;
+                }
+                let k = std::cmp::min(chunk.len(), buf.len() - n);
+                buf[n..n + k].copy_from_slice(&chunk.as_ref()[..k]);
+                n += k;
+            }
+
+            if n > 0 || buf.is_empty() {
+                log::trace!("{}/{}: peeked at {} bytes", self.conn, self.id, n);
+                return Poll::Ready(Ok(n));
+            }
+
+            let mut shared = self.shared();
+            // Data may have been received since the buffer was empty.
+            if self.buffer().is_empty() {
+                return self.poll_empty_buffer(&mut shared, cx);
+            }
+        }
+    }
+
+    /// Write the given application protocol tag as the first bytes of this
+    /// stream, see [`Stream::protocol_tag`].
+    pub(crate) async fn write_protocol_tag(&mut self, tag: u16) -> io::Result<()> {
+        debug_assert!(self.protocol_tag.is_none());
+        self.write_all(&tag.to_be_bytes()).await?;
+        self.protocol_tag = Some(tag);
+        Ok(())
+    }
+
     /// Set the flag that should be set on the next outbound frame header.
     pub(crate) fn set_flag(&mut self, flag: Flag) {
-        self.flag = flag
+        self.shared().flag = flag
+    }
+
+    pub(crate) fn lock_contentions(&self) -> u64 {
+        self.shared.contentions.load(Ordering::Relaxed)
     }
 
-    pub(crate) fn strong_count(&self) -> usize {
-        Arc::strong_count(&self.shared)
+    /// Whether the connection holds the only handle of this stream, i.e. the
+    /// stream has been dropped by everybody else.
+    pub(crate) fn is_dropped(&self) -> bool {
+        self.shared().handles == 1
     }
 
     pub(crate) fn shared(&self) -> MutexGuard<'_, Shared> {
         self.shared.lock()
     }
 
+    /// The received data not yet read. If the state of the stream is locked
+    /// as well, it must be locked first.
+    pub(crate) fn buffer(&self) -> MutexGuard<'_, Chunks> {
+        self.shared.buffer()
+    }
+
+    /// Reset the stream locally, see [`Shared::reset`], discarding the
+    /// buffered data. Returns the previous state.
+    pub(crate) fn reset(&self, shared: &mut Shared, reset: Reset) -> State {
+        let previous = shared.reset(self.conn, self.id, reset);
+        *self.buffer() = Chunks::new();
+        previous
+    }
+
+    /// Account for `n` bytes of data received.
+    pub(crate) fn on_received(&self, shared: &mut Shared, n: u32) {
+        shared.on_received();
+        self.shared
+            .bytes_received
+            .fetch_add(u64::from(n), Ordering::Relaxed);
+    }
+
     pub(crate) fn clone(&self) -> Self {
+        self.shared().handles += 1;
         Stream {
             id: self.id,
             conn: self.conn,
             config: self.config.clone(),
             sender: self.sender.clone(),
-            flag: self.flag,
+            outbound: self.outbound,
             shared: self.shared.clone(),
+            protocol_tag: self.protocol_tag,
+            protocol_tag_read: self.protocol_tag_read,
+            #[cfg(feature = "tracing")]
+            span: self.span.clone(),
         }
     }
 
-    fn write_zero_err(&self) -> io::Error {
-        let msg = format!("{}/{}: connection is closed", self.conn, self.id);
-        io::Error::new(io::ErrorKind::WriteZero, msg)
+    /// The error reads and writes fail with after the remote terminated the
+    /// connection with the given `GoAway` error code.
+    fn go_away_err(&self, code: u32) -> io::Error {
+        let e = ConnectionError::GoAwayReceived {
+            connection: self.conn,
+            code,
+        };
+        io::Error::new(io::ErrorKind::ConnectionAborted, e)
     }
 
-    /// Set ACK or SYN flag if necessary.
-    fn add_flag(&mut self, header: &mut Header<Either<Data, WindowUpdate>>) {
-        match self.flag {
-            Flag::None => (),
-            Flag::Syn => {
-                header.syn();
-                self.flag = Flag::None
-            }
-            Flag::Ack => {
-                header.ack();
-                self.flag = Flag::None
-            }
+    /// The error writes fail with once the connection is gone.
+    fn closed_err(&self) -> io::Error {
+        match self.shared().go_away {
+            Some(code) => self.go_away_err(code),
+            None => self.connection_closed_err(),
         }
     }
 
-    /// Send new credit to the sending side via a window update message if
-    /// permitted.
+    fn reset_err(&self, reset: Reset) -> io::Error {
+        log::debug!("{}/{}: stream was reset: {:?}", self.conn, self.id, reset);
+        let kind = match reset {
+            Reset::Requested | Reset::StopSending => io::ErrorKind::ConnectionReset,
+            Reset::OpenTimeout | Reset::AckDeadline => io::ErrorKind::TimedOut,
+            Reset::WindowViolation | Reset::DataAfterFin => io::ErrorKind::InvalidData,
+            Reset::TransportLost => io::ErrorKind::ConnectionAborted,
+        };
+        StreamError::Reset.into_io(self.conn, self.id, kind)
+    }
+
+    /// The error writes fail with once the stream is closed for writing.
+    fn send_closed_err(&self) -> io::Error {
+        log::debug!("{}/{}: stream is closed for writing", self.conn, self.id);
+        StreamError::SendClosed.into_io(self.conn, self.id, io::ErrorKind::BrokenPipe)
+    }
+
+    fn connection_closed_err(&self) -> io::Error {
+        log::debug!("{}/{}: connection is closed", self.conn, self.id);
+        StreamError::ConnectionClosed.into_io(self.conn, self.id, io::ErrorKind::WriteZero)
+    }
+
+    /// Mark the stream as polled to read and send new credit to the sending
+    /// side via a window update message if permitted.
     fn send_window_update(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
         // When using [`WindowUpdateMode::OnReceive`] window update messages are
-        // send early on data receival (see [`crate::Connection::on_frame`]).
-        if matches!(self.config.window_update_mode, WindowUpdateMode::OnReceive) {
+        // send early on data receival (see [`crate::Connection::on_frame`]),
+        // unless the buffer overflowed.
+        let mut shared = self.shared.lock();
+        shared.on_polled();
+        if matches!(self.config.window_update_mode, WindowUpdateMode::OnReceive)
+            && !shared.backpressured
+        {
             return Poll::Ready(Ok(()));
         }
 
-        let mut shared = self.shared.lock();
-
-        if let Some(credit) = shared.next_window_update() {
-            ready!(self
-                .sender
-                .poll_ready(cx)
-                .map_err(|_| self.write_zero_err())?);
+        let buffered = self.buffer().len();
+        let credit = match self.config.window_update_mode {
+            // While the window is exhausted, a read may make room for the
+            // credit the connection would grant if the stream was not read.
+            WindowUpdateMode::Hybrid => shared
+                .next_window_update(buffered)
+                .or_else(|| shared.next_fallback_window_update(buffered)),
+            _ => shared.next_window_update(buffered),
+        };
+        if let Some(credit) = credit {
+            match self.sender.poll_ready(cx) {
+                Poll::Ready(Ok(())) => shared.window_update_blocked = false,
+                Poll::Ready(Err(_)) => return Poll::Ready(Err(self.connection_closed_err())),
+                Poll::Pending => {
+                    // With `WindowUpdateMode::Hybrid` the connection will
+                    // send the window update on our behalf if need be.
+                    shared.window_update_blocked = true;
+                    return Poll::Pending;
+                }
+            }
 
             shared.window += credit;
-            drop(shared);
+            if buffered < self.config.max_buffer_size {
+                shared.backpressured = false;
+            }
 
             let mut frame = Frame::window_update(self.id, credit).right();
-            self.add_flag(frame.header_mut());
+            shared.add_flag(frame.header_mut());
             let cmd = StreamCommand::SendFrame(frame);
             self.sender
                 .start_send(cmd)
-                .map_err(|_| self.write_zero_err())?;
+                .map_err(|_| self.connection_closed_err())?;
         }
 
         Poll::Ready(Ok(()))
@@ -221,7 +804,13 @@ impl futures::stream::Stream for Stream {
     type Item = io::Result<Packet>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
         if !self.config.read_after_close && self.sender.is_closed() {
+            if let Some(code) = self.shared().go_away {
+                return Poll::Ready(Some(Err(self.go_away_err(code))));
+            }
             return Poll::Ready(None);
         }
 
@@ -232,24 +821,38 @@ impl futures::stream::Stream for Stream {
             Poll::Pending => {}
         }
 
-        let mut shared = self.shared();
-
-        if let Some(bytes) = shared.buffer.pop() {
-            let off = bytes.offset();
-            let mut vec = bytes.into_vec();
-            if off != 0 {
-                // This should generally not happen when the stream is used only as
-                // a `futures::stream::Stream` since the whole point of this impl is
-                // to consume chunks atomically. It may perhaps happen when mixing
-                // this impl and the `AsyncRead` one.
-                log::debug!(
-                    "{}/{}: chunk has been partially consumed",
-                    self.conn,
-                    self.id
-                );
-                vec = vec.split_off(off)
+        let mut shared = loop {
+            let chunk = self.buffer().pop();
+            if let Some(bytes) = chunk {
+                let off = bytes.offset();
+                let mut vec = bytes.into_vec();
+                if off != 0 {
+                    // This should generally not happen when the stream is used only as
+                    // a `futures::stream::Stream` since the whole point of this impl is
+                    // to consume chunks atomically. It may perhaps happen when mixing
+                    // this impl and the `AsyncRead` one.
+                    log::debug!(
+                        "{}/{}: chunk has been partially consumed",
+                        self.conn,
+                        self.id
+                    );
+                    vec = vec.split_off(off)
+                }
+                return Poll::Ready(Some(Ok(Packet(vec))));
             }
-            return Poll::Ready(Some(Ok(Packet(vec))));
+            let shared = self.shared();
+            // Data may have been received since the buffer was empty.
+            if self.buffer().is_empty() {
+                break shared;
+            }
+        };
+
+        if let Some(code) = shared.go_away {
+            return Poll::Ready(Some(Err(self.go_away_err(code))));
+        }
+
+        if let Some(reset) = shared.reset {
+            return Poll::Ready(Some(Err(self.reset_err(reset))));
         }
 
         // Buffer is empty, let's check if we can expect to read more data.
@@ -260,7 +863,7 @@ impl futures::stream::Stream for Stream {
 
         // Since we have no more data at this point, we want to be woken up
         // by the connection when more becomes available for us.
-        shared.reader = Some(cx.waker().clone());
+        shared.register_reader(cx.waker());
 
         Poll::Pending
     }
@@ -274,7 +877,29 @@ impl AsyncRead for Stream {
         cx: &mut Context,
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
+        let mut n = 0;
+        self.poll_read_with(cx, buf.len(), |chunk| {
+            buf[n..n + chunk.len()].copy_from_slice(chunk);
+            n += chunk.len();
+        })
+    }
+}
+
+impl Stream {
+    /// Read up to `max` buffered bytes, passing them to `put` chunk by chunk.
+    pub(crate) fn poll_read_with(
+        &mut self,
+        cx: &mut Context,
+        max: usize,
+        mut put: impl FnMut(&[u8]),
+    ) -> Poll<io::Result<usize>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
         if !self.config.read_after_close && self.sender.is_closed() {
+            if let Some(code) = self.shared().go_away {
+                return Poll::Ready(Err(self.go_away_err(code)));
+            }
             return Poll::Ready(Ok(0));
         }
 
@@ -285,26 +910,53 @@ impl AsyncRead for Stream {
             Poll::Pending => {}
         }
 
-        // Copy data from stream buffer.
-        let mut shared = self.shared();
-        let mut n = 0;
-        while let Some(chunk) = shared.buffer.front_mut() {
-            if chunk.is_empty() {
-                shared.buffer.pop();
-                continue;
+        loop {
+            // Copy data from the stream buffer, holding only its lock, so
+            // that the connection can update the rest of the state meanwhile.
+            let mut n = 0;
+            let mut buffer = self.buffer();
+            while let Some(chunk) = buffer.front_mut() {
+                if chunk.is_empty() {
+                    buffer.drop_front();
+                    continue;
+                }
+                let k = std::cmp::min(chunk.len(), max - n);
+                put(&chunk.as_ref()[..k]);
+                n += k;
+                chunk.advance(k);
+                if chunk.is_empty() {
+                    // Free the chunk right away instead of on the next read.
+                    buffer.drop_front();
+                }
+                if n == max {
+                    break;
+                }
             }
-            let k = std::cmp::min(chunk.len(), buf.len() - n);
-            buf[n..n + k].copy_from_slice(&chunk.as_ref()[..k]);
-            n += k;
-            chunk.advance(k);
-            if n == buf.len() {
-                break;
+            drop(buffer);
+
+            if n > 0 {
+                log::trace!("{}/{}: read {} bytes", self.conn, self.id, n);
+                return Poll::Ready(Ok(n));
+            }
+
+            let mut shared = self.shared();
+            // Data may have been received since the buffer was empty.
+            if self.buffer().is_empty() || max == 0 {
+                return self.poll_empty_buffer(&mut shared, cx);
             }
         }
+    }
+
+    /// Fail if the stream has been reset or the connection is going away,
+    /// return the end of the stream if the remote closed it, or else wait
+    /// for data, as no data is buffered.
+    fn poll_empty_buffer(&self, shared: &mut Shared, cx: &mut Context) -> Poll<io::Result<usize>> {
+        if let Some(code) = shared.go_away {
+            return Poll::Ready(Err(self.go_away_err(code)));
+        }
 
-        if n > 0 {
-            log::trace!("{}/{}: read {} bytes", self.conn, self.id, n);
-            return Poll::Ready(Ok(n));
+        if let Some(reset) = shared.reset {
+            return Poll::Ready(Err(self.reset_err(reset)));
         }
 
         // Buffer is empty, let's check if we can expect to read more data.
@@ -315,7 +967,7 @@ impl AsyncRead for Stream {
 
         // Since we have no more data at this point, we want to be woken up
         // by the connection when more becomes available for us.
-        shared.reader = Some(cx.waker().clone());
+        shared.register_reader(cx.waker());
 
         Poll::Pending
     }
@@ -327,63 +979,412 @@ impl AsyncWrite for Stream {
         cx: &mut Context,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        ready!(self
-            .sender
-            .poll_ready(cx)
-            .map_err(|_| self.write_zero_err())?);
-        let body = {
-            let mut shared = self.shared();
-            if !shared.state().can_write() {
-                log::debug!("{}/{}: can no longer write", self.conn, self.id);
-                return Poll::Ready(Err(self.write_zero_err()));
+        self.poll_write_bufs(cx, &[IoSlice::new(buf)])
+    }
+
+    /// Gathers the slices into frames of up to the split send size, i.e.
+    /// unlike a write per slice, small slices, e.g. a header followed by a
+    /// payload, share a frame.
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        bufs: &[IoSlice],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_write_bufs(cx, bufs)
+    }
+
+    /// Frames are handed to the connection as they are written, so flushing
+    /// only fails if the stream has been reset.
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
+        if let Some(reset) = self.shared().reset {
+            return Poll::Ready(Err(self.reset_err(reset)));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
+        if self.is_write_closed() {
+            return Poll::Ready(Ok(()));
+        }
+        ready!(self.sender.poll_ready(cx).map_err(|_| self.closed_err())?);
+        let this = &mut *self;
+        let mut shared = this.shared.lock();
+        let ack = if shared.flag == Flag::Ack {
+            shared.flag = Flag::None;
+            true
+        } else {
+            false
+        };
+        log::trace!("{}/{}: close", this.conn, this.id);
+        let cmd = StreamCommand::CloseStream { id: this.id, ack };
+        this.sender
+            .start_send(cmd)
+            .map_err(|_| this.connection_closed_err())?;
+        shared.update_state(this.conn, this.id, State::SendClosed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Stream {
+    /// Write as much of the given slices as the credit allows with one frame.
+    fn poll_write_bufs(&mut self, cx: &mut Context, bufs: &[IoSlice]) -> Poll<io::Result<usize>> {
+        #[cfg(feature = "tracing")]
+        let _span = self.span.clone().entered();
+
+        ready!(self.sender.poll_ready(cx).map_err(|_| self.closed_err())?);
+        let mut shared = self.shared.lock();
+        shared.on_polled();
+        if let Some(e) = self.write_err(&shared) {
+            return Poll::Ready(Err(e));
+        }
+        let len = bufs.iter().map(|b| b.len()).sum::<usize>();
+        if len == 0 {
+            // The SYN of a stream opened lazily is still sent by the first
+            // write, empty or not.
+            if shared.flag == Flag::Syn {
+                let mut frame = Frame::data(self.id, Vec::new()).expect("empty body").left();
+                shared.add_flag(frame.header_mut());
+                let cmd = StreamCommand::SendFrame(frame);
+                self.sender
+                    .start_send(cmd)
+                    .map_err(|_| self.connection_closed_err())?;
             }
-            if shared.credit == 0 {
-                log::trace!("{}/{}: no more credit left", self.conn, self.id);
-                shared.writer = Some(cx.waker().clone());
+            return Poll::Ready(Ok(0));
+        }
+        if shared.credit == 0 {
+            log::trace!("{}/{}: no more credit left", self.conn, self.id);
+            shared.register_writer(cx.waker());
+            return Poll::Pending;
+        }
+        let k = std::cmp::min(shared.credit as usize, len);
+        let k = std::cmp::min(k, self.config.split_send_size);
+        if let Some(pacing) = shared.extras.as_mut().and_then(|e| e.pacing.as_mut()) {
+            if pacing.poll_take(k as u64, cx).is_pending() {
+                log::trace!("{}/{}: paced", self.conn, self.id);
+                shared.register_writer(cx.waker());
                 return Poll::Pending;
             }
-            let k = std::cmp::min(shared.credit as usize, buf.len());
-            let k = std::cmp::min(k, self.config.split_send_size);
-            shared.credit = shared.credit.saturating_sub(k as u32);
-            Vec::from(&buf[..k])
+            // Only one writer is woken by the pacing timer, let the others retry.
+            shared.wake_writers();
+        }
+        shared.credit = shared.credit.saturating_sub(k as u32);
+        shared.on_sent(k);
+        // Frames opening or acknowledging the stream are never compressed.
+        // The flag is only ever cleared, i.e. is still clear once we send.
+        let compression = match shared.flag {
+            Flag::None => shared.compression,
+            Flag::Syn | Flag::Ack => Compression::None,
         };
+        // The credit is taken, copy and compress the data without holding
+        // the lock.
+        drop(shared);
+        let mut body = Vec::with_capacity(k);
+        for b in bufs {
+            let n = std::cmp::min(b.len(), k - body.len());
+            body.extend_from_slice(&b[..n]);
+            if body.len() == k {
+                break;
+            }
+        }
         let n = body.len();
+        let compressed = compression.compress(&body);
+        let flag = compressed.as_ref().and(compression.flag());
+        let body = compressed.unwrap_or(body);
         let mut frame = Frame::data(self.id, body).expect("body <= u32::MAX").left();
-        self.add_flag(frame.header_mut());
+        let mut shared = self.shared.lock();
+        // Meanwhile, another `WriteHalf` may have closed the stream or the
+        // connection may have reset it. The frame must not follow the FIN
+        // or RST.
+        if let Some(e) = self.write_err(&shared) {
+            return Poll::Ready(Err(e));
+        }
+        shared.add_flag(frame.header_mut());
+        if let Some(flag) = flag {
+            frame.header_mut().compression(flag)
+        }
         log::trace!("{}/{}: write {} bytes", self.conn, self.id, n);
+        // The frame is enqueued while holding the lock, so that frames of
+        // concurrent `WriteHalf`s are sent in the order their flags were set.
         let cmd = StreamCommand::SendFrame(frame);
         self.sender
             .start_send(cmd)
-            .map_err(|_| self.write_zero_err())?;
+            .map_err(|_| self.connection_closed_err())?;
+        self.shared
+            .bytes_sent
+            .fetch_add(n as u64, Ordering::Relaxed);
         Poll::Ready(Ok(n))
     }
 
-    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<io::Result<()>> {
-        Poll::Ready(Ok(()))
+    /// The error a write fails with, given the state of the stream, if any.
+    fn write_err(&self, shared: &Shared) -> Option<io::Error> {
+        if let Some(code) = shared.go_away {
+            return Some(self.go_away_err(code));
+        }
+        if let Some(reset) = shared.reset {
+            return Some(self.reset_err(reset));
+        }
+        if shared.stop_sending {
+            log::debug!("{}/{}: remote stopped reading", self.conn, self.id);
+            return Some(self.send_closed_err());
+        }
+        // Set once the connection closed, possibly before its command
+        // channel has been closed.
+        if shared.close_reason.is_some() {
+            return Some(self.connection_closed_err());
+        }
+        if !shared.state().can_write() {
+            return Some(self.send_closed_err());
+        }
+        None
+    }
+}
+
+/// The read half of a [`Stream`], see [`Stream::into_split`].
+#[derive(Debug)]
+pub struct ReadHalf(Stream);
+
+impl ReadHalf {
+    /// Get the identifier of the stream.
+    pub fn id(&self) -> StreamId {
+        self.0.id()
+    }
+
+    /// See [`Stream::recv_window`].
+    pub fn recv_window(&self) -> u32 {
+        self.0.recv_window()
+    }
+
+    /// See [`Stream::metrics`].
+    pub fn metrics(&self) -> StreamMetrics {
+        self.0.metrics()
+    }
+
+    /// See [`Stream::is_read_closed`].
+    pub fn is_read_closed(&self) -> bool {
+        self.0.is_read_closed()
+    }
+
+    /// See [`Stream::closed`].
+    pub async fn closed(&self) {
+        self.0.closed().await
+    }
+
+    /// See [`Stream::poll_closed`].
+    pub fn poll_closed(&self, cx: &mut Context) -> Poll<()> {
+        self.0.poll_closed(cx)
+    }
+
+    /// See [`Stream::set_push_mode`].
+    pub fn set_push_mode(&mut self, b: bool) {
+        self.0.set_push_mode(b)
+    }
+
+    /// See [`Stream::stop_sending`].
+    pub async fn stop_sending(&mut self) -> io::Result<()> {
+        self.0.stop_sending().await
+    }
+
+    /// See [`Stream::peek`].
+    pub async fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.peek(buf).await
+    }
+
+    /// See [`Stream::poll_peek`].
+    pub fn poll_peek(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.0.poll_peek(cx, buf)
+    }
+
+    /// See [`Stream::poll_stop_sending`].
+    pub fn poll_stop_sending(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.0.poll_stop_sending(cx)
+    }
+}
+
+impl futures::stream::Stream for ReadHalf {
+    type Item = io::Result<Packet>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+impl AsyncRead for ReadHalf {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+/// The write half of a [`Stream`], see [`Stream::into_split`].
+///
+/// Write halves are cheap to clone. Writes of different clones are not
+/// interleaved within a single `poll_write`, but may well be within larger
+/// operations such as `write_all`. Closing any clone closes the write side
+/// of the stream.
+#[derive(Debug)]
+pub struct WriteHalf(Stream);
+
+impl WriteHalf {
+    /// Get the identifier of the stream.
+    pub fn id(&self) -> StreamId {
+        self.0.id()
+    }
+
+    /// See [`Stream::send_credit`].
+    pub fn send_credit(&self) -> u32 {
+        self.0.send_credit()
+    }
+
+    /// See [`Stream::metrics`].
+    pub fn metrics(&self) -> StreamMetrics {
+        self.0.metrics()
+    }
+
+    /// See [`Stream::is_write_closed`].
+    pub fn is_write_closed(&self) -> bool {
+        self.0.is_write_closed()
+    }
+
+    /// See [`Stream::closed`].
+    pub async fn closed(&self) {
+        self.0.closed().await
+    }
+
+    /// See [`Stream::poll_closed`].
+    pub fn poll_closed(&self, cx: &mut Context) -> Poll<()> {
+        self.0.poll_closed(cx)
+    }
+
+    /// See [`Stream::write_all_vectored`].
+    pub async fn write_all_vectored(&mut self, bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+        self.0.write_all_vectored(bufs).await
+    }
+
+    /// See [`Stream::wait_for_credit`].
+    pub async fn wait_for_credit(&mut self, min_bytes: u32) -> io::Result<u32> {
+        self.0.wait_for_credit(min_bytes).await
+    }
+
+    /// See [`Stream::poll_wait_for_credit`].
+    pub fn poll_wait_for_credit(
+        &mut self,
+        cx: &mut Context,
+        min_bytes: u32,
+    ) -> Poll<io::Result<u32>> {
+        self.0.poll_wait_for_credit(cx, min_bytes)
+    }
+
+    /// See [`Stream::set_send_pacing`].
+    pub fn set_send_pacing(&mut self, bytes_per_sec: Option<u64>) {
+        self.0.set_send_pacing(bytes_per_sec)
+    }
+
+    /// See [`Stream::finish`].
+    pub async fn finish(&mut self) -> io::Result<()> {
+        self.0.finish().await
+    }
+
+    /// See [`Stream::poll_finish`].
+    pub fn poll_finish(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.0.poll_finish(cx)
+    }
+}
+
+impl Clone for WriteHalf {
+    fn clone(&self) -> Self {
+        WriteHalf(self.0.clone())
+    }
+}
+
+impl AsyncWrite for WriteHalf {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        bufs: &[IoSlice],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
-        if self.is_closed() {
-            return Poll::Ready(Ok(()));
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+/// Why a stream was reset locally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Reset {
+    /// Via [`crate::Connection::reset_stream`].
+    Requested,
+    /// The remote did not acknowledge the stream in time, see
+    /// [`crate::Config::set_stream_open_timeout`].
+    OpenTimeout,
+    /// The application did not acknowledge the inbound stream in time, see
+    /// [`crate::Config::set_inbound_stream_ack_deadline`].
+    AckDeadline,
+    /// The remote sent more data than the receive window of the stream, see
+    /// [`crate::Config::set_window_violation_policy`].
+    WindowViolation,
+    /// Via [`Stream::stop_sending`], if the remote does not support `STOP_SENDING`.
+    StopSending,
+    /// The remote sent data after half-closing the stream.
+    DataAfterFin,
+    /// Via [`crate::Connection::reset_streams_in_flight`].
+    TransportLost,
+}
+
+/// The state shared between a stream and its connection.
+///
+/// The received data is kept behind a lock of its own, so that a reader
+/// copies it without holding up the connection, which needs the rest of
+/// the state for every frame of the stream. The counters of
+/// [`Stream::metrics`] are atomics, i.e. are updated and read without
+/// holding either lock.
+#[derive(Debug)]
+pub(crate) struct SharedState {
+    shared: Mutex<Shared>,
+    /// Locked after `shared` if both are held.
+    buffer: Mutex<Chunks>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    /// The number of times either lock was contended.
+    contentions: AtomicU64,
+}
+
+impl SharedState {
+    fn new(shared: Shared) -> Self {
+        SharedState {
+            shared: Mutex::new(shared),
+            buffer: Mutex::new(Chunks::new()),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            contentions: AtomicU64::new(0),
         }
-        ready!(self
-            .sender
-            .poll_ready(cx)
-            .map_err(|_| self.write_zero_err())?);
-        let ack = if self.flag == Flag::Ack {
-            self.flag = Flag::None;
-            true
-        } else {
-            false
-        };
-        log::trace!("{}/{}: close", self.conn, self.id);
-        let cmd = StreamCommand::CloseStream { id: self.id, ack };
-        self.sender
-            .start_send(cmd)
-            .map_err(|_| self.write_zero_err())?;
-        self.shared()
-            .update_state(self.conn, self.id, State::SendClosed);
-        Poll::Ready(Ok(()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock(&self.contentions)
+    }
+
+    fn buffer(&self) -> MutexGuard<'_, Chunks> {
+        self.buffer.lock(&self.contentions)
     }
 }
 
@@ -391,30 +1392,319 @@ impl AsyncWrite for Stream {
 pub(crate) struct Shared {
     state: State,
     pub(crate) window: u32,
+    /// The receive window of this stream, i.e. the max. credit granted to the remote.
+    pub(crate) receive_window: u32,
+    /// The share of the connection's receive budget, if any, which limits the
+    /// receive window, see [`crate::Config::set_max_connection_receive_window`].
+    pub(crate) fair_share: Option<FairShare>,
+    /// Whether the remote has acknowledged this stream.
+    pub(crate) acknowledged: bool,
+    /// Whether the remote asked us to stop sending data.
+    pub(crate) stop_sending: bool,
+    /// Whether we asked the remote to stop sending data, see [`Stream::stop_sending`].
+    pub(crate) stopped_reading: bool,
+    /// The error code of the `GoAway` the remote terminated the connection with.
+    pub(crate) go_away: Option<u32>,
+    /// Why the connection closed the stream, see [`Stream::close_reason`].
+    pub(crate) close_reason: Option<CloseReason>,
+    /// Why the stream was reset locally, if it was.
+    reset: Option<Reset>,
+    /// Whether received data is passed to the inbound handler of the
+    /// connection instead of being buffered, see [`Stream::set_push_mode`].
+    pub(crate) push_mode: bool,
+    /// The flag to set on the next outbound frame header.
+    flag: Flag,
+    /// The compression both sides agreed on, see [`crate::Config::set_compression`].
+    pub(crate) compression: Compression,
+    /// Whether to offer compression on the frame opening this stream.
+    pub(crate) compression_offered: bool,
     pub(crate) credit: u32,
-    pub(crate) buffer: Chunks,
     pub(crate) reader: Option<Waker>,
-    pub(crate) writer: Option<Waker>,
+    /// Tasks waiting to write, more than one if the stream has been split.
+    writers: Vec<Waker>,
+    /// The state of rarely used features, boxed so that the state of every
+    /// stream stays small.
+    extras: Option<Box<Extras>>,
+    /// Whether the last attempt to send a window update failed because the
+    /// command channel to the connection was full.
+    pub(crate) window_update_blocked: bool,
+    /// Whether the buffer overflowed given [`crate::BufferOverflowPolicy::Backpressure`],
+    /// i.e. credit is only granted as the stream is read until the buffer
+    /// is below its limit again.
+    pub(crate) backpressured: bool,
+    /// The number of bytes sent which the remote has not granted credit for
+    /// again, i.e. which may not have reached it yet.
+    unconfirmed: u32,
+    /// The number of bytes sent up to and including the frame carrying the
+    /// SYN of this stream, see [`Shared::on_ack`].
+    sent_with_syn: u32,
+    pub(crate) created: Instant,
+    /// When the SYN opening this stream has been sent, see
+    /// [`crate::Config::set_stream_open_timeout`].
+    pub(crate) syn_sent: Option<Instant>,
+    last_activity: Instant,
+    /// When the stream has last been polled to read or write, see
+    /// [`crate::Config::set_stall_detector`].
+    #[cfg(feature = "stall-detector")]
+    pub(crate) last_polled: Instant,
+    /// Whether a stall has been reported since the stream has last been polled.
+    #[cfg(feature = "stall-detector")]
+    pub(crate) stall_reported: bool,
+    /// The number of `Stream` handles, including the one of the connection.
+    ///
+    /// Unlike the strong count of the `Arc`, it is changed under the lock, so
+    /// that exactly one dropped handle sees only the connection's remain.
+    pub(crate) handles: usize,
     config: Arc<Config>,
 }
 
+/// The state of the rarely used features of a stream, see [`Shared`].
+#[derive(Debug, Default)]
+struct Extras {
+    /// Tasks waiting for the stream to close, see [`Stream::poll_closed`].
+    closing: Vec<Waker>,
+    /// Set if the stream's sending is paced, see [`Stream::set_send_pacing`].
+    pacing: Option<RateLimit>,
+    /// The idle time after which a keep-alive is sent, see [`Stream::set_keepalive`].
+    keepalive: Option<Duration>,
+}
+
 impl Shared {
     fn new(window: u32, credit: u32, config: Arc<Config>) -> Self {
         Shared {
             state: State::Open,
             window,
+            receive_window: config.receive_window,
+            fair_share: None,
+            acknowledged: false,
+            stop_sending: false,
+            stopped_reading: false,
+            go_away: None,
+            close_reason: None,
+            reset: None,
+            push_mode: false,
+            flag: Flag::None,
+            compression: Compression::None,
+            compression_offered: false,
             credit,
-            buffer: Chunks::new(),
             reader: None,
-            writer: None,
+            writers: Vec::new(),
+            extras: None,
+            window_update_blocked: false,
+            backpressured: false,
+            unconfirmed: 0,
+            sent_with_syn: 0,
+            created: config.time.now(),
+            syn_sent: None,
+            last_activity: config.time.now(),
+            #[cfg(feature = "stall-detector")]
+            last_polled: config.time.now(),
+            #[cfg(feature = "stall-detector")]
+            stall_reported: false,
+            handles: 1,
             config,
         }
     }
 
+    /// Whether the SYN of this stream, if outbound, has been sent but the
+    /// remote did not acknowledge the stream yet.
+    pub(crate) fn awaits_ack(&self) -> bool {
+        !self.acknowledged && self.flag != Flag::Syn && self.state != State::Closed
+    }
+
+    /// Whether the ACK of this inbound stream has not been sent yet.
+    pub(crate) fn ack_pending(&self) -> bool {
+        self.flag == Flag::Ack && self.state != State::Closed
+    }
+
+    /// Take the ACK of this inbound stream to send it along with a frame of
+    /// the connection, returning `false` if it has already been sent.
+    pub(crate) fn take_ack(&mut self) -> bool {
+        let pending = self.ack_pending();
+        if pending {
+            self.flag = Flag::None
+        }
+        pending
+    }
+
+    /// Whether the stream was reset locally.
+    pub(crate) fn is_reset(&self) -> bool {
+        self.reset.is_some()
+    }
+
+    /// Reset the stream locally, failing all further reads and writes.
+    /// Returns the previous state. The buffered data is discarded by
+    /// [`Stream::reset`].
+    pub(crate) fn reset(&mut self, cid: connection::Id, sid: StreamId, reset: Reset) -> State {
+        let previous = self.update_state(cid, sid, State::Closed);
+        self.reset = Some(reset);
+        self.wake_all();
+        previous
+    }
+
+    /// Account for `n` bytes of data sent.
+    pub(crate) fn on_sent(&mut self, n: usize) {
+        self.unconfirmed = self.unconfirmed.saturating_add(n as u32);
+        self.last_activity = self.config.time.now();
+    }
+
+    /// Account for `n` bytes of credit granted by the remote.
+    ///
+    /// Credit replenishing consumed bytes confirms as many bytes sent.
+    /// Credit granted along with the ACK may grow the remote's window beyond
+    /// the default instead, which cannot be told apart, so it confirms no
+    /// data, see [`Shared::on_ack`].
+    pub(crate) fn on_credit(&mut self, n: u32, with_ack: bool) {
+        self.credit += n;
+        if !with_ack {
+            self.unconfirmed = self.unconfirmed.saturating_sub(n);
+        }
+    }
+
+    /// Account for the ACK of this outbound stream, which answers the frame
+    /// carrying the SYN, i.e. confirms the data sent up to and including it.
+    pub(crate) fn on_ack(&mut self) {
+        self.acknowledged = true;
+        self.unconfirmed = self
+            .unconfirmed
+            .saturating_sub(std::mem::take(&mut self.sent_with_syn));
+    }
+
+    /// Whether frames of this stream may have been lost with a failed
+    /// transport: data the remote has not granted credit for again, a SYN
+    /// the remote has not acknowledged or a FIN of a half-closed stream.
+    pub(crate) fn may_be_in_flight(&self, outbound: bool) -> bool {
+        self.unconfirmed > 0
+            || self.state == State::SendClosed
+            || (outbound && !self.acknowledged && self.flag != Flag::Syn)
+    }
+
+    /// Account for data received, see [`Stream::on_received`].
+    fn on_received(&mut self) {
+        self.last_activity = self.config.time.now();
+    }
+
     pub(crate) fn state(&self) -> State {
         self.state
     }
 
+    /// The stream is polled to read or write.
+    pub(crate) fn on_polled(&mut self) {
+        #[cfg(feature = "stall-detector")]
+        if self.config.stall_threshold.is_some() {
+            self.last_polled = self.config.time.now();
+            self.stall_reported = false;
+        }
+    }
+
+    /// The number of tasks waiting to write.
+    #[cfg(feature = "stall-detector")]
+    pub(crate) fn writers_waiting(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// Wake the task waiting to read, if any.
+    pub(crate) fn wake_reader(&mut self) {
+        if let Some(w) = self.reader.take() {
+            w.wake()
+        }
+    }
+
+    /// Wake all tasks waiting to read or write, or for the stream to close.
+    pub(crate) fn wake_all(&mut self) {
+        self.wake_reader();
+        self.wake_writers();
+        self.wake_closing()
+    }
+
+    fn wake_closing(&mut self) {
+        if let Some(extras) = self.extras.as_mut() {
+            for w in extras.closing.drain(..) {
+                w.wake()
+            }
+        }
+    }
+
+    fn extras_mut(&mut self) -> &mut Extras {
+        self.extras.get_or_insert_with(Default::default)
+    }
+
+    /// See [`Stream::set_keepalive`].
+    pub(crate) fn keepalive(&self) -> Option<Duration> {
+        self.extras.as_ref().and_then(|e| e.keepalive)
+    }
+
+    /// Take the wakers of all tasks waiting to write, e.g. to wake them
+    /// after releasing the lock.
+    pub(crate) fn take_writers(&mut self) -> Vec<Waker> {
+        std::mem::take(&mut self.writers)
+    }
+
+    /// Wake all tasks waiting to write.
+    pub(crate) fn wake_writers(&mut self) {
+        for w in self.writers.drain(..) {
+            w.wake()
+        }
+    }
+
+    fn register_reader(&mut self, waker: &Waker) {
+        if !self.reader.as_ref().is_some_and(|w| w.will_wake(waker)) {
+            self.reader = Some(waker.clone())
+        }
+    }
+
+    fn register_writer(&mut self, waker: &Waker) {
+        if !self.writers.iter().any(|w| w.will_wake(waker)) {
+            self.writers.push(waker.clone())
+        }
+    }
+
+    /// The time until the next keep-alive is due, zero if overdue, or `None`
+    /// if no keep-alives are to be sent, see [`Stream::set_keepalive`].
+    pub(crate) fn next_keepalive(&self) -> Option<Duration> {
+        let interval = self.keepalive()?;
+        if !self.state.can_write() || self.flag == Flag::Syn {
+            return None;
+        }
+        Some(interval.saturating_sub(self.config.time.elapsed(self.last_activity)))
+    }
+
+    /// Set ACK or SYN flag if necessary.
+    pub(crate) fn add_flag(&mut self, header: &mut Header<Either<Data, WindowUpdate>>) {
+        match self.flag {
+            Flag::None => (),
+            Flag::Syn => {
+                header.syn();
+                self.flag = Flag::None;
+                self.sent_with_syn = self.unconfirmed
+            }
+            Flag::Ack => {
+                header.ack();
+                self.flag = Flag::None
+            }
+        }
+        self.announce_compression(header)
+    }
+
+    /// Announce the compression of this stream on a frame opening or
+    /// acknowledging it, see [`crate::Config::set_compression`].
+    ///
+    /// Opening frames offer compression if it has been negotiated with the
+    /// remote, acknowledging frames only accept an offer of the remote.
+    pub(crate) fn announce_compression<T>(&self, header: &mut Header<T>) {
+        let flags = header.flags();
+        let compression = if flags.contains(header::SYN) && self.compression_offered {
+            self.config.compression
+        } else if flags.contains(header::ACK) {
+            self.compression
+        } else {
+            return;
+        };
+        if let Some(flag) = compression.flag() {
+            header.compression(flag)
+        }
+    }
+
     /// Update the stream state and return the state before it was updated.
     pub(crate) fn update_state(
         &mut self,
@@ -422,21 +1712,10 @@ impl Shared {
         sid: StreamId,
         next: State,
     ) -> State {
-        use self::State::*;
-
         let current = self.state;
-
-        match (current, next) {
-            (Closed, _) => {}
-            (Open, _) => self.state = next,
-            (RecvClosed, Closed) => self.state = Closed,
-            (RecvClosed, Open) => {}
-            (RecvClosed, RecvClosed) => {}
-            (RecvClosed, SendClosed) => self.state = Closed,
-            (SendClosed, Closed) => self.state = Closed,
-            (SendClosed, Open) => {}
-            (SendClosed, RecvClosed) => self.state = Closed,
-            (SendClosed, SendClosed) => {}
+        self.state = current.transition(next);
+        if self.state == State::Closed {
+            self.wake_closing()
         }
 
         log::trace!(
@@ -451,42 +1730,127 @@ impl Shared {
         current // Return the previous stream state for informational purposes.
     }
 
+    /// The receive window, limited to the fair share of the stream, if any.
+    pub(crate) fn effective_receive_window(&self) -> u32 {
+        match &self.fair_share {
+            Some(share) => std::cmp::min(share.get(), self.receive_window),
+            None => self.receive_window,
+        }
+    }
+
     /// Calculate the number of additional window bytes the receiving side
-    /// should grant the sending side via a window update message.
+    /// should grant the sending side via a window update message, given the
+    /// number of bytes buffered.
     ///
     /// Returns `None` if too small to justify a window update message.
     ///
     /// Note: Once a caller successfully sent a window update message, the
     /// locally tracked window size needs to be updated manually by the caller.
-    pub(crate) fn next_window_update(&mut self) -> Option<u32> {
+    pub(crate) fn next_window_update(&mut self, buffered: usize) -> Option<u32> {
         if !self.state.can_read() {
             return None;
         }
 
-        let new_credit = match self.config.window_update_mode {
-            WindowUpdateMode::OnReceive => {
-                debug_assert!(self.config.receive_window >= self.window);
-
-                self.config.receive_window.saturating_sub(self.window)
-            }
-            WindowUpdateMode::OnRead => {
-                debug_assert!(self.config.receive_window >= self.window);
-                let bytes_received = self.config.receive_window.saturating_sub(self.window);
-                let buffer_len: u32 = self.buffer.len().try_into().unwrap_or(std::u32::MAX);
-
-                bytes_received.saturating_sub(buffer_len)
+        // The window may exceed a fair share which shrank after it was granted.
+        let receive_window = self.effective_receive_window();
+        let buffered = match self.config.window_update_mode {
+            WindowUpdateMode::OnReceive if !self.backpressured => 0,
+            WindowUpdateMode::OnReceive | WindowUpdateMode::OnRead | WindowUpdateMode::Hybrid => {
+                buffered.try_into().unwrap_or(u32::MAX)
             }
         };
+        let new_credit = flow::replenishable_credit(receive_window, self.window, buffered);
 
-        // Send WindowUpdate message when half or more of the configured receive
-        // window can be granted as additional credit to the sender.
+        // Send WindowUpdate message when at least the configured threshold,
+        // by default half of the receive window, can be granted as additional
+        // credit to the sender.
         //
         // See https://github.com/paritytech/yamux/issues/100 for a detailed
         // discussion.
-        if new_credit >= self.config.receive_window / 2 {
+        let threshold = self
+            .config
+            .window_update_threshold
+            .min_credit(receive_window);
+        if new_credit >= threshold {
             Some(new_credit)
         } else {
             None
         }
     }
+
+    /// Calculate the number of additional window bytes the connection should
+    /// grant the sending side on behalf of a stream which is not being read,
+    /// given [`WindowUpdateMode::Hybrid`] and the number of bytes buffered.
+    ///
+    /// Returns `None` if the receive window is not exhausted yet or if
+    /// granting more credit would let the buffer grow beyond its limit.
+    ///
+    /// Note: Once a caller successfully sent a window update message, the
+    /// locally tracked window size needs to be updated manually by the caller.
+    pub(crate) fn next_fallback_window_update(&mut self, buffered: usize) -> Option<u32> {
+        if !self.state.can_read() || self.window != 0 {
+            return None;
+        }
+
+        let available = self.config.max_buffer_size.saturating_sub(buffered);
+        let receive_window = self.effective_receive_window();
+        let new_credit = std::cmp::min(receive_window as usize, available) as u32;
+
+        if new_credit
+            >= self
+                .config
+                .window_update_threshold
+                .min_credit(receive_window)
+        {
+            Some(new_credit)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(all(test, yamux_loom))]
+mod loom_tests;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_of_streams_stays_small() {
+        // Every stream allocates its `SharedState`, idle or not. Rarely used
+        // features belong in `Extras`.
+        let size = std::mem::size_of::<SharedState>();
+        assert!(size <= 320, "size of SharedState = {}", size);
+    }
+
+    #[test]
+    fn writes_after_the_connection_closed_fail_with_connection_closed() {
+        // The command channel stays open until the connection is dropped.
+        let (sender, _receiver) = mpsc::channel(1);
+        let conn = connection::Id::random();
+        let id = StreamId::new(1);
+        let config = Arc::new(Config::default());
+        let mut stream = Stream::new(
+            id,
+            conn,
+            config,
+            DEFAULT_CREDIT,
+            DEFAULT_CREDIT,
+            sender,
+            true,
+        );
+        {
+            let mut shared = stream.shared();
+            shared.update_state(conn, id, State::Closed);
+            shared.close_reason = Some(CloseReason::ConnectionClosed);
+        }
+        let e = futures::executor::block_on(stream.write(b"x")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(
+            StreamError::from_io(&e),
+            Some(StreamError::ConnectionClosed)
+        );
+        assert_eq!(e.to_string(), format!("{}/1: connection is closed", conn));
+    }
 }
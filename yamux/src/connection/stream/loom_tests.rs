@@ -0,0 +1,159 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Model checks of a stream and its connection accessing their shared state
+// concurrently, run with
+//
+//     RUSTFLAGS="--cfg yamux_loom" cargo test -p yamux --lib --release loom
+//
+// The connection side does what `Active` does on receiving frames and when
+// collecting garbage, via the same `Shared` methods.
+
+use super::{Shared, State, Stream, StreamCommand};
+use crate::{connection, frame::header::StreamId, Config};
+use futures::{channel::mpsc, future, AsyncReadExt, AsyncWriteExt};
+use loom::thread;
+use std::sync::Arc;
+
+/// A stream with the given send credit and the receiver of its commands.
+fn stream(credit: u32) -> (Stream, mpsc::Receiver<super::StreamCommand>) {
+    let (sender, receiver) = mpsc::channel(16);
+    let config = Arc::new(Config::default());
+    let id = StreamId::new(1);
+    let stream = Stream::new(
+        id,
+        connection::Id::random(),
+        config,
+        crate::DEFAULT_CREDIT,
+        credit,
+        sender,
+        true,
+    );
+    (stream, receiver)
+}
+
+/// Update the shared state of the stream like the connection does.
+fn connection(stream: &Stream, f: impl FnOnce(&mut Shared)) {
+    f(&mut stream.shared())
+}
+
+#[test]
+fn loom_reader_is_woken_by_data() {
+    loom::model(|| {
+        let (mut stream, _receiver) = stream(0);
+        let remote = stream.clone();
+        let conn = thread::spawn(move || {
+            // Like `on_data`, wake the reader after releasing the lock.
+            let mut shared = remote.shared();
+            remote.buffer().push(vec![1, 2, 3]);
+            let reader = shared.reader.take();
+            drop(shared);
+            if let Some(w) = reader {
+                w.wake()
+            }
+        });
+        let mut buf = [0; 3];
+        loom::future::block_on(stream.read_exact(&mut buf)).unwrap();
+        assert_eq!(buf, [1, 2, 3]);
+        conn.join().unwrap();
+    })
+}
+
+#[test]
+fn loom_writer_is_woken_by_credit() {
+    loom::model(|| {
+        let (mut stream, _receiver) = stream(0);
+        let remote = stream.clone();
+        let conn = thread::spawn(move || {
+            // Like `on_window_update`, wake the writers after releasing the lock.
+            let mut shared = remote.shared();
+            shared.credit += 3;
+            let writers = shared.take_writers();
+            drop(shared);
+            for w in writers {
+                w.wake()
+            }
+        });
+        let n = loom::future::block_on(stream.write(&[1, 2, 3])).unwrap();
+        assert_eq!(n, 3);
+        conn.join().unwrap();
+    })
+}
+
+#[test]
+fn loom_reset_wakes_reader_and_writer() {
+    // Bound the preemptions of the three threads, as exploring all their
+    // interleavings takes too long.
+    let mut model = loom::model::Builder::new();
+    model.preemption_bound = Some(3);
+    model.check(|| {
+        let (stream, _receiver) = stream(0);
+        let (mut reader, mut writer) = stream.clone().into_split();
+        let remote = stream;
+        let conn = thread::spawn(move || {
+            connection(&remote, |shared| {
+                shared.update_state(remote.conn(), remote.id(), State::Closed);
+                shared.wake_all()
+            })
+        });
+        let write = thread::spawn(move || {
+            assert!(loom::future::block_on(writer.write(&[1])).is_err());
+        });
+        let mut buf = [0; 1];
+        assert_eq!(loom::future::block_on(reader.read(&mut buf)).unwrap(), 0);
+        write.join().unwrap();
+        conn.join().unwrap();
+    })
+}
+
+#[test]
+fn loom_dropped_stream_is_collected_after_its_close() {
+    loom::model(|| {
+        let (stream, _receiver) = stream(0);
+        let mut user = stream.clone();
+        let user = thread::spawn(move || {
+            loom::future::block_on(future::poll_fn(|cx| {
+                futures::AsyncWrite::poll_close(std::pin::Pin::new(&mut user), cx)
+            }))
+            .unwrap();
+        });
+        // Dropped streams are only removed once no one else holds them,
+        // which must then see all their updates.
+        if stream.is_dropped() {
+            assert_eq!(stream.shared().state(), State::SendClosed);
+        }
+        user.join().unwrap();
+        assert!(stream.is_dropped());
+    })
+}
+
+#[test]
+fn loom_no_data_is_sent_after_close() {
+    loom::model(|| {
+        let (stream, mut receiver) = stream(1);
+        let mut writer = stream.clone();
+        let mut closer = stream;
+        let write = thread::spawn(move || {
+            // Fails if the stream has been closed before.
+            let _ = loom::future::block_on(writer.write(&[1]));
+        });
+        loom::future::block_on(closer.close()).unwrap();
+        write.join().unwrap();
+        let mut closed = false;
+        while let Ok(Some(cmd)) = receiver.try_next() {
+            match cmd {
+                StreamCommand::SendFrame(_) => assert!(!closed, "data after FIN"),
+                StreamCommand::CloseStream { .. } => closed = true,
+                _ => {}
+            }
+        }
+        assert!(closed);
+    })
+}
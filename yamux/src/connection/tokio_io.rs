@@ -0,0 +1,135 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use super::Stream;
+use futures::io::AsyncWrite;
+use std::{
+    io::{self, IoSlice},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A [`Stream`] implementing the I/O traits of tokio, see [`Stream::into_tokio_io`].
+///
+/// With the `hyper` feature it also implements [`hyper::rt::Read`] and
+/// [`hyper::rt::Write`], so that the stream can be served by hyper (and thus
+/// tonic) directly.
+#[derive(Debug)]
+pub struct TokioStream(Stream);
+
+impl Stream {
+    /// Convert the stream into a type implementing [`tokio::io::AsyncRead`]
+    /// and [`tokio::io::AsyncWrite`] (requires the `tokio-io` feature).
+    pub fn into_tokio_io(self) -> TokioStream {
+        TokioStream(self)
+    }
+}
+
+impl TokioStream {
+    pub fn get_ref(&self) -> &Stream {
+        &self.0
+    }
+
+    pub fn get_mut(&mut self) -> &mut Stream {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> Stream {
+        self.0
+    }
+}
+
+impl tokio::io::AsyncRead for TokioStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let max = buf.remaining();
+        self.0
+            .poll_read_with(cx, max, |chunk| buf.put_slice(chunk))
+            .map_ok(drop)
+    }
+}
+
+impl tokio::io::AsyncWrite for TokioStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl hyper::rt::Read for TokioStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: hyper::rt::ReadBufCursor<'_>,
+    ) -> Poll<io::Result<()>> {
+        let max = buf.remaining();
+        self.0
+            .poll_read_with(cx, max, |chunk| buf.put_slice(chunk))
+            .map_ok(drop)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl hyper::rt::Write for TokioStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx)
+    }
+}
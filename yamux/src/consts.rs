@@ -0,0 +1,85 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Constants of the Yamux protocol and the defaults of this implementation,
+//! e.g. to negotiate settings with a remote before the connection is set up.
+
+use crate::frame::header;
+use crate::Flags;
+
+/// The protocol version in the header of each frame.
+pub const PROTOCOL_VERSION: u8 = header::VERSION;
+
+/// The size of a frame header in bytes.
+pub const HEADER_SIZE: usize = header::HEADER_SIZE;
+
+/// The receive window each stream starts out with, as mandated by the
+/// specification, and the min. receive window of [`crate::Config`].
+pub const DEFAULT_RECEIVE_WINDOW: u32 = crate::DEFAULT_CREDIT;
+
+/// The max. body length of a data frame the header can express.
+pub const MAX_FRAME_BODY_LEN: usize = u32::MAX as usize;
+
+/// The default max. body length of inbound data frames, see
+/// [`crate::Config::set_max_frame_body_len`].
+pub const DEFAULT_MAX_FRAME_BODY_LEN: usize = crate::DEFAULT_MAX_BUFFER_SIZE;
+
+/// The default max. body length of outbound data frames, see
+/// [`crate::Config::set_split_send_size`].
+pub const DEFAULT_SPLIT_SEND_SIZE: usize = crate::DEFAULT_SPLIT_SEND_SIZE;
+
+/// Opens a stream.
+pub const FLAG_SYN: Flags = header::SYN;
+
+/// Acknowledges a stream.
+pub const FLAG_ACK: Flags = header::ACK;
+
+/// Half-closes a stream.
+pub const FLAG_FIN: Flags = header::FIN;
+
+/// Resets a stream.
+pub const FLAG_RST: Flags = header::RST;
+
+/// Releases a stream ID for reuse, see [`crate::Config::set_stream_id_recycling`].
+///
+/// Not part of the Yamux specification.
+pub const FLAG_RELEASE: Flags = header::RELEASE;
+
+/// Asks the remote to stop sending data, see [`crate::Stream::stop_sending`].
+///
+/// Not part of the Yamux specification.
+pub const FLAG_STOP_SENDING: Flags = header::STOP_SENDING;
+
+/// Offers, respectively indicates, LZ4 compression, see
+/// [`crate::Config::set_compression`].
+///
+/// Not part of the Yamux specification.
+pub const FLAG_COMPRESS_LZ4: Flags = header::COMPRESS_LZ4;
+
+/// Offers, respectively indicates, Zstandard compression, see
+/// [`crate::Config::set_compression`].
+///
+/// Not part of the Yamux specification.
+pub const FLAG_COMPRESS_ZSTD: Flags = header::COMPRESS_ZSTD;
+
+/// Advertise protocol extensions, one bit per extension, see
+/// [`crate::Config::set_extensions`].
+///
+/// Not part of the Yamux specification.
+pub const FLAGS_EXTENSIONS: Flags = header::EXTENSIONS;
+
+/// The `GoAway` code of a normal termination.
+pub const GO_AWAY_NORMAL: u32 = header::GO_AWAY_NORMAL;
+
+/// The `GoAway` code of a termination because of a protocol error.
+pub const GO_AWAY_PROTOCOL_ERROR: u32 = header::GO_AWAY_PROTOCOL_ERROR;
+
+/// The `GoAway` code of a termination because of an internal error.
+pub const GO_AWAY_INTERNAL_ERROR: u32 = header::GO_AWAY_INTERNAL_ERROR;
@@ -8,8 +8,11 @@
 // at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
 // at https://opensource.org/licenses/MIT.
 
-use crate::MAX_COMMAND_BACKLOG;
-use crate::{error::ConnectionError, Connection, Result, Stream};
+use crate::{
+    error::ConnectionError, CleanupReport, ConfigUpdate, Connection, ConnectionStats, OpenMode,
+    Result, Stream, StreamId, Transport,
+};
+use crate::{DEFAULT_CREDIT, MAX_COMMAND_BACKLOG};
 use futures::{
     channel::{mpsc, oneshot},
     prelude::*,
@@ -23,6 +26,15 @@ use std::task::{Context, Poll};
 ///
 /// A [`Control`] communicates with a [`ControlledConnection`] via a channel. This allows
 /// a [`Control`] to be cloned and shared between tasks and threads.
+///
+/// # Cancellation safety
+///
+/// The futures returned by a [`Control`] may be dropped, e.g. in a `select!`.
+/// Once the command of a future has been sent, the connection carries it out
+/// regardless, e.g. a dropped [`Control::close`] still closes the connection,
+/// with the exception of opening a stream, which is abandoned if the stream
+/// has not yet been opened, e.g. because [`crate::Config::set_max_ack_backlog`]
+/// is exceeded.
 #[derive(Clone, Debug)]
 pub struct Control {
     /// Command channel to [`ControlledConnection`].
@@ -37,6 +49,7 @@ impl Control {
         let connection = ControlledConnection {
             state: State::Idle(connection),
             commands: receiver,
+            ready: Vec::new(),
         };
 
         (control, connection)
@@ -45,10 +58,117 @@ impl Control {
     /// Open a new stream to the remote.
     pub async fn open_stream(&mut self) -> Result<Stream> {
         let (tx, rx) = oneshot::channel();
-        self.sender.send(ControlCommand::OpenStream(tx)).await?;
+        self.sender
+            .send(ControlCommand::OpenStream(tx, None, None))
+            .await?;
+        rx.await?
+    }
+
+    /// Open a new stream to the remote with the given receive window
+    /// (must be >= 256 KiB), instead of the configured one.
+    ///
+    /// # Panics
+    ///
+    /// If the given receive window is < 256 KiB.
+    pub async fn open_stream_with_window(&mut self, receive_window: u32) -> Result<Stream> {
+        assert!(receive_window >= DEFAULT_CREDIT);
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .send(ControlCommand::OpenStream(tx, Some(receive_window), None))
+            .await?;
         rx.await?
     }
 
+    /// Open a new stream to the remote whose SYN is sent according to the
+    /// given [`OpenMode`], instead of the configured one.
+    pub async fn open_stream_with_mode(&mut self, mode: OpenMode) -> Result<Stream> {
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .send(ControlCommand::OpenStream(tx, None, Some(mode)))
+            .await?;
+        rx.await?
+    }
+
+    /// Open a new stream to the remote for the application protocol with the
+    /// given ID, which is written as a 2 byte tag before any other data.
+    ///
+    /// The remote reads the tag via [`Stream::protocol_tag`], e.g. to
+    /// dispatch the stream to the handler of its protocol.
+    pub async fn open_stream_tagged(&mut self, protocol_id: u16) -> Result<Stream> {
+        let mut stream = self.open_stream().await?;
+        stream
+            .write_protocol_tag(protocol_id)
+            .await
+            .map_err(ConnectionError::Io)?;
+        Ok(stream)
+    }
+
+    /// Wait until a new stream can be opened, see [`Connection::poll_ready_outbound`].
+    ///
+    /// Resolves once neither the command channel to the connection is full
+    /// nor opening a stream would fail due to the max. number of outbound
+    /// streams or wait for the ACK backlog, enabling a ready/open pattern
+    /// instead of handling [`ConnectionError::TooManyStreams`]. Readiness is
+    /// not reserved, i.e. other [`Control`]s may open streams in between.
+    pub async fn ready(&mut self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.sender.send(ControlCommand::Ready(tx)).await?;
+        rx.await??;
+        future::poll_fn(|cx| self.sender.poll_ready(cx)).await?;
+        Ok(())
+    }
+
+    /// Reset the stream with the given ID, see [`Connection::reset_stream`].
+    ///
+    /// Returns `false` if there is no such stream.
+    pub async fn reset_stream(&mut self, id: StreamId) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .send(ControlCommand::ResetStream(id, tx))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    /// Change settings of the connection, see [`ConfigUpdate`].
+    ///
+    /// The update is applied between two polls of the connection. Fails with
+    /// [`ConnectionError::Closed`] if the connection is closing or closed.
+    pub async fn update_config(&mut self, update: ConfigUpdate) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .send(ControlCommand::UpdateConfig(update, tx))
+            .await?;
+        if rx.await? {
+            Ok(())
+        } else {
+            Err(ConnectionError::Closed)
+        }
+    }
+
+    /// A summary of the state of the connection, see [`Connection::stats`].
+    pub async fn stats(&mut self) -> Result<ConnectionStats> {
+        let (tx, rx) = oneshot::channel();
+        self.sender.send(ControlCommand::Stats(tx)).await?;
+        Ok(rx.await?)
+    }
+
+    /// Gracefully drain the connection.
+    ///
+    /// No new streams are accepted or can be opened, but existing streams are
+    /// serviced until they are all closed, after which the connection is closed.
+    /// See [`Connection::poll_drain`] for details.
+    pub async fn drain(&mut self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(ControlCommand::Drain(tx)).await.is_err() {
+            // The receiver is closed which means the connection is already closed.
+            return Ok(());
+        }
+        // A dropped `oneshot::Sender` means the `Connection` is gone,
+        // so we do not treat receive errors differently here.
+        let _ = rx.await;
+        Ok(())
+    }
+
     /// Close the connection.
     pub async fn close(&mut self) -> Result<()> {
         let (tx, rx) = oneshot::channel();
@@ -72,12 +192,49 @@ impl Control {
 pub struct ControlledConnection<T> {
     state: State<T>,
     commands: mpsc::Receiver<ControlCommand>,
+    /// The replies to [`Control::ready`] waiting for a stream to be openable.
+    ready: Vec<oneshot::Sender<Result<()>>>,
 }
 
 impl<T> ControlledConnection<T>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Transport + Send + 'static,
 {
+    /// See [`Connection::cleanup_report`].
+    pub fn cleanup_report(&self) -> Option<&CleanupReport> {
+        match &self.state {
+            State::Idle(connection)
+            | State::OpeningNewStream { connection, .. }
+            | State::Draining { connection, .. }
+            | State::Closing {
+                inner: Closing::DrainingControlCommands { connection },
+                ..
+            }
+            | State::Closing {
+                inner: Closing::ClosingConnection { connection },
+                ..
+            } => connection.cleanup_report(),
+            State::Poisoned => None,
+        }
+    }
+
+    /// Reply to the [`Control::ready`]s waiting for a stream to be openable,
+    /// as far as the connection is ready.
+    fn poll_ready(&mut self, connection: &mut Connection<T>, cx: &mut Context<'_>) {
+        self.ready.retain(|reply| !reply.is_canceled());
+        while let Some(reply) = self.ready.pop() {
+            match connection.poll_ready_outbound(cx) {
+                Poll::Ready(result) => {
+                    let _ = reply.send(result);
+                }
+                Poll::Pending => {
+                    self.ready.push(reply);
+                    break;
+                }
+            }
+        }
+    }
+
     fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Stream>>> {
         loop {
             match std::mem::replace(&mut self.state, State::Poisoned) {
@@ -91,12 +248,18 @@ where
                     }
 
                     match self.commands.poll_next_unpin(cx) {
-                        Poll::Ready(Some(ControlCommand::OpenStream(reply))) => {
-                            self.state = State::OpeningNewStream { reply, connection };
+                        Poll::Ready(Some(ControlCommand::OpenStream(reply, window, mode))) => {
+                            self.state = State::OpeningNewStream {
+                                reply,
+                                window,
+                                mode,
+                                connection,
+                            };
                             continue;
                         }
                         Poll::Ready(Some(ControlCommand::CloseConnection(reply))) => {
                             self.commands.close();
+                            self.ready.clear();
 
                             self.state = State::Closing {
                                 reply: Some(reply),
@@ -104,6 +267,33 @@ where
                             };
                             continue;
                         }
+                        Poll::Ready(Some(ControlCommand::Drain(reply))) => {
+                            self.state = State::Draining {
+                                replies: vec![reply],
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::ResetStream(id, reply))) => {
+                            let _ = reply.send(connection.reset_stream(id));
+                            self.state = State::Idle(connection);
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::UpdateConfig(update, reply))) => {
+                            let _ = reply.send(connection.update_config(&update));
+                            self.state = State::Idle(connection);
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::Stats(reply))) => {
+                            let _ = reply.send(connection.stats());
+                            self.state = State::Idle(connection);
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::Ready(reply))) => {
+                            self.ready.push(reply);
+                            self.state = State::Idle(connection);
+                            continue;
+                        }
                         Poll::Ready(None) => {
                             // Last `Control` sender was dropped, close te connection.
                             self.state = State::Closing {
@@ -115,29 +305,151 @@ where
                         Poll::Pending => {}
                     }
 
+                    self.poll_ready(&mut connection, cx);
                     self.state = State::Idle(connection);
                     return Poll::Pending;
                 }
                 State::OpeningNewStream {
-                    reply,
+                    mut reply,
+                    window,
+                    mode,
                     mut connection,
-                } => match connection.poll_new_outbound(cx) {
-                    Poll::Ready(stream) => {
-                        let _ = reply.send(stream);
-
+                } => {
+                    if reply.poll_canceled(cx).is_ready() {
+                        // The future of `Control::open_stream` has been dropped.
+                        log::debug!("abandon opening a stream");
                         self.state = State::Idle(connection);
                         continue;
                     }
-                    Poll::Pending => {
-                        self.state = State::OpeningNewStream { reply, connection };
-                        return Poll::Pending;
+                    match connection.poll_new_outbound_inner(cx, window, mode) {
+                        Poll::Ready(stream) => {
+                            let _ = reply.send(stream);
+
+                            self.state = State::Idle(connection);
+                            continue;
+                        }
+                        Poll::Pending => {
+                            // Keep driving the connection, e.g. to receive the
+                            // ACKs opening the stream waits for.
+                            let poll = connection.poll_next_inbound(cx);
+                            self.state = State::OpeningNewStream {
+                                reply,
+                                window,
+                                mode,
+                                connection,
+                            };
+                            return poll;
+                        }
                     }
-                },
+                }
+                State::Draining {
+                    mut replies,
+                    mut connection,
+                } => {
+                    // No more streams can be opened.
+                    self.ready.clear();
+                    match self.commands.poll_next_unpin(cx) {
+                        Poll::Ready(Some(ControlCommand::OpenStream(reply, ..))) => {
+                            let _ = reply.send(Err(ConnectionError::Closed));
+
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::Drain(reply))) => {
+                            replies.push(reply);
+
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::ResetStream(id, reply))) => {
+                            let _ = reply.send(connection.reset_stream(id));
+
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::UpdateConfig(update, reply))) => {
+                            let _ = reply.send(connection.update_config(&update));
+
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::Stats(reply))) => {
+                            let _ = reply.send(connection.stats());
+
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::Ready(reply))) => {
+                            let _ = reply.send(Err(ConnectionError::Closed));
+
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            continue;
+                        }
+                        Poll::Ready(Some(ControlCommand::CloseConnection(reply))) => {
+                            // Closing takes precedence over draining.
+                            for r in replies {
+                                let _ = r.send(());
+                            }
+                            self.commands.close();
+
+                            self.state = State::Closing {
+                                reply: Some(reply),
+                                inner: Closing::DrainingControlCommands { connection },
+                            };
+                            continue;
+                        }
+                        // Even if the last `Control` was dropped, we keep draining.
+                        Poll::Ready(None) | Poll::Pending => {}
+                    }
+
+                    match connection.poll_drain(cx) {
+                        Poll::Ready(result) => {
+                            for r in replies {
+                                let _ = r.send(());
+                            }
+                            self.commands.close();
+
+                            self.state = State::Closing {
+                                reply: None,
+                                inner: Closing::DrainingControlCommands { connection },
+                            };
+                            if let Err(e) = result {
+                                return Poll::Ready(Some(Err(e)));
+                            }
+                            continue;
+                        }
+                        Poll::Pending => {
+                            self.state = State::Draining {
+                                replies,
+                                connection,
+                            };
+                            return Poll::Pending;
+                        }
+                    }
+                }
                 State::Closing {
                     reply,
                     inner: Closing::DrainingControlCommands { connection },
                 } => match self.commands.poll_next_unpin(cx) {
-                    Poll::Ready(Some(ControlCommand::OpenStream(new_reply))) => {
+                    Poll::Ready(Some(ControlCommand::OpenStream(new_reply, ..))) => {
                         let _ = new_reply.send(Err(ConnectionError::Closed));
 
                         self.state = State::Closing {
@@ -146,7 +458,17 @@ where
                         };
                         continue;
                     }
-                    Poll::Ready(Some(ControlCommand::CloseConnection(new_reply))) => {
+                    Poll::Ready(Some(ControlCommand::Ready(new_reply))) => {
+                        let _ = new_reply.send(Err(ConnectionError::Closed));
+
+                        self.state = State::Closing {
+                            reply,
+                            inner: Closing::DrainingControlCommands { connection },
+                        };
+                        continue;
+                    }
+                    Poll::Ready(Some(ControlCommand::CloseConnection(new_reply)))
+                    | Poll::Ready(Some(ControlCommand::Drain(new_reply))) => {
                         let _ = new_reply.send(());
 
                         self.state = State::Closing {
@@ -155,6 +477,27 @@ where
                         };
                         continue;
                     }
+                    Poll::Ready(Some(ControlCommand::ResetStream(_, new_reply)))
+                    | Poll::Ready(Some(ControlCommand::UpdateConfig(_, new_reply))) => {
+                        // All streams are closed along with the connection,
+                        // which is no longer active.
+                        let _ = new_reply.send(false);
+
+                        self.state = State::Closing {
+                            reply,
+                            inner: Closing::DrainingControlCommands { connection },
+                        };
+                        continue;
+                    }
+                    Poll::Ready(Some(ControlCommand::Stats(new_reply))) => {
+                        let _ = new_reply.send(connection.stats());
+
+                        self.state = State::Closing {
+                            reply,
+                            inner: Closing::DrainingControlCommands { connection },
+                        };
+                        continue;
+                    }
                     Poll::Ready(None) => {
                         self.state = State::Closing {
                             reply,
@@ -202,10 +545,25 @@ where
 
 #[derive(Debug)]
 enum ControlCommand {
-    /// Open a new stream to the remote end.
-    OpenStream(oneshot::Sender<Result<Stream>>),
+    /// Open a new stream to the remote end, optionally with a custom receive
+    /// window and open mode.
+    OpenStream(
+        oneshot::Sender<Result<Stream>>,
+        Option<u32>,
+        Option<OpenMode>,
+    ),
     /// Close the whole connection.
     CloseConnection(oneshot::Sender<()>),
+    /// Drain the connection, then close it.
+    Drain(oneshot::Sender<()>),
+    /// Reset a stream, replying whether it exists.
+    ResetStream(StreamId, oneshot::Sender<bool>),
+    /// Change settings, replying whether the connection is active.
+    UpdateConfig(ConfigUpdate, oneshot::Sender<bool>),
+    /// Reply with a summary of the state of the connection.
+    Stats(oneshot::Sender<ConnectionStats>),
+    /// Reply once a new stream can be opened.
+    Ready(oneshot::Sender<Result<()>>),
 }
 
 /// The state of a [`ControlledConnection`].
@@ -213,6 +571,13 @@ enum State<T> {
     Idle(Connection<T>),
     OpeningNewStream {
         reply: oneshot::Sender<Result<Stream>>,
+        window: Option<u32>,
+        mode: Option<OpenMode>,
+        connection: Connection<T>,
+    },
+    Draining {
+        /// The channels to the [`Control`]s which requested draining.
+        replies: Vec<oneshot::Sender<()>>,
         connection: Connection<T>,
     },
     Closing {
@@ -236,7 +601,7 @@ enum Closing<T> {
 
 impl<T> futures::Stream for ControlledConnection<T>
 where
-    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    T: Transport + Send + 'static,
 {
     type Item = Result<Stream>;
 
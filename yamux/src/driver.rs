@@ -0,0 +1,222 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Driving connections in tasks of their own, see `Connection::spawn`, which
+// `server::Listener` and `client::Connector` build on.
+
+use crate::{
+    ConfigError, Connection, ConnectionStats, Control, ControlledConnection, Result, Stream,
+    Transport,
+};
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, BoxFuture, Either, Shared},
+    FutureExt, StreamExt,
+};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// Spawns the tasks driving connections on an executor, e.g.
+/// `Arc::new(|f| { tokio::spawn(f); })`.
+pub type Spawn = Arc<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>;
+
+/// The error of starting background tasks without a spawner.
+pub(crate) fn no_spawner() -> ConfigError {
+    ConfigError::new("spawner", "none configured, see `Config::set_spawner`")
+}
+
+/// A [`Spawn`] of a [`crate::Config`].
+#[derive(Clone)]
+pub(crate) struct Spawner(pub(crate) Spawn);
+
+impl fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Spawner")
+    }
+}
+
+impl<T: Transport + Send + 'static> Connection<T> {
+    /// Run the connection in a task spawned with `spawn`, see [`Connection::drive`].
+    pub fn spawn(self, spawn: Spawn) -> Handle {
+        let (handle, driver) = self.drive();
+        spawn(driver.boxed());
+        handle
+    }
+
+    /// Run the connection in a task spawned with the spawner of its
+    /// configuration, see [`crate::Config::set_spawner`] and [`Connection::spawn`].
+    ///
+    /// Fails if no spawner has been configured, or the connection is closed.
+    pub fn start(self) -> std::result::Result<Handle, ConfigError> {
+        let spawn = self.spawner().ok_or_else(no_spawner)?;
+        Ok(self.spawn(spawn))
+    }
+
+    /// Split the connection into a [`Handle`] and the future driving it.
+    ///
+    /// The future runs the event loop of the connection, i.e. it polls for
+    /// inbound streams and commands of the handle, until the connection is
+    /// closed. It must be spawned or otherwise polled for the handle to make
+    /// progress.
+    pub fn drive(self) -> (Handle, impl Future<Output = ()> + Send + 'static) {
+        let (control, connection) = Control::new(self);
+        let (tx, rx) = mpsc::unbounded();
+        let shutdown = ShutdownHandle::new();
+        let driver = drive(connection, control.clone(), tx, shutdown.clone());
+        let handle = Handle {
+            control,
+            incoming: Incoming(rx),
+            shutdown,
+        };
+        (handle, driver)
+    }
+}
+
+/// A [`Connection`] driven in the background, see [`Connection::spawn`].
+///
+/// Dropping the handle closes the connection, see [`Handle::shutdown_handle`].
+#[derive(Debug)]
+pub struct Handle {
+    control: Control,
+    incoming: Incoming,
+    shutdown: ShutdownHandle,
+}
+
+impl Handle {
+    /// Open a new stream to the remote.
+    pub async fn open_stream(&mut self) -> Result<Stream> {
+        self.control.open_stream().await
+    }
+
+    /// The streams opened by the remote.
+    pub fn incoming(&mut self) -> &mut Incoming {
+        &mut self.incoming
+    }
+
+    /// Close the connection.
+    pub async fn close(&mut self) -> Result<()> {
+        self.control.close().await
+    }
+
+    /// A summary of the state of the connection, see [`Connection::stats`].
+    pub async fn stats(&mut self) -> Result<ConnectionStats> {
+        self.control.stats().await
+    }
+
+    /// A [`Control`] of the connection, e.g. to open streams from other tasks.
+    pub fn control(&self) -> Control {
+        self.control.clone()
+    }
+
+    /// A handle to shut down the connection.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.shutdown.shutdown()
+    }
+}
+
+/// The inbound streams of a [`Handle`], which end once the connection is closed.
+#[derive(Debug)]
+pub struct Incoming(mpsc::UnboundedReceiver<Stream>);
+
+impl futures::Stream for Incoming {
+    type Item = Stream;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Stream>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// Shuts down a [`Handle`], a [`Listener`](crate::server::Listener) or a
+/// [`Connector`](crate::client::Connector).
+///
+/// All its connections are closed gracefully and a listener stops accepting
+/// new ones.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    trigger: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    signal: Shared<oneshot::Receiver<()>>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Self {
+        let (tx, rx) = oneshot::channel();
+        ShutdownHandle {
+            trigger: Arc::new(Mutex::new(Some(tx))),
+            signal: rx.shared(),
+        }
+    }
+
+    /// Initiate the shutdown. Does nothing if it has already been initiated.
+    pub fn shutdown(&self) {
+        if let Some(tx) = self.trigger.lock().take() {
+            let _ = tx.send(());
+        }
+    }
+
+    /// Whether the shutdown has been initiated.
+    pub fn is_shutdown(&self) -> bool {
+        self.trigger.lock().is_none()
+    }
+
+    /// A future which completes once the shutdown has been initiated.
+    pub(crate) fn signal(&self) -> Shared<oneshot::Receiver<()>> {
+        self.signal.clone()
+    }
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("shutdown", &self.is_shutdown())
+            .finish()
+    }
+}
+
+/// Drive the connection, passing inbound streams to `inbound`, until it is
+/// closed by the remote, fails or is shut down.
+pub(crate) async fn drive<T>(
+    mut connection: ControlledConnection<T>,
+    mut control: Control,
+    inbound: mpsc::UnboundedSender<Stream>,
+    shutdown: ShutdownHandle,
+) where
+    T: Transport + Send + 'static,
+{
+    let mut signal = shutdown.signal();
+    loop {
+        match future::select(connection.next(), &mut signal).await {
+            Either::Left((Some(Ok(stream)), _)) => {
+                // The stream is dropped, i.e. reset, if nobody accepts streams anymore.
+                let _ = inbound.unbounded_send(stream);
+            }
+            Either::Left((Some(Err(e)), _)) => {
+                log::debug!("connection failed: {}", e);
+                return;
+            }
+            Either::Left((None, _)) => return,
+            Either::Right(_) => break,
+        }
+    }
+    // Close the connection, driving it until the remote acknowledged.
+    let drain = connection.for_each(|_| future::ready(()));
+    let _ = future::join(control.close(), drain).await;
+}
@@ -8,7 +8,12 @@
 // at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
 // at https://opensource.org/licenses/MIT.
 
-use crate::frame::FrameDecodeError;
+use crate::connection::Id;
+use crate::frame::{
+    header::{Header, StreamId},
+    FrameDecodeError,
+};
+use std::{fmt, io, time::Duration};
 
 /// The various error cases a connection may encounter.
 #[non_exhaustive]
@@ -17,25 +22,106 @@ pub enum ConnectionError {
     /// An underlying I/O error occured.
     Io(std::io::Error),
     /// Decoding a Yamux message frame failed.
-    Decode(FrameDecodeError),
+    Decode {
+        /// The connection which received the undecodable frame.
+        connection: Id,
+        /// The decoding error.
+        source: FrameDecodeError,
+    },
+    /// A protocol violation terminated the connection.
+    Protocol(ProtocolError),
+    /// The remote terminated the connection with a `GoAway` error code.
+    GoAwayReceived {
+        /// The connection which has been terminated.
+        connection: Id,
+        /// The error code sent by the remote.
+        code: u32,
+    },
     /// The whole range of stream IDs has been used up.
     NoMoreStreamIds,
     /// An operation fails because the connection is closed.
     Closed,
     /// Too many streams are open, so no further ones can be opened at this time.
     TooManyStreams,
+    /// The remote stopped responding, see [`crate::Config::set_liveness_probe`].
+    PeerUnresponsive {
+        /// The connection to the unresponsive remote.
+        connection: Id,
+        /// For how long the last keep-alive ping had not been answered, if
+        /// the remote failed to answer it in time.
+        ping_unanswered: Option<Duration>,
+        /// For how long writes to the I/O resource had been blocked, if they were.
+        write_blocked: Option<Duration>,
+    },
 }
 
-impl std::fmt::Display for ConnectionError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl ConnectionError {
+    /// Whether the connection is unusable after this error.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            ConnectionError::Io(_)
+            | ConnectionError::Decode { .. }
+            | ConnectionError::Protocol(_)
+            | ConnectionError::GoAwayReceived { .. }
+            | ConnectionError::NoMoreStreamIds
+            | ConnectionError::Closed
+            | ConnectionError::PeerUnresponsive { .. } => true,
+            ConnectionError::TooManyStreams => false,
+        }
+    }
+
+    /// Whether the failed operation may succeed if retried later on the
+    /// same connection, e.g. once other streams have been closed.
+    pub fn is_recoverable(&self) -> bool {
+        !self.is_fatal()
+    }
+
+    /// The connection this error originated from, if known.
+    pub fn connection(&self) -> Option<Id> {
+        match self {
+            ConnectionError::Decode { connection, .. }
+            | ConnectionError::GoAwayReceived { connection, .. }
+            | ConnectionError::PeerUnresponsive { connection, .. } => Some(*connection),
+            ConnectionError::Protocol(e) => Some(e.connection),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ConnectionError::Io(e) => write!(f, "i/o error: {}", e),
-            ConnectionError::Decode(e) => write!(f, "decode error: {}", e),
+            ConnectionError::Decode { connection, source } => {
+                write!(f, "{}: decode error: {}", connection, source)
+            }
+            ConnectionError::Protocol(e) => e.fmt(f),
+            ConnectionError::GoAwayReceived { connection, code } => {
+                write!(
+                    f,
+                    "{}: remote sent go away with error code {}",
+                    connection, code
+                )
+            }
             ConnectionError::NoMoreStreamIds => {
                 f.write_str("number of stream ids has been exhausted")
             }
             ConnectionError::Closed => f.write_str("connection is closed"),
             ConnectionError::TooManyStreams => f.write_str("maximum number of streams reached"),
+            ConnectionError::PeerUnresponsive {
+                connection,
+                ping_unanswered,
+                write_blocked,
+            } => {
+                write!(f, "{}: remote is unresponsive", connection)?;
+                if let Some(d) = ping_unanswered {
+                    write!(f, ", ping unanswered for {:?}", d)?;
+                }
+                if let Some(d) = write_blocked {
+                    write!(f, ", writes blocked for {:?}", d)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -44,10 +130,13 @@ impl std::error::Error for ConnectionError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             ConnectionError::Io(e) => Some(e),
-            ConnectionError::Decode(e) => Some(e),
-            ConnectionError::NoMoreStreamIds
+            ConnectionError::Decode { source, .. } => Some(source),
+            ConnectionError::Protocol(_)
+            | ConnectionError::GoAwayReceived { .. }
+            | ConnectionError::NoMoreStreamIds
             | ConnectionError::Closed
-            | ConnectionError::TooManyStreams => None,
+            | ConnectionError::TooManyStreams
+            | ConnectionError::PeerUnresponsive { .. } => None,
         }
     }
 }
@@ -58,12 +147,6 @@ impl From<std::io::Error> for ConnectionError {
     }
 }
 
-impl From<FrameDecodeError> for ConnectionError {
-    fn from(e: FrameDecodeError) -> Self {
-        ConnectionError::Decode(e)
-    }
-}
-
 impl From<futures::channel::mpsc::SendError> for ConnectionError {
     fn from(_: futures::channel::mpsc::SendError) -> Self {
         ConnectionError::Closed
@@ -75,3 +158,190 @@ impl From<futures::channel::oneshot::Canceled> for ConnectionError {
         ConnectionError::Closed
     }
 }
+
+/// Why an operation on a [`crate::Stream`] failed.
+///
+/// Reads, writes, flushes and closes of a stream fail with an [`io::Error`]
+/// carrying a `StreamError`, see [`StreamError::from_io`]. If the remote
+/// terminated the connection with a `GoAway` error code, the [`io::Error`]
+/// carries [`ConnectionError::GoAwayReceived`] instead.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamError {
+    /// The stream is closed for writing, because it has been closed via
+    /// [`futures::AsyncWriteExt::close`] or the remote stopped reading, see
+    /// [`crate::Stream::stop_sending`].
+    ///
+    /// The [`io::Error`] is of kind [`io::ErrorKind::BrokenPipe`].
+    SendClosed,
+    /// The stream has been reset.
+    ///
+    /// The [`io::Error`] is of kind [`io::ErrorKind::ConnectionReset`], or
+    /// [`io::ErrorKind::TimedOut`] if the remote did not acknowledge the stream
+    /// in time, or [`io::ErrorKind::InvalidData`] if the remote exceeded the
+    /// receive window of the stream.
+    Reset,
+    /// The connection of the stream is closed.
+    ///
+    /// The [`io::Error`] is of kind [`io::ErrorKind::WriteZero`].
+    ConnectionClosed,
+}
+
+impl StreamError {
+    /// The `StreamError` the given error of a stream operation carries, if any.
+    pub fn from_io(e: &io::Error) -> Option<StreamError> {
+        let failure = e.get_ref()?.downcast_ref::<StreamFailure>()?;
+        Some(failure.error)
+    }
+
+    /// An [`io::Error`] of the given kind carrying this error of the given
+    /// stream, whose IDs prefix the error message.
+    pub(crate) fn into_io(
+        self,
+        connection: Id,
+        stream: StreamId,
+        kind: io::ErrorKind,
+    ) -> io::Error {
+        let failure = StreamFailure {
+            connection,
+            stream,
+            error: self,
+        };
+        io::Error::new(kind, failure)
+    }
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::SendClosed => f.write_str("stream is closed for writing"),
+            StreamError::Reset => f.write_str("stream was reset"),
+            StreamError::ConnectionClosed => f.write_str("connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// A [`StreamError`] along with the stream it occurred on, as carried by an
+/// [`io::Error`].
+#[derive(Debug)]
+struct StreamFailure {
+    connection: Id,
+    stream: StreamId,
+    error: StreamError,
+}
+
+impl fmt::Display for StreamFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}: {}", self.connection, self.stream, self.error)
+    }
+}
+
+impl std::error::Error for StreamFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A violation of the Yamux protocol by the remote.
+#[derive(Clone, Debug)]
+pub struct ProtocolError {
+    connection: Id,
+    stream: Option<StreamId>,
+    header: Option<Header<()>>,
+    reason: &'static str,
+}
+
+impl ProtocolError {
+    pub(crate) fn new(connection: Id, reason: &'static str) -> Self {
+        ProtocolError {
+            connection,
+            stream: None,
+            header: None,
+            reason,
+        }
+    }
+
+    /// Set the frame header which triggered the error, along with its stream.
+    pub(crate) fn with_header(mut self, header: impl Into<Header<()>>) -> Self {
+        let header = header.into();
+        if !header.stream_id().is_session() {
+            self.stream = Some(header.stream_id())
+        }
+        self.header = Some(header);
+        self
+    }
+
+    /// The connection the error occurred on.
+    pub fn connection(&self) -> Id {
+        self.connection
+    }
+
+    /// The stream the offending frame was sent on, unless it concerned the
+    /// whole connection.
+    pub fn stream(&self) -> Option<StreamId> {
+        self.stream
+    }
+
+    /// The header of the frame which triggered the error.
+    pub fn header(&self) -> Option<&Header<()>> {
+        self.header.as_ref()
+    }
+
+    /// A description of the violation.
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.connection)?;
+        if let Some(stream) = self.stream {
+            write!(f, "/{}", stream)?;
+        }
+        write!(f, ": protocol error: {}", self.reason)?;
+        if let Some(header) = &self.header {
+            write!(f, " {}", header)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// An invalid [`Config`](crate::Config) setting, see
+/// [`Config::validate`](crate::Config::validate).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    setting: &'static str,
+    reason: String,
+}
+
+impl ConfigError {
+    pub(crate) fn new(setting: &'static str, reason: impl Into<String>) -> Self {
+        ConfigError {
+            setting,
+            reason: reason.into(),
+        }
+    }
+
+    /// The name of the invalid setting, e.g. `receive_window`.
+    pub fn setting(&self) -> &'static str {
+        self.setting
+    }
+
+    /// A description of what is wrong with the setting.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.setting, self.reason)
+    }
+}
+
+impl std::error::Error for ConfigError {}
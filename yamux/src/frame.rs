@@ -11,12 +11,14 @@
 pub mod header;
 mod io;
 
-use futures::future::Either;
-use header::{Data, GoAway, Header, Ping, StreamId, WindowUpdate};
+use header::{Data, Either, GoAway, Header, Ping, StreamId, WindowUpdate};
 use std::{convert::TryInto, num::TryFromIntError};
 
 pub use io::FrameDecodeError;
-pub(crate) use io::Io;
+#[cfg(feature = "transform")]
+pub use io::FrameTransform;
+pub use io::Io;
+pub(crate) use io::INLINE_BODY_LEN;
 
 /// A Yamux message frame consisting of header and body.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -41,6 +43,16 @@ impl<T> Frame<T> {
         &mut self.header
     }
 
+    #[cfg(feature = "codec")]
+    pub(crate) fn from_parts(header: Header<T>, body: Vec<u8>) -> Self {
+        Frame { header, body }
+    }
+
+    #[cfg(feature = "codec")]
+    pub(crate) fn into_parts(self) -> (Header<T>, Vec<u8>) {
+        (self.header, self.body)
+    }
+
     /// Introduce this frame to the right of a binary frame type.
     pub(crate) fn right<U>(self) -> Frame<Either<U, T>> {
         Frame {
@@ -58,8 +70,28 @@ impl<T> Frame<T> {
     }
 }
 
-impl<A: header::private::Sealed> From<Frame<A>> for Frame<()> {
-    fn from(f: Frame<A>) -> Frame<()> {
+macro_rules! impl_from_typed_frame {
+    ($($t:ty),*) => {
+        $(
+            impl From<Frame<$t>> for Frame<()> {
+                fn from(f: Frame<$t>) -> Frame<()> {
+                    Frame {
+                        header: f.header.into(),
+                        body: f.body,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_typed_frame!(Data, WindowUpdate, Ping, GoAway);
+
+impl<A, B> From<Frame<Either<A, B>>> for Frame<()>
+where
+    Header<Either<A, B>>: Into<Header<()>>,
+{
+    fn from(f: Frame<Either<A, B>>) -> Frame<()> {
         Frame {
             header: f.header.into(),
             body: f.body,
@@ -68,6 +100,12 @@ impl<A: header::private::Sealed> From<Frame<A>> for Frame<()> {
 }
 
 impl Frame<()> {
+    /// The frame body, which is empty unless this is a data frame.
+    #[cfg(feature = "codec")]
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
     pub(crate) fn into_data(self) -> Frame<Data> {
         Frame {
             header: self.header.into_data(),
@@ -82,11 +120,13 @@ impl Frame<()> {
         }
     }
 
-    pub(crate) fn into_ping(self) -> Frame<Ping> {
-        Frame {
-            header: self.header.into_ping(),
-            body: self.body,
-        }
+    /// Append the body of another data frame to the body of this data frame.
+    pub(crate) fn append<T>(&mut self, other: Frame<T>) {
+        debug_assert_eq!(self.header.tag(), header::Tag::Data);
+        debug_assert_eq!(other.header.tag(), header::Tag::Data);
+        self.body.extend_from_slice(&other.body);
+        // Safe cast since the caller limits the body length to a `u32`.
+        self.header.set_len(self.body.len() as u32);
     }
 }
 
@@ -118,9 +158,21 @@ impl Frame<Data> {
         self.body().len() as u32
     }
 
+    /// The number of bytes allocated for the body.
+    pub(crate) fn body_capacity(&self) -> usize {
+        self.body.capacity()
+    }
+
     pub fn into_body(self) -> Vec<u8> {
         self.body
     }
+
+    /// Replace the body, e.g. with its decompressed version.
+    pub(crate) fn set_body(&mut self, body: Vec<u8>) {
+        self.header
+            .set_len(body.len().try_into().expect("body <= u32::MAX"));
+        self.body = body
+    }
 }
 
 impl Frame<WindowUpdate> {
@@ -130,6 +182,18 @@ impl Frame<WindowUpdate> {
             body: Vec::new(),
         }
     }
+
+    pub fn release_stream(id: StreamId) -> Self {
+        let mut header = Header::window_update(id, 0);
+        header.release();
+        Frame::new(header)
+    }
+
+    pub fn stop_sending(id: StreamId) -> Self {
+        let mut header = Header::window_update(id, 0);
+        header.stop_sending();
+        Frame::new(header)
+    }
 }
 
 impl Frame<GoAway> {
@@ -13,144 +13,299 @@ use super::{
     Frame,
 };
 use crate::connection::Id;
+use crate::record::{FrameDirection, Recorder};
+use crate::transport::{EncodedFrame, FrameTransport, Frames, Transport};
 use futures::{prelude::*, ready};
 use std::{
-    fmt, io,
+    collections::VecDeque,
+    fmt,
+    io::{self, IoSlice},
     pin::Pin,
     task::{Context, Poll},
 };
+#[cfg(feature = "transform")]
+pub use transform::FrameTransform;
 
 /// A [`Stream`] and writer of [`Frame`] values.
 #[derive(Debug)]
-pub(crate) struct Io<T> {
+pub struct Io<T> {
     id: Id,
     io: T,
     read_state: ReadState,
-    write_state: WriteState,
+    write_queue: WriteQueue,
+    /// The max. number of frames queued before they are written.
+    max_write_batch: usize,
     max_body_len: usize,
+    pool: BufferPool,
+    /// A buffer for the next small body, see [`Io::recycle`].
+    small_body: Option<Vec<u8>>,
+    recorder: Option<Recorder>,
+    #[cfg(feature = "transform")]
+    transform: Option<transform::Transform>,
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin> Io<T> {
+/// Bodies of inbound frames of up to this many bytes are read into the
+/// reading state itself and then copied into a recycled buffer, see
+/// [`Io::recycle`].
+pub(crate) const INLINE_BODY_LEN: usize = 224;
+
+impl<T> Io<T> {
     pub(crate) fn new(id: Id, io: T, max_frame_body_len: usize) -> Self {
         Io {
             id,
             io,
             read_state: ReadState::Init,
-            write_state: WriteState::Init,
+            write_queue: WriteQueue::default(),
+            max_write_batch: 1,
             max_body_len: max_frame_body_len,
+            pool: BufferPool::new(0, 0),
+            small_body: None,
+            recorder: None,
+            #[cfg(feature = "transform")]
+            transform: None,
+        }
+    }
+
+    /// Get back the underlying I/O resource and the queued frames which have
+    /// not been written at all. A partially read or written frame is discarded.
+    pub(crate) fn into_parts(self) -> (T, Vec<Frame<()>>) {
+        let skip = usize::from(self.write_queue.offset > 0);
+        let unwritten = self
+            .write_queue
+            .frames
+            .into_iter()
+            .skip(skip)
+            .map(|queued| {
+                let header = header::decode(&queued.header).expect("valid encoded header");
+                Frame {
+                    header,
+                    body: queued.body,
+                }
+            })
+            .collect();
+        (self.io, unwritten)
+    }
+
+    /// The headers of the queued frames which have not been completely
+    /// written, in order.
+    pub(crate) fn unwritten_headers(&self) -> impl Iterator<Item = header::Header<()>> + '_ {
+        self.write_queue
+            .frames
+            .iter()
+            .map(|queued| header::decode(&queued.header).expect("valid encoded header"))
+    }
+
+    /// Reuse the bodies of written frames for reading frame bodies of up to
+    /// `max_buffer_len` bytes, keeping at most `capacity` buffers around.
+    pub(crate) fn with_buffer_pool(mut self, capacity: usize, max_buffer_len: usize) -> Self {
+        self.pool = BufferPool::new(capacity, max_buffer_len);
+        self
+    }
+
+    /// Hand back the body of a frame once its bytes have been copied
+    /// elsewhere, so that the next small body is read without allocating.
+    pub(crate) fn recycle(&mut self, mut body: Vec<u8>) {
+        if self.small_body.is_none() && body.capacity() == INLINE_BODY_LEN {
+            body.clear();
+            self.small_body = Some(body)
         }
     }
+
+    /// Pass all frames sent and received to the given recorder.
+    pub(crate) fn with_recorder(mut self, recorder: Option<Recorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Queue up to `n` frames before writing them with a single vectored
+    /// write, or until flushed.
+    pub(crate) fn with_write_batch(mut self, n: usize) -> Self {
+        self.max_write_batch = n;
+        self
+    }
+
+    /// Transform all frames written and read from now on, see
+    /// [`crate::Connection::set_frame_transform`].
+    #[cfg(feature = "transform")]
+    pub(crate) fn set_transform(&mut self, transform: Box<dyn FrameTransform>) {
+        self.transform = Some(transform::Transform::new(transform))
+    }
 }
 
-/// The stages of writing a new `Frame`.
-enum WriteState {
-    Init,
-    Header {
-        header: [u8; header::HEADER_SIZE],
-        buffer: Vec<u8>,
-        offset: usize,
-    },
-    Body {
-        buffer: Vec<u8>,
-        offset: usize,
-    },
+#[cfg(feature = "transform")]
+mod transform;
+
+/// Frame body buffers for reuse.
+#[derive(Debug)]
+struct BufferPool {
+    buffers: Vec<Vec<u8>>,
+    capacity: usize,
+    max_buffer_len: usize,
 }
 
-impl fmt::Debug for WriteState {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            WriteState::Init => f.write_str("(WriteState::Init)"),
-            WriteState::Header { offset, .. } => {
-                write!(f, "(WriteState::Header (offset {}))", offset)
+impl BufferPool {
+    fn new(capacity: usize, max_buffer_len: usize) -> Self {
+        BufferPool {
+            buffers: Vec::with_capacity(capacity),
+            capacity,
+            max_buffer_len,
+        }
+    }
+
+    /// Get a zeroed buffer of the given length.
+    fn take(&mut self, len: usize) -> Vec<u8> {
+        if len <= self.max_buffer_len {
+            if let Some(mut buffer) = self.buffers.pop() {
+                buffer.resize(len, 0);
+                return buffer;
             }
-            WriteState::Body { offset, buffer } => {
-                write!(
-                    f,
-                    "(WriteState::Body (offset {}) (buffer-len {}))",
-                    offset,
-                    buffer.len()
-                )
+        }
+        vec![0; len]
+    }
+
+    /// Return a buffer whose contents are no longer needed.
+    fn put(&mut self, mut buffer: Vec<u8>) {
+        if self.buffers.len() < self.capacity && buffer.capacity() <= self.max_buffer_len {
+            buffer.clear();
+            self.buffers.push(buffer)
+        }
+    }
+}
+
+/// The max. number of buffers passed to a single vectored write.
+const MAX_IO_SLICES: usize = 64;
+
+/// An encoded frame waiting to be written.
+struct Queued {
+    header: [u8; header::HEADER_SIZE],
+    body: Vec<u8>,
+    /// The transformed frame which is written instead, if frames are transformed.
+    sealed: Option<Vec<u8>>,
+}
+
+impl Queued {
+    /// The number of bytes to write.
+    fn len(&self) -> usize {
+        match &self.sealed {
+            Some(sealed) => sealed.len(),
+            None => self.header.len() + self.body.len(),
+        }
+    }
+}
+
+/// Encoded frames waiting to be written.
+#[derive(Default)]
+struct WriteQueue {
+    frames: VecDeque<Queued>,
+    /// The number of bytes of the first frame which have been written.
+    offset: usize,
+}
+
+impl WriteQueue {
+    /// Collect the unwritten parts of the queued frames, returning the
+    /// number of slices used.
+    fn slices<'a>(&'a self, slices: &mut [IoSlice<'a>; MAX_IO_SLICES]) -> usize {
+        let mut n = 0;
+        let mut offset = self.offset;
+        for Queued {
+            header,
+            body,
+            sealed,
+        } in &self.frames
+        {
+            if n + 2 > MAX_IO_SLICES {
+                break;
+            }
+            if let Some(sealed) = sealed {
+                slices[n] = IoSlice::new(&sealed[offset..]);
+                n += 1;
+            } else if offset < header.len() {
+                slices[n] = IoSlice::new(&header[offset..]);
+                n += 1;
+                if !body.is_empty() {
+                    slices[n] = IoSlice::new(body);
+                    n += 1;
+                }
+            } else {
+                slices[n] = IoSlice::new(&body[offset - header.len()..]);
+                n += 1;
+            }
+            offset = 0;
+        }
+        n
+    }
+
+    /// Remove `n` written bytes from the queue, returning the bodies of
+    /// completely written frames to the pool.
+    fn advance(&mut self, mut n: usize, pool: &mut BufferPool) {
+        while n > 0 {
+            let queued = self.frames.front().expect("written bytes are queued");
+            let remaining = queued.len() - self.offset;
+            if n < remaining {
+                self.offset += n;
+                return;
+            }
+            n -= remaining;
+            self.offset = 0;
+            if let Some(queued) = self.frames.pop_front() {
+                if queued.body.capacity() > 0 {
+                    pool.put(queued.body)
+                }
             }
         }
     }
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin> Sink<Frame<()>> for Io<T> {
+impl fmt::Debug for WriteQueue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "(WriteQueue (frames {}) (offset {}))",
+            self.frames.len(),
+            self.offset
+        )
+    }
+}
+
+impl<T: Transport> Sink<Frame<()>> for Io<T> {
     type Error = io::Error;
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let this = Pin::into_inner(self);
-        loop {
-            log::trace!("{}: write: {:?}", this.id, this.write_state);
-            match &mut this.write_state {
-                WriteState::Init => return Poll::Ready(Ok(())),
-                WriteState::Header {
-                    header,
-                    buffer,
-                    ref mut offset,
-                } => match Pin::new(&mut this.io).poll_write(cx, &header[*offset..]) {
-                    Poll::Pending => return Poll::Pending,
-                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                    Poll::Ready(Ok(n)) => {
-                        if n == 0 {
-                            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
-                        }
-                        *offset += n;
-                        if *offset == header.len() {
-                            if !buffer.is_empty() {
-                                let buffer = std::mem::take(buffer);
-                                this.write_state = WriteState::Body { buffer, offset: 0 };
-                            } else {
-                                this.write_state = WriteState::Init;
-                            }
-                        }
-                    }
-                },
-                WriteState::Body {
-                    buffer,
-                    ref mut offset,
-                } => match Pin::new(&mut this.io).poll_write(cx, &buffer[*offset..]) {
-                    Poll::Pending => return Poll::Pending,
-                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                    Poll::Ready(Ok(n)) => {
-                        if n == 0 {
-                            return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
-                        }
-                        *offset += n;
-                        if *offset == buffer.len() {
-                            this.write_state = WriteState::Init;
-                        }
-                    }
-                },
-            }
-        }
+        T::poll_ready(self.get_mut(), cx)
     }
 
     fn start_send(self: Pin<&mut Self>, f: Frame<()>) -> Result<(), Self::Error> {
-        let header = header::encode(&f.header);
-        let buffer = f.body;
-        self.get_mut().write_state = WriteState::Header {
-            header,
-            buffer,
-            offset: 0,
-        };
-        Ok(())
+        let this = self.get_mut();
+        if let Some(recorder) = &this.recorder {
+            recorder.record(this.id, FrameDirection::Outbound, &f.header, &f.body)
+        }
+        T::start_send(this, f)
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let this = Pin::into_inner(self);
-        ready!(this.poll_ready_unpin(cx))?;
-        Pin::new(&mut this.io).poll_flush(cx)
+        T::poll_flush(self.get_mut(), cx)
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let this = Pin::into_inner(self);
-        ready!(this.poll_ready_unpin(cx))?;
-        Pin::new(&mut this.io).poll_close(cx)
+        T::poll_close(self.get_mut(), cx)
+    }
+}
+
+impl<T: Transport> Stream for Io<T> {
+    type Item = Result<Frame<()>, FrameDecodeError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let next = T::poll_next(this, cx);
+        if let (Some(recorder), Poll::Ready(Some(Ok(frame)))) = (&this.recorder, &next) {
+            recorder.record(this.id, FrameDirection::Inbound, &frame.header, &frame.body)
+        }
+        next
     }
 }
 
 /// The stages of reading a new `Frame`.
+#[allow(clippy::large_enum_variant)] // Small bodies do not allocate.
 enum ReadState {
     /// Initial reading state.
     Init,
@@ -165,13 +320,65 @@ enum ReadState {
         offset: usize,
         buffer: Vec<u8>,
     },
+    /// Reading a frame body of up to [`INLINE_BODY_LEN`] bytes.
+    SmallBody {
+        header: header::Header<()>,
+        offset: usize,
+        buffer: [u8; INLINE_BODY_LEN],
+    },
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Io<T> {
-    type Item = Result<Frame<()>, FrameDecodeError>;
+/// Frames are written to and read from a byte stream.
+impl<T: AsyncRead + AsyncWrite + Unpin> Transport for T {
+    fn poll_ready(this: &mut Io<T>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while this.write_queue.frames.len() >= this.max_write_batch {
+            ready!(poll_write_queued(this, cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(this: &mut Io<T>, f: Frame<()>) -> io::Result<()> {
+        debug_assert!(
+            this.write_queue.frames.len() < this.max_write_batch,
+            "{}: frame sent without `poll_ready`",
+            this.id
+        );
+        let header = header::encode(&f.header);
+        #[cfg(feature = "transform")]
+        let sealed = match this.transform.as_mut() {
+            Some(transform) => Some(transform.seal(&header, &f.body)?),
+            None => None,
+        };
+        #[cfg(not(feature = "transform"))]
+        let sealed = None;
+        this.write_queue.frames.push_back(Queued {
+            header,
+            body: f.body,
+            sealed,
+        });
+        Ok(())
+    }
+
+    fn poll_flush(this: &mut Io<T>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !this.write_queue.frames.is_empty() {
+            ready!(poll_write_queued(this, cx))?;
+        }
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_close(this: &mut Io<T>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(<T as Transport>::poll_flush(this, cx))?;
+        Pin::new(&mut this.io).poll_close(cx)
+    }
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        let mut this = &mut *self;
+    fn poll_next(
+        this: &mut Io<T>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<()>, FrameDecodeError>>> {
+        #[cfg(feature = "transform")]
+        if this.transform.is_some() {
+            return transform::poll_next(this, cx);
+        }
         loop {
             log::trace!("{}: read: {:?}", this.id, this.read_state);
             match this.read_state {
@@ -206,10 +413,18 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Io<T> {
                             ))));
                         }
 
-                        this.read_state = ReadState::Body {
-                            header,
-                            offset: 0,
-                            buffer: vec![0; body_len],
+                        this.read_state = if body_len > 0 && body_len <= INLINE_BODY_LEN {
+                            ReadState::SmallBody {
+                                header,
+                                offset: 0,
+                                buffer: [0; INLINE_BODY_LEN],
+                            }
+                        } else {
+                            ReadState::Body {
+                                header,
+                                offset: 0,
+                                buffer: this.pool.take(body_len),
+                            }
                         };
 
                         continue;
@@ -241,6 +456,33 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Io<T> {
                         return Poll::Ready(Some(Ok(Frame { header: h, body: v })));
                     }
 
+                    let buf = &mut buffer[*offset..body_len];
+                    match ready!(Pin::new(&mut this.io).poll_read(cx, buf))? {
+                        0 => {
+                            let e = FrameDecodeError::Io(io::ErrorKind::UnexpectedEof.into());
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                        n => *offset += n,
+                    }
+                }
+                ReadState::SmallBody {
+                    ref header,
+                    ref mut offset,
+                    ref mut buffer,
+                } => {
+                    let body_len = header.len().val() as usize;
+
+                    if *offset == body_len {
+                        let h = header.clone();
+                        let mut v = this
+                            .small_body
+                            .take()
+                            .unwrap_or_else(|| Vec::with_capacity(INLINE_BODY_LEN));
+                        v.extend_from_slice(&buffer[..body_len]);
+                        this.read_state = ReadState::Init;
+                        return Poll::Ready(Some(Ok(Frame { header: h, body: v })));
+                    }
+
                     let buf = &mut buffer[*offset..body_len];
                     match ready!(Pin::new(&mut this.io).poll_read(cx, buf))? {
                         0 => {
@@ -255,6 +497,84 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Io<T> {
     }
 }
 
+/// Write as many of the queued frames as possible with a single vectored write.
+fn poll_write_queued<T>(this: &mut Io<T>, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+where
+    T: AsyncWrite + Unpin,
+{
+    log::trace!("{}: write: {:?}", this.id, this.write_queue);
+    let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+    let n = this.write_queue.slices(&mut slices);
+    let written = ready!(Pin::new(&mut this.io).poll_write_vectored(cx, &slices[..n]))?;
+    if written == 0 {
+        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+    }
+    this.write_queue.advance(written, &mut this.pool);
+    Poll::Ready(Ok(()))
+}
+
+/// Frames are handed to and received from a [`FrameTransport`] as a whole.
+impl<F: FrameTransport> Transport for Frames<F> {
+    fn poll_ready(this: &mut Io<Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        this.io.get_mut().poll_ready(cx)
+    }
+
+    fn start_send(this: &mut Io<Self>, f: Frame<()>) -> io::Result<()> {
+        log::trace!("{}: write: {}", this.id, f.header);
+        let frame = EncodedFrame {
+            header: header::encode(&f.header),
+            body: f.body,
+        };
+        this.io.get_mut().start_send(frame)
+    }
+
+    fn poll_flush(this: &mut Io<Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        this.io.get_mut().poll_flush(cx)
+    }
+
+    fn poll_close(this: &mut Io<Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        this.io.get_mut().poll_close(cx)
+    }
+
+    fn poll_next(
+        this: &mut Io<Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<()>, FrameDecodeError>>> {
+        let frame = match ready!(this.io.get_mut().poll_recv(cx)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+            None => return Poll::Ready(None),
+        };
+
+        let header = header::decode(&frame.header)?;
+
+        log::trace!("{}: read: {}", this.id, header);
+
+        let body_len = if header.tag() == header::Tag::Data {
+            header.len().val() as usize
+        } else {
+            0
+        };
+
+        if body_len > this.max_body_len {
+            return Poll::Ready(Some(Err(FrameDecodeError::FrameTooLarge(body_len))));
+        }
+
+        if frame.body.len() != body_len {
+            let e = io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame body length does not match header",
+            );
+            return Poll::Ready(Some(Err(e.into())));
+        }
+
+        Poll::Ready(Some(Ok(Frame {
+            header,
+            body: frame.body,
+        })))
+    }
+}
+
 impl fmt::Debug for ReadState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -275,6 +595,13 @@ impl fmt::Debug for ReadState {
                     buffer.len()
                 )
             }
+            ReadState::SmallBody { header, offset, .. } => {
+                write!(
+                    f,
+                    "(ReadState::SmallBody (header {}) (offset {}))",
+                    header, offset
+                )
+            }
         }
     }
 }
@@ -289,6 +616,9 @@ pub enum FrameDecodeError {
     Header(HeaderDecodeError),
     /// A data frame body length is larger than the configured maximum.
     FrameTooLarge(usize),
+    /// Undoing the transformation of an inbound frame failed, see
+    /// `FrameTransform::decrypt_inbound`.
+    Transform(io::Error),
 }
 
 impl std::fmt::Display for FrameDecodeError {
@@ -297,6 +627,7 @@ impl std::fmt::Display for FrameDecodeError {
             FrameDecodeError::Io(e) => write!(f, "i/o error: {}", e),
             FrameDecodeError::Header(e) => write!(f, "decode error: {}", e),
             FrameDecodeError::FrameTooLarge(n) => write!(f, "frame body is too large ({})", n),
+            FrameDecodeError::Transform(e) => write!(f, "transform error: {}", e),
         }
     }
 }
@@ -307,6 +638,7 @@ impl std::error::Error for FrameDecodeError {
             FrameDecodeError::Io(e) => Some(e),
             FrameDecodeError::Header(e) => Some(e),
             FrameDecodeError::FrameTooLarge(_) => None,
+            FrameDecodeError::Transform(e) => Some(e),
         }
     }
 }
@@ -326,12 +658,18 @@ impl From<HeaderDecodeError> for FrameDecodeError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::frame::header::StreamId;
     use quickcheck::{Arbitrary, Gen, QuickCheck};
     use rand::RngCore;
 
     impl Arbitrary for Frame<()> {
         fn arbitrary(g: &mut Gen) -> Self {
-            let mut header: header::Header<()> = Arbitrary::arbitrary(g);
+            let mut buf = [0; header::HEADER_SIZE];
+            for b in &mut buf[2..] {
+                *b = Arbitrary::arbitrary(g);
+            }
+            buf[1] = *g.choose(&[0, 1, 2, 3]).unwrap();
+            let mut header = header::decode(&buf).expect("valid header");
             let body = if header.tag() == header::Tag::Data {
                 header.set_len(header.len().val() % 4096);
                 let mut b = vec![0; header.len().val() as usize];
@@ -344,6 +682,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn small_bodies_reuse_recycled_buffers() {
+        futures::executor::block_on(async move {
+            let id = crate::connection::Id::random();
+            let mut io = Io::new(id, futures::io::Cursor::new(Vec::new()), 4096);
+            let small = Frame::data(StreamId::new(1), vec![1; INLINE_BODY_LEN]).unwrap();
+            let large = Frame::data(StreamId::new(1), vec![2; INLINE_BODY_LEN + 1]).unwrap();
+            io.send(small.clone().into()).await.unwrap();
+            io.send(small.clone().into()).await.unwrap();
+            io.send(large.clone().into()).await.unwrap();
+            io.io.set_position(0);
+
+            let frame = io.try_next().await.unwrap().unwrap();
+            assert_eq!(frame, small.clone().into());
+            let body = frame.into_data().into_body();
+            let ptr = body.as_ptr();
+            io.recycle(body);
+            let frame = io.try_next().await.unwrap().unwrap();
+            assert_eq!(frame.body.as_ptr(), ptr);
+            assert_eq!(frame, small.into());
+            io.recycle(frame.into_data().into_body());
+            let frame = io.try_next().await.unwrap().unwrap();
+            assert_ne!(frame.body.as_ptr(), ptr);
+            assert_eq!(frame, large.into());
+        })
+    }
+
     #[test]
     fn encode_decode_identity() {
         fn property(f: Frame<()>) -> bool {
@@ -369,4 +734,120 @@ mod tests {
             .tests(10_000)
             .quickcheck(property as fn(Frame<()>) -> bool)
     }
+
+    #[test]
+    fn encode_decode_identity_with_buffer_pool() {
+        fn property(frames: Vec<Frame<()>>) -> bool {
+            futures::executor::block_on(async move {
+                let id = crate::connection::Id::random();
+                let mut io = Io::new(id, futures::io::Cursor::new(Vec::new()), 4096)
+                    .with_buffer_pool(4, 4096);
+                for f in &frames {
+                    if io.send(f.clone()).await.is_err() {
+                        return false;
+                    }
+                }
+                if io.flush().await.is_err() {
+                    return false;
+                }
+                io.io.set_position(0);
+                for f in &frames {
+                    match io.try_next().await {
+                        Ok(Some(x)) if x == *f => {}
+                        _ => return false,
+                    }
+                }
+                true
+            })
+        }
+
+        QuickCheck::new()
+            .tests(1_000)
+            .quickcheck(property as fn(Vec<Frame<()>>) -> bool)
+    }
+
+    #[test]
+    fn encode_decode_identity_with_write_batch() {
+        fn property(frames: Vec<Frame<()>>, max_write: u16) -> bool {
+            futures::executor::block_on(async move {
+                let id = crate::connection::Id::random();
+                let writer = PartialWriter {
+                    inner: futures::io::Cursor::new(Vec::new()),
+                    max_write: usize::from(max_write) + 1,
+                };
+                let mut io = Io::new(id, writer, 4096).with_write_batch(8);
+                for f in &frames {
+                    if io.feed(f.clone()).await.is_err() {
+                        return false;
+                    }
+                }
+                if io.flush().await.is_err() {
+                    return false;
+                }
+                io.io.inner.set_position(0);
+                for f in &frames {
+                    match io.try_next().await {
+                        Ok(Some(x)) if x == *f => {}
+                        _ => return false,
+                    }
+                }
+                true
+            })
+        }
+
+        QuickCheck::new()
+            .tests(1_000)
+            .quickcheck(property as fn(Vec<Frame<()>>, u16) -> bool)
+    }
+
+    /// A writer which writes at most `max_write` bytes at a time.
+    struct PartialWriter {
+        inner: futures::io::Cursor<Vec<u8>>,
+        max_write: usize,
+    }
+
+    impl AsyncRead for PartialWriter {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for PartialWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let n = std::cmp::min(buf.len(), self.max_write);
+            Pin::new(&mut self.inner).poll_write(cx, &buf[..n])
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let mut written = 0;
+            for b in bufs {
+                let n = std::cmp::min(b.len(), self.max_write - written);
+                written += ready!(Pin::new(&mut self.inner).poll_write(cx, &b[..n]))?;
+                if written == self.max_write {
+                    break;
+                }
+            }
+            Poll::Ready(Ok(written))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_close(cx)
+        }
+    }
 }
@@ -0,0 +1,174 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Transformed frames, see `Connection::set_frame_transform`.
+//
+// Each frame is encoded, i.e. its header followed by its body, transformed
+// and written with a big-endian `u32` length prefix, as the transformed
+// frame may differ in length and its header may no longer be readable.
+
+use super::{FrameDecodeError, Io};
+use crate::frame::{header, Frame};
+use bytes::BytesMut;
+use futures::{prelude::*, ready};
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// The length prefix of a transformed frame.
+const PREFIX_SIZE: usize = 4;
+
+/// A transformation of whole frames at the boundary of the I/O resource, e.g.
+/// authenticated encryption for deployments which cannot run TLS, see
+/// [`Connection::set_frame_transform`](crate::Connection::set_frame_transform).
+///
+/// The frames of a connection are transformed in order, so that implementations
+/// can derive nonces from frame counters and thereby detect replayed, reordered
+/// or dropped frames.
+pub trait FrameTransform: Send + 'static {
+    /// Transform an encoded outbound frame, i.e. its 12 byte header followed
+    /// by its body, in place, e.g. by encrypting it.
+    fn encrypt_outbound(&mut self, frame: &mut BytesMut) -> io::Result<()>;
+
+    /// Undo the transformation of the remote on an inbound frame in place.
+    ///
+    /// An error fails the connection.
+    fn decrypt_inbound(&mut self, frame: &mut BytesMut) -> io::Result<()>;
+
+    /// The max. number of bytes [`FrameTransform::encrypt_outbound`] of the
+    /// remote adds to a frame, e.g. the 16 byte tag of ChaCha20-Poly1305.
+    ///
+    /// Larger inbound frames are rejected before they are read.
+    fn max_overhead(&self) -> usize;
+}
+
+pub(crate) struct Transform {
+    inner: Box<dyn FrameTransform>,
+    /// The length prefix of the frame being read.
+    prefix: [u8; PREFIX_SIZE],
+    /// The number of bytes of the frame being read, including its prefix.
+    offset: usize,
+    buffer: BytesMut,
+}
+
+impl Transform {
+    pub(crate) fn new(inner: Box<dyn FrameTransform>) -> Self {
+        Transform {
+            inner,
+            prefix: [0; PREFIX_SIZE],
+            offset: 0,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Transform an encoded frame, returning it with its length prefix.
+    pub(crate) fn seal(
+        &mut self,
+        header: &[u8; header::HEADER_SIZE],
+        body: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        let mut frame = BytesMut::with_capacity(header.len() + body.len());
+        frame.extend_from_slice(header);
+        frame.extend_from_slice(body);
+        self.inner.encrypt_outbound(&mut frame)?;
+        let len = u32::try_from(frame.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "transformed frame too large")
+        })?;
+        let mut sealed = Vec::with_capacity(PREFIX_SIZE + frame.len());
+        sealed.extend_from_slice(&len.to_be_bytes());
+        sealed.extend_from_slice(&frame);
+        Ok(sealed)
+    }
+
+    /// Undo the transformation of a frame and decode it.
+    fn open(&mut self, max_body_len: usize) -> Result<Frame<()>, FrameDecodeError> {
+        let mut frame = std::mem::take(&mut self.buffer);
+        self.inner
+            .decrypt_inbound(&mut frame)
+            .map_err(FrameDecodeError::Transform)?;
+        if frame.len() < header::HEADER_SIZE {
+            let e = io::Error::new(io::ErrorKind::InvalidData, "frame shorter than its header");
+            return Err(e.into());
+        }
+        let body = frame.split_off(header::HEADER_SIZE);
+        let header = header::decode(frame[..].try_into().expect("frame is a header"))?;
+        let body_len = if header.tag() == header::Tag::Data {
+            header.len().val() as usize
+        } else {
+            0
+        };
+        if body_len > max_body_len {
+            return Err(FrameDecodeError::FrameTooLarge(body_len));
+        }
+        if body.len() != body_len {
+            let e = io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame body length does not match header",
+            );
+            return Err(e.into());
+        }
+        Ok(Frame {
+            header,
+            body: body.to_vec(),
+        })
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(Transform (offset {}))", self.offset)
+    }
+}
+
+/// Read the next transformed frame.
+pub(crate) fn poll_next<T>(
+    this: &mut Io<T>,
+    cx: &mut Context<'_>,
+) -> Poll<Option<Result<Frame<()>, FrameDecodeError>>>
+where
+    T: AsyncRead + Unpin,
+{
+    let max_body_len = this.max_body_len;
+    let transform = this.transform.as_mut().expect("frames are transformed");
+    loop {
+        if transform.offset < PREFIX_SIZE {
+            let buf = &mut transform.prefix[transform.offset..];
+            match ready!(Pin::new(&mut this.io).poll_read(cx, buf))? {
+                0 if transform.offset == 0 => return Poll::Ready(None),
+                0 => break,
+                n => transform.offset += n,
+            }
+            if transform.offset == PREFIX_SIZE {
+                let len = u32::from_be_bytes(transform.prefix) as usize;
+                let max = header::HEADER_SIZE + max_body_len + transform.inner.max_overhead();
+                if len > max {
+                    return Poll::Ready(Some(Err(FrameDecodeError::FrameTooLarge(len))));
+                }
+                transform.buffer = BytesMut::zeroed(len);
+            }
+            continue;
+        }
+        let read = transform.offset - PREFIX_SIZE;
+        if read == transform.buffer.len() {
+            transform.offset = 0;
+            return Poll::Ready(Some(transform.open(max_body_len)));
+        }
+        let buf = &mut transform.buffer[read..];
+        match ready!(Pin::new(&mut this.io).poll_read(cx, buf))? {
+            0 => break,
+            n => transform.offset += n,
+        }
+    }
+    let e = FrameDecodeError::Io(io::ErrorKind::UnexpectedEof.into());
+    Poll::Ready(Some(Err(e)))
+}
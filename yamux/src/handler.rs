@@ -0,0 +1,112 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Serving inbound streams with a `StreamHandler`, see `Connection::serve`.
+
+use crate::driver::{no_spawner, ShutdownHandle, Spawn};
+use crate::{ConfigError, Connection, Stream, StreamId, Transport};
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    FutureExt, StreamExt,
+};
+use std::{future::Future, sync::Arc};
+
+/// Handles the inbound streams of a connection, see [`Connection::serve`].
+///
+/// Implementations may use `async fn handle(&self, stream: Stream, id: StreamId)`.
+pub trait StreamHandler: Send + Sync + 'static {
+    /// Handle an inbound stream, e.g. by serving requests over it until the
+    /// remote closes it.
+    fn handle(&self, stream: Stream, id: StreamId) -> impl Future<Output = ()> + Send;
+}
+
+impl<T: Transport + Send + 'static> Connection<T> {
+    /// Serve the inbound streams of the connection, handling each in a task
+    /// spawned with `spawn` (requires the `handler` feature).
+    ///
+    /// Returns a [`ShutdownHandle`] and the future serving the connection,
+    /// which must be spawned or otherwise polled. Once the shutdown has been
+    /// initiated, the connection is drained, i.e. further inbound streams are
+    /// reset while the streams being handled are serviced until they are
+    /// closed, after which the connection is closed. The future completes once
+    /// the connection is closed and all spawned handlers have returned.
+    pub fn serve<H: StreamHandler>(
+        self,
+        handler: H,
+        spawn: Spawn,
+    ) -> (ShutdownHandle, impl Future<Output = ()> + Send + 'static) {
+        let shutdown = ShutdownHandle::new();
+        let serve = serve(self, Arc::new(handler), spawn, shutdown.clone());
+        (shutdown, serve)
+    }
+
+    /// Serve the inbound streams of the connection in a task spawned with the
+    /// spawner of its configuration, which also spawns the handlers, see
+    /// [`crate::Config::set_spawner`] and [`Connection::serve`].
+    ///
+    /// Fails if no spawner has been configured, or the connection is closed.
+    pub fn start_serving<H: StreamHandler>(
+        self,
+        handler: H,
+    ) -> Result<ShutdownHandle, ConfigError> {
+        let spawn = self.spawner().ok_or_else(no_spawner)?;
+        let (shutdown, serve) = self.serve(handler, spawn.clone());
+        spawn(serve.boxed());
+        Ok(shutdown)
+    }
+}
+
+/// Spawn a handler for each inbound stream until the connection is closed
+/// or shut down, then wait for all handlers to return.
+async fn serve<T, H>(
+    mut connection: Connection<T>,
+    handler: Arc<H>,
+    spawn: Spawn,
+    shutdown: ShutdownHandle,
+) where
+    T: Transport + Send + 'static,
+    H: StreamHandler,
+{
+    // Each handler holds a sender, so the receiver ends once all returned.
+    let (running, mut finished) = mpsc::channel::<()>(0);
+    let mut signal = shutdown.signal();
+    let shutdown = loop {
+        let next = future::poll_fn(|cx| connection.poll_next_inbound(cx));
+        match future::select(next, &mut signal).await {
+            Either::Left((Some(Ok(stream)), _)) => {
+                let handler = handler.clone();
+                let running = running.clone();
+                spawn(
+                    async move {
+                        let id = stream.id();
+                        handler.handle(stream, id).await;
+                        drop(running)
+                    }
+                    .boxed(),
+                );
+            }
+            Either::Left((Some(Err(e)), _)) => {
+                log::debug!("{}: connection failed: {}", connection.id(), e);
+                break false;
+            }
+            Either::Left((None, _)) => break false,
+            Either::Right(_) => break true,
+        }
+    };
+    // Draining closes the connection once all streams are closed.
+    if shutdown {
+        if let Err(e) = future::poll_fn(|cx| connection.poll_drain(cx)).await {
+            log::debug!("{}: draining failed: {}", connection.id(), e);
+        }
+    }
+    drop(running);
+    while finished.next().await.is_some() {}
+}
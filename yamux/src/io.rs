@@ -0,0 +1,163 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! I/O utilities for [`Stream`](crate::Stream)s, e.g. to tunnel them.
+
+use crate::DEFAULT_SPLIT_SEND_SIZE;
+use futures::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+    ready,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Copy data in both directions between `a` and `b` until both reached EOF.
+///
+/// Unlike running `futures::io::copy` in each direction, half-closes are
+/// propagated: once one side reached EOF, the other one is closed for
+/// writing, which for a [`Stream`](crate::Stream) sends a FIN, while data
+/// keeps flowing in the opposite direction.
+///
+/// Each direction reuses a single buffer of the default split send size
+/// (see [`Config::set_split_send_size`](crate::Config::set_split_send_size)),
+/// so that each write to a stream results in one data frame. Use
+/// [`copy_bidirectional_with_buffer_size`] for a different split send size.
+///
+/// Returns the number of bytes copied from `a` to `b` and from `b` to `a`.
+pub async fn copy_bidirectional<A, B>(a: &mut A, b: &mut B) -> io::Result<(u64, u64)>
+where
+    A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+    B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    copy_bidirectional_with_buffer_size(a, b, DEFAULT_SPLIT_SEND_SIZE).await
+}
+
+/// Like [`copy_bidirectional`], with buffers of `size` bytes.
+///
+/// # Panics
+///
+/// If the given size is 0.
+pub async fn copy_bidirectional_with_buffer_size<A, B>(
+    a: &mut A,
+    b: &mut B,
+    size: usize,
+) -> io::Result<(u64, u64)>
+where
+    A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+    B: AsyncRead + AsyncWrite + Unpin + ?Sized,
+{
+    assert!(size > 0);
+    let mut a_to_b = Transfer::new(size);
+    let mut b_to_a = Transfer::new(size);
+    future::poll_fn(|cx| {
+        let a_to_b_done = a_to_b.poll(cx, &mut *a, &mut *b)?.is_ready();
+        let b_to_a_done = b_to_a.poll(cx, &mut *b, &mut *a)?.is_ready();
+        if a_to_b_done && b_to_a_done {
+            Poll::Ready(Ok((a_to_b.copied, b_to_a.copied)))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// The copying of data in one direction.
+struct Transfer {
+    state: TransferState,
+    buf: Box<[u8]>,
+    /// The start of the data in `buf` not yet written.
+    pos: usize,
+    /// The end of the data in `buf`.
+    end: usize,
+    /// Whether data has been written since the last flush.
+    needs_flush: bool,
+    copied: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TransferState {
+    /// Data is being copied.
+    Copying,
+    /// The reader reached EOF, the writer is being closed.
+    Closing,
+    /// The writer has been closed.
+    Done,
+}
+
+impl Transfer {
+    fn new(size: usize) -> Self {
+        Transfer {
+            state: TransferState::Copying,
+            buf: vec![0; size].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            needs_flush: false,
+            copied: 0,
+        }
+    }
+
+    fn poll<R, W>(
+        &mut self,
+        cx: &mut Context,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Poll<io::Result<()>>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        loop {
+            match self.state {
+                TransferState::Copying => {
+                    if self.pos == self.end {
+                        let n = match Pin::new(&mut *reader).poll_read(cx, &mut self.buf) {
+                            Poll::Ready(result) => result?,
+                            Poll::Pending => {
+                                // Do not hold back written data while waiting for more.
+                                if self.needs_flush {
+                                    ready!(Pin::new(&mut *writer).poll_flush(cx))?;
+                                    self.needs_flush = false
+                                }
+                                return Poll::Pending;
+                            }
+                        };
+                        if n == 0 {
+                            self.state = TransferState::Closing;
+                            continue;
+                        }
+                        self.pos = 0;
+                        self.end = n
+                    }
+                    while self.pos < self.end {
+                        let buf = &self.buf[self.pos..self.end];
+                        let n = ready!(Pin::new(&mut *writer).poll_write(cx, buf))?;
+                        if n == 0 {
+                            let msg = "failed to write whole buffer";
+                            return Poll::Ready(Err(io::Error::new(io::ErrorKind::WriteZero, msg)));
+                        }
+                        self.pos += n;
+                        self.copied += n as u64;
+                        self.needs_flush = true
+                    }
+                }
+                TransferState::Closing => {
+                    // Closing flushes any data written before.
+                    ready!(Pin::new(&mut *writer).poll_close(cx))?;
+                    self.state = TransferState::Done
+                }
+                TransferState::Done => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
@@ -20,24 +20,165 @@
 //!   [`futures::io::AsyncWrite`], and
 //! - [`Control`], to asynchronously control the [`Connection`].
 //!
+//! [`server::Listener`] and [`client::Connector`] build on these, driving
+//! connections in tasks of their own and handing out [`Stream`]s. Tasks are
+//! spawned with a [`Spawn`], e.g. the one of [`Config::set_spawner`], rather
+//! than on a particular async runtime.
+//! [`pool::ConnectionPool`] spreads streams over several connections to the
+//! same peer and [`io::copy_bidirectional`] tunnels data between streams.
+//! [`supervisor::Supervisor`] pauses many connections once together they carry
+//! too many streams or buffer too many bytes.
+//! Streams opened via [`Control::open_stream_tagged`] carry the ID of their
+//! application protocol, which [`Stream::protocol_tag`] reads on the remote.
+//!
+//! The encoding of frame headers, the state machine of streams and the accounting
+//! of flow-control credit are provided by the `yamux-core` crate, which builds
+//! without the standard library, e.g. to run the protocol logic on embedded
+//! targets with an event loop of their own. [`blocking::Connection`] builds on
+//! it to use Yamux over blocking sockets, without an async runtime.
+//!
+//! Timers, as used by [`Config::set_idle_timeout`], [`Config::set_stream_open_timeout`],
+//! [`Config::set_inbound_stream_ack_deadline`], [`Config::set_close_timeout`],
+//! [`Config::set_coalesce_writes`] and [`Config::set_liveness_probe`], are
+//! based on `futures-timer` by default. The
+//! `tokio` feature uses the timers of the tokio runtime instead, for timers created
+//! within a tokio runtime, and the `async-io` feature those of the reactor
+//! underlying async-std and smol, so that enabling either feature does not break
+//! connections run elsewhere. With
+//! [`Config::set_clock`] they are driven by a [`Clock`], e.g. a [`VirtualClock`]
+//! which, together with [`Config::set_rng_seed`], makes test runs reproducible.
+//!
+//! The `tokio-io` feature, implied by `tokio`, adds `Stream::into_tokio_io`, to use
+//! streams with the I/O traits of tokio, and the `hyper` feature those of hyper,
+//! without changing the timers. The `framed`
+//! feature adds `Stream::into_framed`, to send and receive length-prefixed messages,
+//! and `Stream::read_exact_timeout` and `Stream::read_until_fin`, to read into `Bytes`.
+//! The `handler` feature adds `Connection::serve`, which hands each inbound
+//! stream to a `StreamHandler` in a task of its own, and the `tower` feature
+//! the `tower` module, to send requests over streams with a tower `Service`.
+//!
+//! The [`consts`] module holds the constants of the protocol and the defaults
+//! of this implementation, e.g. for negotiating settings ahead of a connection.
+//!
+//! [`Config::preset`] creates a configuration from a [`Preset`], a tested
+//! combination of window sizes, frame sizes, window update mode, keep-alives
+//! and buffer caps for a kind of deployment.
+//!
+//! [`Config::from_env`] reads a configuration from environment variables. The
+//! `serde` feature implements `Serialize` and `Deserialize` for [`Config`], to
+//! load it from configuration files. Both validate the configuration, see
+//! [`Config::validate`].
+//!
+//! The `metrics` feature adds the `metrics` module, whose `PrometheusExporter`
+//! aggregates the stats of connections and exports them in the Prometheus text
+//! format or via the `metrics` crate.
+//!
+//! The `stall-detector` feature adds `Config::set_stall_detector`, to report
+//! streams which are not polled even though they could make progress, e.g.
+//! because a waker has been lost.
+//!
+//! The `lz4` and `zstd` features add compression algorithms for
+//! [`Config::set_compression`].
+//!
+//! [`Config::set_extensions`] advertises protocol extensions to the remote
+//! with flags stock peers ignore, [`Connection::negotiated_extensions`] tells
+//! which extensions both endpoints support.
+//!
+//! The `transform` feature adds `Connection::set_frame_transform`, to transform
+//! frames at the boundary of the I/O resource with a `FrameTransform`, e.g. to
+//! encrypt them where TLS is not an option.
+//!
+//! The `frame-sink` feature adds `Connection::frame_sink`, an expert API to
+//! inject pings of the session, e.g. with vendor specific nonces, subject to
+//! the same backpressure as the frames of streams.
+//!
+//! The `tls` feature adds the `secured` module, to run connections over TLS, and
+//! the `ws` feature the `transport::ws` module, to run connections over WebSocket.
+//! The `messages` feature adds the `transport::messages` module, to run connections
+//! over any transport of messages, e.g. a QUIC stream, one frame per message.
+//! The `libp2p` feature adds the `libp2p` module, to use this crate as the stream
+//! muxer of rust-libp2p.
+//!
 //! [1]: https://github.com/hashicorp/yamux/blob/master/spec.md
 
 #![forbid(unsafe_code)]
 
+pub mod blocking;
 mod chunks;
+pub mod client;
+mod clock;
+#[cfg(feature = "codec")]
+pub mod codec;
+mod config;
+pub mod consts;
 mod control;
+mod driver;
 mod error;
 mod frame;
+#[cfg(feature = "handler")]
+mod handler;
+pub mod io;
+#[cfg(feature = "libp2p")]
+pub mod libp2p;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+mod policy;
+pub mod pool;
+mod reconnect;
+mod record;
+#[cfg(feature = "tls")]
+pub mod secured;
+pub mod server;
+pub mod supervisor;
+mod sync;
+mod timer;
+#[cfg(feature = "tower")]
+pub mod tower;
+pub mod transport;
 
 pub(crate) mod connection;
 
-pub use crate::connection::{Connection, Mode, Packet, Stream};
+use crate::{
+    connection::SeededIds,
+    driver::Spawner,
+    policy::{OverflowObserver, Policy},
+    record::Recorder,
+    timer::Time,
+};
+use std::{sync::Arc, time::Duration};
+
+pub use crate::clock::{Clock, VirtualClock};
+pub use crate::config::ConfigUpdate;
+#[cfg(feature = "frame-sink")]
+pub use crate::connection::FrameSink;
+#[cfg(feature = "framed")]
+pub use crate::connection::FramedStream;
+#[cfg(feature = "tokio-io")]
+pub use crate::connection::TokioStream;
+pub use crate::connection::{
+    CleanupReport, CloseReason, Connection, ConnectionStats, Id as ConnectionId, Mode, Packet,
+    ReadHalf, ResetReason, ResetsSent, Snapshot, State, Stream, StreamMetrics, StreamSnapshot,
+    WriteHalf,
+};
+#[cfg(feature = "stall-detector")]
+pub use crate::connection::{StallObserver, StalledStream};
 pub use crate::control::{Control, ControlledConnection};
-pub use crate::error::ConnectionError;
+pub use crate::driver::{Handle, Incoming, ShutdownHandle, Spawn};
+pub use crate::error::{ConfigError, ConnectionError, ProtocolError, StreamError};
+#[cfg(feature = "transform")]
+pub use crate::frame::FrameTransform;
 pub use crate::frame::{
-    header::{HeaderDecodeError, StreamId},
+    header::{Flags, Header, HeaderDecodeError, Len, StreamId, Tag},
     FrameDecodeError,
 };
+#[cfg(feature = "handler")]
+pub use crate::handler::StreamHandler;
+pub use crate::policy::{
+    BufferOverflow, BufferOverflowObserver, ConnectionInfo, InboundStreamPolicy, Verdict,
+};
+pub use crate::reconnect::{Connect, Reconnect, ReconnectEvent};
+pub use crate::record::{FrameDirection, FrameRecorder, RecordedFrame};
+pub use crate::transport::{EncodedFrame, FrameTransport, Frames, Transport, FRAME_HEADER_SIZE};
 #[cfg(not(target_os = "espidf"))]
 pub const DEFAULT_CREDIT: u32 = 256 * 1024; // as per yamux specification
 #[cfg(target_os = "espidf")]
@@ -51,6 +192,11 @@ pub type Result<T> = std::result::Result<T, ConnectionError>;
 /// actual upper bound is this value + number of clones.
 const MAX_COMMAND_BACKLOG: usize = 32;
 
+/// How long the connection waits for the woken reader of a [`Stream`] to read
+/// once its receive window is exhausted, before granting credit on its behalf,
+/// given [`WindowUpdateMode::Hybrid`].
+const HYBRID_FALLBACK_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
 /// Default maximum number of bytes a Yamux data frame might carry as its
 /// payload when being send. Larger Payloads will be split.
 ///
@@ -70,7 +216,19 @@ const DEFAULT_SPLIT_SEND_SIZE: usize = 16 * 1024;
 #[cfg(target_os = "espidf")]
 const DEFAULT_SPLIT_SEND_SIZE: usize = 4 * 1024;
 
+/// Default max. number of bytes buffered per stream, which also bounds the
+/// body length of inbound data frames unless configured otherwise.
+#[cfg(not(target_os = "espidf"))]
+const DEFAULT_MAX_BUFFER_SIZE: usize = 1024 * 1024;
+#[cfg(target_os = "espidf")]
+const DEFAULT_MAX_BUFFER_SIZE: usize = 4 * 1024;
+
 /// Specifies when window update frames are sent.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WindowUpdateMode {
     /// Send window updates as soon as a [`Stream`]'s receive window drops to 0.
@@ -95,6 +253,311 @@ pub enum WindowUpdateMode {
     ///   of the frame lengths is less or equal to the available credit of *A* and *B*
     ///   respectively.
     OnRead,
+
+    /// Send window updates on read like [`WindowUpdateMode::OnRead`], but let
+    /// the connection send them on receipt if a [`Stream`] is not being read.
+    ///
+    /// Whenever the receive window of a [`Stream`] is exhausted while no task
+    /// is waiting to read from it, or while the stream was unable to enqueue a
+    /// window update itself, the connection grants new credit to the sender,
+    /// as long as the buffered data does not exceed the max. buffer size (see
+    /// `set_max_buffer_size`). If a task was waiting, the connection checks
+    /// again shortly after, in case the task does not come back to read, and
+    /// each read grants such credit once the buffer has room again. This avoids the deadlock described for
+    /// [`WindowUpdateMode::OnRead`] for writes up to the max. buffer size,
+    /// while still exercising back pressure beyond it.
+    Hybrid,
+}
+
+/// Specifies how much credit must be grantable before a window update frame
+/// is sent, see [`Config::set_window_update_threshold`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowUpdateThreshold {
+    /// A fraction (in `(0, 1]`) of the receive window of a [`Stream`].
+    Fraction(f64),
+
+    /// A number of bytes, capped at the receive window of a [`Stream`].
+    Bytes(u32),
+}
+
+impl WindowUpdateThreshold {
+    /// The min. credit to grant, given the receive window of a stream.
+    pub(crate) fn min_credit(&self, receive_window: u32) -> u32 {
+        let n = match *self {
+            WindowUpdateThreshold::Fraction(f) => (f64::from(receive_window) * f) as u32,
+            WindowUpdateThreshold::Bytes(n) => n,
+        };
+        n.clamp(1, receive_window.max(1))
+    }
+}
+
+/// Specifies how an inbound stream is rejected if it would exceed the
+/// max. number of inbound streams (plus grace margin, see
+/// [`Config::set_max_num_streams_grace`]).
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaxStreamsResponse {
+    /// Terminate the whole connection.
+    ///
+    /// The GoAway carries an internal error if the stream was opened with a
+    /// data frame and a protocol error if it was opened with a window update.
+    GoAway,
+
+    /// Reset only the offending stream and keep the connection open.
+    ///
+    /// This is useful if many unrelated streams are carried over a single
+    /// connection and a single excess stream (e.g. due to a race between
+    /// the remote opening a stream and a local stream being dropped)
+    /// should not terminate all others.
+    Reset,
+}
+
+/// Specifies how a frame whose body exceeds the receive window of its
+/// stream is handled, see [`Config::set_window_violation_policy`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowViolationPolicy {
+    /// Terminate the whole connection with a protocol error.
+    GoAway,
+
+    /// Reset only the offending stream and keep the connection open.
+    ///
+    /// Violations are counted in [`ConnectionStats::window_violations`].
+    Reset,
+}
+
+/// Specifies how a frame is handled which would let the buffer of its stream
+/// grow beyond its limit, see [`Config::set_buffer_overflow_policy`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferOverflowPolicy {
+    /// Reset the stream and discard its buffered data.
+    Reset,
+
+    /// Terminate the whole connection with a protocol error.
+    GoAway,
+
+    /// Buffer the frame, which is within the receive window of the stream,
+    /// and withhold further credit until the stream has been read, as with
+    /// [`WindowUpdateMode::OnRead`].
+    ///
+    /// This keeps the data of a slow reader at the cost of buffering up to
+    /// the receive window beyond the limit.
+    Backpressure,
+}
+
+/// Specifies how the connection responds to an inbound stream which the
+/// application has not acknowledged within the deadline of
+/// [`Config::set_inbound_stream_ack_deadline`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AckDeadlineResponse {
+    /// Acknowledge the stream on behalf of the application, without
+    /// granting more credit.
+    Ack,
+
+    /// Reset the stream, failing its pending and future reads and writes
+    /// with an [`std::io::Error`] of kind [`std::io::ErrorKind::TimedOut`].
+    Reset,
+}
+
+/// Specifies when the SYN of an outbound stream is sent, see
+/// [`Config::set_open_mode`] and [`Control::open_stream_with_mode`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Defer the SYN until the stream sends its first frame, e.g. on the
+    /// first write, saving a frame for streams which are never used.
+    ///
+    /// A receive window larger than the default is granted to the remote
+    /// with a subsequent window update.
+    Lazy,
+
+    /// Send the SYN, along with any receive window larger than the default,
+    /// as soon as the stream is opened, so that the remote sees the stream
+    /// right away.
+    Eager,
+}
+
+/// Specifies how a peer which stopped responding is detected, see
+/// [`Config::set_liveness_probe`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeMode {
+    /// Ping the remote every `interval` and consider it unresponsive if a
+    /// ping has not been answered within `timeout`.
+    KeepAlive {
+        interval: Duration,
+        timeout: Duration,
+    },
+
+    /// Consider the remote unresponsive if writes to the I/O resource have
+    /// been blocked for longer than `timeout`, i.e. if the remote stopped
+    /// reading while frames are pending to be sent.
+    WriteStall { timeout: Duration },
+
+    /// Both [`ProbeMode::KeepAlive`] and [`ProbeMode::WriteStall`], with the
+    /// same timeout.
+    Both {
+        interval: Duration,
+        timeout: Duration,
+    },
+}
+
+impl ProbeMode {
+    /// The interval of keep-alive pings, if any are sent.
+    pub(crate) fn ping_interval(&self) -> Option<Duration> {
+        match *self {
+            ProbeMode::KeepAlive { interval, .. } | ProbeMode::Both { interval, .. } => {
+                Some(interval)
+            }
+            ProbeMode::WriteStall { .. } => None,
+        }
+    }
+
+    pub(crate) fn timeout(&self) -> Duration {
+        match *self {
+            ProbeMode::KeepAlive { timeout, .. }
+            | ProbeMode::WriteStall { timeout }
+            | ProbeMode::Both { timeout, .. } => timeout,
+        }
+    }
+
+    /// Whether blocked writes are considered a sign of an unresponsive remote.
+    pub(crate) fn detects_write_stall(&self) -> bool {
+        !matches!(self, ProbeMode::KeepAlive { .. })
+    }
+}
+
+/// A coherent combination of settings for a kind of deployment, see
+/// [`Config::preset`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Small frames written right away, so that small messages are not
+    /// delayed behind large ones, and early window updates, so that senders
+    /// do not stall waiting for credit.
+    LowLatency,
+
+    /// Large windows, frames and write batches for bulk transfers over
+    /// links with a high bandwidth-delay product.
+    HighThroughput,
+
+    /// The min. receive window, tight caps on buffered data per stream and
+    /// per connection and fewer streams, for constrained hosts.
+    LowMemory,
+
+    /// Coalesced writes to save radio wake-ups, keep-alive pings to detect
+    /// dead links after network changes, and timeouts for idle connections
+    /// and streams which are never acknowledged.
+    Mobile,
+}
+
+/// Specifies how the bodies of data frames are compressed, see
+/// [`Config::set_compression`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Do not compress.
+    None,
+
+    /// Compress with LZ4, which is fast but compresses less (requires the
+    /// `lz4` feature).
+    #[cfg(feature = "lz4")]
+    Lz4,
+
+    /// Compress with Zstandard at the given level, e.g. 3 (requires the
+    /// `zstd` feature).
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+/// A set of protocol extensions, one bit per extension, see
+/// [`Config::set_extensions`] and [`Connection::negotiated_extensions`].
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Extensions(u8);
+
+impl Extensions {
+    /// No extensions.
+    pub const NONE: Extensions = Extensions(0);
+
+    /// The recycling of stream IDs, advertised if enabled via
+    /// [`Config::set_stream_id_recycling`].
+    pub const STREAM_ID_RECYCLING: Extensions = Extensions(0x01);
+
+    /// The `STOP_SENDING` signal of [`Stream::stop_sending`].
+    pub const STOP_SENDING: Extensions = Extensions(0x02);
+
+    /// The compression of data frames, advertised if enabled via
+    /// [`Config::set_compression`].
+    pub const COMPRESSION: Extensions = Extensions(0x04);
+
+    /// The extensions of the given bits.
+    pub const fn from_bits(bits: u8) -> Self {
+        Extensions(bits)
+    }
+
+    /// The bits of these extensions.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether all of the given extensions are contained in these.
+    pub const fn contains(self, other: Extensions) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The extensions contained in these and the given ones.
+    pub const fn intersection(self, other: Extensions) -> Self {
+        Extensions(self.0 & other.0)
+    }
+
+    /// The extensions contained in these or the given ones.
+    pub const fn union(self, other: Extensions) -> Self {
+        Extensions(self.0 | other.0)
+    }
+
+    /// Whether there are no extensions.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
 }
 
 /// Yamux configuration.
@@ -103,18 +566,128 @@ pub enum WindowUpdateMode {
 ///
 /// - receive window = 256 KiB
 /// - max. buffer size (per stream) = 1 MiB
+/// - max. receive buffer memory (per stream) = unlimited
+/// - max. connection receive window = unlimited
+/// - max. frame body length = max. buffer size
 /// - max. number of streams = 8192
+/// - max. number of inbound streams = none (only the max. number of streams applies)
+/// - max. number of outbound streams = none (only the max. number of streams applies)
+/// - max. number of streams grace margin = 0
+/// - max. number of streams response = go away
+/// - window violation policy = go away
+/// - buffer overflow policy = reset
+/// - buffer overflow observer = none
+/// - reset log budget = 10 per second
+/// - spawner = none
 /// - window update mode = on read
+/// - window update threshold = half of the receive window
 /// - read after close = true
 /// - split send size = 16 KiB
+/// - idle timeout = none
+/// - stream open timeout = none
+/// - inbound stream ACK deadline = none
+/// - ACK deadline response = ack
+/// - close timeout = none
+/// - open mode = lazy
+/// - max. ACK backlog = unlimited
+/// - stream ID recycling = false
+/// - buffer pool capacity = 0 (disabled)
+/// - write coalescing = disabled
+/// - max. send rate = unlimited
+/// - liveness probe = none
+/// - compression = none
+/// - extensions = none
+/// - frame budget = 16
+/// - poll budget = 128
+/// - write batch size = 16
+/// - frame recorder = none
+/// - inbound stream policy = none (accept all)
+/// - stall detector = none (requires the `stall-detector` feature)
+/// - clock = system time and timers of the async runtime
+/// - RNG seed = none (random connection IDs)
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(remote = "Self", default, deny_unknown_fields)
+)]
 pub struct Config {
     receive_window: u32,
     max_buffer_size: usize,
+    max_stream_receive_buffer_bytes: Option<usize>,
+    max_connection_receive_window: Option<usize>,
+    max_frame_body_len: Option<usize>,
     max_num_streams: usize,
+    max_inbound_streams: Option<usize>,
+    max_outbound_streams: Option<usize>,
+    max_num_streams_grace: usize,
+    max_num_streams_response: MaxStreamsResponse,
+    window_violation_policy: WindowViolationPolicy,
+    buffer_overflow_policy: BufferOverflowPolicy,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    buffer_overflow_observer: Option<OverflowObserver>,
+    reset_log_budget: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    spawner: Option<Spawner>,
     window_update_mode: WindowUpdateMode,
+    window_update_threshold: WindowUpdateThreshold,
     read_after_close: bool,
     split_send_size: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "idle_timeout_ms", with = "config::opt_millis")
+    )]
+    idle_timeout: Option<Duration>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "stream_open_timeout_ms", with = "config::opt_millis")
+    )]
+    stream_open_timeout: Option<Duration>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "inbound_stream_ack_deadline_ms", with = "config::opt_millis")
+    )]
+    inbound_stream_ack_deadline: Option<Duration>,
+    ack_deadline_response: AckDeadlineResponse,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "close_timeout_ms", with = "config::opt_millis")
+    )]
+    close_timeout: Option<Duration>,
+    open_mode: OpenMode,
+    max_ack_backlog: Option<usize>,
+    stream_id_recycling: bool,
+    buffer_pool: usize,
+    #[cfg_attr(feature = "serde", serde(with = "config::coalesce_writes"))]
+    coalesce_writes: Option<(Duration, usize)>,
+    max_send_rate: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(with = "config::liveness_probe"))]
+    liveness_probe: Option<ProbeMode>,
+    compression: Compression,
+    extensions: Extensions,
+    frame_budget: usize,
+    poll_budget: usize,
+    write_batch_size: usize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frame_recorder: Option<Recorder>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    inbound_stream_policy: Option<Policy>,
+    #[cfg(feature = "stall-detector")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "stall_threshold_ms", with = "config::opt_millis")
+    )]
+    stall_threshold: Option<Duration>,
+    #[cfg(feature = "stall-detector")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stall_observer: Option<connection::StallObserverRef>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    time: Time,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "rng_seed", with = "config::rng_seed")
+    )]
+    seeded_ids: Option<SeededIds>,
 }
 
 impl Default for Config {
@@ -122,22 +695,98 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             receive_window: DEFAULT_CREDIT,
-            max_buffer_size: 1024 * 1024,
+            max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            max_stream_receive_buffer_bytes: None,
+            max_connection_receive_window: None,
+            max_frame_body_len: None,
             max_num_streams: 8192,
+            max_inbound_streams: None,
+            max_outbound_streams: None,
+            max_num_streams_grace: 0,
+            max_num_streams_response: MaxStreamsResponse::GoAway,
+            window_violation_policy: WindowViolationPolicy::GoAway,
+            buffer_overflow_policy: BufferOverflowPolicy::Reset,
+            buffer_overflow_observer: None,
+            reset_log_budget: 10,
+            spawner: None,
             window_update_mode: WindowUpdateMode::OnRead,
+            window_update_threshold: WindowUpdateThreshold::Fraction(0.5),
             read_after_close: true,
             split_send_size: DEFAULT_SPLIT_SEND_SIZE,
+            idle_timeout: None,
+            stream_open_timeout: None,
+            inbound_stream_ack_deadline: None,
+            ack_deadline_response: AckDeadlineResponse::Ack,
+            close_timeout: None,
+            open_mode: OpenMode::Lazy,
+            max_ack_backlog: None,
+            stream_id_recycling: false,
+            buffer_pool: 0,
+            coalesce_writes: None,
+            max_send_rate: None,
+            liveness_probe: None,
+            compression: Compression::None,
+            extensions: Extensions::NONE,
+            frame_budget: 16,
+            poll_budget: 128,
+            write_batch_size: 16,
+            frame_recorder: None,
+            inbound_stream_policy: None,
+            #[cfg(feature = "stall-detector")]
+            stall_threshold: None,
+            #[cfg(feature = "stall-detector")]
+            stall_observer: None,
+            time: Time::default(),
+            seeded_ids: None,
         }
     }
     #[cfg(target_os = "espidf")]
     fn default() -> Self {
         Config {
             receive_window: DEFAULT_CREDIT,
-            max_buffer_size: 4 * 1024,
+            max_buffer_size: DEFAULT_MAX_BUFFER_SIZE,
+            max_stream_receive_buffer_bytes: None,
+            max_connection_receive_window: None,
+            max_frame_body_len: None,
             max_num_streams: 8192,
+            max_inbound_streams: None,
+            max_outbound_streams: None,
+            max_num_streams_grace: 0,
+            max_num_streams_response: MaxStreamsResponse::GoAway,
+            window_violation_policy: WindowViolationPolicy::GoAway,
+            buffer_overflow_policy: BufferOverflowPolicy::Reset,
+            buffer_overflow_observer: None,
+            reset_log_budget: 10,
+            spawner: None,
             window_update_mode: WindowUpdateMode::OnRead,
+            window_update_threshold: WindowUpdateThreshold::Fraction(0.5),
             read_after_close: true,
             split_send_size: DEFAULT_SPLIT_SEND_SIZE,
+            idle_timeout: None,
+            stream_open_timeout: None,
+            inbound_stream_ack_deadline: None,
+            ack_deadline_response: AckDeadlineResponse::Ack,
+            close_timeout: None,
+            open_mode: OpenMode::Lazy,
+            max_ack_backlog: None,
+            stream_id_recycling: false,
+            buffer_pool: 0,
+            coalesce_writes: None,
+            max_send_rate: None,
+            liveness_probe: None,
+            compression: Compression::None,
+            extensions: Extensions::NONE,
+            frame_budget: 16,
+            poll_budget: 128,
+            write_batch_size: 16,
+            frame_recorder: None,
+            inbound_stream_policy: None,
+            #[cfg(feature = "stall-detector")]
+            stall_threshold: None,
+            #[cfg(feature = "stall-detector")]
+            stall_observer: None,
+            time: Time::default(),
+            seeded_ids: None,
         }
     }
 }
@@ -160,18 +809,186 @@ impl Config {
         self
     }
 
-    /// Set the max. number of streams.
+    /// Set the max. memory in bytes the receive buffer of a stream may hold.
+    ///
+    /// Unlike the max. buffer size, which limits the number of received bytes
+    /// not yet read, this accounts for all memory allocated for buffered frame
+    /// bodies, i.e. including bytes already read from a partially read body
+    /// and the spare capacity of bodies read into pooled buffers (see
+    /// [`Config::set_buffer_pool`]). A stream whose buffer would exceed it is
+    /// reset.
+    pub fn set_max_stream_receive_buffer_bytes(&mut self, n: usize) -> &mut Self {
+        self.max_stream_receive_buffer_bytes = Some(n);
+        self
+    }
+
+    /// Set the receive budget of a connection, i.e. the max. sum of the
+    /// receive windows of its streams.
+    ///
+    /// The budget is divided among the streams which can receive data using
+    /// max-min fairness: each stream gets its receive window if possible and
+    /// whatever is left is split evenly among the streams with larger windows,
+    /// so that bulk transfers cannot starve other streams of credit. The
+    /// shares are recomputed as streams are opened and dropped and limit the
+    /// credit granted by subsequent window updates. Their sum never exceeds
+    /// the budget, i.e. if there are more streams than bytes in the budget,
+    /// streams get no more credit until others are dropped. Regardless, each
+    /// stream starts out with the 256 KiB window mandated by the specification.
+    pub fn set_max_connection_receive_window(&mut self, n: usize) -> &mut Self {
+        self.max_connection_receive_window = Some(n);
+        self
+    }
+
+    /// Set the max. body length of inbound data frames.
+    ///
+    /// The length is checked as soon as the frame header is read, i.e. before
+    /// the body is read or any memory is allocated for it. A frame announcing
+    /// a larger body terminates the connection with a protocol error. Unless
+    /// set, the max. buffer size applies.
+    pub fn set_max_frame_body_len(&mut self, n: usize) -> &mut Self {
+        self.max_frame_body_len = Some(n);
+        self
+    }
+
+    /// Set the max. number of streams, inbound and outbound ones combined.
+    ///
+    /// The max. number of inbound and of outbound streams, see
+    /// [`Config::set_max_inbound_streams`] and
+    /// [`Config::set_max_outbound_streams`], limit either kind further.
     pub fn set_max_num_streams(&mut self, n: usize) -> &mut Self {
         self.max_num_streams = n;
         self
     }
 
+    /// Set the max. number of streams opened by the remote.
+    ///
+    /// Further inbound streams are rejected as per
+    /// [`Config::set_max_num_streams_response`]. The max. number of streams
+    /// applies regardless, see [`Config::set_max_num_streams`].
+    pub fn set_max_inbound_streams(&mut self, n: usize) -> &mut Self {
+        self.max_inbound_streams = Some(n);
+        self
+    }
+
+    /// Set the max. number of streams opened by us.
+    ///
+    /// Opening further streams fails with [`ConnectionError::TooManyStreams`]
+    /// until some are closed. The max. number of streams applies regardless,
+    /// see [`Config::set_max_num_streams`].
+    pub fn set_max_outbound_streams(&mut self, n: usize) -> &mut Self {
+        self.max_outbound_streams = Some(n);
+        self
+    }
+
+    /// Set the number of inbound streams which are still accepted beyond
+    /// the max. number of inbound streams.
+    ///
+    /// A small margin absorbs races between the remote opening new streams
+    /// and local streams being closed.
+    pub fn set_max_num_streams_grace(&mut self, n: usize) -> &mut Self {
+        self.max_num_streams_grace = n;
+        self
+    }
+
+    /// Set how to respond to an inbound stream exceeding the max. number
+    /// of inbound streams (plus grace margin).
+    pub fn set_max_num_streams_response(&mut self, r: MaxStreamsResponse) -> &mut Self {
+        self.max_num_streams_response = r;
+        self
+    }
+
+    /// Set how to handle a frame of the remote whose body exceeds the
+    /// receive window of its stream.
+    pub fn set_window_violation_policy(&mut self, p: WindowViolationPolicy) -> &mut Self {
+        self.window_violation_policy = p;
+        self
+    }
+
+    /// Set how to handle a frame of the remote which would let the buffer
+    /// of its stream grow beyond the max. buffer size or the max. receive
+    /// buffer memory.
+    pub fn set_buffer_overflow_policy(&mut self, p: BufferOverflowPolicy) -> &mut Self {
+        self.buffer_overflow_policy = p;
+        self
+    }
+
+    /// Pass each buffer overflow of a stream to the given observer before
+    /// it is handled according to [`Config::set_buffer_overflow_policy`].
+    ///
+    /// The observer is called while polling the connection and must not block.
+    pub fn set_buffer_overflow_observer(&mut self, observer: BufferOverflowObserver) -> &mut Self {
+        self.buffer_overflow_observer = Some(OverflowObserver(observer));
+        self
+    }
+
+    /// Set how many RSTs a connection sends per second are logged as
+    /// warnings along with their [`ResetReason`], further ones are logged
+    /// at debug level.
+    ///
+    /// Resets requested by the application are always logged at debug level.
+    /// All resets are counted in [`ConnectionStats::resets_sent`].
+    pub fn set_reset_log_budget(&mut self, n: u32) -> &mut Self {
+        self.reset_log_budget = n;
+        self
+    }
+
+    /// Set the spawner of the tasks which run in the background, e.g. those
+    /// of [`Connection::start`], [`Connection::start_serving`],
+    /// [`server::Listener::start`], [`client::Connector::start`] and
+    /// [`pool::ConnectionPool::start`], independent of any particular runtime.
+    pub fn set_spawner(&mut self, spawn: Spawn) -> &mut Self {
+        self.spawner = Some(Spawner(spawn));
+        self
+    }
+
+    /// The extensions to advertise, i.e. those of [`Config::set_extensions`]
+    /// along with the ones of enabled features.
+    pub(crate) fn advertised_extensions(&self) -> Extensions {
+        let mut extensions = self.extensions;
+        if self.stream_id_recycling {
+            extensions = extensions.union(Extensions::STREAM_ID_RECYCLING)
+        }
+        if self.compression != Compression::None {
+            extensions = extensions.union(Extensions::COMPRESSION)
+        }
+        extensions
+    }
+
+    /// The spawner of [`Config::set_spawner`], or an error if none has been set.
+    pub(crate) fn spawner(&self) -> std::result::Result<Spawn, ConfigError> {
+        match &self.spawner {
+            Some(spawner) => Ok(spawner.0.clone()),
+            None => Err(driver::no_spawner()),
+        }
+    }
+
     /// Set the window update mode to use.
     pub fn set_window_update_mode(&mut self, m: WindowUpdateMode) -> &mut Self {
         self.window_update_mode = m;
         self
     }
 
+    /// Set how much credit a stream must be able to grant before it sends
+    /// a window update, half of its receive window by default.
+    ///
+    /// A small threshold keeps the sender from stalling on latency-sensitive
+    /// streams at the cost of more window update frames, a large one saves
+    /// frames for bulk transfers. The threshold is capped at the receive
+    /// window, which may be smaller than configured if a connection receive
+    /// window is set (see [`Config::set_max_connection_receive_window`]).
+    ///
+    /// # Panics
+    ///
+    /// If a fraction is not in `(0, 1]` or the number of bytes is 0.
+    pub fn set_window_update_threshold(&mut self, t: WindowUpdateThreshold) -> &mut Self {
+        match t {
+            WindowUpdateThreshold::Fraction(f) => assert!(f > 0.0 && f <= 1.0),
+            WindowUpdateThreshold::Bytes(n) => assert!(n > 0),
+        }
+        self.window_update_threshold = t;
+        self
+    }
+
     /// Allow or disallow streams to read from buffered data after
     /// the connection has been closed.
     pub fn set_read_after_close(&mut self, b: bool) -> &mut Self {
@@ -185,6 +1002,318 @@ impl Config {
         self.split_send_size = n;
         self
     }
+
+    /// Set the duration after which a connection without any streams and
+    /// without any frames sent or received is closed.
+    ///
+    /// The connection is closed gracefully, i.e. like [`Connection::poll_close`]
+    /// does, after which [`Connection::poll_next_inbound`] returns `None`.
+    pub fn set_idle_timeout(&mut self, d: Duration) -> &mut Self {
+        self.idle_timeout = Some(d);
+        self
+    }
+
+    /// Set the duration within which the remote must acknowledge an outbound
+    /// stream, measured from when its SYN has been sent, i.e. from the first
+    /// write of a lazily opened stream (see [`OpenMode::Lazy`]).
+    ///
+    /// Otherwise the stream is reset and its pending and future reads and
+    /// writes fail with an [`std::io::Error`] of kind
+    /// [`std::io::ErrorKind::TimedOut`], instead of waiting for data or credit
+    /// which may never arrive.
+    pub fn set_stream_open_timeout(&mut self, d: Duration) -> &mut Self {
+        self.stream_open_timeout = Some(d);
+        self
+    }
+
+    /// Set the duration within which an inbound stream must be acknowledged,
+    /// i.e. within which the application must write to it or read enough to
+    /// grant the remote more credit.
+    ///
+    /// Otherwise the connection responds as set via
+    /// [`Config::set_ack_deadline_response`], by default by acknowledging the
+    /// stream, so that a busy accept loop does not make the remote run into
+    /// its stream open timeout.
+    pub fn set_inbound_stream_ack_deadline(&mut self, d: Duration) -> &mut Self {
+        self.inbound_stream_ack_deadline = Some(d);
+        self
+    }
+
+    /// Set how to respond to an inbound stream which has not been acknowledged
+    /// within the deadline of [`Config::set_inbound_stream_ack_deadline`].
+    pub fn set_ack_deadline_response(&mut self, r: AckDeadlineResponse) -> &mut Self {
+        self.ack_deadline_response = r;
+        self
+    }
+
+    /// Set the max. duration of closing a connection gracefully.
+    ///
+    /// Closing flushes the frames pending to be sent and closes the underlying
+    /// I/O resource, which never completes if the remote stops reading. Once
+    /// the timeout elapses, the remaining frames are discarded, the I/O
+    /// resource is dropped without being closed and closing completes
+    /// successfully.
+    pub fn set_close_timeout(&mut self, d: Duration) -> &mut Self {
+        self.close_timeout = Some(d);
+        self
+    }
+
+    /// Set when the SYN of outbound streams is sent, unless given explicitly
+    /// via [`Control::open_stream_with_mode`] or
+    /// [`Connection::poll_new_outbound_with_mode`].
+    pub fn set_open_mode(&mut self, m: OpenMode) -> &mut Self {
+        self.open_mode = m;
+        self
+    }
+
+    /// Set the max. number of outbound streams whose SYN has been sent but
+    /// not yet acknowledged by the remote (must be > 0).
+    ///
+    /// Once reached, opening further streams waits until the remote
+    /// acknowledges streams, like go-yamux does with its accept backlog.
+    /// Streams are opened lazily, i.e. a stream counts towards the backlog
+    /// once it sends its first frame. The connection must keep being polled
+    /// for acknowledgements to be received.
+    ///
+    /// # Panics
+    ///
+    /// If the given number is 0.
+    pub fn set_max_ack_backlog(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.max_ack_backlog = Some(n);
+        self
+    }
+
+    /// Enable or disable the recycling of stream IDs.
+    ///
+    /// Without recycling, a connection fails with [`ConnectionError::NoMoreStreamIds`]
+    /// after 2^31 outbound streams. With recycling, each endpoint announces when it
+    /// no longer uses the ID of a stream the remote opened, and once fresh IDs are
+    /// exhausted, IDs of streams released by both endpoints are reused.
+    ///
+    /// This is an extension to the Yamux specification, which forbids the reuse of
+    /// stream IDs. It is advertised as [`Extensions::STREAM_ID_RECYCLING`] and stream
+    /// IDs are only released and reused once the remote advertised it as well.
+    pub fn set_stream_id_recycling(&mut self, b: bool) -> &mut Self {
+        self.stream_id_recycling = b;
+        self
+    }
+
+    /// Set the max. number of frame body buffers a connection keeps for reuse.
+    ///
+    /// Instead of allocating a new buffer for every frame body read, the buffers
+    /// of frame bodies written are reused, if their capacity does not exceed
+    /// the split send size (see [`Config::set_split_send_size`]). A value of 0
+    /// disables buffer pooling.
+    pub fn set_buffer_pool(&mut self, capacity: usize) -> &mut Self {
+        self.buffer_pool = capacity;
+        self
+    }
+
+    /// Coalesce consecutive data frames of a stream which are waiting to be sent
+    /// into data frames of up to `max_bytes` (must be > 0).
+    ///
+    /// In addition, a single pending data frame smaller than `max_bytes` is held
+    /// back for up to `max_delay` in anticipation of further writes to merge,
+    /// similar to Nagle's algorithm. `max_bytes` must not exceed the max. buffer
+    /// size of the remote (see [`Config::set_max_buffer_size`]).
+    ///
+    /// # Panics
+    ///
+    /// If the given max. number of bytes is 0 or exceeds `u32::MAX`.
+    pub fn set_coalesce_writes(&mut self, max_delay: Duration, max_bytes: usize) -> &mut Self {
+        assert!(max_bytes > 0 && max_bytes <= u32::MAX as usize);
+        self.coalesce_writes = Some((max_delay, max_bytes));
+        self
+    }
+
+    /// Limit the rate at which data is sent over the connection to the given
+    /// number of bytes per second (must be > 0).
+    ///
+    /// Only the bodies of data frames are taken into account. Bursts of up to
+    /// one second's worth of data, or one max. sized frame (see
+    /// [`Config::set_split_send_size`]) if larger, are sent without delay.
+    ///
+    /// # Panics
+    ///
+    /// If the given rate is 0.
+    pub fn set_max_send_rate(&mut self, bytes_per_sec: u64) -> &mut Self {
+        assert!(bytes_per_sec > 0);
+        self.max_send_rate = Some(bytes_per_sec);
+        self
+    }
+
+    /// Detect a remote which stopped responding, e.g. because the underlying
+    /// connection is half-open, instead of waiting for the operating system
+    /// to give up on it, which may take minutes.
+    ///
+    /// Once the remote is found unresponsive, the connection fails and
+    /// [`Connection::poll_next_inbound`] returns
+    /// [`ConnectionError::PeerUnresponsive`].
+    ///
+    /// # Panics
+    ///
+    /// If an interval or the timeout of the given mode is zero.
+    pub fn set_liveness_probe(&mut self, mode: ProbeMode) -> &mut Self {
+        assert!(mode.ping_interval() != Some(Duration::ZERO));
+        assert!(!mode.timeout().is_zero());
+        self.liveness_probe = Some(mode);
+        self
+    }
+
+    /// Compress the bodies of data frames with the given algorithm.
+    ///
+    /// Compression is negotiated per stream with a flag on the frames opening
+    /// and acknowledging it, which is not part of the Yamux specification, once
+    /// [`Extensions::COMPRESSION`] has been negotiated with the remote, i.e. the
+    /// first stream of a connection is never compressed. A stream only
+    /// compresses if both peers are configured with the same algorithm and
+    /// sends uncompressed frames otherwise, so that peers without compression
+    /// support are unaffected. Bodies which do not shrink are sent
+    /// uncompressed. Flow-control credit is accounted in uncompressed bytes.
+    ///
+    /// # Panics
+    ///
+    /// If the given Zstandard level is not supported.
+    pub fn set_compression(&mut self, compression: Compression) -> &mut Self {
+        assert!(compression.is_valid());
+        self.compression = compression;
+        self
+    }
+
+    /// Advertise the given protocol extensions to the remote.
+    ///
+    /// Each endpoint advertises its extensions with the upper 8 flag bits of
+    /// the first frame it sends to open or acknowledge a stream, which is not
+    /// part of the Yamux specification. An extension is negotiated once both
+    /// endpoints advertised it, see [`Connection::negotiated_extensions`].
+    /// Peers which do not know about extensions ignore these flags and do
+    /// not set them, i.e. no extension is negotiated with them.
+    ///
+    /// The flags of an extension are only sent once it has been negotiated.
+    /// [`Extensions::STREAM_ID_RECYCLING`] and [`Extensions::COMPRESSION`] are
+    /// advertised along with the given extensions if the respective feature
+    /// is enabled.
+    pub fn set_extensions(&mut self, extensions: Extensions) -> &mut Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Set the max. number of frames the connection sends, respectively
+    /// receives, in a row before turning to the other direction (must be > 0).
+    ///
+    /// Under sustained load in one direction this bounds the delay of frames
+    /// in the other, e.g. of window updates the remote sends while we have
+    /// data queued for sending.
+    ///
+    /// # Panics
+    ///
+    /// If the given budget is 0.
+    pub fn set_frame_budget(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.frame_budget = n;
+        self
+    }
+
+    /// Set the max. number of units of work, e.g. frames sent or received,
+    /// the connection performs in a single poll before yielding to other
+    /// tasks of the executor (must be > 0).
+    ///
+    /// Once exhausted, the connection wakes its task and returns
+    /// `Poll::Pending`, to continue when polled next.
+    ///
+    /// # Panics
+    ///
+    /// If the given budget is 0.
+    pub fn set_poll_budget(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.poll_budget = n;
+        self
+    }
+
+    /// Set the max. number of frames which are written to the socket with a
+    /// single vectored write (must be > 0).
+    ///
+    /// Frames are collected until this many are pending or the connection
+    /// flushes, which it does whenever it has no more frames to send. A value
+    /// of 1 writes each frame on its own.
+    ///
+    /// # Panics
+    ///
+    /// If the given size is 0.
+    pub fn set_write_batch_size(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.write_batch_size = n;
+        self
+    }
+
+    /// Pass every frame sent or received to the given recorder, e.g. to
+    /// debug interoperability issues.
+    pub fn set_frame_recorder(&mut self, recorder: Arc<dyn FrameRecorder>) -> &mut Self {
+        self.frame_recorder = Some(Recorder(recorder));
+        self
+    }
+
+    /// Decide with the given policy whether to accept each inbound stream,
+    /// e.g. to enforce per-peer quotas of streams.
+    ///
+    /// The policy is called when the SYN of a stream arrives, after the
+    /// max. number of inbound streams has been checked, and must not block. A stream
+    /// which is not accepted is reset or, as per [`Verdict`], the whole
+    /// connection is terminated with an internal error.
+    pub fn set_inbound_stream_policy(&mut self, policy: InboundStreamPolicy) -> &mut Self {
+        self.inbound_stream_policy = Some(Policy(policy));
+        self
+    }
+
+    /// Report streams which stall, i.e. which have not been polled to read or
+    /// write for longer than the given threshold even though data has been
+    /// received or credit is available to a task waiting to write, e.g.
+    /// because the waker of the task has been lost (requires the
+    /// `stall-detector` feature).
+    ///
+    /// Stalls are logged as warnings along with a snapshot of the stream, or
+    /// passed to the observer set via [`Config::set_stall_observer`], once
+    /// per stall and within twice the threshold.
+    ///
+    /// # Panics
+    ///
+    /// If the given threshold is zero.
+    #[cfg(feature = "stall-detector")]
+    pub fn set_stall_detector(&mut self, threshold: Duration) -> &mut Self {
+        assert!(!threshold.is_zero());
+        self.stall_threshold = Some(threshold);
+        self
+    }
+
+    /// Pass the streams the stall detector detects to the given observer
+    /// instead of logging them, see [`Config::set_stall_detector`].
+    #[cfg(feature = "stall-detector")]
+    pub fn set_stall_observer(&mut self, observer: Arc<dyn StallObserver>) -> &mut Self {
+        self.stall_observer = Some(connection::StallObserverRef(observer));
+        self
+    }
+
+    /// Drive the timers of connections, e.g. of idle and stream open timeouts,
+    /// keep-alives and rate limits, by the given clock instead of the async
+    /// runtime, e.g. a [`VirtualClock`] to run tests deterministically.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.time = Time(Some(clock));
+        self
+    }
+
+    /// Derive the IDs of connections from the given seed instead of choosing
+    /// them at random, so that test runs, including their logs, can be
+    /// reproduced.
+    ///
+    /// Connections draw their ID, and a [`pool::ConnectionPool`] the jitter of
+    /// its retries, from a generator shared by all clones of this
+    /// configuration, i.e. IDs depend on the order in which connections are
+    /// created.
+    pub fn set_rng_seed(&mut self, seed: u64) -> &mut Self {
+        self.seeded_ids = Some(SeededIds::new(seed));
+        self
+    }
 }
 
 // Check that we can safely cast a `usize` to a `u64`.
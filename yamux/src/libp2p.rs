@@ -0,0 +1,177 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Integration with rust-libp2p (requires the `libp2p` feature).
+//!
+//! [`Config`] is a connection upgrade negotiating `/yamux/1.0.0`, whose
+//! output [`Muxer`] implements [`StreamMuxer`]. It can thus be passed to
+//! e.g. `Transport::multiplex` of libp2p-core in place of `libp2p_yamux::Config`.
+
+use crate::{Config, Connection, ConnectionError, Mode, Stream, Transport};
+use futures::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+};
+use libp2p_core::{
+    muxing::{StreamMuxer, StreamMuxerEvent},
+    upgrade::{InboundConnectionUpgrade, OutboundConnectionUpgrade, UpgradeInfo},
+};
+use std::{
+    collections::VecDeque,
+    fmt, iter,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// The max. number of inbound streams a [`Muxer`] buffers until they are
+/// polled via [`StreamMuxer::poll_inbound`]. Further streams are dropped.
+const MAX_BUFFERED_INBOUND_STREAMS: usize = 256;
+
+/// A [`Connection`] implementing [`StreamMuxer`].
+///
+/// [`StreamMuxer::poll`] drives the connection and buffers inbound streams
+/// until they are polled via [`StreamMuxer::poll_inbound`].
+pub struct Muxer<T> {
+    connection: Connection<T>,
+    inbound: VecDeque<Stream>,
+    inbound_waker: Option<Waker>,
+}
+
+impl<T> Muxer<T> {
+    pub fn new(connection: Connection<T>) -> Self {
+        Muxer {
+            connection,
+            inbound: VecDeque::new(),
+            inbound_waker: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Connection<T> {
+        &self.connection
+    }
+
+    pub fn get_mut(&mut self) -> &mut Connection<T> {
+        &mut self.connection
+    }
+}
+
+impl<T: Transport> fmt::Debug for Muxer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Muxer")
+            .field("connection", &self.connection.id())
+            .field("inbound", &self.inbound.len())
+            .finish()
+    }
+}
+
+impl<T: Transport> Muxer<T> {
+    fn poll_next_inbound(&mut self, cx: &mut Context<'_>) -> Poll<Result<Stream, ConnectionError>> {
+        match self.connection.poll_next_inbound(cx) {
+            Poll::Ready(Some(result)) => Poll::Ready(result),
+            Poll::Ready(None) => Poll::Ready(Err(ConnectionError::Closed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: Transport> StreamMuxer for Muxer<T> {
+    type Substream = Stream;
+    type Error = ConnectionError;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Stream, ConnectionError>> {
+        let this = self.get_mut();
+        if let Some(stream) = this.inbound.pop_front() {
+            return Poll::Ready(Ok(stream));
+        }
+        if let Poll::Ready(result) = this.poll_next_inbound(cx) {
+            return Poll::Ready(result);
+        }
+        this.inbound_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Stream, ConnectionError>> {
+        self.get_mut().connection.poll_new_outbound(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ConnectionError>> {
+        match self.get_mut().connection.poll_close(cx) {
+            Poll::Ready(Err(ConnectionError::Closed)) => Poll::Ready(Ok(())),
+            other => other,
+        }
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, ConnectionError>> {
+        let this = self.get_mut();
+        loop {
+            let stream = match this.poll_next_inbound(cx) {
+                Poll::Ready(Ok(stream)) => stream,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if this.inbound.len() >= MAX_BUFFERED_INBOUND_STREAMS {
+                log::warn!(
+                    "{}: dropping {}, too many buffered inbound streams",
+                    this.connection.id(),
+                    stream
+                );
+                continue;
+            }
+            this.inbound.push_back(stream);
+            if let Some(waker) = this.inbound_waker.take() {
+                waker.wake()
+            }
+        }
+    }
+}
+
+impl UpgradeInfo for Config {
+    type Info = &'static str;
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once("/yamux/1.0.0")
+    }
+}
+
+impl<C> InboundConnectionUpgrade<C> for Config
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = Muxer<C>;
+    type Error = std::io::Error;
+    type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+    fn upgrade_inbound(self, socket: C, _: Self::Info) -> Self::Future {
+        future::ready(Ok(Muxer::new(Connection::new(socket, self, Mode::Server))))
+    }
+}
+
+impl<C> OutboundConnectionUpgrade<C> for Config
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = Muxer<C>;
+    type Error = std::io::Error;
+    type Future = future::Ready<Result<Self::Output, Self::Error>>;
+
+    fn upgrade_outbound(self, socket: C, _: Self::Info) -> Self::Future {
+        future::ready(Ok(Muxer::new(Connection::new(socket, self, Mode::Client))))
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Statistics of all connections of a process, see [`PrometheusExporter`].
+
+use crate::{ConnectionStats, Control};
+use std::fmt;
+
+/// Aggregates the [`ConnectionStats`] of registered connections and exports
+/// them in the Prometheus text exposition format, see [`PrometheusExporter::render`],
+/// or via the `metrics` crate facade, see [`PrometheusExporter::publish`].
+///
+/// Connections are registered with their [`Control`] and removed once they
+/// closed. Counters include the connections which have been removed, so that
+/// they never decrease, gauges only the open ones.
+#[derive(Debug, Default)]
+pub struct PrometheusExporter {
+    connections: Vec<Registered>,
+    /// The counters of the connections which have been removed.
+    removed: SessionStats,
+}
+
+#[derive(Debug)]
+struct Registered {
+    control: Control,
+    /// The stats last collected.
+    last: Option<ConnectionStats>,
+}
+
+/// The aggregated statistics of the connections of a [`PrometheusExporter`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    /// The number of open connections.
+    pub connections: u64,
+    /// The number of connections which closed since they were registered.
+    pub connections_closed: u64,
+    /// The number of open streams.
+    pub streams: u64,
+    /// The number of frames whose body exceeded the receive window of
+    /// their stream, see [`ConnectionStats::window_violations`].
+    pub window_violations: u64,
+    /// The number of RSTs sent to the remotes, see [`ConnectionStats::resets_sent`].
+    pub resets_sent: u64,
+    /// The number of times streams or their connections had to wait for the
+    /// lock of their shared state, see [`ConnectionStats::lock_contentions`].
+    pub lock_contentions: u64,
+}
+
+impl PrometheusExporter {
+    /// Create an exporter without connections.
+    pub fn new() -> Self {
+        PrometheusExporter::default()
+    }
+
+    /// Export the stats of the connection of the given [`Control`].
+    pub fn register(&mut self, control: Control) {
+        self.connections.push(Registered {
+            control,
+            last: None,
+        })
+    }
+
+    /// The number of registered connections, including those which closed
+    /// since stats were last collected.
+    pub fn num_connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Collect the stats of all registered connections, removing those
+    /// which closed.
+    pub async fn collect(&mut self) -> SessionStats {
+        let mut stats = self.removed;
+        let mut i = 0;
+        while i < self.connections.len() {
+            let registered = &mut self.connections[i];
+            match registered.control.stats().await {
+                Ok(s) => {
+                    stats.connections += 1;
+                    stats.streams += s.num_streams as u64;
+                    stats.window_violations += s.window_violations;
+                    stats.resets_sent += s.resets_sent.total();
+                    stats.lock_contentions += s.lock_contentions;
+                    registered.last = Some(s);
+                    i += 1
+                }
+                Err(_) => {
+                    let removed = self.connections.swap_remove(i);
+                    self.removed.connections_closed += 1;
+                    stats.connections_closed += 1;
+                    if let Some(last) = removed.last {
+                        self.removed.window_violations += last.window_violations;
+                        stats.window_violations += last.window_violations;
+                        self.removed.resets_sent += last.resets_sent.total();
+                        stats.resets_sent += last.resets_sent.total();
+                        self.removed.lock_contentions += last.lock_contentions;
+                        stats.lock_contentions += last.lock_contentions;
+                    }
+                }
+            }
+        }
+        stats
+    }
+
+    /// Collect the stats of all registered connections and render them in
+    /// the Prometheus text exposition format.
+    pub async fn render(&mut self) -> String {
+        self.collect().await.to_string()
+    }
+
+    /// Collect the stats of all registered connections and record them with
+    /// the recorder of the `metrics` crate, under the names used by
+    /// [`PrometheusExporter::render`].
+    pub async fn publish(&mut self) {
+        let stats = self.collect().await;
+        for metric in stats.metrics() {
+            match metric.kind {
+                Kind::Gauge => ::metrics::gauge!(metric.name).set(metric.value as f64),
+                Kind::Counter => ::metrics::counter!(metric.name).absolute(metric.value),
+            }
+        }
+    }
+}
+
+enum Kind {
+    Gauge,
+    Counter,
+}
+
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+    value: u64,
+}
+
+impl SessionStats {
+    fn metrics(&self) -> [Metric; 6] {
+        [
+            Metric {
+                name: "yamux_connections",
+                help: "Number of open connections.",
+                kind: Kind::Gauge,
+                value: self.connections,
+            },
+            Metric {
+                name: "yamux_connections_closed_total",
+                help: "Number of connections which closed.",
+                kind: Kind::Counter,
+                value: self.connections_closed,
+            },
+            Metric {
+                name: "yamux_streams",
+                help: "Number of open streams.",
+                kind: Kind::Gauge,
+                value: self.streams,
+            },
+            Metric {
+                name: "yamux_window_violations_total",
+                help: "Number of frames exceeding the receive window of their stream.",
+                kind: Kind::Counter,
+                value: self.window_violations,
+            },
+            Metric {
+                name: "yamux_resets_sent_total",
+                help: "Number of RSTs sent to the remotes.",
+                kind: Kind::Counter,
+                value: self.resets_sent,
+            },
+            Metric {
+                name: "yamux_lock_contentions_total",
+                help: "Number of times streams had to wait for the lock of their state.",
+                kind: Kind::Counter,
+                value: self.lock_contentions,
+            },
+        ]
+    }
+}
+
+/// The Prometheus text exposition format.
+impl fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for m in self.metrics() {
+            let kind = match m.kind {
+                Kind::Gauge => "gauge",
+                Kind::Counter => "counter",
+            };
+            writeln!(f, "# HELP {} {}", m.name, m.help)?;
+            writeln!(f, "# TYPE {} {}", m.name, kind)?;
+            writeln!(f, "{} {}", m.name, m.value)?;
+        }
+        Ok(())
+    }
+}
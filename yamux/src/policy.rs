@@ -0,0 +1,84 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use crate::{connection::Id, frame::header::StreamId, BufferOverflowPolicy, Mode};
+use std::{fmt, sync::Arc};
+
+/// The connection an inbound stream is opened on, as passed to the policy
+/// of [`crate::Config::set_inbound_stream_policy`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionInfo {
+    /// The connection the stream is opened on.
+    pub connection: Id,
+    /// Whether we are the client or the server of the connection.
+    pub mode: Mode,
+    /// The number of streams of the connection, not counting the new one.
+    pub num_streams: usize,
+    /// The number of those streams opened by the remote.
+    pub num_inbound_streams: usize,
+}
+
+/// What to do with an inbound stream, see
+/// [`crate::Config::set_inbound_stream_policy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Accept the stream.
+    Accept,
+    /// Reset the stream and keep the connection open.
+    ResetStream,
+    /// Terminate the whole connection with an internal error.
+    TerminateConnection,
+}
+
+/// Decides whether to accept an inbound stream, see
+/// [`crate::Config::set_inbound_stream_policy`].
+pub type InboundStreamPolicy = Arc<dyn Fn(StreamId, &ConnectionInfo) -> Verdict + Send + Sync>;
+
+/// An [`InboundStreamPolicy`] of a [`crate::Config`].
+#[derive(Clone)]
+pub(crate) struct Policy(pub(crate) InboundStreamPolicy);
+
+impl fmt::Debug for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Policy")
+    }
+}
+
+/// A frame which would let the buffer of its stream grow beyond its limit, as
+/// passed to the observer of [`crate::Config::set_buffer_overflow_observer`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug)]
+pub struct BufferOverflow {
+    /// The connection of the stream.
+    pub connection: Id,
+    /// The stream whose buffer overflows.
+    pub stream: StreamId,
+    /// The number of bytes buffered, not counting the frame.
+    pub buffered: usize,
+    /// The body length of the frame.
+    pub frame_len: usize,
+    /// How the frame is handled.
+    pub policy: BufferOverflowPolicy,
+}
+
+/// Observes the buffer overflows of streams, see
+/// [`crate::Config::set_buffer_overflow_observer`].
+pub type BufferOverflowObserver = Arc<dyn Fn(&BufferOverflow) + Send + Sync>;
+
+/// A [`BufferOverflowObserver`] of a [`crate::Config`].
+#[derive(Clone)]
+pub(crate) struct OverflowObserver(pub(crate) BufferOverflowObserver);
+
+impl fmt::Debug for OverflowObserver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("OverflowObserver")
+    }
+}
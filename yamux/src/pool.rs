@@ -0,0 +1,262 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Opening streams over several connections to the same peer, see [`ConnectionPool`].
+
+use crate::{
+    error::{ConfigError, ConnectionError},
+    Config, Connect, Connection, Control, Mode, Result, Spawn, Stream, Transport,
+};
+use futures::{future, FutureExt, StreamExt};
+use rand::Rng;
+use std::{fmt, time::Duration};
+
+/// A set of client [`Connection`]s to the same peer, over which streams are
+/// opened via [`ConnectionPool::open`].
+///
+/// A stream is opened over the connection with the fewest streams. Once every
+/// connection carries the max. number of streams per connection, a new one is
+/// dialed with the given [`Connect`] function, unless the max. number of
+/// connections is reached, in which case the least loaded connection is used
+/// regardless. Connections which closed or ran out of stream IDs are removed
+/// from the pool.
+///
+/// If no connection can open another stream, e.g. because each reached the
+/// max. number of outbound streams of the [`Config`], and no connection can
+/// be dialed, opening fails with [`ConnectionError::TooManyStreams`], unless
+/// it is retried according to a [`RetryPolicy`].
+///
+/// Each connection is driven in a task spawned with the given [`Spawn`]
+/// function, or the one of the [`Config`], see [`ConnectionPool::start`]. Streams opened by the remote are reset. Dropping the pool closes
+/// all connections once their streams are dropped.
+///
+/// By default up to 100 streams per connection and an unlimited number of
+/// connections are used.
+pub struct ConnectionPool<T> {
+    connect: Connect<T>,
+    config: Config,
+    spawn: Spawn,
+    connections: Vec<Control>,
+    max_streams_per_connection: usize,
+    max_connections: usize,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl<T> fmt::Debug for ConnectionPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("connections", &self.connections.len())
+            .field(
+                "max_streams_per_connection",
+                &self.max_streams_per_connection,
+            )
+            .field("max_connections", &self.max_connections)
+            .field("retry_policy", &self.retry_policy)
+            .finish()
+    }
+}
+
+impl<T: Transport + Send + 'static> ConnectionPool<T> {
+    /// Create an empty pool, which dials connections with `connect` as needed.
+    pub fn new(connect: Connect<T>, cfg: Config, spawn: Spawn) -> Self {
+        ConnectionPool {
+            connect,
+            config: cfg,
+            spawn,
+            connections: Vec::new(),
+            max_streams_per_connection: 100,
+            max_connections: usize::MAX,
+            retry_policy: None,
+        }
+    }
+
+    /// Create an empty pool, which dials connections with `connect` as needed
+    /// and drives them with the spawner of `cfg`, see [`Config::set_spawner`].
+    ///
+    /// Fails if no spawner has been configured.
+    pub fn start(connect: Connect<T>, cfg: Config) -> std::result::Result<Self, ConfigError> {
+        let spawn = cfg.spawner()?;
+        Ok(ConnectionPool::new(connect, cfg, spawn))
+    }
+
+    /// Set the number of streams per connection beyond which a new
+    /// connection is dialed.
+    ///
+    /// # Panics
+    ///
+    /// If the given number is 0.
+    pub fn set_max_streams_per_connection(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.max_streams_per_connection = n;
+        self
+    }
+
+    /// Set the max. number of connections.
+    ///
+    /// # Panics
+    ///
+    /// If the given number is 0.
+    pub fn set_max_connections(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0);
+        self.max_connections = n;
+        self
+    }
+
+    /// Retry to open streams which failed with [`ConnectionError::TooManyStreams`]
+    /// or [`ConnectionError::NoMoreStreamIds`] according to the given policy,
+    /// instead of returning the error right away.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// The number of connections in the pool, including those which closed
+    /// since the last call to [`ConnectionPool::open`].
+    pub fn num_connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Open a new stream over the least loaded connection, dialing a new
+    /// connection if needed.
+    pub async fn open(&mut self) -> Result<Stream> {
+        let mut retry = 0;
+        loop {
+            let e = match self.try_open().await {
+                Err(e @ (ConnectionError::TooManyStreams | ConnectionError::NoMoreStreamIds)) => e,
+                result => return result,
+            };
+            let policy = match &self.retry_policy {
+                Some(policy) if retry < policy.max_retries => policy,
+                _ => return Err(e),
+            };
+            retry += 1;
+            let delay = match &self.config.seeded_ids {
+                Some(ids) => ids.with_rng(|rng| policy.jittered_delay(retry, rng)),
+                None => policy.jittered_delay(retry, &mut rand::thread_rng()),
+            };
+            log::debug!(
+                "failed to open stream: {}, retry {} in {:?}",
+                e,
+                retry,
+                delay
+            );
+            self.config.time.delay(delay).await
+        }
+    }
+
+    async fn try_open(&mut self) -> Result<Stream> {
+        // The index and number of streams of the least loaded connection.
+        let mut least_loaded: Option<(usize, usize)> = None;
+        let mut i = 0;
+        while i < self.connections.len() {
+            match self.connections[i].stats().await {
+                Ok(stats) if stats.can_open_outbound => {
+                    if least_loaded.is_none_or(|(_, n)| stats.num_streams < n) {
+                        least_loaded = Some((i, stats.num_streams))
+                    }
+                    i += 1
+                }
+                // The connection is at its max. number of outbound streams or
+                // going away. Either way it is removed once it closes.
+                Ok(stats) if stats.remaining_stream_ids > 0 => i += 1,
+                // The connection is closed or out of stream IDs.
+                _ => {
+                    self.connections.swap_remove(i);
+                }
+            }
+        }
+        let i = match least_loaded {
+            Some((i, n))
+                if n < self.max_streams_per_connection
+                    || self.connections.len() >= self.max_connections =>
+            {
+                i
+            }
+            _ if self.connections.len() >= self.max_connections => {
+                return Err(ConnectionError::TooManyStreams)
+            }
+            _ => self.dial().await?,
+        };
+        self.connections[i].open_stream().await
+    }
+
+    /// Dial a new connection and add it to the pool, returning its index.
+    async fn dial(&mut self) -> Result<usize> {
+        let socket = (self.connect)().await.map_err(ConnectionError::Io)?;
+        let connection = Connection::new(socket, self.config.clone(), Mode::Client);
+        log::debug!("{}: dialed", connection.id());
+        let (control, connection) = Control::new(connection);
+        (self.spawn)(connection.for_each(|_| future::ready(())).boxed());
+        self.connections.push(control);
+        Ok(self.connections.len() - 1)
+    }
+}
+
+/// How [`ConnectionPool::open`] retries to open a stream which failed with
+/// [`ConnectionError::TooManyStreams`] or [`ConnectionError::NoMoreStreamIds`],
+/// see [`ConnectionPool::set_retry_policy`].
+///
+/// The delay before each retry doubles, starting at the initial delay, up to
+/// the max. delay. A random part of up to half of each delay is skipped, so
+/// that callers which failed at the same time do not retry in lock-step.
+///
+/// By default up to 5 retries are made, with delays from 10 ms up to 1 s.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Set the max. number of retries after the first attempt failed.
+    pub fn set_max_retries(&mut self, n: u32) -> &mut Self {
+        self.max_retries = n;
+        self
+    }
+
+    /// Set the delay before the first retry.
+    pub fn set_initial_delay(&mut self, delay: Duration) -> &mut Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set the max. delay between two retries.
+    pub fn set_max_delay(&mut self, delay: Duration) -> &mut Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// The delay before the given retry, starting at 1, without jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+
+    /// The delay before the given retry, of which a random part of up to
+    /// half, drawn from `rng`, is skipped.
+    fn jittered_delay(&self, retry: u32, rng: &mut impl Rng) -> Duration {
+        self.delay(retry).mul_f64(rng.gen_range(0.5..=1.0))
+    }
+}
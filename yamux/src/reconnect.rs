@@ -0,0 +1,251 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use crate::{
+    error::ConnectionError, timer::Delay, Connection, Result, Snapshot, Stream, Transport,
+};
+use futures::{channel::mpsc, future::BoxFuture, ready, FutureExt};
+use std::{
+    fmt, io,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Creates a new I/O resource to resume a connection over, see [`Reconnect`].
+pub type Connect<T> = Box<dyn FnMut() -> BoxFuture<'static, io::Result<T>> + Send>;
+
+/// Events of a [`Reconnect`], see [`Reconnect::events`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum ReconnectEvent {
+    /// The transport failed with the given error and the connection has
+    /// been detached from it.
+    Disconnected(ConnectionError),
+    /// Starting the given attempt to reconnect, counting from 1.
+    Attempt(u32),
+    /// The given attempt to reconnect failed.
+    AttemptFailed(u32, io::Error),
+    /// The connection has been resumed over a new transport.
+    Reconnected(u32),
+    /// All attempts failed, the connection is closed.
+    GaveUp,
+}
+
+/// A [`Connection`] which re-establishes its transport after a failure.
+///
+/// If the transport fails, e.g. with an I/O error or an unexpected EOF, the
+/// connection is detached from it (see [`Connection::into_parts`]) and
+/// resumed over a new I/O resource created with the given [`Connect`]
+/// function. Streams remain open in the meantime. Failures of the remote,
+/// e.g. protocol violations, are not recovered from.
+///
+/// The remote must resume the session as well. The dialing endpoint
+/// typically dials again, while the listening endpoint's [`Connect`]
+/// function waits for the next inbound I/O resource of the session, which
+/// the application has to identify.
+///
+/// Frames written to the failed transport may not have reached the remote,
+/// and Yamux does not retransmit them. Once resumed, streams which may have
+/// lost frames are therefore reset with [`crate::ResetReason::TransportLost`]
+/// (see [`Connection::reset_streams_in_flight`]) instead of continuing with
+/// data missing. Only streams without data in flight survive a reconnect.
+///
+/// By default up to 10 attempts are made, one second apart.
+pub struct Reconnect<T> {
+    connection: Connection<T>,
+    connect: Connect<T>,
+    reconnecting: Option<Reconnecting<T>>,
+    max_attempts: u32,
+    retry_delay: Duration,
+    events: Option<mpsc::UnboundedSender<ReconnectEvent>>,
+}
+
+/// An ongoing attempt to reconnect.
+struct Reconnecting<T> {
+    snapshot: Snapshot,
+    attempt: u32,
+    step: Step<T>,
+}
+
+enum Step<T> {
+    /// Waiting before the next attempt.
+    Delay(Delay),
+    /// Waiting for the new I/O resource.
+    Connect(BoxFuture<'static, io::Result<T>>),
+}
+
+impl<T: Transport> fmt::Debug for Reconnect<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reconnect")
+            .field("connection", &self.connection.id())
+            .field("attempt", &self.reconnecting.as_ref().map(|r| r.attempt))
+            .finish()
+    }
+}
+
+impl<T: Transport + Send + 'static> Reconnect<T> {
+    /// Wrap the given connection, using `connect` to create new I/O
+    /// resources once its transport failed.
+    pub fn new(mut connection: Connection<T>, connect: Connect<T>) -> Self {
+        connection.set_detach_on_failure(true);
+        Reconnect {
+            connection,
+            connect,
+            reconnecting: None,
+            max_attempts: 10,
+            retry_delay: Duration::from_secs(1),
+            events: None,
+        }
+    }
+
+    /// Set the max. number of attempts to reconnect after a failure.
+    ///
+    /// # Panics
+    ///
+    /// If the given number is 0.
+    pub fn set_max_attempts(&mut self, n: u32) -> &mut Self {
+        assert!(n > 0);
+        self.max_attempts = n;
+        self
+    }
+
+    /// Set the delay between two attempts to reconnect. The first attempt
+    /// is made right away.
+    pub fn set_retry_delay(&mut self, delay: Duration) -> &mut Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Receive the [`ReconnectEvent`]s from now on. Only the receiver
+    /// returned last gets events.
+    pub fn events(&mut self) -> mpsc::UnboundedReceiver<ReconnectEvent> {
+        let (tx, rx) = mpsc::unbounded();
+        self.events = Some(tx);
+        rx
+    }
+
+    /// The connection currently in use.
+    pub fn connection(&self) -> &Connection<T> {
+        &self.connection
+    }
+
+    /// Whether the connection is currently detached from a failed transport.
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnecting.is_some()
+    }
+
+    /// See [`Connection::poll_next_inbound`].
+    pub fn poll_next_inbound(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Stream>>> {
+        loop {
+            if self.reconnecting.is_some() {
+                if let Err(e) = ready!(self.poll_reconnect(cx)) {
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+            match ready!(self.connection.poll_next_inbound(cx)) {
+                Some(Err(e)) => match self.connection.take_detached() {
+                    Some(snapshot) => {
+                        log::debug!("{}: transport failed, reconnecting", snapshot.id());
+                        self.emit(ReconnectEvent::Disconnected(e));
+                        self.start_attempt(snapshot, 1);
+                    }
+                    None => return Poll::Ready(Some(Err(e))),
+                },
+                other => return Poll::Ready(other),
+            }
+        }
+    }
+
+    /// See [`Connection::poll_new_outbound`]. Pending while reconnecting.
+    pub fn poll_new_outbound(&mut self, cx: &mut Context<'_>) -> Poll<Result<Stream>> {
+        if self.reconnecting.is_some() {
+            ready!(self.poll_reconnect(cx))?;
+        }
+        self.connection.poll_new_outbound(cx)
+    }
+
+    /// See [`Connection::poll_close`]. Closes all streams while reconnecting.
+    pub fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.reconnecting.take().is_some() {
+            return Poll::Ready(Ok(()));
+        }
+        self.connection.poll_close(cx)
+    }
+
+    fn start_attempt(&mut self, snapshot: Snapshot, attempt: u32) {
+        let step = if attempt == 1 {
+            self.emit(ReconnectEvent::Attempt(attempt));
+            Step::Connect((self.connect)())
+        } else {
+            Step::Delay(snapshot.config.time.delay(self.retry_delay))
+        };
+        self.reconnecting = Some(Reconnecting {
+            snapshot,
+            attempt,
+            step,
+        })
+    }
+
+    /// Drive the ongoing reconnection to completion, i.e. until the
+    /// connection is resumed or all attempts failed.
+    fn poll_reconnect(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            let reconnecting = match self.reconnecting.as_mut() {
+                Some(r) => r,
+                None => return Poll::Ready(Ok(())),
+            };
+            let attempt = reconnecting.attempt;
+            match &mut reconnecting.step {
+                Step::Delay(delay) => {
+                    ready!(delay.poll_unpin(cx));
+                    reconnecting.step = Step::Connect((self.connect)());
+                    self.emit(ReconnectEvent::Attempt(attempt));
+                }
+                Step::Connect(connect) => match ready!(connect.poll_unpin(cx)) {
+                    Ok(socket) => {
+                        let snapshot = self.reconnecting.take().expect("reconnecting").snapshot;
+                        log::debug!("{}: reconnected after {} attempts", snapshot.id(), attempt);
+                        self.connection = Connection::from_parts(socket, snapshot);
+                        self.connection.set_detach_on_failure(true);
+                        self.connection.reset_streams_in_flight();
+                        self.emit(ReconnectEvent::Reconnected(attempt));
+                        return Poll::Ready(Ok(()));
+                    }
+                    Err(e) => {
+                        let snapshot = self.reconnecting.take().expect("reconnecting").snapshot;
+                        log::debug!(
+                            "{}: reconnect attempt {} failed: {}",
+                            snapshot.id(),
+                            attempt,
+                            e
+                        );
+                        let error = io::Error::new(e.kind(), e.to_string());
+                        self.emit(ReconnectEvent::AttemptFailed(attempt, e));
+                        if attempt >= self.max_attempts {
+                            // Dropping the snapshot closes all streams.
+                            drop(snapshot);
+                            self.emit(ReconnectEvent::GaveUp);
+                            return Poll::Ready(Err(ConnectionError::Io(error)));
+                        }
+                        self.start_attempt(snapshot, attempt + 1);
+                    }
+                },
+            }
+        }
+    }
+
+    fn emit(&mut self, event: ReconnectEvent) {
+        if let Some(events) = &self.events {
+            if events.unbounded_send(event).is_err() {
+                self.events = None
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+use crate::{
+    connection::Id,
+    frame::header::{self, Header},
+    FRAME_HEADER_SIZE,
+};
+use std::{fmt, sync::Arc, time::SystemTime};
+
+/// Receives the frames a connection sends and receives, e.g. to log them
+/// for debugging, see [`crate::Config::set_frame_recorder`].
+///
+/// The recorder is called while polling the connection and must not block.
+pub trait FrameRecorder: Send + Sync {
+    /// Record the given frame.
+    fn record(&self, frame: RecordedFrame<'_>);
+
+    /// The max. number of bytes of the body of a data frame to record.
+    ///
+    /// Defaults to 0, i.e. only headers are recorded.
+    fn max_body_len(&self) -> usize {
+        0
+    }
+}
+
+/// Whether a [`RecordedFrame`] was sent or received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// The frame was received from the remote.
+    Inbound,
+    /// The frame was sent to the remote.
+    Outbound,
+}
+
+/// A frame passed to a [`FrameRecorder`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug)]
+pub struct RecordedFrame<'a> {
+    /// The connection which sent or received the frame.
+    pub connection: Id,
+    /// Whether the frame was sent or received.
+    pub direction: FrameDirection,
+    /// When the frame was received from, or queued to be written to, the
+    /// underlying I/O resource.
+    pub time: SystemTime,
+    /// The encoded header.
+    pub header: [u8; FRAME_HEADER_SIZE],
+    /// The body of a data frame, truncated to [`FrameRecorder::max_body_len`].
+    pub body: &'a [u8],
+}
+
+/// A [`FrameRecorder`] of a [`crate::Config`].
+#[derive(Clone)]
+pub(crate) struct Recorder(pub(crate) Arc<dyn FrameRecorder>);
+
+impl Recorder {
+    pub(crate) fn record(
+        &self,
+        connection: Id,
+        direction: FrameDirection,
+        header: &Header<()>,
+        body: &[u8],
+    ) {
+        self.0.record(RecordedFrame {
+            connection,
+            direction,
+            time: SystemTime::now(),
+            header: header::encode(header),
+            body: &body[..body.len().min(self.0.max_body_len())],
+        })
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Recorder")
+    }
+}
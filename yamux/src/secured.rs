@@ -0,0 +1,78 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Yamux connections over TLS (requires the `tls` feature).
+//!
+//! [`Connection::new_secured`] performs the TLS handshake over a plain I/O
+//! resource and multiplexes streams over the resulting encrypted stream.
+//!
+//! When the connection is closed, e.g. via [`Connection::poll_close`], the
+//! `close_notify` alert is sent after the final `GoAway` frame, before the
+//! underlying I/O resource is closed. The remote thus observes a clean end of
+//! the TLS session rather than a truncation.
+//!
+//! The rustls crypto provider is not selected by this crate, see
+//! [`rustls::crypto::CryptoProvider`].
+
+use crate::{Config, Connection, Mode};
+use futures::io::{AsyncRead, AsyncWrite};
+use futures_rustls::{
+    pki_types::ServerName,
+    rustls::{ClientConfig, ServerConfig},
+    TlsAcceptor, TlsConnector,
+};
+use std::{fmt, io, sync::Arc};
+
+pub use futures_rustls::{rustls, TlsStream};
+
+/// The TLS configuration of one endpoint of a secured connection.
+#[derive(Clone)]
+pub enum TlsConfig {
+    /// Initiate the handshake as a client, verifying the certificate of
+    /// the server against the given name.
+    Client(Arc<ClientConfig>, ServerName<'static>),
+    /// Accept the handshake as a server.
+    Server(Arc<ServerConfig>),
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TlsConfig::Client(_, name) => f.debug_tuple("Client").field(name).finish(),
+            TlsConfig::Server(_) => f.write_str("Server"),
+        }
+    }
+}
+
+impl<T> Connection<TlsStream<T>>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Perform the TLS handshake over the given socket and create a new
+    /// connection over the encrypted stream.
+    ///
+    /// The TLS role given by `tls` is independent of the Yamux `mode`,
+    /// though typically the TLS client is also the Yamux client.
+    pub async fn new_secured(
+        socket: T,
+        tls: TlsConfig,
+        cfg: Config,
+        mode: Mode,
+    ) -> io::Result<Self> {
+        let stream = match tls {
+            TlsConfig::Client(config, name) => TlsConnector::from(config)
+                .connect(name, socket)
+                .await?
+                .into(),
+            TlsConfig::Server(config) => TlsAcceptor::from(config).accept(socket).await?.into(),
+        };
+        Ok(Connection::new(stream, cfg, mode))
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Accepting streams from many connections, see [`Listener`].
+
+use crate::driver::{drive, ShutdownHandle, Spawn};
+use crate::{Config, ConfigError, Connection, Control, Mode, Stream, Transport};
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    FutureExt, StreamExt,
+};
+use std::io;
+
+/// Accepts the inbound streams of all connections over the sockets of a
+/// listening socket.
+///
+/// Each socket yielded by `incoming` becomes a server [`Connection`], which is
+/// driven in a task of its own. Errors accepting a socket are logged and
+/// otherwise ignored.
+///
+/// Dropping the listener shuts it down, see [`Listener::shutdown_handle`].
+#[derive(Debug)]
+pub struct Listener {
+    inbound: mpsc::UnboundedReceiver<Stream>,
+    shutdown: ShutdownHandle,
+}
+
+impl Listener {
+    /// Start accepting the sockets yielded by `incoming`, spawning the
+    /// accept loop and a task per connection with `spawn`.
+    pub fn new<I, T>(incoming: I, cfg: Config, spawn: Spawn) -> Self
+    where
+        I: futures::Stream<Item = io::Result<T>> + Send + Unpin + 'static,
+        T: Transport + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded();
+        let shutdown = ShutdownHandle::new();
+        let accept = accept(incoming, cfg, spawn.clone(), tx, shutdown.clone());
+        spawn(accept.boxed());
+        Listener {
+            inbound: rx,
+            shutdown,
+        }
+    }
+
+    /// Start accepting the sockets yielded by `incoming`, spawning the
+    /// tasks with the spawner of `cfg`, see [`Config::set_spawner`].
+    ///
+    /// Fails if no spawner has been configured.
+    pub fn start<I, T>(incoming: I, cfg: Config) -> Result<Self, ConfigError>
+    where
+        I: futures::Stream<Item = io::Result<T>> + Send + Unpin + 'static,
+        T: Transport + Send + 'static,
+    {
+        let spawn = cfg.spawner()?;
+        Ok(Listener::new(incoming, cfg, spawn))
+    }
+
+    /// Accept the next inbound stream of any connection.
+    ///
+    /// Returns `None` once the listener has been shut down, or `incoming`
+    /// ended, and all connections are closed.
+    pub async fn accept(&mut self) -> Option<Stream> {
+        self.inbound.next().await
+    }
+
+    /// A handle to shut down the listener.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.shutdown.shutdown()
+    }
+}
+
+/// Create a connection for each socket yielded by `incoming` until it ends
+/// or the listener is shut down.
+async fn accept<I, T>(
+    mut incoming: I,
+    cfg: Config,
+    spawn: Spawn,
+    inbound: mpsc::UnboundedSender<Stream>,
+    shutdown: ShutdownHandle,
+) where
+    I: futures::Stream<Item = io::Result<T>> + Send + Unpin + 'static,
+    T: Transport + Send + 'static,
+{
+    let mut signal = shutdown.signal();
+    loop {
+        match future::select(incoming.next(), &mut signal).await {
+            Either::Left((Some(Ok(socket)), _)) => {
+                let connection = Connection::new(socket, cfg.clone(), Mode::Server);
+                log::debug!("{}: accepted", connection.id());
+                let (control, connection) = Control::new(connection);
+                let driver = drive(connection, control, inbound.clone(), shutdown.clone());
+                spawn(driver.boxed());
+            }
+            Either::Left((Some(Err(e)), _)) => log::debug!("failed to accept socket: {}", e),
+            Either::Left((None, _)) | Either::Right(_) => return,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Limits shared by many connections, see [`Supervisor`].
+
+use crate::{ConfigUpdate, Control};
+
+/// Enforces limits on the total number of streams and of buffered bytes
+/// across many connections.
+///
+/// Connections are registered with their [`Control`]. Whenever
+/// [`Supervisor::enforce`] is called, the usage of all registered connections
+/// is collected and if a limit is reached, every connection is paused (see
+/// [`ConfigUpdate::set_paused`]), i.e. no new streams are opened or accepted,
+/// until the usage drops below the limits again. Existing streams are never
+/// affected. Connections which closed are removed.
+///
+/// Limits are only checked by [`Supervisor::enforce`], which should therefore
+/// be called periodically, e.g. from a task of its own.
+///
+/// By default the number of streams and of buffered bytes is unlimited.
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    connections: Vec<Supervised>,
+    max_streams: Option<usize>,
+    max_buffered_bytes: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Supervised {
+    control: Control,
+    paused: bool,
+}
+
+/// The total usage of the connections of a [`Supervisor`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of open connections.
+    pub connections: usize,
+    /// The number of open streams.
+    pub streams: usize,
+    /// The number of received bytes buffered by the streams, see
+    /// [`ConnectionStats::buffered_bytes`](crate::ConnectionStats::buffered_bytes).
+    pub buffered_bytes: usize,
+    /// Whether the connections are paused because a limit has been reached.
+    pub paused: bool,
+}
+
+impl Supervisor {
+    /// Create a supervisor without connections.
+    pub fn new() -> Self {
+        Supervisor::default()
+    }
+
+    /// Set the max. number of streams of all connections together.
+    pub fn set_max_streams(&mut self, n: usize) -> &mut Self {
+        self.max_streams = Some(n);
+        self
+    }
+
+    /// Set the max. number of received bytes buffered by the streams of all
+    /// connections together.
+    pub fn set_max_buffered_bytes(&mut self, n: usize) -> &mut Self {
+        self.max_buffered_bytes = Some(n);
+        self
+    }
+
+    /// Supervise the connection of the given [`Control`].
+    ///
+    /// The connection is paused by the next [`Supervisor::enforce`] if a
+    /// limit is reached.
+    pub fn register(&mut self, control: Control) {
+        self.connections.push(Supervised {
+            control,
+            paused: false,
+        })
+    }
+
+    /// The number of registered connections, including those which closed
+    /// since the limits were last enforced.
+    pub fn num_connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Collect the usage of all registered connections, removing those
+    /// which closed, and pause or resume them depending on whether a limit
+    /// is reached.
+    pub async fn enforce(&mut self) -> Usage {
+        let mut usage = Usage::default();
+        let mut i = 0;
+        while i < self.connections.len() {
+            match self.connections[i].control.stats().await {
+                Ok(s) => {
+                    usage.connections += 1;
+                    usage.streams += s.num_streams;
+                    usage.buffered_bytes += s.buffered_bytes;
+                    i += 1
+                }
+                Err(_) => {
+                    self.connections.swap_remove(i);
+                }
+            }
+        }
+        usage.paused = self.max_streams.is_some_and(|n| usage.streams >= n)
+            || self
+                .max_buffered_bytes
+                .is_some_and(|n| usage.buffered_bytes >= n);
+        for c in &mut self.connections {
+            if c.paused == usage.paused {
+                continue;
+            }
+            let mut update = ConfigUpdate::new();
+            update.set_paused(usage.paused);
+            // A connection which closed meanwhile is removed next time.
+            if c.control.update_config(update).await.is_ok() {
+                c.paused = usage.paused
+            }
+        }
+        usage
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// The synchronisation primitives of the state shared between a stream and
+// its connection.
+//
+// Built with `--cfg yamux_loom`, those of loom are used instead, so that the
+// loom tests of `connection::stream` can explore the interleavings of
+// streams and connections accessing the shared state.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(not(yamux_loom))]
+pub(crate) use parking_lot::MutexGuard;
+#[cfg(not(yamux_loom))]
+pub(crate) use std::sync::Arc;
+
+#[cfg(yamux_loom)]
+pub(crate) use loom::sync::{Arc, MutexGuard};
+
+#[cfg(not(yamux_loom))]
+type Inner<T> = parking_lot::Mutex<T>;
+
+#[cfg(yamux_loom)]
+type Inner<T> = loom::sync::Mutex<T>;
+
+/// A mutex counting how often it was contended, i.e. a lock had to wait
+/// for the mutex to be released.
+///
+/// The count is kept by the caller, so that mutexes of the same state
+/// share one counter.
+#[derive(Debug)]
+pub(crate) struct Mutex<T> {
+    inner: Inner<T>,
+}
+
+impl<T> Mutex<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Mutex {
+            inner: Inner::new(value),
+        }
+    }
+
+    #[cfg(not(yamux_loom))]
+    pub(crate) fn lock(&self, contentions: &AtomicU64) -> MutexGuard<'_, T> {
+        if let Some(guard) = self.inner.try_lock() {
+            return guard;
+        }
+        count(contentions);
+        self.inner.lock()
+    }
+
+    #[cfg(yamux_loom)]
+    pub(crate) fn lock(&self, contentions: &AtomicU64) -> MutexGuard<'_, T> {
+        if let Ok(guard) = self.inner.try_lock() {
+            return guard;
+        }
+        count(contentions);
+        self.inner.lock().expect("not poisoned")
+    }
+}
+
+fn count(contentions: &AtomicU64) {
+    contentions.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(all(test, not(yamux_loom)))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn contended_locks_are_counted() {
+        let mutex = Arc::new(Mutex::new(0));
+        let contentions = Arc::new(AtomicU64::new(0));
+        drop(mutex.lock(&contentions));
+        assert_eq!(contentions.load(Ordering::Relaxed), 0);
+
+        let guard = mutex.lock(&contentions);
+        let other = thread::spawn({
+            let mutex = mutex.clone();
+            let contentions = contentions.clone();
+            move || *mutex.lock(&contentions) += 1
+        });
+        // The lock is counted before waiting for the mutex.
+        while contentions.load(Ordering::Relaxed) == 0 {
+            thread::yield_now()
+        }
+        drop(guard);
+        other.join().unwrap();
+        assert_eq!(*mutex.lock(&contentions), 1);
+        assert_eq!(contentions.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+// Timers used by the time-based features of a connection.
+//
+// The timer implementation is selected whenever a timer is created, so that
+// the features are additive: with the `tokio` feature, timers created within
+// a tokio runtime are tokio timers. Other timers are those of `async-io` (the
+// reactor of async-std and smol) with that feature and otherwise those of
+// `futures-timer`, which runs its own timer thread. Timers of a connection
+// with a `Clock`, see `Config::set_clock`, are driven by that clock instead.
+
+use crate::Clock;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// A future which completes once a given duration has elapsed.
+pub(crate) trait Timer: Future<Output = ()> + Send + Unpin + Sized {
+    /// Create a timer which completes after the given duration.
+    fn after(duration: Duration) -> Self;
+
+    /// Restart the timer to complete after the given duration.
+    fn restart(&mut self, duration: Duration);
+}
+
+/// A timer of the async runtime, see the module documentation.
+#[derive(Debug)]
+pub(crate) enum RuntimeDelay {
+    #[cfg(feature = "tokio")]
+    Tokio(tokio_timer::Delay),
+    #[cfg(feature = "async-io")]
+    AsyncIo(async_io_timer::Delay),
+    #[cfg(not(feature = "async-io"))]
+    FuturesTimer(futures_timer::Delay),
+}
+
+impl Timer for RuntimeDelay {
+    fn after(duration: Duration) -> Self {
+        #[cfg(feature = "tokio")]
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return RuntimeDelay::Tokio(tokio_timer::Delay::after(duration));
+        }
+        #[cfg(feature = "async-io")]
+        return RuntimeDelay::AsyncIo(async_io_timer::Delay::after(duration));
+        #[cfg(not(feature = "async-io"))]
+        return RuntimeDelay::FuturesTimer(futures_timer::Delay::after(duration));
+    }
+
+    fn restart(&mut self, duration: Duration) {
+        match self {
+            #[cfg(feature = "tokio")]
+            RuntimeDelay::Tokio(delay) => delay.restart(duration),
+            #[cfg(feature = "async-io")]
+            RuntimeDelay::AsyncIo(delay) => delay.restart(duration),
+            #[cfg(not(feature = "async-io"))]
+            RuntimeDelay::FuturesTimer(delay) => delay.restart(duration),
+        }
+    }
+}
+
+impl Future for RuntimeDelay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.get_mut() {
+            #[cfg(feature = "tokio")]
+            RuntimeDelay::Tokio(delay) => Pin::new(delay).poll(cx),
+            #[cfg(feature = "async-io")]
+            RuntimeDelay::AsyncIo(delay) => Pin::new(delay).poll(cx),
+            #[cfg(not(feature = "async-io"))]
+            RuntimeDelay::FuturesTimer(delay) => Pin::new(delay).poll(cx),
+        }
+    }
+}
+
+/// A timer of the async runtime or of a [`Clock`].
+#[derive(Debug)]
+pub(crate) enum Delay {
+    Runtime(RuntimeDelay),
+    Clock(Time, Instant),
+}
+
+impl Timer for Delay {
+    fn after(duration: Duration) -> Self {
+        Delay::Runtime(RuntimeDelay::after(duration))
+    }
+
+    fn restart(&mut self, duration: Duration) {
+        match self {
+            Delay::Runtime(delay) => delay.restart(duration),
+            Delay::Clock(time, deadline) => *deadline = time.now() + duration,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.get_mut() {
+            Delay::Runtime(delay) => Pin::new(delay).poll(cx),
+            Delay::Clock(time, deadline) => {
+                let clock = time.0.as_ref().expect("clock");
+                if clock.now() >= *deadline {
+                    return Poll::Ready(());
+                }
+                clock.wake_at(*deadline, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// The source of time of a connection, i.e. the system time and timers of
+/// the async runtime unless a [`Clock`] is set.
+#[derive(Clone, Default)]
+pub(crate) struct Time(pub(crate) Option<Arc<dyn Clock>>);
+
+impl Time {
+    pub(crate) fn now(&self) -> Instant {
+        match &self.0 {
+            Some(clock) => clock.now(),
+            None => Instant::now(),
+        }
+    }
+
+    /// The time elapsed since the given instant.
+    pub(crate) fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+
+    /// Create a timer which completes after the given duration.
+    pub(crate) fn delay(&self, duration: Duration) -> Delay {
+        match &self.0 {
+            Some(_) => Delay::Clock(self.clone(), self.now() + duration),
+            None => Delay::after(duration),
+        }
+    }
+}
+
+impl fmt::Debug for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(_) => f.write_str("Clock"),
+            None => f.write_str("System"),
+        }
+    }
+}
+
+#[cfg(not(feature = "async-io"))]
+impl Timer for futures_timer::Delay {
+    fn after(duration: Duration) -> Self {
+        futures_timer::Delay::new(duration)
+    }
+
+    fn restart(&mut self, duration: Duration) {
+        futures_timer::Delay::reset(self, duration)
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_timer {
+    use std::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    /// A timer of the tokio runtime.
+    ///
+    /// Must be created within the context of a tokio runtime, see
+    /// [`super::RuntimeDelay`].
+    #[derive(Debug)]
+    pub(crate) struct Delay(Pin<Box<tokio::time::Sleep>>);
+
+    impl super::Timer for Delay {
+        fn after(duration: Duration) -> Self {
+            Delay(Box::pin(tokio::time::sleep(duration)))
+        }
+
+        fn restart(&mut self, duration: Duration) {
+            let deadline = tokio::time::Instant::now() + duration;
+            self.0.as_mut().reset(deadline)
+        }
+    }
+
+    impl Future for Delay {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.0.as_mut().poll(cx)
+        }
+    }
+}
+
+#[cfg(feature = "async-io")]
+mod async_io_timer {
+    use std::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    /// A timer of the `async-io` reactor, as used by async-std and smol.
+    #[derive(Debug)]
+    pub(crate) struct Delay(Box<async_io::Timer>);
+
+    impl super::Timer for Delay {
+        fn after(duration: Duration) -> Self {
+            Delay(Box::new(async_io::Timer::after(duration)))
+        }
+
+        fn restart(&mut self, duration: Duration) {
+            self.0.set_after(duration)
+        }
+    }
+
+    impl Future for Delay {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            Pin::new(&mut *self.0).poll(cx).map(drop)
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Request-response over streams with [`tower_service::Service`]s, see
+//! [`Client`] and [`Server`].
+//!
+//! Each request opens a stream, over which the client sends the request as
+//! a length-prefixed message (see [`crate::FramedStream`]) and closes the
+//! stream for sending. The server answers with the response as a single
+//! message and closes the stream as well. If the service fails, the server
+//! closes the stream without a response.
+
+use crate::{Control, Stream, StreamHandler, StreamId};
+use bytes::Bytes;
+use futures::{
+    future::{self, BoxFuture},
+    lock::Mutex,
+    ready, FutureExt, SinkExt, StreamExt,
+};
+use std::{
+    fmt, io,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower_service::Service;
+
+/// The default max. size of requests and responses, see
+/// [`Client::set_max_message_size`] and [`Server::set_max_message_size`].
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// A [`Service`] sending each request over a new stream of a connection
+/// and resolving to the response of the remote [`Server`].
+///
+/// The client is ready once a stream can be opened, see [`Control::ready`].
+pub struct Client {
+    control: Control,
+    max_message_size: usize,
+    /// The pending [`Control::ready`] of [`Service::poll_ready`].
+    ready: Option<BoxFuture<'static, crate::Result<()>>>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("control", &self.control)
+            .field("max_message_size", &self.max_message_size)
+            .finish()
+    }
+}
+
+impl Clone for Client {
+    fn clone(&self) -> Self {
+        Client {
+            control: self.control.clone(),
+            max_message_size: self.max_message_size,
+            ready: None,
+        }
+    }
+}
+
+impl Client {
+    pub fn new(control: Control) -> Self {
+        Client {
+            control,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            ready: None,
+        }
+    }
+
+    /// Set the max. size of requests and responses, 1 MiB by default, see
+    /// [`crate::FramedStream::set_max_message_size`].
+    ///
+    /// # Panics
+    ///
+    /// If the given size exceeds `u32::MAX`.
+    pub fn set_max_message_size(&mut self, n: usize) -> &mut Self {
+        assert!(n <= u32::MAX as usize);
+        self.max_message_size = n;
+        self
+    }
+}
+
+impl Service<Bytes> for Client {
+    type Response = Bytes;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, io::Result<Bytes>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let control = &self.control;
+        let ready = self.ready.get_or_insert_with(|| {
+            let mut control = control.clone();
+            async move { control.ready().await }.boxed()
+        });
+        let result = ready!(ready.poll_unpin(cx));
+        self.ready = None;
+        Poll::Ready(result.map_err(io::Error::other))
+    }
+
+    fn call(&mut self, request: Bytes) -> Self::Future {
+        let mut control = self.control.clone();
+        let max_message_size = self.max_message_size;
+        async move {
+            let stream = control.open_stream().await.map_err(io::Error::other)?;
+            let mut framed = stream.into_framed();
+            framed.set_max_message_size(max_message_size);
+            framed.send(request).await?;
+            framed.close().await?;
+            match framed.next().await {
+                Some(response) => response,
+                None => {
+                    let msg = "stream closed without a response";
+                    Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg))
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+/// A [`StreamHandler`] dispatching the request received over an inbound
+/// stream to a [`Service`] and sending back its response, e.g. via
+/// [`crate::Connection::serve`].
+///
+/// Requests are dispatched to the service one at a time, i.e. while it is
+/// polled for readiness and called, other requests wait, but responses are
+/// awaited concurrently. The service need not be `Clone` or `Sync`, e.g. a
+/// `tower::util::BoxService`. Clones of the server share the service.
+pub struct Server<S> {
+    service: Arc<Mutex<S>>,
+    max_message_size: usize,
+}
+
+impl<S> fmt::Debug for Server<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("max_message_size", &self.max_message_size)
+            .finish()
+    }
+}
+
+impl<S> Clone for Server<S> {
+    fn clone(&self) -> Self {
+        Server {
+            service: self.service.clone(),
+            max_message_size: self.max_message_size,
+        }
+    }
+}
+
+impl<S> Server<S> {
+    pub fn new(service: S) -> Self {
+        Server {
+            service: Arc::new(Mutex::new(service)),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Set the max. size of requests and responses, 1 MiB by default, see
+    /// [`crate::FramedStream::set_max_message_size`].
+    ///
+    /// # Panics
+    ///
+    /// If the given size exceeds `u32::MAX`.
+    pub fn set_max_message_size(&mut self, n: usize) -> &mut Self {
+        assert!(n <= u32::MAX as usize);
+        self.max_message_size = n;
+        self
+    }
+}
+
+impl<S> StreamHandler for Server<S>
+where
+    S: Service<Bytes, Response = Bytes> + Send + 'static,
+    S::Error: fmt::Display,
+    S::Future: Send,
+{
+    async fn handle(&self, stream: Stream, id: StreamId) {
+        let mut framed = stream.into_framed();
+        framed.set_max_message_size(self.max_message_size);
+        let response = match framed.next().await {
+            Some(Ok(request)) => self.call(request, id).await,
+            Some(Err(e)) => {
+                log::debug!("{}: failed to receive request: {}", id, e);
+                None
+            }
+            None => None,
+        };
+        if let Some(response) = response {
+            if let Err(e) = framed.feed(response).await {
+                log::debug!("{}: failed to send response: {}", id, e);
+                return;
+            }
+        }
+        let _ = framed.close().await;
+    }
+}
+
+impl<S> Server<S>
+where
+    S: Service<Bytes, Response = Bytes>,
+    S::Error: fmt::Display,
+{
+    /// Call the service once it is ready, logging its failure.
+    async fn call(&self, request: Bytes, id: StreamId) -> Option<Bytes> {
+        let response = {
+            let mut service = self.service.lock().await;
+            if let Err(e) = future::poll_fn(|cx| service.poll_ready(cx)).await {
+                log::debug!("{}: service failed: {}", id, e);
+                return None;
+            }
+            service.call(request)
+        };
+        match response.await {
+            Ok(response) => Some(response),
+            Err(e) => {
+                log::debug!("{}: service failed: {}", id, e);
+                None
+            }
+        }
+    }
+}
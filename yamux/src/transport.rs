@@ -0,0 +1,120 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! The transports a [`Connection`](crate::Connection) can run over.
+
+use crate::frame::{Frame, FrameDecodeError, Io};
+use std::{
+    io,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "messages")]
+pub mod messages;
+#[cfg(feature = "ws")]
+pub mod ws;
+
+/// The size of an encoded frame header in bytes.
+pub const FRAME_HEADER_SIZE: usize = crate::frame::header::HEADER_SIZE;
+
+/// The underlying transport of a [`Connection`](crate::Connection).
+///
+/// This trait is sealed. It is implemented for every byte stream, i.e.
+/// [`futures::io::AsyncRead`] + [`futures::io::AsyncWrite`], and for
+/// [`Frames`], which wraps a [`FrameTransport`].
+pub trait Transport: Unpin + Sized + private::Sealed {
+    #[doc(hidden)]
+    fn poll_ready(io: &mut Io<Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    #[doc(hidden)]
+    fn start_send(io: &mut Io<Self>, frame: Frame<()>) -> io::Result<()>;
+
+    #[doc(hidden)]
+    fn poll_flush(io: &mut Io<Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    #[doc(hidden)]
+    fn poll_close(io: &mut Io<Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    #[doc(hidden)]
+    fn poll_next(
+        io: &mut Io<Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<()>, FrameDecodeError>>>;
+}
+
+/// A transport which sends and receives whole Yamux frames instead of bytes.
+///
+/// Completion-based I/O (e.g. io_uring) or message-oriented transports
+/// can implement this trait and hand owned buffers to and from a
+/// [`Connection`](crate::Connection) (see
+/// [`Connection::with_transport`](crate::Connection::with_transport)),
+/// without going through [`futures::io::AsyncRead`] and
+/// [`futures::io::AsyncWrite`].
+///
+/// The transport must deliver frames reliably and in order.
+pub trait FrameTransport: Unpin {
+    /// Poll whether the transport is ready to accept the next frame.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Send a frame. Must only be called after `poll_ready` returned
+    /// `Poll::Ready(Ok(()))`.
+    fn start_send(&mut self, frame: EncodedFrame) -> io::Result<()>;
+
+    /// Poll until all frames passed to `start_send` have been sent.
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Poll until the transport has been closed.
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Poll for the next received frame. `None` signals that the remote
+    /// closed the transport.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<EncodedFrame>>>;
+}
+
+/// A Yamux frame as sent and received by a [`FrameTransport`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncodedFrame {
+    /// The encoded frame header.
+    pub header: [u8; FRAME_HEADER_SIZE],
+    /// The frame body. Only data frames have a non-empty body, which is
+    /// exactly as long as stated in the header.
+    pub body: Vec<u8>,
+}
+
+/// A [`FrameTransport`] to be used as the [`Transport`] of a
+/// [`Connection`](crate::Connection).
+#[derive(Debug)]
+pub struct Frames<F>(F);
+
+impl<F> Frames<F> {
+    pub fn new(transport: F) -> Self {
+        Frames(transport)
+    }
+
+    pub fn get_ref(&self) -> &F {
+        &self.0
+    }
+
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> F {
+        self.0
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl<T: futures::AsyncRead + futures::AsyncWrite + Unpin> Sealed for T {}
+
+    impl<F: super::FrameTransport> Sealed for super::Frames<F> {}
+}
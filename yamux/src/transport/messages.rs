@@ -0,0 +1,101 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Yamux over message transports (requires the `messages` feature).
+//!
+//! [`Messages`] maps each Yamux frame onto one message, i.e. the encoded
+//! header followed by the frame body, of any transport which preserves
+//! message boundaries and delivers messages reliably and in order, e.g. a
+//! single QUIC stream with a length-delimited codec on top. Frames are taken
+//! from and handed to the transport as a whole, without the reassembly of
+//! frames from a byte stream.
+
+use super::{EncodedFrame, FrameTransport, FRAME_HEADER_SIZE};
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A [`FrameTransport`] sending one Yamux frame per message of a
+/// `Sink<Bytes>` + `Stream` message transport.
+///
+/// The transport may yield any type of message convertible into [`Bytes`],
+/// e.g. the `BytesMut` of a `tokio_util::codec::Framed`. An error, whose
+/// type must be convertible into [`io::Error`], fails the connection.
+#[derive(Debug)]
+pub struct Messages<S>(S);
+
+impl<S> Messages<S> {
+    pub fn new(transport: S) -> Self {
+        Messages(transport)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.0
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+impl<S, M, E> FrameTransport for Messages<S>
+where
+    S: Sink<Bytes, Error = E> + Stream<Item = Result<M, E>> + Unpin,
+    M: Into<Bytes>,
+    E: Into<io::Error>,
+{
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(&mut self, frame: EncodedFrame) -> io::Result<()> {
+        let mut message = Vec::with_capacity(FRAME_HEADER_SIZE + frame.body.len());
+        message.extend_from_slice(&frame.header);
+        message.extend_from_slice(&frame.body);
+        Pin::new(&mut self.0)
+            .start_send(message.into())
+            .map_err(Into::into)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_close(cx).map_err(Into::into)
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<EncodedFrame>>> {
+        let message: Bytes = match ready!(Pin::new(&mut self.0).poll_next(cx)) {
+            Some(Ok(message)) => message.into(),
+            Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+            None => return Poll::Ready(None),
+        };
+        if message.len() < FRAME_HEADER_SIZE {
+            let e = io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message shorter than a frame header",
+            );
+            return Poll::Ready(Some(Err(e)));
+        }
+        let mut header = [0; FRAME_HEADER_SIZE];
+        header.copy_from_slice(&message[..FRAME_HEADER_SIZE]);
+        let body = message[FRAME_HEADER_SIZE..].to_vec();
+        Poll::Ready(Some(Ok(EncodedFrame { header, body })))
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) 2018-2019 Parity Technologies (UK) Ltd.
+//
+// Licensed under the Apache License, Version 2.0 or MIT license, at your option.
+//
+// A copy of the Apache License, Version 2.0 is included in the software as
+// LICENSE-APACHE and a copy of the MIT license is included in the software
+// as LICENSE-MIT. You may also obtain a copy of the Apache License, Version 2.0
+// at https://www.apache.org/licenses/LICENSE-2.0 and a copy of the MIT license
+// at https://opensource.org/licenses/MIT.
+
+//! Yamux over WebSocket (requires the `ws` feature).
+//!
+//! [`WebSocket`] maps each Yamux frame onto one binary WebSocket message,
+//! i.e. the encoded header followed by the frame body. It wraps any
+//! [`tungstenite`] based WebSocket stream, e.g. the `WebSocketStream` of
+//! `async-tungstenite` or `tokio-tungstenite`, after the handshake has been
+//! performed. Text messages are rejected, ping and pong messages are left to
+//! the WebSocket implementation and a close message ends the connection.
+
+use super::{EncodedFrame, FrameTransport, FRAME_HEADER_SIZE};
+use futures::{ready, Sink, Stream};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tungstenite::{Error, Message};
+
+pub use tungstenite;
+
+/// A [`FrameTransport`] sending one Yamux frame per binary WebSocket message.
+#[derive(Debug)]
+pub struct WebSocket<S>(S);
+
+impl<S> WebSocket<S> {
+    pub fn new(socket: S) -> Self {
+        WebSocket(socket)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.0
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+impl<S> FrameTransport for WebSocket<S>
+where
+    S: Sink<Message, Error = Error> + Stream<Item = Result<Message, Error>> + Unpin,
+{
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_ready(cx).map_err(into_io_error)
+    }
+
+    fn start_send(&mut self, frame: EncodedFrame) -> io::Result<()> {
+        let mut message = Vec::with_capacity(FRAME_HEADER_SIZE + frame.body.len());
+        message.extend_from_slice(&frame.header);
+        message.extend_from_slice(&frame.body);
+        Pin::new(&mut self.0)
+            .start_send(Message::Binary(message.into()))
+            .map_err(into_io_error)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx).map_err(into_io_error)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match ready!(Pin::new(&mut self.0).poll_close(cx)) {
+            Ok(()) | Err(Error::ConnectionClosed) | Err(Error::AlreadyClosed) => {
+                Poll::Ready(Ok(()))
+            }
+            Err(e) => Poll::Ready(Err(into_io_error(e))),
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<EncodedFrame>>> {
+        loop {
+            let message = match ready!(Pin::new(&mut self.0).poll_next(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(Error::ConnectionClosed)) | None => return Poll::Ready(None),
+                Some(Err(e)) => return Poll::Ready(Some(Err(into_io_error(e)))),
+            };
+            match message {
+                Message::Binary(bytes) => {
+                    if bytes.len() < FRAME_HEADER_SIZE {
+                        let e = io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "websocket message shorter than a frame header",
+                        );
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                    let mut header = [0; FRAME_HEADER_SIZE];
+                    header.copy_from_slice(&bytes[..FRAME_HEADER_SIZE]);
+                    let body = bytes[FRAME_HEADER_SIZE..].to_vec();
+                    return Poll::Ready(Some(Ok(EncodedFrame { header, body })));
+                }
+                Message::Text(_) => {
+                    let e = io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unexpected websocket text message",
+                    );
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Message::Close(_) => return Poll::Ready(None),
+                Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+            }
+        }
+    }
+}
+
+fn into_io_error(e: Error) -> io::Error {
+    match e {
+        Error::Io(e) => e,
+        Error::ConnectionClosed | Error::AlreadyClosed => io::ErrorKind::BrokenPipe.into(),
+        e => io::Error::other(e),
+    }
+}